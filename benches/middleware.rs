@@ -0,0 +1,42 @@
+//! The overhead of the middleware chain, measured against a backend without any I/O.
+
+mod support;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+use futures::future::BoxFuture;
+use surf::middleware::{HttpClient, Middleware, Next, Request, Response};
+
+use support::Mock;
+
+/// Middleware that only passes the request on.
+struct Noop;
+
+impl<C: HttpClient> Middleware<C> for Noop {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, surf::Exception>> {
+        Box::pin(async move { next.run(req, client).await })
+    }
+}
+
+fn chain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("middleware chain");
+    for &n in &[0, 1, 8, 32] {
+        let mut builder = surf::Client::with_client(Mock).scoped();
+        for _ in 0..n {
+            builder = builder.middleware(Noop);
+        }
+        let client = builder.build();
+        group.bench_function(n.to_string(), |b| {
+            b.iter(|| block_on(client.get("https://httpbin.org/get")).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, chain);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Sending a large file to a server on a local port, read into memory, streamed, or mapped.
+
+mod support;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use futures::io::AllowStdIo;
+
+use support::Server;
+
+use std::fs::{self, File};
+use std::io::BufReader;
+
+/// The size of the file sent.
+const SIZE: usize = 64 * 1024 * 1024;
+
+fn upload(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("surf-bench-{}.bin", std::process::id()));
+    fs::write(&path, vec![0x5a; SIZE]).unwrap();
+    let server = Server::start(b"ok", "text/plain");
+    let client = surf::Client::new();
+
+    let mut group = c.benchmark_group("file body");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let req = client.put(server.url()).body_file(&path).unwrap();
+            block_on(req.recv_bytes()).unwrap()
+        })
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| {
+            let file = BufReader::with_capacity(64 * 1024, File::open(&path).unwrap());
+            let req = client.put(server.url()).body(AllowStdIo::new(file));
+            block_on(req.recv_bytes()).unwrap()
+        })
+    });
+    group.bench_function("mapped", |b| {
+        b.iter(|| {
+            // Nothing writes to the file while it's mapped.
+            let req = unsafe { client.put(server.url()).body_file_mapped(&path).unwrap() };
+            block_on(req.recv_bytes()).unwrap()
+        })
+    });
+    group.finish();
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, upload);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+//! The overhead of building requests and decoding responses, without any I/O.
+
+mod support;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+
+use support::{Document, Mock, JSON};
+
+fn build(c: &mut Criterion) {
+    let client = surf::Client::with_client(Mock);
+    c.bench_function("build get", |b| {
+        b.iter(|| client.get("https://httpbin.org/get"))
+    });
+    c.bench_function("build get with headers", |b| {
+        b.iter(|| {
+            client
+                .get("https://httpbin.org/get?page=2&sort=name")
+                .set_header("Accept", "application/json")
+                .set_header("Authorization", "Bearer 0123456789abcdef")
+                .set_header("User-Agent", "surf-bench")
+        })
+    });
+    c.bench_function("build post json", |b| {
+        let doc: serde_json::Value = serde_json::from_str(JSON).unwrap();
+        b.iter(|| {
+            client
+                .post("https://httpbin.org/post")
+                .body_json(&doc)
+                .unwrap()
+        })
+    });
+}
+
+fn decode(c: &mut Criterion) {
+    let client = surf::Client::with_client(Mock);
+    c.bench_function("recv bytes", |b| {
+        b.iter(|| block_on(client.get("https://httpbin.org/get").recv_bytes()).unwrap())
+    });
+    c.bench_function("recv json typed", |b| {
+        b.iter(|| {
+            block_on(
+                client
+                    .get("https://httpbin.org/get")
+                    .recv_json::<Document>(),
+            )
+            .unwrap()
+        })
+    });
+    c.bench_function("recv json value", |b| {
+        b.iter(|| {
+            block_on(
+                client
+                    .get("https://httpbin.org/get")
+                    .recv_json::<serde_json::Value>(),
+            )
+            .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, build, decode);
+criterion_main!(benches);
@@ -0,0 +1,153 @@
+//! Utilities shared by the benchmarks.
+
+#![allow(dead_code)]
+
+use futures::future::BoxFuture;
+use surf::middleware::{Body, HttpClient, Request, Response};
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// A JSON document of about 1 KiB, served by the local server and the mock backend.
+pub const JSON: &str = r#"{"id":1,"name":"Chashu","tags":["cat","tofu","nori","hello","world"],"owner":{"name":"Yoshua","email":"yoshua@example.com","verified":true},"scores":[1.5,2.25,3.125,4.0625,5.03125,6.015625,7.0078125,8.00390625],"description":"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.","created":"2019-10-01T12:00:00Z","updated":"2019-10-02T12:00:00Z","active":true,"count":42,"ratio":0.75,"parent":null,"children":[{"id":2,"name":"Nori"},{"id":3,"name":"Tofu"},{"id":4,"name":"Miso"}]}"#;
+
+/// The document served as `JSON`.
+#[derive(Debug, serde::Deserialize)]
+pub struct Document {
+    pub id: u64,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub owner: Owner,
+    pub scores: Vec<f64>,
+    pub description: String,
+    pub created: String,
+    pub updated: String,
+    pub active: bool,
+    pub count: u64,
+    pub ratio: f64,
+    pub parent: Option<u64>,
+    pub children: Vec<Child>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Owner {
+    pub name: String,
+    pub email: String,
+    pub verified: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Child {
+    pub id: u64,
+    pub name: String,
+}
+
+/// An HTTP/1.1 server on a local port, answering every request with the same response.
+///
+/// Connections are kept alive and each one is served by a thread of its own, so the server adds
+/// as little overhead as possible to the measurements.
+#[derive(Debug)]
+pub struct Server {
+    url: String,
+}
+
+impl Server {
+    /// Start a server responding with a body and a content type.
+    pub fn start(body: &'static [u8], content_type: &'static str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let head = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\n\r\n",
+            content_type,
+            body.len()
+        );
+        let mut response = head.into_bytes();
+        response.extend_from_slice(body);
+        let response: &'static [u8] = Box::leak(response.into_boxed_slice());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || serve(stream, response));
+            }
+        });
+        Self { url }
+    }
+
+    /// Get the URL of the server.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Answer the requests of a connection until the client closes it.
+fn serve(stream: TcpStream, response: &[u8]) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+    loop {
+        let mut length = 0;
+        let mut chunked = false;
+        let mut expect = false;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let mut parts = header.splitn(2, ':');
+            let name = parts.next().unwrap_or_default();
+            let value = parts.next().unwrap_or_default().trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            } else if name.eq_ignore_ascii_case("expect") {
+                expect = value.eq_ignore_ascii_case("100-continue");
+            }
+        }
+        // Clients wait for a while before sending the body if this isn't answered.
+        if expect {
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        }
+        if chunked {
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                let size = line.trim_end().split(';').next().unwrap_or_default();
+                let size = u64::from_str_radix(size, 16).unwrap_or(0);
+                // Skip the chunk and its CRLF, or the empty trailer after the last chunk.
+                io::copy(&mut (&mut reader).take(size + 2), &mut io::sink())?;
+                if size == 0 {
+                    break;
+                }
+            }
+        } else {
+            io::copy(&mut (&mut reader).take(length), &mut io::sink())?;
+        }
+        writer.write_all(response)?;
+    }
+}
+
+/// A backend answering every request with a JSON document, without any I/O.
+#[derive(Debug, Clone)]
+pub struct Mock;
+
+impl HttpClient for Mock {
+    type Error = io::Error;
+
+    fn send(&self, _req: Request) -> BoxFuture<'static, Result<Response, Self::Error>> {
+        Box::pin(async move {
+            let mut res = Response::new(Body::from(JSON.as_bytes().to_vec()));
+            res.headers_mut().insert(
+                "content-type",
+                http::HeaderValue::from_static("application/json"),
+            );
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,40 @@
+//! Sending small requests to a server on a local port through the native backend.
+
+mod support;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use futures::future::try_join_all;
+
+use support::{Document, Server, JSON};
+
+fn get(c: &mut Criterion) {
+    let server = Server::start(b"hello world", "text/plain");
+    let client = surf::Client::new();
+    c.bench_function("get small body", |b| {
+        b.iter(|| block_on(client.get(server.url()).recv_bytes()).unwrap())
+    });
+
+    let mut group = c.benchmark_group("get concurrent");
+    for &n in &[8, 64] {
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_function(n.to_string(), |b| {
+            b.iter(|| {
+                let requests = (0..n).map(|_| client.get(server.url()).recv_bytes());
+                block_on(try_join_all(requests)).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn json(c: &mut Criterion) {
+    let server = Server::start(JSON.as_bytes(), "application/json");
+    let client = surf::Client::new();
+    c.bench_function("get json", |b| {
+        b.iter(|| block_on(client.get(server.url()).recv_json::<Document>()).unwrap())
+    });
+}
+
+criterion_group!(benches, get, json);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+//! Metadata about the attempts made to complete a request.
+
+use http::StatusCode;
+
+use std::slice;
+use std::time::{Duration, Instant};
+
+use crate::http_client::Response;
+
+/// Why an attempt didn't complete a request, and another attempt was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttemptReason {
+    /// The response was a redirect that was followed.
+    Redirect(StatusCode),
+    /// The response had a status that's retried, such as `503 Service Unavailable`.
+    Status(StatusCode),
+    /// The attempt timed out.
+    Timeout,
+    /// The attempt failed with an error, such as a connection error.
+    Error(String),
+}
+
+/// A single attempt to complete a request.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    started: Instant,
+    duration: Duration,
+    status: Option<StatusCode>,
+    reason: Option<AttemptReason>,
+}
+
+impl Attempt {
+    /// Get the time the attempt was started.
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Get the time it took to receive the response headers, or to fail.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Get the status of the response, if one was received.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
+
+    /// Get the reason another attempt was made after this one.
+    ///
+    /// This is `None` for the last attempt.
+    pub fn reason(&self) -> Option<&AttemptReason> {
+        self.reason.as_ref()
+    }
+}
+
+/// The attempts made to complete a request, including retries and followed redirects.
+///
+/// This makes it possible to tell the latency of the first attempt apart from the total latency.
+///
+/// Idempotent requests are also sent again when the server closed the connection they were sent
+/// over before responding, which happens when a pooled keep-alive connection is reused just as
+/// the server closes it. The failed attempt is included with an `AttemptReason::Error` reason.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::middleware::redirect::Redirect;
+///
+/// let client = surf::Client::new().scoped().middleware(Redirect::new()).build();
+/// let res = client.get("https://httpbin.org/redirect/2").await?;
+/// let attempts = res.attempts();
+/// assert_eq!(attempts.len(), 3);
+/// println!("first attempt: {:?}", attempts.first().unwrap().duration());
+/// println!("total: {:?}", attempts.elapsed());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Attempts {
+    attempts: Vec<Attempt>,
+}
+
+impl Attempts {
+    /// Get the number of attempts.
+    pub fn len(&self) -> usize {
+        self.attempts.len()
+    }
+
+    /// Check whether no attempts were recorded.
+    ///
+    /// This is only the case for responses that weren't received through a backend.
+    pub fn is_empty(&self) -> bool {
+        self.attempts.is_empty()
+    }
+
+    /// Get the first attempt.
+    pub fn first(&self) -> Option<&Attempt> {
+        self.attempts.first()
+    }
+
+    /// Get the last attempt, which produced the response.
+    pub fn last(&self) -> Option<&Attempt> {
+        self.attempts.last()
+    }
+
+    /// Iterate over the attempts, in the order they were made.
+    pub fn iter(&self) -> slice::Iter<'_, Attempt> {
+        self.attempts.iter()
+    }
+
+    /// Get the time from the start of the first attempt to the end of the last one, including
+    /// any delays between attempts.
+    pub fn elapsed(&self) -> Duration {
+        match (self.first(), self.last()) {
+            (Some(first), Some(last)) => (last.started + last.duration) - first.started,
+            _ => Duration::default(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Attempts {
+    type Item = &'a Attempt;
+    type IntoIter = slice::Iter<'a, Attempt>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Record the attempt that produced a response in its extensions.
+pub(crate) fn record(res: &mut Response, started: Instant) {
+    let attempt = Attempt {
+        started,
+        duration: started.elapsed(),
+        status: Some(res.status()),
+        reason: None,
+    };
+    res.extensions_mut().insert(Attempts {
+        attempts: vec![attempt],
+    });
+}
+
+/// Take the attempts out of a response that didn't complete the request, because of `reason`.
+pub(crate) fn take(res: &mut Response, reason: AttemptReason) -> Attempts {
+    let mut attempts = res
+        .extensions_mut()
+        .remove::<Attempts>()
+        .unwrap_or_default();
+    if let Some(last) = attempts.attempts.last_mut() {
+        last.reason = Some(reason);
+    }
+    attempts
+}
+
+/// Prepend earlier attempts to the attempts of a response.
+pub(crate) fn prepend(res: &mut Response, mut earlier: Attempts) {
+    if let Some(attempts) = res.extensions_mut().remove::<Attempts>() {
+        earlier.attempts.extend(attempts.attempts);
+    }
+    res.extensions_mut().insert(earlier);
+}
+
+/// Record an attempt that failed with an error after earlier attempts.
+pub(crate) fn failed(attempts: &mut Attempts, started: Instant, reason: AttemptReason) {
+    attempts.attempts.push(Attempt {
+        started,
+        duration: started.elapsed(),
+        status: None,
+        reason: Some(reason),
+    });
+}
+
+/// Append later attempts to earlier attempts.
+pub(crate) fn append(earlier: &mut Attempts, later: Attempts) {
+    earlier.attempts.extend(later.attempts);
+}
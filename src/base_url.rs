@@ -0,0 +1,116 @@
+//! Resolving request URLs against the base URL of a client.
+
+use futures::future::{self, BoxFuture};
+use url::Url;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+/// An error returned when the URL of a request isn't within the base URL of its client, and the
+/// client only allows URLs within its base URL.
+///
+/// See `ScopedBuilder::base_url` for how URLs are resolved.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::BaseUrlError;
+///
+/// let client = surf::Client::new()
+///     .scoped()
+///     .base_url("https://api.example.com/v1/".parse()?)
+///     .reject_other_urls()
+///     .build();
+/// let err = client.get("https://example.org/").await.unwrap_err();
+/// assert!(err.is::<BaseUrlError>());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseUrlError {
+    url: Url,
+    base: Url,
+}
+
+impl BaseUrlError {
+    /// Get the URL of the request.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the base URL of the client.
+    pub fn base(&self) -> &Url {
+        &self.base
+    }
+}
+
+impl fmt::Display for BaseUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "URL {} is not within base URL {}", self.url, self.base)
+    }
+}
+
+impl Error for BaseUrlError {}
+
+/// Resolve `input` against `base`.
+///
+/// Paths are resolved relative to the path of the base, whether or not they start with a `/`,
+/// while absolute URLs replace it.
+///
+/// # Panics
+///
+/// This will panic if the resolved URL is malformed.
+pub(crate) fn resolve(base: &Url, input: &str) -> Url {
+    let dir = directory(base);
+    let absolute = input.starts_with("//") || Url::parse(input).is_ok();
+    let url = if absolute {
+        dir.join(input)
+    } else {
+        dir.join(input.trim_start_matches('/'))
+    };
+    url.unwrap()
+}
+
+/// Check that `url` is within `base`, rejecting it otherwise.
+pub(crate) fn check(base: &Url, url: &Url) -> Result<(), Reject> {
+    let dir = directory(base);
+    if url.origin() == dir.origin() && url.path().starts_with(dir.path()) {
+        return Ok(());
+    }
+    Err(Reject(Box::new(BaseUrlError {
+        url: url.clone(),
+        base: base.clone(),
+    })))
+}
+
+/// Get the base URL without its query and fragment, and with a path ending in a `/`.
+fn directory(base: &Url) -> Url {
+    let mut dir = base.clone();
+    dir.set_query(None);
+    dir.set_fragment(None);
+    if !dir.path().ends_with('/') {
+        let path = format!("{}/", dir.path());
+        dir.set_path(&path);
+    }
+    dir
+}
+
+/// Middleware failing a request with the error resolving its URL.
+#[derive(Debug)]
+pub(crate) struct Reject(Box<BaseUrlError>);
+
+impl<C: HttpClient> Middleware<C> for Reject {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        _req: Request,
+        _client: C,
+        _next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(future::err(self.0.clone() as Exception))
+    }
+}
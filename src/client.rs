@@ -1,8 +1,30 @@
-use crate::http_client::HttpClient;
+use crate::base_url;
+use crate::codec::Codecs;
+use crate::http_client::{DrainOnDrop, HttpClient, LenientFraming, NormalizeText, Timeout};
+use crate::middleware::cache::Cache;
+use crate::middleware::cookies::CookieJar;
+use crate::middleware::redirect::{Redirect, RedirectPolicy};
+use crate::middleware::validate::Validate;
+use crate::middleware::{self, Middleware};
+use crate::pipeline::Pipeline;
 use crate::Request;
 
+use http::header::{HeaderMap, HeaderValue};
+use http::Method;
+use url::Url;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
 #[cfg(feature = "native-client")]
 use super::http_client::native::NativeClient;
+#[cfg(feature = "native-client")]
+use super::http_client::Config;
+#[cfg(feature = "native-client")]
+use crate::proxy::Proxy;
+#[cfg(feature = "native-client")]
+use crate::tls::Keystore;
 
 /// An HTTP client, capable of creating new `Request`s.
 ///
@@ -17,9 +39,37 @@ use super::http_client::native::NativeClient;
 /// let (str1, str2) = futures::future::try_join(req1, req2).await?;
 /// # Ok(()) }
 /// ```
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct Client<C: HttpClient> {
     client: C,
+    /// The URL the URLs of requests created from this client are resolved against.
+    base_url: Option<Url>,
+    /// Whether requests created from this client may have URLs outside of the base URL.
+    reject_other_urls: bool,
+    /// Headers set on every request created from this client.
+    headers: HeaderMap,
+    /// The timeout set on every request created from this client.
+    timeout: Option<Duration>,
+    /// Whether the framing of requests created from this client and their responses is checked.
+    lenient_framing: bool,
+    /// Whether the text of responses to requests created from this client is normalized.
+    normalize_text: bool,
+    /// How much of an unread response body to read when a response is dropped.
+    drain_on_drop: Option<u64>,
+    /// The codecs available to requests created from this client.
+    codecs: Option<Arc<Codecs>>,
+    /// The pipeline processing responses to requests created from this client.
+    pipeline: Option<Arc<Pipeline>>,
+    /// The rules every request created from this client is checked against.
+    validate: Option<Arc<Validate>>,
+    /// The middleware following redirects for requests created from this client.
+    redirect: Option<Arc<Redirect>>,
+    /// The cookie jar used by requests created from this client.
+    cookies: Option<CookieJar>,
+    /// The cache used by requests created from this client.
+    cache: Option<Cache>,
+    /// Middleware pushed onto every request created from this client.
+    middleware: Vec<Arc<dyn Middleware<C>>>,
 }
 
 #[cfg(feature = "native-client")]
@@ -37,6 +87,205 @@ impl Client<NativeClient> {
     pub fn new() -> Self {
         Self::with_client(NativeClient::new())
     }
+
+    /// Create a new `ClientBuilder` to configure a `Client`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use std::time::Duration;
+    ///
+    /// let client = surf::Client::builder()
+    ///     .tcp_keepalive(Duration::from_secs(30))
+    ///     .tcp_nodelay()
+    ///     .max_connections_per_host(8)
+    ///     .http_version(surf::http::Version::HTTP_11)
+    ///     .build();
+    /// # Ok(()) }
+    /// ```
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+/// A builder for configuring a `Client` backed by the native HTTP client.
+///
+/// Options that aren't supported by the active backend are ignored. In particular `window.fetch`
+/// doesn't expose any socket options in the browser.
+///
+/// Every built client has a connection pool of its own, which is shared by its clones and by
+/// clients created with `Client::scoped`. Requests created with the free functions, such as
+/// `surf::get`, share a pool with default settings.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+/// use std::time::Duration;
+///
+/// let client = surf::ClientBuilder::new()
+///     .tcp_keepalive(Duration::from_secs(30))
+///     .build();
+/// let string = client.get("https://httpbin.org/get").recv_string().await?;
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "native-client")]
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    config: Config,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "native-client")]
+impl ClientBuilder {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable TCP keepalive, sending a probe every `interval` on idle connections.
+    ///
+    /// By default TCP keepalive is disabled.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.config.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Set the `TCP_NODELAY` option on new connections, disabling Nagle's algorithm.
+    pub fn tcp_nodelay(mut self) -> Self {
+        self.config.tcp_nodelay = true;
+        self
+    }
+
+    /// Set the default timeout for every request.
+    ///
+    /// Requests that don't complete within the timeout, including reading the response body, are
+    /// cancelled and fail with a `TimeoutError`. By default requests don't time out.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set a header on every request, such as a `User-Agent` or an API key.
+    ///
+    /// Headers set on a request with `Request::set_header` replace the default value.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the value isn't a valid header value.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let client = surf::ClientBuilder::new()
+    ///     .default_header("User-Agent", "my-sdk/1.0")
+    ///     .default_header("X-Api-Key", "secret")
+    ///     .build();
+    /// let res = client.get("https://httpbin.org/headers").await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn default_header(mut self, key: &'static str, value: impl AsRef<str>) -> Self {
+        let value: HeaderValue = value.as_ref().parse().unwrap();
+        self.headers.insert(key, value);
+        self
+    }
+
+    /// Set headers on every request, replacing the default values of headers with the same name.
+    ///
+    /// Headers set on a request with `Request::set_header` replace the default values.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Send requests through a proxy.
+    ///
+    /// This can be called several times, for example to set different proxies for `http` and
+    /// `https` requests. Each request uses the first proxy that applies to it. Requests that no
+    /// proxy applies to use the proxies set in the environment, or in the system settings, see
+    /// the [proxy] submodule.
+    ///
+    /// [proxy]: proxy/index.html
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.config.proxies.push(proxy);
+        self
+    }
+
+    /// Limit the number of connections open to a single host at once.
+    ///
+    /// Requests that would need another connection wait until one becomes available. By default
+    /// the number of connections isn't limited.
+    pub fn max_connections_per_host(mut self, max: usize) -> Self {
+        self.config.max_connections_per_host = Some(max);
+        self
+    }
+
+    /// Limit the number of idle connections kept open to be reused by later requests.
+    ///
+    /// The curl backend keeps a single pool for all hosts, so the limit applies to all hosts
+    /// together. By default the backend picks a limit.
+    pub fn max_idle_connections(mut self, max: usize) -> Self {
+        self.config.max_idle_connections = Some(max);
+        self
+    }
+
+    /// Set whether connections are kept open to be reused by later requests, defaults to `true`.
+    ///
+    /// Disabling this closes every connection once its response has been read.
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.config.close_connections = !enabled;
+        self
+    }
+
+    /// Set the HTTP version to try first.
+    ///
+    /// This is a preference: another version is used if the server doesn't support it. By
+    /// default HTTP/2 is used when the server supports it over TLS.
+    pub fn http_version(mut self, version: http::Version) -> Self {
+        self.config.http_version = Some(version);
+        self
+    }
+
+    /// Present a client certificate to servers that ask for one.
+    ///
+    /// The certificate is read again when the keystore is reloaded, see the [tls] submodule.
+    /// Browsers manage client certificates themselves, so the keystore is ignored there.
+    ///
+    /// [tls]: tls/index.html
+    pub fn keystore(mut self, keystore: Keystore) -> Self {
+        self.config.keystore = Some(keystore);
+        self
+    }
+
+    /// Choose the proxies of requests with a proxy auto-config (PAC) file, see the [pac]
+    /// submodule.
+    ///
+    /// Proxies set with `ClientBuilder::proxy` take precedence over the file.
+    ///
+    /// [pac]: pac/index.html
+    #[cfg(feature = "pac")]
+    pub fn pac(mut self, pac: crate::pac::Pac) -> Self {
+        self.config.pac = Some(pac);
+        self
+    }
+
+    /// Create the `Client`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the backend fails to initialize.
+    pub fn build(self) -> Client<NativeClient> {
+        let mut client = Client::with_client(NativeClient::with_config(&self.config));
+        client.headers = self.headers;
+        client.timeout = self.timeout;
+        client
+    }
 }
 
 impl<C: HttpClient> Client<C> {
@@ -45,8 +294,171 @@ impl<C: HttpClient> Client<C> {
     #[doc(hidden)]
     #[allow(missing_doc_code_examples)]
     pub fn with_client(client: C) -> Self {
-        let client = client;
-        Self { client }
+        Self {
+            client,
+            base_url: None,
+            reject_other_urls: false,
+            headers: HeaderMap::new(),
+            timeout: None,
+            lenient_framing: false,
+            normalize_text: false,
+            drain_on_drop: None,
+            codecs: None,
+            pipeline: None,
+            validate: None,
+            redirect: None,
+            cookies: None,
+            cache: None,
+            middleware: vec![],
+        }
+    }
+
+    /// Create a builder for a client that shares this client's connection pool, but applies
+    /// additional settings to its requests.
+    ///
+    /// This is useful to configure clients per tenant or per feature without opening new
+    /// connections for each of them. The new client inherits all headers, the timeout and the
+    /// middleware of this client.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use std::time::Duration;
+    ///
+    /// let client = surf::Client::new();
+    /// let tenant = client
+    ///     .scoped()
+    ///     .header("X-Tenant", "chashu")
+    ///     .timeout(Duration::from_secs(5))
+    ///     .build();
+    /// let string = tenant.get("https://httpbin.org/get").recv_string().await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn scoped(&self) -> ScopedBuilder<C> {
+        ScopedBuilder {
+            client: self.clone(),
+        }
+    }
+
+    /// Create a new request using the client's backend and settings.
+    fn request(&self, method: Method, uri: impl AsRef<str>) -> Request<C> {
+        let uri = match &self.base_url {
+            Some(base) => base_url::resolve(base, uri.as_ref()),
+            None => uri.as_ref().to_owned().parse().unwrap(),
+        };
+        let rejected = match &self.base_url {
+            Some(base) if self.reject_other_urls => base_url::check(base, &uri).err(),
+            _ => None,
+        };
+        let mut req = Request::with_client(method, uri, self.client.clone());
+        if let Some(reject) = rejected {
+            req.push_middleware(Arc::new(reject));
+        }
+        for (name, value) in self.headers.iter() {
+            req.headers_mut().append(name, value.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            req.extensions_mut().insert(Timeout(timeout));
+        }
+        if self.lenient_framing {
+            req.extensions_mut().insert(LenientFraming);
+        }
+        if self.normalize_text {
+            req.extensions_mut().insert(NormalizeText);
+        }
+        if let Some(limit) = self.drain_on_drop {
+            req.extensions_mut().insert(DrainOnDrop(limit));
+        }
+        if let Some(codecs) = &self.codecs {
+            req.extensions_mut().insert(codecs.clone());
+        }
+        if let Some(pipeline) = &self.pipeline {
+            req.extensions_mut().insert(pipeline.clone());
+        }
+        if let Some(validate) = &self.validate {
+            req.push_middleware(validate.clone());
+        }
+        if let Some(redirect) = &self.redirect {
+            req.push_middleware(redirect.clone());
+        }
+        if let Some(cookies) = &self.cookies {
+            req.push_middleware(Arc::new(cookies.clone()));
+        }
+        for mw in &self.middleware {
+            req.push_middleware(mw.clone());
+        }
+        // After the middleware, so credentials it sets are part of the cache key.
+        if let Some(cache) = &self.cache {
+            req.push_middleware(Arc::new(cache.clone()));
+        }
+        req
+    }
+
+    /// Get the URL the URLs of requests are resolved against, if any.
+    ///
+    /// See `ScopedBuilder::base_url` for how URLs are resolved.
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
+    /// Get the cache used by requests created from this client, if any.
+    ///
+    /// See the [cache] submodule for how to inspect and purge it.
+    ///
+    /// [cache]: middleware/cache/index.html
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Follow redirects for every request created from this client, according to `policy`.
+    ///
+    /// By default redirects aren't followed. Redirects are followed before any other middleware
+    /// of the client runs, so the middleware runs again for every redirected request.
+    ///
+    /// See the [redirect] submodule for more information on following redirects.
+    ///
+    /// [redirect]: middleware/redirect/index.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use surf::middleware::redirect::RedirectPolicy;
+    ///
+    /// let client = surf::Client::new().redirect_policy(RedirectPolicy::limited(5));
+    /// let res = client.get("https://httpbin.org/redirect/3").await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect = Some(Arc::new(Redirect::with_policy(policy)));
+        self
+    }
+
+    /// Push middleware onto the middleware stack of every request created from this client.
+    ///
+    /// The middleware runs after the middleware of the client that is already on the stack, and
+    /// before middleware pushed onto a request with `Request::middleware`. Use
+    /// `ScopedBuilder::middleware` to add middleware to a copy of the client instead. See the
+    /// [middleware] submodule for more information on middleware.
+    ///
+    /// [middleware]: middleware/index.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let client = surf::Client::new().middleware(surf::middleware::logger::new());
+    /// let res = client.get("https://httpbin.org/get").await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn middleware(mut self, mw: impl Middleware<C>) -> Self {
+        self.middleware.push(Arc::new(mw));
+        self
     }
 
     /// Perform an HTTP `GET` request using the `Client` connection.
@@ -69,8 +481,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn get(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::GET, uri, self.client.clone())
+        self.request(Method::GET, uri)
     }
 
     /// Perform an HTTP `HEAD` request using the `Client` connection.
@@ -93,8 +504,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn head(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::HEAD, uri, self.client.clone())
+        self.request(Method::HEAD, uri)
     }
 
     /// Perform an HTTP `POST` request using the `Client` connection.
@@ -117,8 +527,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn post(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::POST, uri, self.client.clone())
+        self.request(Method::POST, uri)
     }
 
     /// Perform an HTTP `PUT` request using the `Client` connection.
@@ -141,8 +550,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn put(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::PUT, uri, self.client.clone())
+        self.request(Method::PUT, uri)
     }
 
     /// Perform an HTTP `DELETE` request using the `Client` connection.
@@ -165,8 +573,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn delete(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::DELETE, uri, self.client.clone())
+        self.request(Method::DELETE, uri)
     }
 
     /// Perform an HTTP `CONNECT` request using the `Client` connection.
@@ -189,8 +596,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn connect(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::CONNECT, uri, self.client.clone())
+        self.request(Method::CONNECT, uri)
     }
 
     /// Perform an HTTP `OPTIONS` request using the `Client` connection.
@@ -213,8 +619,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn options(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::OPTIONS, uri, self.client.clone())
+        self.request(Method::OPTIONS, uri)
     }
 
     /// Perform an HTTP `TRACE` request using the `Client` connection.
@@ -237,8 +642,7 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn trace(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::TRACE, uri, self.client.clone())
+        self.request(Method::TRACE, uri)
     }
 
     /// Perform an HTTP `PATCH` request using the `Client` connection.
@@ -261,7 +665,253 @@ impl<C: HttpClient> Client<C> {
     /// # Ok(()) }
     /// ```
     pub fn patch(&self, uri: impl AsRef<str>) -> Request<C> {
-        let uri = uri.as_ref().to_owned().parse().unwrap();
-        Request::with_client(http::Method::PATCH, uri, self.client.clone())
+        self.request(Method::PATCH, uri)
+    }
+}
+
+impl<C: HttpClient> Clone for Client<C> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            base_url: self.base_url.clone(),
+            reject_other_urls: self.reject_other_urls,
+            headers: self.headers.clone(),
+            timeout: self.timeout,
+            lenient_framing: self.lenient_framing,
+            normalize_text: self.normalize_text,
+            drain_on_drop: self.drain_on_drop,
+            codecs: self.codecs.clone(),
+            pipeline: self.pipeline.clone(),
+            validate: self.validate.clone(),
+            redirect: self.redirect.clone(),
+            cookies: self.cookies.clone(),
+            cache: self.cache.clone(),
+            middleware: self.middleware.clone(),
+        }
+    }
+}
+
+impl<C: HttpClient> fmt::Debug for Client<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("client", &self.client)
+            .field("base_url", &self.base_url)
+            .field("reject_other_urls", &self.reject_other_urls)
+            .field("headers", &self.headers)
+            .field("timeout", &self.timeout)
+            .field("lenient_framing", &self.lenient_framing)
+            .field("normalize_text", &self.normalize_text)
+            .field("drain_on_drop", &self.drain_on_drop)
+            .field("codecs", &self.codecs)
+            .field("pipeline", &self.pipeline)
+            .field("validate", &self.validate)
+            .field("redirect", &self.redirect)
+            .field("cookies", &self.cookies)
+            .field("cache", &self.cache)
+            .field("middleware", &self.middleware.len())
+            .finish()
+    }
+}
+
+/// A builder for a `Client` that shares its connection pool with another `Client`.
+///
+/// Created by [`Client::scoped`].
+///
+/// [`Client::scoped`]: struct.Client.html#method.scoped
+#[derive(Debug)]
+pub struct ScopedBuilder<C: HttpClient> {
+    client: Client<C>,
+}
+
+impl<C: HttpClient> ScopedBuilder<C> {
+    /// Resolve the URLs of requests against a base URL, replacing any base URL inherited from the
+    /// parent client.
+    ///
+    /// Paths are appended to the path of the base URL, whether or not they start with a `/`, so
+    /// with a base URL of `https://api.example.com/v1`, both `users` and `/users` resolve to
+    /// `https://api.example.com/v1/users`. Queries, fragments and `..` segments are resolved
+    /// as in links, and absolute URLs, such as the URLs of `Link` headers, replace the base URL.
+    /// Call `reject_other_urls` to fail requests with URLs outside of the base URL instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let api = surf::Client::new()
+    ///     .scoped()
+    ///     .base_url("https://api.github.com/repos/http-rs/".parse()?)
+    ///     .build();
+    /// let string = api.get("/surf/releases?per_page=10").recv_string().await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn base_url(mut self, url: Url) -> Self {
+        self.client.base_url = Some(url);
+        self
+    }
+
+    /// Fail requests with URLs outside of the base URL with a `BaseUrlError`, before anything is
+    /// sent.
+    ///
+    /// This keeps credentials set on the client from being sent to other hosts, when URLs come
+    /// from untrusted input. URLs are only checked when a base URL is set.
+    pub fn reject_other_urls(mut self) -> Self {
+        self.client.reject_other_urls = true;
+        self
+    }
+
+    /// Set a header on every request, replacing any value inherited from the parent client.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the value isn't a valid header value.
+    pub fn header(mut self, key: &'static str, value: impl AsRef<str>) -> Self {
+        let value: HeaderValue = value.as_ref().parse().unwrap();
+        self.client.headers.insert(key, value);
+        self
+    }
+
+    /// Set the timeout for every request, replacing any timeout inherited from the parent client.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client.timeout = Some(timeout);
+        self
+    }
+
+    /// Skip the checks for ambiguous framing on every request and its response.
+    ///
+    /// See `Request::lenient_framing` for when this is needed.
+    pub fn lenient_framing(mut self) -> Self {
+        self.client.lenient_framing = true;
+        self
+    }
+
+    /// Normalize the text of every response, removing byte order marks and replacing `\r\n`
+    /// line endings.
+    ///
+    /// See `Request::normalize_text` for how text is normalized.
+    pub fn normalize_text(mut self) -> Self {
+        self.client.normalize_text = true;
+        self
+    }
+
+    /// Read up to `limit` bytes of unread response bodies when responses are dropped, so their
+    /// connections can be reused.
+    ///
+    /// See `Request::drain_on_drop` for how this works.
+    pub fn drain_on_drop(mut self, limit: u64) -> Self {
+        self.client.drain_on_drop = Some(limit);
+        self
+    }
+
+    /// Set the codecs used by `Request::body_as` and `Response::body_as`, replacing any codecs
+    /// inherited from the parent client.
+    ///
+    /// See the [codec] submodule for more information on codecs.
+    ///
+    /// [codec]: codec/index.html
+    pub fn codecs(mut self, codecs: Codecs) -> Self {
+        self.client.codecs = Some(Arc::new(codecs));
+        self
+    }
+
+    /// Set the pipeline processing responses, replacing any pipeline inherited from the parent
+    /// client.
+    ///
+    /// See the [pipeline] submodule for more information on the pipeline.
+    ///
+    /// [pipeline]: pipeline/index.html
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.client.pipeline = Some(Arc::new(pipeline));
+        self
+    }
+
+    /// Check every request against a set of rules before it's sent, replacing any rules inherited
+    /// from the parent client.
+    ///
+    /// Requests breaking a rule fail with a `PolicyError`. See the [validate] submodule for more
+    /// information.
+    ///
+    /// [validate]: middleware/validate/index.html
+    pub fn validate(mut self, validate: Validate) -> Self {
+        self.client.validate = Some(Arc::new(validate));
+        self
+    }
+
+    /// Store cookies in a jar, and send them with matching requests, replacing any jar inherited
+    /// from the parent client.
+    ///
+    /// Cookies are handled for every redirected request when redirects are followed. See the
+    /// [cookies] submodule for more information.
+    ///
+    /// [cookies]: middleware/cookies/index.html
+    pub fn cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.client.cookies = Some(jar);
+        self
+    }
+
+    /// Cache responses, replacing any cache inherited from the parent client.
+    ///
+    /// Responses are cached after redirects are followed, cookies are handled and the other
+    /// middleware of the client ran, so responses to requests with credentials set by that
+    /// middleware are only returned for the same credentials. See the [cache] submodule for more
+    /// information.
+    ///
+    /// [cache]: middleware/cache/index.html
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.client.cache = Some(cache);
+        self
+    }
+
+    /// Push middleware onto the middleware stack, after any middleware inherited from the parent
+    /// client.
+    ///
+    /// See the [middleware] submodule for more information on middleware.
+    ///
+    /// [middleware]: middleware/index.html
+    pub fn middleware(mut self, mw: impl Middleware<C>) -> Self {
+        self.client.middleware.push(Arc::new(mw));
+        self
+    }
+
+    /// Push middleware that only applies to requests matching an origin pattern, such as
+    /// `https://*.internal.example.com`.
+    ///
+    /// The pattern is evaluated for every request, so a single client can sign requests for
+    /// internal services without leaking credentials to other hosts. See [`middleware::origin`]
+    /// for the pattern syntax.
+    ///
+    /// [`middleware::origin`]: middleware/fn.origin.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use futures::future::BoxFuture;
+    /// use surf::middleware::{HttpClient, Next, Request, Response};
+    ///
+    /// fn sign<'a, C: HttpClient>(
+    ///     mut req: Request,
+    ///     client: C,
+    ///     next: Next<'a, C>,
+    /// ) -> BoxFuture<'a, Result<Response, surf::Exception>> {
+    ///     req.headers_mut().insert("authorization", "Bearer secret".parse().unwrap());
+    ///     next.run(req, client)
+    /// }
+    ///
+    /// let client = surf::Client::new()
+    ///     .scoped()
+    ///     .middleware_for("*.internal.example.com", sign)
+    ///     .build();
+    /// client.get("https://billing.internal.example.com/invoices").await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn middleware_for(self, pattern: &str, mw: impl Middleware<C>) -> Self {
+        self.middleware(middleware::when(middleware::origin(pattern), mw))
+    }
+
+    /// Create the `Client`.
+    pub fn build(self) -> Client<C> {
+        self.client
     }
 }
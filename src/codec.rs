@@ -0,0 +1,207 @@
+//! Pluggable body formats.
+//!
+//! A [`Codec`] converts values of a type to and from bodies of a content type. Codecs are
+//! registered once in a [`Codecs`] registry attached to a `Client`, after which any request made
+//! through that client can send values with `Request::body_as` and read them with
+//! `Response::body_as` or `Request::recv_as`, without naming the format at the call site.
+//!
+//! Several codecs can be registered for the same type. Requests are encoded with the first one,
+//! and responses are decoded with the one matching their `Content-Type`, falling back to the
+//! first one.
+//!
+//! [`Codec`]: trait.Codec.html
+//! [`Codecs`]: struct.Codecs.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use serde::{Deserialize, Serialize};
+//! use surf::codec::{Codecs, Json};
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct Ip {
+//!     origin: String,
+//! }
+//!
+//! let codecs = Codecs::new().register::<Ip>(Json);
+//! let client = surf::Client::new().scoped().codecs(codecs).build();
+//! let ip: Ip = client.get("https://httpbin.org/ip").recv_as().await?;
+//! # Ok(()) }
+//! ```
+
+use crate::Exception;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Converts values of type `T` to and from bodies.
+pub trait Codec<T>: Send + Sync + 'static {
+    /// Get the content type of encoded bodies, such as `application/x-protobuf`.
+    fn content_type(&self) -> &str;
+
+    /// Encode a value.
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Exception>;
+
+    /// Decode a value.
+    fn decode(&self, body: &[u8]) -> Result<T, Exception>;
+}
+
+/// A codec for JSON, for any type implementing `Serialize` and `DeserializeOwned`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for Json {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Exception> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<T, Exception> {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+/// A codec for protobuf, for any `prost` message.
+#[cfg(feature = "protobuf")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protobuf;
+
+#[cfg(feature = "protobuf")]
+impl<T: prost::Message + Default> Codec<T> for Protobuf {
+    fn content_type(&self) -> &str {
+        "application/x-protobuf"
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Exception> {
+        let mut buf = Vec::with_capacity(value.encoded_len());
+        value.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<T, Exception> {
+        Ok(T::decode(body)?)
+    }
+}
+
+/// A codec for MessagePack, for any type implementing `Serialize` and `DeserializeOwned`.
+///
+/// Structs are encoded as maps with the names of their fields.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for MsgPack {
+    fn content_type(&self) -> &str {
+        "application/msgpack"
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Exception> {
+        Ok(rmp_serde::to_vec_named(value)?)
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<T, Exception> {
+        Ok(rmp_serde::from_slice(body)?)
+    }
+}
+
+/// A codec for XML, for any type implementing `Serialize` and `DeserializeOwned`.
+///
+/// The root element is named after the type.
+#[cfg(feature = "xml")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xml;
+
+#[cfg(feature = "xml")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for Xml {
+    fn content_type(&self) -> &str {
+        "application/xml"
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, Exception> {
+        Ok(quick_xml::se::to_string(value)?.into_bytes())
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<T, Exception> {
+        Ok(quick_xml::de::from_reader(body)?)
+    }
+}
+
+/// A registry of codecs, keyed by the type they encode.
+#[derive(Default)]
+pub struct Codecs {
+    codecs: HashMap<TypeId, Vec<Box<dyn Any + Send + Sync>>>,
+}
+
+impl Codecs {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a codec for `T`.
+    pub fn register<T: 'static>(mut self, codec: impl Codec<T>) -> Self {
+        let codec: Arc<dyn Codec<T>> = Arc::new(codec);
+        self.codecs
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .push(Box::new(codec));
+        self
+    }
+
+    fn all<T: 'static>(&self) -> impl Iterator<Item = &Arc<dyn Codec<T>>> {
+        self.codecs
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flatten()
+            .filter_map(|codec| codec.downcast_ref::<Arc<dyn Codec<T>>>())
+    }
+
+    /// Get the codec used to encode `T`.
+    pub fn encoder<T: 'static>(&self) -> Option<&dyn Codec<T>> {
+        self.all::<T>().next().map(|codec| &**codec)
+    }
+
+    /// Get the codec used to decode `T` from a body of `content_type`.
+    ///
+    /// Media type parameters such as `charset` are ignored when matching.
+    pub fn decoder<T: 'static>(&self, content_type: Option<&str>) -> Option<&dyn Codec<T>> {
+        let essence = |mime: &str| {
+            mime.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        };
+        let wanted = content_type.map(essence);
+        self.all::<T>()
+            .find(|codec| Some(essence(codec.content_type())) == wanted)
+            .or_else(|| self.all::<T>().next())
+            .map(|codec| &**codec)
+    }
+}
+
+impl fmt::Debug for Codecs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Codecs")
+            .field("types", &self.codecs.len())
+            .finish()
+    }
+}
+
+/// The error returned when no codec is registered for a type.
+pub(crate) fn missing<T>() -> Exception {
+    let msg = format!("no codec registered for `{}`", std::any::type_name::<T>());
+    io::Error::new(io::ErrorKind::InvalidInput, msg).into()
+}
@@ -0,0 +1,306 @@
+//! Fetching updates to a resource as deltas.
+//!
+//! Update clients that keep a local copy of a large resource, such as a package index, can often
+//! fetch only what changed. [`DeltaFetch`] sends a conditional request for a resource, with the
+//! strong `ETag` of the local copy as the base, and supports two ways of receiving only the
+//! difference:
+//!
+//! - Delta encoding in HTTP (RFC 3229): the request lists the instance manipulations the client
+//!   can apply in an `A-IM` header, and a server that supports one of them responds with
+//!   `226 IM Used` and a delta against the base. Manipulations, such as `vcdiff`, are registered
+//!   with `DeltaFetch::manipulation`.
+//! - Append-only resources, such as logs: the request asks for the bytes after the local copy
+//!   with a range, starting a few bytes early. The response is only used if these overlapping
+//!   bytes match the end of the local copy, as a check that the resource was only appended to.
+//!
+//! Servers that support neither respond with the whole resource, and unchanged resources get a
+//! `304 Not Modified` response without a body.
+//!
+//! [`DeltaFetch`]: struct.DeltaFetch.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::delta::{DeltaFetch, UpdateKind};
+//!
+//! let index = std::fs::read("index.json")?;
+//! let etag = std::fs::read_to_string("index.json.etag")?;
+//!
+//! let update = DeltaFetch::new(surf::Client::new(), "https://example.com/index.json".parse()?)
+//!     .base(index, etag)
+//!     .append_only(true)
+//!     .fetch()
+//!     .await?;
+//! if update.kind() != UpdateKind::Unchanged {
+//!     println!("received {} bytes", update.transferred());
+//!     std::fs::write("index.json", update.body())?;
+//!     std::fs::write("index.json.etag", update.etag().unwrap_or_default())?;
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::{Client, Exception, Response};
+
+use http::StatusCode;
+use url::Url;
+
+use std::fmt;
+use std::io;
+
+/// The number of bytes at the end of the local copy that are fetched again in append-only mode.
+const DEFAULT_OVERLAP: usize = 256;
+
+type Apply = Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, Exception> + Send + Sync>;
+
+/// Fetches updates to a resource as deltas against a local copy.
+pub struct DeltaFetch<C: HttpClient> {
+    client: Client<C>,
+    url: Url,
+    base: Option<(Vec<u8>, String)>,
+    manipulations: Vec<(String, Apply)>,
+    append_only: bool,
+    overlap: usize,
+}
+
+impl<C: HttpClient> DeltaFetch<C> {
+    /// Create a new instance, sending requests through `client`.
+    pub fn new(client: Client<C>, url: Url) -> Self {
+        Self {
+            client,
+            url,
+            base: None,
+            manipulations: vec![],
+            append_only: false,
+            overlap: DEFAULT_OVERLAP,
+        }
+    }
+
+    /// Set the local copy of the resource, and its `ETag`.
+    ///
+    /// Deltas are only requested against a strong `ETag`. With a weak one, the request is only
+    /// conditional, and a changed resource is fetched whole.
+    pub fn base(mut self, body: Vec<u8>, etag: impl Into<String>) -> Self {
+        self.base = Some((body, etag.into()));
+        self
+    }
+
+    /// Register an instance manipulation, which computes the new resource from the local copy
+    /// and a delta.
+    ///
+    /// `apply` is called with the local copy and the body of a `226 IM Used` response using the
+    /// manipulation named `name`, as registered with IANA, such as `vcdiff`.
+    pub fn manipulation(
+        mut self,
+        name: impl Into<String>,
+        apply: impl Fn(&[u8], &[u8]) -> Result<Vec<u8>, Exception> + Send + Sync + 'static,
+    ) -> Self {
+        self.manipulations
+            .push((name.into().to_ascii_lowercase(), Box::new(apply)));
+        self
+    }
+
+    /// Set whether the resource is only ever appended to, so only the bytes after the local copy
+    /// need to be fetched. Defaults to `false`.
+    pub fn append_only(mut self, enabled: bool) -> Self {
+        self.append_only = enabled;
+        self
+    }
+
+    /// Set how many bytes at the end of the local copy are fetched again in append-only mode, to
+    /// check that the resource was only appended to. Defaults to 256.
+    pub fn overlap(mut self, len: usize) -> Self {
+        self.overlap = len;
+        self
+    }
+
+    /// Fetch the current version of the resource.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the response has an error status, if a delta
+    /// can't be applied, or if the overlap of an append-only update doesn't match the local
+    /// copy, which means the resource changed in another way. In the last case, fetching without
+    /// `DeltaFetch::append_only` gets the whole resource.
+    pub async fn fetch(&self) -> Result<Update, Exception> {
+        let mut req = self.client.get(self.url.as_str());
+        let mut range_start = None;
+        if let Some((body, etag)) = &self.base {
+            req = req.set_header("If-None-Match", etag);
+            if !etag.starts_with("W/") {
+                if !self.manipulations.is_empty() {
+                    let names: Vec<_> =
+                        self.manipulations.iter().map(|(name, _)| &**name).collect();
+                    req = req.set_header("A-IM", names.join(", "));
+                } else if self.append_only && !body.is_empty() {
+                    let start = body.len() - self.overlap.min(body.len());
+                    req = req.set_header("Range", format!("bytes={}-", start));
+                    range_start = Some(start);
+                }
+            }
+        }
+
+        let mut res = req.await?;
+        let etag = res.header("ETag").map(str::to_string);
+        let status = res.status();
+        let base = self.base.as_ref().map(|(body, _)| &body[..]);
+        let (body, kind, transferred) = match (status, base, range_start) {
+            (StatusCode::NOT_MODIFIED, Some(base), _) => {
+                let etag = self.base.as_ref().map(|(_, etag)| etag.clone());
+                return Ok(Update {
+                    body: base.to_vec(),
+                    etag,
+                    kind: UpdateKind::Unchanged,
+                    transferred: 0,
+                });
+            }
+            (StatusCode::IM_USED, Some(base), _) => {
+                let delta = res.body_bytes().await?;
+                let body = self.apply(&res, base, &delta)?;
+                (body, UpdateKind::Delta, delta.len())
+            }
+            (StatusCode::PARTIAL_CONTENT, Some(base), Some(start)) => {
+                let tail = res.body_bytes().await?;
+                let body = append(&res, base, start, &tail)?;
+                (body, UpdateKind::Appended, tail.len())
+            }
+            _ => {
+                let mut res = res.error_for_status()?;
+                if status != StatusCode::OK {
+                    let msg = format!("unexpected status {}", status);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg).into());
+                }
+                let body = res.body_bytes().await?;
+                let len = body.len();
+                (body, UpdateKind::Full, len)
+            }
+        };
+        Ok(Update {
+            body,
+            etag,
+            kind,
+            transferred: transferred as u64,
+        })
+    }
+
+    /// Apply the instance manipulations of a `226 IM Used` response to the local copy.
+    fn apply(&self, res: &Response, base: &[u8], delta: &[u8]) -> Result<Vec<u8>, Exception> {
+        let base_etag = self.base.as_ref().map(|(_, etag)| etag.as_str());
+        if let Some(delta_base) = res.header("Delta-Base") {
+            if Some(delta_base) != base_etag {
+                return Err(delta_error("delta against another base").into());
+            }
+        }
+        let names: Vec<_> = res
+            .header("IM")
+            .ok_or_else(|| delta_error("missing IM header"))?
+            .split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty() && name != "identity")
+            .collect();
+        let name = match &names[..] {
+            [name] => name,
+            _ => return Err(delta_error("unsupported instance manipulations").into()),
+        };
+        let (_, apply) = self
+            .manipulations
+            .iter()
+            .find(|(registered, _)| registered == name)
+            .ok_or_else(|| delta_error("unsupported instance manipulation"))?;
+        apply(base, delta)
+    }
+}
+
+/// Append the tail of a resource to the local copy, checking that it overlaps the local copy
+/// from `start`.
+fn append(res: &Response, base: &[u8], start: usize, tail: &[u8]) -> io::Result<Vec<u8>> {
+    let range = res
+        .content_range()
+        .and_then(|range| Some((range.range()?, range)));
+    let (range, content_range) = range.ok_or_else(|| delta_error("invalid Content-Range"))?;
+    if range.start != start as u64 || range.end - range.start != tail.len() as u64 {
+        return Err(delta_error("response has another range than requested"));
+    }
+    if let Some(len) = content_range.complete_length() {
+        if len != range.end {
+            return Err(delta_error(
+                "response doesn't extend to the end of the resource",
+            ));
+        }
+    }
+    let overlap = &base[start..];
+    if tail.len() < overlap.len() || tail[..overlap.len()] != *overlap {
+        return Err(delta_error("resource changed other than by appending"));
+    }
+    let mut body = base.to_vec();
+    body.extend_from_slice(&tail[overlap.len()..]);
+    Ok(body)
+}
+
+fn delta_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("delta: {}", msg))
+}
+
+impl<C: HttpClient> fmt::Debug for DeltaFetch<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let manipulations: Vec<_> = self.manipulations.iter().map(|(name, _)| name).collect();
+        f.debug_struct("DeltaFetch")
+            .field("url", &self.url.as_str())
+            .field("base", &self.base.as_ref().map(|(_, etag)| etag))
+            .field("manipulations", &manipulations)
+            .field("append_only", &self.append_only)
+            .field("overlap", &self.overlap)
+            .finish()
+    }
+}
+
+/// How an `Update` was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    /// The resource didn't change.
+    Unchanged,
+    /// A delta against the local copy was applied.
+    Delta,
+    /// The bytes appended to the resource were fetched.
+    Appended,
+    /// The whole resource was fetched.
+    Full,
+}
+
+/// The current version of a resource, fetched with `DeltaFetch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    body: Vec<u8>,
+    etag: Option<String>,
+    kind: UpdateKind,
+    transferred: u64,
+}
+
+impl Update {
+    /// Get the body of the resource.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Take the body of the resource.
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Get the `ETag` of the resource, to use as the base of the next update.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Get how the update was obtained.
+    pub fn kind(&self) -> UpdateKind {
+        self.kind
+    }
+
+    /// Get the number of body bytes that were received.
+    pub fn transferred(&self) -> u64 {
+        self.transferred
+    }
+}
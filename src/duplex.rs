@@ -0,0 +1,65 @@
+//! Request bodies written while the response is read.
+
+use futures::channel::mpsc;
+use futures::io::AsyncWrite;
+use futures::prelude::*;
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::http_client::Body;
+
+/// The writing half of a request body, created by `Request::body_writer`.
+///
+/// Everything written is streamed to the server as the backend sends the body, which ends when
+/// the writer is closed or dropped. Writes wait until the backend took the previous write, so a
+/// slow connection slows down the writer rather than buffering the body in memory.
+///
+/// Writes fail with a `BrokenPipe` error once the request has failed or completed, and the body
+/// isn't read anymore.
+#[derive(Debug)]
+pub struct BodyWriter {
+    sender: mpsc::Sender<io::Result<Vec<u8>>>,
+}
+
+/// Create a body that's read as it's written to the returned writer.
+pub(crate) fn pipe() -> (Body, BodyWriter) {
+    let (sender, receiver) = mpsc::channel(0);
+    let body = Body::from_reader(receiver.into_async_read());
+    (body, BodyWriter { sender })
+}
+
+fn broken_pipe() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "the request body isn't read anymore",
+    )
+}
+
+impl AsyncWrite for BodyWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        futures::ready!(self.sender.poll_ready(cx)).map_err(|_| broken_pipe())?;
+        self.sender
+            .start_send(Ok(buf.to_vec()))
+            .map_err(|_| broken_pipe())?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Writes are handed to the backend directly, there's nothing to flush.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,230 @@
+//! Classifying errors.
+
+use http::StatusCode;
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use crate::framing::FramingError;
+use crate::middleware::decompression_limit::LimitExceeded;
+use crate::middleware::rate_limit::RateLimited;
+use crate::middleware::redirect::{RedirectError, RedirectLoop};
+use crate::middleware::validate::PolicyError;
+use crate::{BaseUrlError, Exception, PreconditionFailed, TimeoutError, UploadInterrupted};
+
+/// The kind of an `Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The connection to the server couldn't be established, or broke down.
+    Connect,
+    /// The host name couldn't be resolved.
+    Dns,
+    /// The TLS handshake failed, for example because of an invalid certificate.
+    Tls,
+    /// The request didn't complete within its timeout.
+    Timeout,
+    /// The response had an error status, see `Response::error_for_status`.
+    Status(StatusCode),
+    /// The response body couldn't be decoded, such as invalid JSON or UTF-8.
+    Decode,
+    /// The server broke the protocol, for example by sending a response with ambiguous framing.
+    Protocol,
+    /// A middleware of this crate rejected the request or response, such as a redirect loop or a
+    /// failed validation rule.
+    Middleware,
+    /// Another I/O error.
+    Io,
+    /// Any other error, including errors from middleware outside this crate.
+    Other,
+}
+
+/// An error sending a request or reading its response, classified by kind.
+///
+/// Requests and middleware fail with a boxed `Exception`, which can be converted into this type
+/// to find out what went wrong. Errors of this type convert back into an `Exception`, so `?`
+/// keeps working in functions returning either.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::{Error, ErrorKind};
+///
+/// match surf::get("https://httpbin.org/get").await.map_err(Error::from) {
+///     Ok(res) => println!("{}", res.status()),
+///     Err(err) => match err.kind() {
+///         ErrorKind::Connect | ErrorKind::Dns | ErrorKind::Timeout => println!("try again later"),
+///         _ => return Err(err.into()),
+///     },
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    inner: Exception,
+}
+
+impl Error {
+    /// Create a new instance of a kind, wrapping another error.
+    pub fn new(kind: ErrorKind, inner: impl Into<Exception>) -> Self {
+        Self {
+            kind,
+            inner: inner.into(),
+        }
+    }
+
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Get the status of the response, for `ErrorKind::Status` errors.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self.kind {
+            ErrorKind::Status(status) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Get a reference to the wrapped error.
+    pub fn get_ref(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        &*self.inner
+    }
+
+    /// Get the wrapped error.
+    pub fn into_inner(self) -> Exception {
+        self.inner
+    }
+}
+
+impl From<Exception> for Error {
+    fn from(err: Exception) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => *err,
+            Err(err) => Self {
+                kind: classify(&*err),
+                inner: err,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.inner.source()
+    }
+}
+
+/// The error of `Response::error_for_status`.
+#[derive(Debug)]
+pub(crate) struct StatusError(pub(crate) StatusCode);
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request failed with status {}", self.0)
+    }
+}
+
+impl StdError for StatusError {}
+
+/// Find out the kind of an error from its type.
+fn classify(err: &(dyn StdError + 'static)) -> ErrorKind {
+    if let Some(err) = err.downcast_ref::<Error>() {
+        return err.kind;
+    }
+    if let Some(StatusError(status)) = err.downcast_ref() {
+        return ErrorKind::Status(*status);
+    }
+    if err.is::<PreconditionFailed>() {
+        return ErrorKind::Status(StatusCode::PRECONDITION_FAILED);
+    }
+    if err.is::<TimeoutError>() {
+        return ErrorKind::Timeout;
+    }
+    if err.is::<FramingError>() {
+        return ErrorKind::Protocol;
+    }
+    if err.is::<UploadInterrupted>() {
+        return ErrorKind::Connect;
+    }
+    if err.is::<RedirectError>()
+        || err.is::<RedirectLoop>()
+        || err.is::<PolicyError>()
+        || err.is::<BaseUrlError>()
+        || err.is::<LimitExceeded>()
+        || err.is::<RateLimited>()
+    {
+        return ErrorKind::Middleware;
+    }
+    if err.is::<serde_json::Error>()
+        || err.is::<serde_urlencoded::de::Error>()
+        || err.is::<std::string::FromUtf8Error>()
+    {
+        return ErrorKind::Decode;
+    }
+    #[cfg(all(feature = "curl-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<isahc::Error>() {
+            return match err {
+                isahc::Error::ConnectFailed | isahc::Error::NoResponse => ErrorKind::Connect,
+                isahc::Error::CouldntResolveHost | isahc::Error::CouldntResolveProxy => {
+                    ErrorKind::Dns
+                }
+                isahc::Error::BadClientCertificate(_)
+                | isahc::Error::BadServerCertificate(_)
+                | isahc::Error::SSLConnectFailed(_)
+                | isahc::Error::SSLEngineError(_) => ErrorKind::Tls,
+                isahc::Error::Timeout => ErrorKind::Timeout,
+                isahc::Error::InvalidContentEncoding(_) | isahc::Error::InvalidUtf8 => {
+                    ErrorKind::Decode
+                }
+                isahc::Error::InvalidHttpFormat(_) => ErrorKind::Protocol,
+                isahc::Error::Io(err) => classify(err),
+                _ => ErrorKind::Other,
+            };
+        }
+    }
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            return if err.is_connect() {
+                ErrorKind::Connect
+            } else if err.is_parse() {
+                ErrorKind::Protocol
+            } else {
+                ErrorKind::Other
+            };
+        }
+    }
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        // Errors reading a body are wrapped in an `io::Error`.
+        if let Some(inner) = err.get_ref() {
+            match classify(inner) {
+                ErrorKind::Other | ErrorKind::Io => {}
+                kind => return kind,
+            }
+        }
+        return match err.kind() {
+            io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => ErrorKind::Connect,
+            io::ErrorKind::InvalidData => ErrorKind::Decode,
+            _ => ErrorKind::Io,
+        };
+    }
+    ErrorKind::Other
+}
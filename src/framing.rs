@@ -0,0 +1,168 @@
+//! Rejecting messages with ambiguous framing.
+//!
+//! HTTP/1.1 messages have two ways to delimit their body: `Content-Length` and
+//! `Transfer-Encoding`. When a message is ambiguous about which applies, or has headers that
+//! parse differently in different implementations, a proxy and the server behind it can
+//! disagree about where one message ends and the next begins. An attacker can use this to
+//! smuggle a request past the proxy, or to poison responses to other users of a connection.
+//!
+//! By default requests and responses are checked for the known ambiguities before a request is
+//! sent and before a response is returned, and fail with a `FramingError`.
+
+use http::header::{HeaderMap, CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use http::Version;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::http_client::{Request, Response};
+
+/// What's ambiguous about the framing of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramingErrorKind {
+    /// The message has both a `Content-Length` and a `Transfer-Encoding` header.
+    ConflictingFraming,
+    /// The `Content-Length` header isn't a number, or there are several different ones.
+    InvalidContentLength,
+    /// The `Transfer-Encoding` header is malformed, lists `chunked` anywhere but last in a
+    /// request or more than once, or is sent in an HTTP/1.0 message.
+    InvalidTransferEncoding,
+    /// The request has more than one `Host` header.
+    DuplicateHost,
+}
+
+/// An error returned when a request or response has ambiguous framing, which could be used to
+/// smuggle requests.
+///
+/// Use `Request::lenient_framing` or `ScopedBuilder::lenient_framing` to skip these checks, for
+/// example to talk to a broken server that can be trusted.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::FramingError;
+///
+/// let res = surf::post("https://httpbin.org/post")
+///     .set_header("Content-Length", "5")
+///     .set_header("Transfer-Encoding", "chunked")
+///     .await;
+/// match res {
+///     Err(err) if err.is::<FramingError>() => println!("{}", err),
+///     res => println!("{:?}", res?.status()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FramingError {
+    kind: FramingErrorKind,
+    response: bool,
+}
+
+impl FramingError {
+    /// Get what's ambiguous about the message.
+    pub fn kind(&self) -> &FramingErrorKind {
+        &self.kind
+    }
+
+    /// Check whether the response was rejected, rather than the request.
+    pub fn is_response(&self) -> bool {
+        self.response
+    }
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = if self.response { "response" } else { "request" };
+        match &self.kind {
+            FramingErrorKind::ConflictingFraming => write!(
+                f,
+                "{} has both a Content-Length and a Transfer-Encoding header",
+                message
+            ),
+            FramingErrorKind::InvalidContentLength => {
+                write!(f, "{} has an invalid Content-Length header", message)
+            }
+            FramingErrorKind::InvalidTransferEncoding => {
+                write!(f, "{} has an invalid Transfer-Encoding header", message)
+            }
+            FramingErrorKind::DuplicateHost => write!(f, "{} has several Host headers", message),
+        }
+    }
+}
+
+impl Error for FramingError {}
+
+/// Check the framing of a request before it's sent.
+pub(crate) fn check_request(req: &Request) -> Result<(), FramingError> {
+    let fail = |kind| FramingError {
+        kind,
+        response: false,
+    };
+    check(req.headers(), req.version(), false).map_err(fail)?;
+    if req.headers().get_all(HOST).iter().count() > 1 {
+        return Err(fail(FramingErrorKind::DuplicateHost));
+    }
+    Ok(())
+}
+
+/// Check the framing of a response before it's returned.
+pub(crate) fn check_response(res: &Response) -> Result<(), FramingError> {
+    check(res.headers(), res.version(), true).map_err(|kind| FramingError {
+        kind,
+        response: true,
+    })
+}
+
+/// Check the framing headers of a message.
+fn check(headers: &HeaderMap, version: Version, response: bool) -> Result<(), FramingErrorKind> {
+    let mut length = None;
+    for value in headers.get_all(CONTENT_LENGTH) {
+        let value = value
+            .to_str()
+            .map_err(|_| FramingErrorKind::InvalidContentLength)?;
+        // Repeated identical values, as in `Content-Length: 5, 5`, are allowed by RFC 7230.
+        for part in value.split(',').map(str::trim) {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(FramingErrorKind::InvalidContentLength);
+            }
+            let part = part.trim_start_matches('0');
+            match length {
+                Some(length) if length != part => {
+                    return Err(FramingErrorKind::InvalidContentLength)
+                }
+                _ => length = Some(part),
+            }
+        }
+    }
+
+    let mut codings = Vec::new();
+    for value in headers.get_all(TRANSFER_ENCODING) {
+        let value = value
+            .to_str()
+            .map_err(|_| FramingErrorKind::InvalidTransferEncoding)?;
+        for coding in value.split(',').map(str::trim) {
+            // Only plain tokens, so `chunked;` or `"chunked"` can't be read two ways.
+            let token = |b: u8| b.is_ascii_alphanumeric() || b == b'-';
+            if coding.is_empty() || !coding.bytes().all(token) {
+                return Err(FramingErrorKind::InvalidTransferEncoding);
+            }
+            codings.push(coding.to_ascii_lowercase());
+        }
+    }
+    if codings.is_empty() {
+        return Ok(());
+    }
+    if length.is_some() {
+        return Err(FramingErrorKind::ConflictingFraming);
+    }
+    let chunked = codings.iter().filter(|coding| *coding == "chunked").count();
+    let last = codings.last().map(String::as_str) == Some("chunked");
+    // A response may end its body by closing the connection instead; a request can't.
+    if version == Version::HTTP_10 || chunked > 1 || (chunked == 1 && !last) || (!response && !last)
+    {
+        return Err(FramingErrorKind::InvalidTransferEncoding);
+    }
+    Ok(())
+}
@@ -0,0 +1,246 @@
+//! Experimental gRPC-Web clients.
+//!
+//! [gRPC-Web] carries gRPC calls over plain HTTP/1.1, which makes gRPC services reachable from
+//! browsers and simple clients through a proxy such as Envoy. [`GrpcWebClient`] supports unary
+//! calls using the binary `application/grpc-web+proto` format. Streaming calls and the base64
+//! `application/grpc-web-text` format aren't supported.
+//!
+//! Messages are sent as length-prefixed frames, and the call status arrives either in the
+//! response headers or in a trailer frame at the end of the body. Calls that don't end with
+//! status `0` fail with a [`GrpcStatus`].
+//!
+//! [gRPC-Web]: https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md
+//! [`GrpcWebClient`]: struct.GrpcWebClient.html
+//! [`GrpcStatus`]: struct.GrpcStatus.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::grpc_web::GrpcWebClient;
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloRequest {
+//!     #[prost(string, tag = "1")]
+//!     name: String,
+//! }
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloReply {
+//!     #[prost(string, tag = "1")]
+//!     message: String,
+//! }
+//!
+//! let client = GrpcWebClient::new(surf::Client::new(), "https://envoy.example.com".parse()?);
+//! let request = HelloRequest { name: "chashu".to_string() };
+//! let reply: HelloReply = client.call("helloworld.Greeter", "SayHello", &request).await?;
+//! println!("{}", reply.message);
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::{Client, Exception};
+
+use url::Url;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+const CONTENT_TYPE: &str = "application/grpc-web+proto";
+
+/// Frame flag marking a trailer frame.
+const TRAILER_FLAG: u8 = 0x80;
+
+/// A client making unary gRPC-Web calls to a single server.
+#[derive(Debug, Clone)]
+pub struct GrpcWebClient<C: HttpClient> {
+    client: Client<C>,
+    base: Url,
+}
+
+impl<C: HttpClient> GrpcWebClient<C> {
+    /// Create a new instance calling services on the server at `base`.
+    pub fn new(client: Client<C>, base: Url) -> Self {
+        Self { client, base }
+    }
+
+    /// Make a unary call.
+    ///
+    /// `service` is the fully qualified name of the service, such as `helloworld.Greeter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GrpcStatus` if the call ends with a non-zero status, or any other error if the
+    /// request fails or the response isn't valid gRPC-Web.
+    pub async fn call<Req, Res>(
+        &self,
+        service: &str,
+        method: &str,
+        request: &Req,
+    ) -> Result<Res, Exception>
+    where
+        Req: prost::Message,
+        Res: prost::Message + Default,
+    {
+        let mut message = Vec::with_capacity(request.encoded_len());
+        request.encode(&mut message)?;
+        let mut body = Vec::with_capacity(message.len() + 5);
+        body.push(0);
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(&message);
+
+        let url = format!(
+            "{}/{}/{}",
+            self.base.as_str().trim_end_matches('/'),
+            service,
+            method
+        );
+        let mut res = self
+            .client
+            .post(url)
+            .set_header("Content-Type", CONTENT_TYPE)
+            .set_header("Accept", CONTENT_TYPE)
+            .set_header("X-Grpc-Web", "1")
+            .body_bytes(body)
+            .await?;
+        if !res.status().is_success() {
+            let msg = format!("gRPC-Web call failed with HTTP status {}", res.status());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg).into());
+        }
+
+        // A trailers-only response carries the status in the headers.
+        let mut trailers: HashMap<String, String> = res
+            .headers()
+            .iter()
+            .filter(|(name, _)| name.starts_with("grpc-"))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        let body = res.body_bytes().await?;
+        let mut message = None;
+        let mut rest = &body[..];
+        while !rest.is_empty() {
+            if rest.len() < 5 {
+                return Err(invalid("truncated frame header"));
+            }
+            let flags = rest[0];
+            let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+            if rest.len() - 5 < len {
+                return Err(invalid("truncated frame"));
+            }
+            let payload = &rest[5..5 + len];
+            rest = &rest[5 + len..];
+
+            if flags & TRAILER_FLAG != 0 {
+                trailers.extend(parse_trailers(payload));
+            } else if message.is_none() {
+                message = Some(payload.to_vec());
+            } else {
+                return Err(invalid("unary call returned more than one message"));
+            }
+        }
+
+        let status = GrpcStatus::from_trailers(trailers)?;
+        if status.code != 0 {
+            return Err(status.into());
+        }
+        match message {
+            Some(message) => Ok(Res::decode(&message[..])?),
+            None => Err(invalid("unary call returned no message")),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> Exception {
+    io::Error::new(io::ErrorKind::InvalidData, format!("gRPC-Web: {}", msg)).into()
+}
+
+/// Parse a trailer frame, formatted as HTTP/1 headers.
+fn parse_trailers(payload: &[u8]) -> HashMap<String, String> {
+    String::from_utf8_lossy(payload)
+        .split("\r\n")
+        .filter_map(|line| {
+            let i = line.find(':')?;
+            let name = line[..i].trim().to_ascii_lowercase();
+            Some((name, line[i + 1..].trim().to_string()))
+        })
+        .collect()
+}
+
+/// Decode a percent-encoded `grpc-message`.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The status of a failed gRPC call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcStatus {
+    code: u32,
+    message: String,
+    metadata: HashMap<String, String>,
+}
+
+impl GrpcStatus {
+    fn from_trailers(mut trailers: HashMap<String, String>) -> Result<Self, Exception> {
+        let code = trailers
+            .remove("grpc-status")
+            .ok_or_else(|| invalid("response has no grpc-status"))?;
+        let code = code
+            .parse()
+            .map_err(|_| invalid("response has an invalid grpc-status"))?;
+        let message = trailers
+            .remove("grpc-message")
+            .map(|message| percent_decode(&message))
+            .unwrap_or_default();
+        Ok(Self {
+            code,
+            message,
+            metadata: trailers,
+        })
+    }
+
+    /// Get the status code, such as `5` for `NOT_FOUND`.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Get the status message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the remaining trailers.
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+}
+
+impl fmt::Display for GrpcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gRPC status {}: {}", self.code, self.message)
+    }
+}
+
+impl Error for GrpcStatus {}
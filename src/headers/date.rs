@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// A timestamp as used in HTTP headers such as `Date`, `Last-Modified` and `If-Modified-Since`.
+///
+/// Dates are formatted as IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`). Parsing also accepts
+/// the obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime
+/// (`Sun Nov  6 08:49:37 1994`) formats, as recipients are required to. HTTP dates have a
+/// resolution of one second, so sub-second precision is truncated.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use surf::headers::HttpDate;
+///
+/// let date: HttpDate = "Sunday, 06-Nov-94 08:49:37 GMT".parse()?;
+/// assert_eq!(date.to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// assert_eq!(date, HttpDate::from(UNIX_EPOCH + Duration::from_secs(784111777)));
+/// # Ok::<(), surf::headers::ParseHttpDateError>(())
+/// ```
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::headers::HttpDate;
+///
+/// let cached = HttpDate::now();
+/// let res = surf::get("https://httpbin.org/cache")
+///     .set_header("If-Modified-Since", cached.to_string())
+///     .await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HttpDate(SystemTime);
+
+impl HttpDate {
+    /// Get the current time.
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// Get the timestamp as a `SystemTime`.
+    pub fn system_time(&self) -> SystemTime {
+        self.0
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    /// # Panics
+    ///
+    /// This will panic if `time` is before 1970 or after 9999, which HTTP dates can't express.
+    fn from(time: SystemTime) -> Self {
+        // Round-trip through the formatter to truncate to whole seconds.
+        Self(httpdate::HttpDate::from(time).into())
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(date: HttpDate) -> Self {
+        date.0
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = ParseHttpDateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        httpdate::parse_http_date(s.trim())
+            .map(Self)
+            .map_err(|_| ParseHttpDateError(()))
+    }
+}
+
+impl fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&httpdate::HttpDate::from(self.0), f)
+    }
+}
+
+/// The error returned when parsing an invalid `HttpDate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHttpDateError(());
+
+impl fmt::Display for ParseHttpDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid HTTP date")
+    }
+}
+
+impl Error for ParseHttpDateError {}
@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An entity tag, as used in the `ETag`, `If-Match` and `If-None-Match` headers.
+///
+/// Entity tags are opaque strings identifying a version of a resource. Weak tags, written with a
+/// `W/` prefix, only identify equivalent versions, rather than byte for byte identical ones.
+///
+/// # Examples
+///
+/// ```
+/// use surf::headers::ETag;
+///
+/// let etag: ETag = r#"W/"0815""#.parse()?;
+/// assert!(etag.is_weak());
+/// assert_eq!(etag.tag(), "0815");
+/// assert!(etag.weak_eq(&ETag::strong("0815")));
+/// assert!(!etag.strong_eq(&ETag::strong("0815")));
+/// # Ok::<(), surf::headers::ParseETagError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ETag {
+    tag: String,
+    weak: bool,
+}
+
+impl ETag {
+    /// Create a strong entity tag.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `tag` contains a `"`, or characters that aren't visible ASCII.
+    pub fn strong(tag: impl Into<String>) -> Self {
+        Self::new(tag.into(), false)
+    }
+
+    /// Create a weak entity tag.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `tag` contains a `"`, or characters that aren't visible ASCII.
+    pub fn weak(tag: impl Into<String>) -> Self {
+        Self::new(tag.into(), true)
+    }
+
+    fn new(tag: String, weak: bool) -> Self {
+        assert!(is_valid(&tag), "invalid entity tag");
+        Self { tag, weak }
+    }
+
+    /// Get the tag, without quotes and the `W/` prefix.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns `true` if the tag is weak.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Compare two tags with the strong comparison function: both must be strong, and have the
+    /// same tag.
+    ///
+    /// This is how `If-Match` compares tags.
+    pub fn strong_eq(&self, other: &ETag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Compare two tags with the weak comparison function: they must have the same tag, whether
+    /// they are weak or not.
+    ///
+    /// This is how `If-None-Match` compares tags.
+    pub fn weak_eq(&self, other: &ETag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+/// Check whether a string only has characters allowed in entity tags.
+fn is_valid(tag: &str) -> bool {
+    tag.bytes()
+        .all(|byte| byte == 0x21 || (0x23..=0x7e).contains(&byte))
+}
+
+impl FromStr for ETag {
+    type Err = ParseETagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (weak, quoted) = match s.get(..2) {
+            Some("W/") => (true, &s[2..]),
+            _ => (false, s),
+        };
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return Err(ParseETagError(()));
+        }
+        let tag = &quoted[1..quoted.len() - 1];
+        if !is_valid(tag) {
+            return Err(ParseETagError(()));
+        }
+        Ok(Self {
+            tag: tag.to_string(),
+            weak,
+        })
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// The error returned when parsing an invalid `ETag`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseETagError(());
+
+impl fmt::Display for ParseETagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid entity tag")
+    }
+}
+
+impl Error for ParseETagError {}
@@ -1,5 +1,11 @@
 //! HTTP Headers.
 
+mod date;
+mod etag;
+
+pub use date::{HttpDate, ParseHttpDateError};
+pub use etag::{ETag, ParseETagError};
+
 use std::iter::{IntoIterator, Iterator};
 
 /// A collection of HTTP Headers.
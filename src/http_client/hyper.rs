@@ -16,7 +16,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use super::{Body, HttpClient, Request, Response};
+use super::{Body, DangerAcceptInvalidCerts, HttpClient, Isolated, Request, Response};
 
 /// Hyper HTTP Client.
 #[derive(Debug)]
@@ -27,10 +27,14 @@ pub struct HyperClient {
 impl HyperClient {
     /// Create a new instance.
     pub(crate) fn new() -> Self {
+        Self::with_tls(TlsConnector::new().unwrap())
+    }
+
+    /// Create a new instance, connecting with a TLS connector.
+    fn with_tls(tls_connector: TlsConnector) -> Self {
         // Create a TLS decoder, TCP stream, and combine them into a `Connector` to be passed to
         // Hyper.
         let tcp_connector = RuntimeTcpConnector::new();
-        let tls_connector = TlsConnector::new().unwrap();
         let https = HttpsConnector::from((tcp_connector, tls_connector));
 
         // Create the Hyper client with the `Connector`, and make sure we use `runtime-tokio` to
@@ -59,7 +63,21 @@ impl HttpClient for HyperClient {
     type Error = hyper::error::Error;
 
     fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Self::Error>> {
-        let client = self.client.clone();
+        // Isolated requests get a client of their own, so they don't share connections with any
+        // other request. So do requests skipping certificate verification, so their connections
+        // aren't reused by requests that verify certificates.
+        let client = if req.extensions().get::<DangerAcceptInvalidCerts>().is_some() {
+            let tls_connector = TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .unwrap();
+            HyperClient::with_tls(tls_connector).client
+        } else if req.extensions().get::<Isolated>().is_some() {
+            HyperClient::new().client
+        } else {
+            self.client.clone()
+        };
         Box::pin(async move {
             // Convert the request body.
             let (parts, body) = req.into_parts();
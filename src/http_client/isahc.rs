@@ -1,13 +1,50 @@
-use super::{Body, HttpClient, Request, Response};
+use super::{
+    Body, Config, DangerAcceptInvalidCerts, Duplex, FreshConnection, HttpClient, Isolated, Request,
+    Response, Reused,
+};
+use crate::pipeline::content_decoded;
+use crate::proxy::{self, Proxy};
+use crate::tls::Keystore;
 
 use futures::future::BoxFuture;
+use futures::io::AsyncRead;
+use http::header::{HeaderValue, CONNECTION, CONTENT_ENCODING, EXPECT};
+use isahc::config::{ClientCertificate, DnsCache, PrivateKey};
+use isahc::RequestBuilderExt;
 
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// The number of hosts remembered to have a connection kept open, see `Inner::kept_alive`.
+const MAX_KEPT_ALIVE: usize = 1024;
 
 /// Curl-based HTTP Client.
+///
+/// Clones share the same connection pool and configuration.
 #[derive(Debug)]
 pub struct IsahcClient {
-    client: Arc<isahc::HttpClient>,
+    inner: Arc<Inner>,
+}
+
+/// The state shared by the clones of a client, so cloning it for every middleware is cheap.
+#[derive(Debug)]
+struct Inner {
+    /// The client, and the generation of the keystore it was built for.
+    client: Mutex<(u64, Arc<isahc::HttpClient>)>,
+    /// The client sending requests over new connections, built when it's first needed, and the
+    /// generation of the keystore it was built for.
+    fresh: Mutex<Option<(u64, Arc<isahc::HttpClient>)>>,
+    /// The hosts, or proxies, that the client has received a response from over a connection it
+    /// may have kept open. Curl doesn't tell which connection a request was sent over, so requests
+    /// to these are flagged with `Reused`.
+    kept_alive: Mutex<HashSet<String>>,
+    config: Config,
+    /// The configured proxies, followed by the ones set in the environment and in the system
+    /// settings.
+    proxies: Vec<Proxy>,
 }
 
 impl Default for IsahcClient {
@@ -19,16 +56,169 @@ impl Default for IsahcClient {
 impl IsahcClient {
     /// Create a new instance.
     pub fn new() -> Self {
+        Self::with_config(&Config::default())
+    }
+
+    /// Create a new instance from a `Config`.
+    pub(crate) fn with_config(config: &Config) -> Self {
+        let mut proxies = config.proxies.clone();
+        proxies.extend(proxy::from_env());
+        #[cfg(feature = "system-proxy")]
+        proxies.extend(proxy::from_system());
+        let generation = config.keystore.as_ref().map_or(0, Keystore::generation);
+        let inner = Inner {
+            client: Mutex::new((generation, Arc::new(builder(config).build().unwrap()))),
+            fresh: Mutex::new(None),
+            kept_alive: Mutex::new(HashSet::new()),
+            config: config.clone(),
+            proxies,
+        };
         Self {
-            client: Arc::new(isahc::HttpClient::new().unwrap()),
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl IsahcClient {
+    /// Get the client to send requests with, replacing it if the keystore was reloaded.
+    fn client(&self) -> Result<Arc<isahc::HttpClient>, isahc::Error> {
+        let generation = match &self.inner.config.keystore {
+            Some(keystore) => keystore.generation(),
+            None => return Ok(self.inner.client.lock().unwrap().1.clone()),
+        };
+        let mut client = self.inner.client.lock().unwrap();
+        if client.0 != generation {
+            // A new client has a connection pool and TLS session cache of its own, so its
+            // connections are authenticated with the current certificate.
+            *client = (generation, Arc::new(builder(&self.inner.config).build()?));
+            self.inner.kept_alive.lock().unwrap().clear();
         }
+        Ok(client.1.clone())
+    }
+
+    /// Get the client to send requests over a new connection with.
+    fn fresh_client(&self) -> Result<Arc<isahc::HttpClient>, isahc::Error> {
+        let generation = self
+            .inner
+            .config
+            .keystore
+            .as_ref()
+            .map_or(0, Keystore::generation);
+        let mut fresh = self.inner.fresh.lock().unwrap();
+        if let Some((built, client)) = &*fresh {
+            if *built == generation {
+                return Ok(client.clone());
+            }
+        }
+        // Without a connection cache, every request is sent over a new connection.
+        let client = Arc::new(
+            builder(&self.inner.config)
+                .connection_cache_size(0)
+                .build()?,
+        );
+        *fresh = Some((generation, client.clone()));
+        Ok(client)
+    }
+
+    /// Find the proxy to send a request to a URI through.
+    ///
+    /// The configured proxies come first, then the PAC file, and then the proxies set in the
+    /// environment. `ftp` requests can only be sent through HTTP proxies.
+    fn proxy(&self, uri: &http::Uri) -> Option<Proxy> {
+        let url = url::Url::parse(&uri.to_string()).ok()?;
+        let ftp = is_ftp(uri);
+        let usable = |proxy: &&Proxy| proxy.applies_to(&url) && (!ftp || proxy.is_http());
+        let configured = self.inner.config.proxies.len();
+        let (configured, env) = self.inner.proxies.split_at(configured);
+        if let Some(proxy) = configured.iter().find(usable) {
+            return Some(proxy.clone());
+        }
+        #[cfg(feature = "pac")]
+        {
+            if let Some(pac) = &self.inner.config.pac {
+                match pac.proxy(&url) {
+                    Ok(proxy) => return proxy.filter(|proxy| !ftp || proxy.is_http()),
+                    Err(err) => log::warn!("failed to evaluate PAC file for {}: {}", url, err),
+                }
+            }
+        }
+        env.iter().find(usable).cloned()
+    }
+}
+
+/// Create a builder for an isahc client from a `Config`.
+fn builder(config: &Config) -> isahc::HttpClientBuilder {
+    let mut builder = isahc::HttpClient::builder();
+    if let Some(interval) = config.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    if config.tcp_nodelay {
+        builder = builder.tcp_nodelay();
     }
+    if let Some(max) = config.max_connections_per_host {
+        builder = builder.max_connections_per_host(max);
+    }
+    if config.close_connections {
+        builder = builder.connection_cache_size(0);
+    } else if let Some(max) = config.max_idle_connections {
+        builder = builder.connection_cache_size(max);
+    }
+    if let Some(version) = config.http_version {
+        builder = builder.preferred_http_version(version);
+    }
+    if let Some(keystore) = &config.keystore {
+        let password = keystore.key_password().map(str::to_string);
+        builder = builder.ssl_client_certificate(ClientCertificate::PEM {
+            path: keystore.certificate().to_path_buf(),
+            private_key: Some(PrivateKey::PEM {
+                path: keystore.private_key().to_path_buf(),
+                password,
+            }),
+        });
+    }
+    builder
+}
+
+/// Check whether curl decodes a content coding.
+fn decodes(coding: &str) -> bool {
+    let version = curl::Version::get();
+    match coding.trim().to_ascii_lowercase().as_str() {
+        "identity" => true,
+        "gzip" | "x-gzip" | "deflate" => version.feature_libz(),
+        "br" => version.feature_brotli(),
+        _ => false,
+    }
+}
+
+/// Check whether a request is for an `ftp` URL.
+fn is_ftp(uri: &http::Uri) -> bool {
+    match uri.scheme_str() {
+        Some(scheme) => scheme.eq_ignore_ascii_case("ftp"),
+        None => false,
+    }
+}
+
+/// Get the origin of a URI, as the host a connection is made to.
+fn origin(uri: &http::Uri) -> String {
+    let scheme = uri.scheme_str().unwrap_or("http");
+    let port = match uri.port_u16() {
+        Some(port) => port,
+        None if scheme.eq_ignore_ascii_case("https") => 443,
+        None => 80,
+    };
+    format!("{}://{}:{}", scheme, uri.host().unwrap_or_default(), port)
+}
+
+/// Convert a proxy URL to the `Uri` expected by isahc.
+fn proxy_uri(url: &url::Url) -> http::Uri {
+    // Proxy URLs always have a host, which makes them valid URIs.
+    url.as_str().parse().unwrap()
 }
 
 impl Clone for IsahcClient {
     fn clone(&self) -> Self {
         Self {
-            client: self.client.clone(),
+            inner: self.inner.clone(),
         }
     }
 }
@@ -37,18 +227,137 @@ impl HttpClient for IsahcClient {
     type Error = isahc::Error;
 
     fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Self::Error>> {
-        let client = self.client.clone();
+        // Isolated requests get a client of their own, so they don't share connections, DNS
+        // results or TLS sessions with any other request.
+        let isolated = req.extensions().get::<Isolated>().is_some();
+        let fresh = req.extensions().get::<FreshConnection>().is_some();
+        let client = if isolated {
+            match builder(&self.inner.config)
+                .dns_cache(DnsCache::Disable)
+                .build()
+            {
+                Ok(client) => Arc::new(client),
+                Err(err) => return Box::pin(async move { Err(err) }),
+            }
+        } else if fresh {
+            match self.fresh_client() {
+                Ok(client) => client,
+                Err(err) => return Box::pin(async move { Err(err) }),
+            }
+        } else {
+            match self.client() {
+                Ok(client) => client,
+                Err(err) => return Box::pin(async move { Err(err) }),
+            }
+        };
+        let proxy = self.proxy(req.uri());
+        if proxy.is_none() && is_ftp(req.uri()) {
+            let msg = "ftp URLs can only be requested through an HTTP proxy";
+            let err = isahc::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, msg));
+            return Box::pin(async move { Err(err) });
+        }
+        // Only the shared client keeps connections open.
+        let kept_alive = if isolated || fresh || self.inner.config.close_connections {
+            None
+        } else {
+            let key = match &proxy {
+                Some(proxy) => proxy.url().to_string(),
+                None => origin(req.uri()),
+            };
+            if self.inner.kept_alive.lock().unwrap().contains(&key) {
+                if let Some(reused) = req.extensions().get::<Reused>() {
+                    reused.mark();
+                }
+            }
+            Some((self.inner.clone(), key))
+        };
         Box::pin(async move {
             let (parts, body) = req.into_parts();
             let body = isahc::Body::reader(body);
-            let req: http::Request<isahc::Body> = http::Request::from_parts(parts, body);
+
+            // Per-request options can only be set through isahc's request builder, so rebuild the
+            // request with it.
+            let mut builder = http::Request::builder();
+            builder
+                .method(parts.method)
+                .uri(parts.uri)
+                .version(parts.version);
+            if let Some(proxy) = &proxy {
+                let url = match parts.extensions.get() {
+                    Some(credentials) => proxy.url_with(credentials),
+                    None => proxy.url().clone(),
+                };
+                builder.proxy(proxy_uri(&url));
+            }
+            if parts.extensions.get::<DangerAcceptInvalidCerts>().is_some() {
+                builder.danger_allow_unsafe_ssl(true);
+            }
+            let mut req = builder.body(body).unwrap();
+            *req.headers_mut() = parts.headers;
+            if parts.extensions.get::<Duplex>().is_some() && !req.headers().contains_key(EXPECT) {
+                // Curl waits for a `100 Continue` response before sending bodies of unknown
+                // length, which servers expecting the body to arrive first never send. An empty
+                // header keeps curl from adding its own.
+                req.headers_mut()
+                    .insert(EXPECT, HeaderValue::from_static(""));
+            }
 
             let res = client.send_async(req).await?;
 
+            if let Some((inner, key)) = kept_alive {
+                let close = res
+                    .headers()
+                    .get_all(CONNECTION)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .flat_map(|value| value.split(','))
+                    .any(|token| token.trim().eq_ignore_ascii_case("close"));
+                let mut kept_alive = inner.kept_alive.lock().unwrap();
+                if close {
+                    kept_alive.remove(&key);
+                } else {
+                    if kept_alive.len() >= MAX_KEPT_ALIVE && !kept_alive.contains(&key) {
+                        kept_alive.clear();
+                    }
+                    kept_alive.insert(key);
+                }
+            }
+
             let (parts, body) = res.into_parts();
-            let body = Body::from_reader(body);
-            let res = http::Response::from_parts(parts, body);
+            let decoded = parts
+                .headers
+                .get(CONTENT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.split(',').all(decodes))
+                .unwrap_or(false);
+            // Dropping the client aborts its transfers, so keep it around until the body has
+            // been dropped. Isolated clients, and clients replaced by a keystore reload, would be
+            // dropped before that otherwise.
+            let body = Body::from_reader(KeepAlive {
+                body,
+                _client: client,
+            });
+            let mut res = http::Response::from_parts(parts, body);
+            if decoded {
+                content_decoded(&mut res);
+            }
             Ok(res)
         })
     }
 }
+
+/// A response body keeping the client it was received with alive.
+struct KeepAlive {
+    body: isahc::Body,
+    _client: Arc<isahc::HttpClient>,
+}
+
+impl AsyncRead for KeepAlive {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.body).poll_read(cx, buf)
+    }
+}
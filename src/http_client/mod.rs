@@ -6,7 +6,10 @@ use std::error::Error;
 use std::fmt::{self, Debug};
 use std::io;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
 pub(crate) mod hyper;
@@ -39,7 +42,9 @@ pub type Response = http::Response<Body>;
 /// request is enabled.
 ///
 /// How `Clone` is implemented is up to the implementors, but in an ideal scenario combining this
-/// with the `Client` builder will allow for high connection reuse, improving latency.
+/// with the `Client` builder will allow for high connection reuse, improving latency. The client
+/// is cloned for every middleware a request passes through, so cloning should be cheap, such as
+/// bumping the reference count of an `Arc` holding the shared state.
 pub trait HttpClient: Debug + Unpin + Send + Sync + Clone + 'static {
     /// The associated error type.
     type Error: Error + Send + Sync;
@@ -48,6 +53,187 @@ pub trait HttpClient: Debug + Unpin + Send + Sync + Clone + 'static {
     fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Self::Error>>;
 }
 
+/// Configuration shared by the native HTTP backends.
+///
+/// Backends apply the options they support and ignore the rest.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    /// Enable TCP keepalive with the given probe interval.
+    pub(crate) tcp_keepalive: Option<Duration>,
+    /// Set `TCP_NODELAY` on new connections.
+    pub(crate) tcp_nodelay: bool,
+    /// Send requests through the first of these proxies that applies to them.
+    pub(crate) proxies: Vec<crate::proxy::Proxy>,
+    /// Limit the number of connections open to a single host at once.
+    pub(crate) max_connections_per_host: Option<usize>,
+    /// Limit the number of idle connections kept open for reuse.
+    pub(crate) max_idle_connections: Option<usize>,
+    /// Close connections after every request, instead of keeping them open for reuse.
+    pub(crate) close_connections: bool,
+    /// The HTTP version to try first.
+    pub(crate) http_version: Option<http::Version>,
+    /// The client certificate to present to servers.
+    pub(crate) keystore: Option<crate::tls::Keystore>,
+    /// Choose proxies for the requests no configured proxy applies to with a PAC file.
+    #[cfg(feature = "pac")]
+    pub(crate) pac: Option<crate::pac::Pac>,
+}
+
+/// The timeout for a request, stored in the request's extensions.
+///
+/// Covers the whole exchange, including streaming the response body.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timeout(pub(crate) Duration);
+
+/// How much of an unread response body to read when the response is dropped, stored in the
+/// extensions of a request and its response.
+///
+/// Set it with `Request::drain_on_drop`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DrainOnDrop(pub(crate) u64);
+
+/// Marker stored in the extensions of a request whose framing isn't checked.
+///
+/// Set it with `Request::lenient_framing`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LenientFraming;
+
+/// Marker stored in the extensions of a request and its response whose text is normalized.
+///
+/// Set it with `Request::normalize_text`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NormalizeText;
+
+/// Marker stored in the extensions of a request whose body is written while its response is
+/// read.
+///
+/// Set it with `Request::body_writer`. The body must not be read ahead of sending the request,
+/// since its next part may only be written once the response arrives.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Duplex;
+
+/// Marker stored in the extensions of a request that must not share any state with other
+/// requests.
+///
+/// Set it with `Request::isolated`. Backends send isolated requests over a new connection,
+/// without using cached DNS results, TLS sessions or cookies. Middleware that keeps state across
+/// requests (such as cookies or cached responses) should neither use nor update that state for
+/// isolated requests.
+#[derive(Debug, Clone, Copy)]
+pub struct Isolated;
+
+/// A flag stored in the extensions of a request, set by backends when they send the request over
+/// a connection that may have been kept open after an earlier request.
+///
+/// `send` in the `request` module only retries requests that fail over such a connection, see the
+/// `reuse` module. Backends that can't tell leave it unset.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Reused(Arc<AtomicBool>);
+
+impl Reused {
+    /// Flag the request as sent over a connection that may have been reused.
+    pub(crate) fn mark(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether the request was sent over a connection that may have been reused.
+    pub(crate) fn get(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Marker stored in the extensions of a request that must be sent over a new connection.
+///
+/// Unlike `Isolated` requests, the request still uses cached DNS results and TLS sessions.
+/// Backends that never set `Reused` may ignore it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FreshConnection;
+
+/// Marker stored in the extensions of a request whose server certificate must not be verified.
+///
+/// Set it with `Request::danger_accept_invalid_certs`. Backends accept any certificate for any
+/// host for such a request, or fail it if they can't skip verification. The marker isn't copied
+/// to auxiliary requests or followed redirects, which are verified as usual.
+#[derive(Debug, Clone, Copy)]
+pub struct DangerAcceptInvalidCerts;
+
+/// A function creating the body of a request, stored in the request's extensions.
+///
+/// Set it with `Request::replayable_body`. It makes it possible to send the body again, for
+/// example when following a redirect.
+#[derive(Clone)]
+pub(crate) struct BodyFactory(pub(crate) Arc<dyn Fn() -> Body + Send + Sync + 'static>);
+
+impl Debug for BodyFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BodyFactory").field(&"<hidden>").finish()
+    }
+}
+
+/// Copy the extensions defined by this crate from one request to another.
+///
+/// Extensions can't be cloned in general, so middleware that sends a request more than once
+/// uses this to carry over the settings of the original request.
+pub(crate) fn copy_extensions(from: &http::Extensions, to: &mut http::Extensions) {
+    macro_rules! copy {
+        ($($ty:ty),*) => {
+            $(
+                if let Some(value) = from.get::<$ty>() {
+                    to.insert(value.clone());
+                }
+            )*
+        };
+    }
+    copy!(
+        Timeout,
+        DrainOnDrop,
+        LenientFraming,
+        Duplex,
+        Isolated,
+        BodyFactory,
+        Arc<crate::codec::Codecs>,
+        Arc<crate::pipeline::Pipeline>,
+        crate::proxy::Credentials,
+        crate::progress::Reporter,
+        crate::middleware::tenant::Tenant
+    );
+    #[cfg(feature = "middleware-decompress")]
+    copy!(crate::middleware::decompress::Identity);
+}
+
+/// The reason phrase sent by the server, stored in the extensions of a response.
+///
+/// Only backends that have access to the raw status line set this.
+#[derive(Debug, Clone)]
+pub(crate) struct ReasonPhrase(pub(crate) String);
+
+/// Marker stored in the extensions of a response that carries no message body.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NoBody;
+
+/// Strip the body from responses that can't carry one.
+///
+/// Responses to `HEAD` requests, `1xx`, `204 No Content` and `304 Not Modified` responses never
+/// have a body, regardless of any framing headers sent by the server (RFC 7230, section 3.3.3).
+/// The same goes for an explicit `Content-Length: 0`. The body of such responses is replaced with
+/// an empty body so it's never read from the connection, and they're marked with `NoBody`.
+pub(crate) fn strip_body(method: &http::Method, res: &mut Response) {
+    let status = res.status();
+    let empty = method == http::Method::HEAD
+        || status.is_informational()
+        || status == http::StatusCode::NO_CONTENT
+        || status == http::StatusCode::NOT_MODIFIED
+        || res
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .map(|len| len == "0")
+            .unwrap_or(false);
+    if empty {
+        *res.body_mut() = Body::empty();
+        res.extensions_mut().insert(NoBody);
+    }
+}
+
 /// The raw body of an http request or response.
 ///
 /// A body is a stream of `Bytes` values, which are shared handles to byte buffers.
@@ -55,6 +241,8 @@ pub trait HttpClient: Debug + Unpin + Send + Sync + Clone + 'static {
 /// like `Vec<u8>` or `String`, using the `From` trait.
 pub struct Body {
     reader: Box<dyn AsyncRead + Unpin + Send + 'static>,
+    /// The length of the body, if it's known up front.
+    len: Option<u64>,
 }
 
 impl Body {
@@ -62,6 +250,7 @@ impl Body {
     pub fn empty() -> Self {
         Self {
             reader: Box::new(futures::io::empty()),
+            len: Some(0),
         }
     }
 
@@ -69,8 +258,14 @@ impl Body {
     pub fn from_reader(reader: impl AsyncRead + Unpin + Send + 'static) -> Self {
         Self {
             reader: Box::new(reader),
+            len: None,
         }
     }
+
+    /// Get the length of the body, if it's known up front.
+    pub(crate) fn len(&self) -> Option<u64> {
+        self.len
+    }
 }
 
 impl AsyncRead for Body {
@@ -96,6 +291,7 @@ impl From<Vec<u8>> for Body {
     #[inline]
     fn from(vec: Vec<u8>) -> Body {
         Self {
+            len: Some(vec.len() as u64),
             reader: Box::new(io::Cursor::new(vec)),
         }
     }
@@ -105,6 +301,6 @@ impl<R: AsyncRead + Unpin + Send + 'static> From<Box<R>> for Body {
     /// Converts an `AsyncRead` into a Body.
     #[allow(missing_doc_code_examples)]
     fn from(reader: Box<R>) -> Self {
-        Self { reader }
+        Self { reader, len: None }
     }
 }
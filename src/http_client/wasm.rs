@@ -1,4 +1,7 @@
-use super::{Body, HttpClient, Request, Response};
+use super::{
+    Body, Config, DangerAcceptInvalidCerts, Duplex, HttpClient, Isolated, ReasonPhrase, Request,
+    Response,
+};
 
 use futures::future::BoxFuture;
 use futures::prelude::*;
@@ -18,6 +21,13 @@ impl WasmClient {
     pub fn new() -> Self {
         Self { _priv: () }
     }
+
+    /// Create a new instance from a `Config`.
+    ///
+    /// `window.fetch` doesn't expose any socket options, so the config is ignored.
+    pub(crate) fn with_config(_config: &Config) -> Self {
+        Self::new()
+    }
 }
 
 impl Clone for WasmClient {
@@ -31,13 +41,30 @@ impl HttpClient for WasmClient {
 
     fn send(&self, req: Request) -> BoxFuture<'static, Result<Response, Self::Error>> {
         let fut = Box::pin(async move {
+            if req.extensions().get::<DangerAcceptInvalidCerts>().is_some() {
+                let msg = "browsers can't skip certificate verification";
+                return Err(io::Error::new(io::ErrorKind::Other, msg));
+            }
+            if req.extensions().get::<Duplex>().is_some() {
+                let msg = "browsers can't stream request bodies";
+                return Err(io::Error::new(io::ErrorKind::Other, msg));
+            }
             let url = format!("{}", req.uri());
-            let req = fetch::new(req.method().as_str(), &url);
+            let isolated = req.extensions().get::<Isolated>().is_some();
+            let mut req = fetch::new(req.method().as_str(), &url);
+            if isolated {
+                req.isolate();
+            }
             let mut res = req.send().await?;
 
             let body = res.body_bytes();
             let mut response = Response::new(Body::from(body));
-            *response.status_mut() = http::StatusCode::from_u16(res.status()).unwrap();
+            *response.status_mut() = http::StatusCode::from_u16(res.status())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            let reason = res.status_text();
+            if !reason.is_empty() {
+                response.extensions_mut().insert(ReasonPhrase(reason));
+            }
 
             for (name, value) in res.headers() {
                 let name: http::header::HeaderName = name.parse().unwrap();
@@ -103,6 +130,12 @@ mod fetch {
             }
         }
 
+        /// Don't send credentials, and bypass the HTTP cache.
+        pub(crate) fn isolate(&mut self) {
+            self.init.credentials(web_sys::RequestCredentials::Omit);
+            self.init.cache(web_sys::RequestCache::NoStore);
+        }
+
         /// Submit a request
         // TODO(yoshuawuyts): turn this into a `Future` impl on `Request` instead.
         pub(crate) async fn send(self) -> Result<Response, io::Error> {
@@ -159,6 +192,11 @@ mod fetch {
         pub(crate) fn status(&self) -> u16 {
             self.res.status()
         }
+
+        /// Get the HTTP status message.
+        pub(crate) fn status_text(&self) -> String {
+            self.res.status_text()
+        }
     }
 
     /// HTTP Headers.
@@ -0,0 +1,236 @@
+//! Kubernetes in-cluster configuration.
+//!
+//! Pods get a service account token, the cluster CA certificate and the API server address
+//! injected by Kubernetes. [`InCluster`] reads them, and builds clients that authenticate to the
+//! API server with the service account token. The token is re-read periodically, since projected
+//! service account tokens are rotated by the kubelet, and it's only sent to the API server.
+//!
+//! The API server's certificate is issued by the cluster's own CA, which no system trust store
+//! contains, and the `curl` backend can't be configured with extra CA certificates. Requests made
+//! with [`InCluster::client`] fail to verify the API server until the certificate at
+//! [`InCluster::ca_cert_path`] has been added to the system trust store of the container image,
+//! for example with `update-ca-certificates` in the image's entrypoint.
+//!
+//! [`InCluster`]: struct.InCluster.html
+//! [`InCluster::client`]: struct.InCluster.html#method.client
+//! [`InCluster::ca_cert_path`]: struct.InCluster.html#method.ca_cert_path
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::kubernetes::InCluster;
+//!
+//! let config = InCluster::load()?;
+//! let client = config.client();
+//! let url = config.url(&format!("/api/v1/namespaces/{}/pods", config.namespace()));
+//! let pods = client.get(url).recv_string().await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+#[cfg(feature = "native-client")]
+use crate::http_client::native::NativeClient;
+#[cfg(feature = "native-client")]
+use crate::Client;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, AUTHORIZATION};
+use url::{Origin, Url};
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The directory the service account credentials are mounted in.
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// How often the token file is re-read.
+const TOKEN_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The configuration of a pod running inside a Kubernetes cluster.
+#[derive(Debug, Clone)]
+pub struct InCluster {
+    api_server: Url,
+    namespace: String,
+    ca_cert_path: PathBuf,
+    token: ServiceAccountToken,
+}
+
+impl InCluster {
+    /// Load the configuration from the environment and the mounted service account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::ErrorKind::NotFound` error when not running inside a cluster, or an error
+    /// if the service account files can't be read.
+    pub fn load() -> io::Result<Self> {
+        let var = |name: &str| {
+            env::var(name).map_err(|_| {
+                let msg = format!("`{}` is not set, not running in a cluster?", name);
+                io::Error::new(io::ErrorKind::NotFound, msg)
+            })
+        };
+        let host = var("KUBERNETES_SERVICE_HOST")?;
+        let port = var("KUBERNETES_SERVICE_PORT")?;
+        let host = if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host
+        };
+        let api_server = format!("https://{}:{}", host, port)
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let dir = Path::new(SERVICE_ACCOUNT_DIR);
+        let namespace = fs::read_to_string(dir.join("namespace"))?
+            .trim()
+            .to_string();
+        let ca_cert_path = dir.join("ca.crt");
+        if !ca_cert_path.is_file() {
+            let msg = format!("`{}` does not exist", ca_cert_path.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, msg));
+        }
+        let token = ServiceAccountToken::new(dir.join("token"), &api_server)?;
+
+        Ok(Self {
+            api_server,
+            namespace,
+            ca_cert_path,
+            token,
+        })
+    }
+
+    /// Get the address of the API server.
+    pub fn api_server(&self) -> &Url {
+        &self.api_server
+    }
+
+    /// Get the namespace the pod runs in.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Get the path of the cluster CA certificate.
+    ///
+    /// The backends don't read it, see the [module documentation](index.html).
+    pub fn ca_cert_path(&self) -> &Path {
+        &self.ca_cert_path
+    }
+
+    /// Get the middleware authenticating requests to the API server with the service account
+    /// token.
+    pub fn token(&self) -> ServiceAccountToken {
+        self.token.clone()
+    }
+
+    /// Resolve an API path, such as `/api/v1/namespaces`, against the API server.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `path` can't be joined with the API server address.
+    pub fn url(&self, path: &str) -> Url {
+        self.api_server.join(path).unwrap()
+    }
+
+    /// Create a client authenticating to the API server with the service account token.
+    ///
+    /// The API server's certificate is only trusted once the cluster CA certificate has been
+    /// added to the system trust store, see the [module documentation](index.html).
+    #[cfg(feature = "native-client")]
+    pub fn client(&self) -> Client<NativeClient> {
+        Client::new().scoped().middleware(self.token()).build()
+    }
+}
+
+/// Middleware sending a service account token to the API server, re-reading the token file once
+/// a minute.
+///
+/// Requests to any other origin are sent without the token. If the token file can't be re-read,
+/// the previous token keeps being used.
+#[derive(Clone)]
+pub struct ServiceAccountToken {
+    path: Arc<PathBuf>,
+    api_server: Origin,
+    token: Arc<Mutex<(HeaderValue, Instant)>>,
+}
+
+impl ServiceAccountToken {
+    /// Create a new instance, reading the token from `path` and sending it to the origin of
+    /// `api_server`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token file can't be read.
+    pub fn new(path: impl Into<PathBuf>, api_server: &Url) -> io::Result<Self> {
+        let path = path.into();
+        let token = read_token(&path)?;
+        Ok(Self {
+            path: Arc::new(path),
+            api_server: api_server.origin(),
+            token: Arc::new(Mutex::new((token, Instant::now()))),
+        })
+    }
+
+    /// Get the current token, re-reading it if it's stale.
+    fn current(&self) -> HeaderValue {
+        let mut token = self.token.lock().unwrap();
+        if token.1.elapsed() >= TOKEN_RELOAD_INTERVAL {
+            match read_token(&self.path) {
+                Ok(value) => token.0 = value,
+                Err(err) => log::warn!(
+                    "failed to reload service account token from {}: {}",
+                    self.path.display(),
+                    err
+                ),
+            }
+            token.1 = Instant::now();
+        }
+        token.0.clone()
+    }
+}
+
+/// Read a token file into an `Authorization` header value.
+fn read_token(path: &Path) -> io::Result<HeaderValue> {
+    let token = fs::read_to_string(path)?;
+    let mut value: HeaderValue = format!("Bearer {}", token.trim())
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+impl fmt::Debug for ServiceAccountToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountToken")
+            .field("path", &self.path)
+            .field("api_server", &self.api_server.ascii_serialization())
+            .field("token", &"<hidden>")
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for ServiceAccountToken {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let origin = Url::parse(&req.uri().to_string()).map(|url| url.origin());
+            if origin.as_ref() == Ok(&self.api_server) {
+                req.headers_mut().insert(AUTHORIZATION, self.current());
+            }
+            next.run(req, client).await
+        })
+    }
+}
@@ -64,9 +64,30 @@
 //! The following features are available.
 //! - __`native-client` (default):__ use `curl` on the server and `window.fetch` in the browser.
 //! - __`middleware-logger` (default):__ enables logging requests and responses using a middleware.
+//! - __`tracing`:__ enables the distributed tracing middleware, and emitting the records of the
+//!   logger middleware as `tracing` events.
+//! - __`metrics`:__ enables a middleware recording request metrics with the `metrics` crate.
+//! - __`middleware-audit`:__ enables single-line audit logs of requests using a middleware.
+//! - __`middleware-decompress`:__ enables decompressing `gzip`, `deflate` and `br` response
+//!   bodies using a middleware.
 //! - __`curl-client`:__ use `curl` (through `isahc`) as the HTTP backend.
 //! - __`hyper-client`:__ use `hyper` as the HTTP backend.
 //! - __`wasm-client`:__ use `window.fetch` as the HTTP backend.
+//! - __`tus`:__ enables resumable uploads using the tus protocol.
+//! - __`mirror`:__ enables downloading from the fastest of several mirrors.
+//! - __`mmap`:__ enables sending memory-mapped files as request bodies.
+//! - __`system-proxy`:__ enables using the proxies set in the system settings on Windows and
+//!   macOS.
+//! - __`pac`:__ enables choosing proxies with proxy auto-config (PAC) files.
+//! - __`azure`:__ enables the Azure Storage Shared Key and SAS middleware.
+//! - __`gcp`:__ enables the Google Cloud service account middleware.
+//! - __`oauth2`:__ enables the OAuth 2.0 middleware refreshing access tokens.
+//! - __`protobuf`:__ enables protobuf request and response bodies using `prost`.
+//! - __`msgpack`:__ enables MessagePack request and response bodies using `rmp-serde`.
+//! - __`xml`:__ enables XML request and response bodies using `quick-xml`.
+//! - __`twirp`:__ enables calling Twirp services.
+//! - __`grpc-web`:__ enables experimental unary gRPC-Web calls.
+//! - __`testing`:__ enables the mock HTTP backend and response assertions for tests.
 
 #![forbid(future_incompatible, rust_2018_idioms)]
 #![deny(missing_debug_implementations, nonstandard_style)]
@@ -74,21 +95,74 @@
 // #![warn(missing_docs, missing_doc_code_examples, unreachable_pub)] TODO(yw): re-enable me
 #![cfg_attr(test, deny(warnings))]
 
+mod attempt;
+mod base_url;
 mod client;
+mod duplex;
+mod error;
+mod framing;
 mod http_client;
+mod precondition;
+mod range;
 mod request;
 mod response;
+mod reuse;
+mod status;
+mod timeout;
+mod transfer;
 
+pub mod codec;
+pub mod delta;
 pub mod headers;
+pub mod kubernetes;
 pub mod middleware;
+pub mod multipart;
+pub mod patch;
+pub mod pipeline;
+pub mod progress;
+pub mod proxy;
+pub mod query;
+pub mod tls;
+pub mod upload;
+pub mod watch;
 
+pub use bytes;
 pub use http;
 pub use mime;
 pub use url;
 
-pub use client::Client;
+pub use attempt::{Attempt, AttemptReason, Attempts};
+pub use base_url::BaseUrlError;
+#[cfg(feature = "native-client")]
+pub use client::ClientBuilder;
+pub use client::{Client, ScopedBuilder};
+pub use duplex::BodyWriter;
+pub use error::{Error, ErrorKind};
+pub use framing::{FramingError, FramingErrorKind};
+pub use precondition::{PreconditionFailed, Validators};
+pub use range::{ContentRange, RangeIgnored};
 pub use request::Request;
 pub use response::Response;
+pub use reuse::UploadInterrupted;
+pub use status::{StatusClass, StatusMatch};
+pub use timeout::TimeoutError;
+pub use transfer::TransferSize;
+
+#[cfg(feature = "grpc-web")]
+pub mod grpc_web;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+#[cfg(feature = "pac")]
+pub mod pac;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tus")]
+pub mod tus;
+#[cfg(feature = "twirp")]
+pub mod twirp;
+
+#[cfg(feature = "mmap")]
+mod mmap;
 
 #[cfg(feature = "native-client")]
 mod one_off;
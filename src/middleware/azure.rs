@@ -0,0 +1,383 @@
+//! Azure Storage authorization.
+//!
+//! [`SharedKey`] signs requests with a storage account key, following the [Shared Key
+//! authorization] scheme used by the Blob, Queue and File services. [`Sas`] attaches a shared
+//! access signature token to the query string of every request instead.
+//!
+//! [`SharedKey`]: struct.SharedKey.html
+//! [`Sas`]: struct.Sas.html
+//! [Shared Key authorization]: https://docs.microsoft.com/rest/api/storageservices/authorize-with-shared-key
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::azure::SharedKey;
+//!
+//! let key = SharedKey::new("myaccount", "c2VjcmV0IGtleQ==")?;
+//! let client = surf::Client::new().scoped().middleware(key).build();
+//! client
+//!     .put("https://myaccount.blob.core.windows.net/photos/chashu.jpg")
+//!     .set_header("x-ms-blob-type", "BlockBlob")
+//!     .body_bytes(&[0xff, 0xd8])
+//!     .await?;
+//! # Ok(()) }
+//! ```
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::azure::Sas;
+//!
+//! let sas = Sas::new("?sv=2019-02-02&ss=b&srt=o&sp=r&se=2019-12-31T00:00:00Z&sig=c2lnbmF0dXJl");
+//! let client = surf::Client::new().scoped().middleware(sas).build();
+//! let photo = client
+//!     .get("https://myaccount.blob.core.windows.net/photos/chashu.jpg")
+//!     .recv_bytes()
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::attempt::{self, AttemptReason};
+use crate::headers::HttpDate;
+use crate::http_client::copy_extensions;
+use crate::middleware::redirect::BodyReplay;
+use crate::middleware::replay::Replay;
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use hmac::{Hmac, Mac};
+use http::header::{self, HeaderValue};
+use http::StatusCode;
+use percent_encoding::percent_decode_str;
+use sha2::Sha256;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The service version sent when the request doesn't set `x-ms-version`.
+const DEFAULT_VERSION: &str = "2019-02-02";
+
+/// The maximum skew between the `x-ms-date` of a request and the service's clock.
+const MAX_SKEW: Duration = Duration::from_secs(15 * 60);
+
+/// Middleware signing requests with a storage account key.
+///
+/// Requests get an `x-ms-date` header, an `x-ms-version` header unless one is already set, and
+/// an `Authorization` header. If a request with a body has no `Content-Length` header, the body
+/// is buffered to compute it, since the length is part of the signature.
+///
+/// The service rejects requests with an `x-ms-date` more than 15 minutes off. When a request is
+/// rejected with `403 Forbidden` and the `Date` of the response shows a larger skew, the local
+/// clock is corrected by the skew for this and later requests, and the request is signed and sent
+/// again once. Whether its body can be sent again depends on the `BodyReplay` policy set with
+/// `SharedKey::replay`, like for redirects.
+#[derive(Clone)]
+pub struct SharedKey {
+    account: String,
+    key: Vec<u8>,
+    /// The offset of the service's clock from the local clock, in seconds.
+    offset: Arc<AtomicI64>,
+    replay: BodyReplay,
+}
+
+impl SharedKey {
+    /// Create a new instance from an account name and its base64 encoded access key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::ErrorKind::InvalidInput` error if the key isn't valid base64.
+    pub fn new(account: impl Into<String>, key: impl AsRef<str>) -> io::Result<Self> {
+        let key = base64::decode(key.as_ref())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(Self {
+            account: account.into(),
+            key,
+            offset: Arc::new(AtomicI64::new(0)),
+            replay: BodyReplay::default(),
+        })
+    }
+
+    /// Set how to deal with request bodies that need to be sent again after correcting the
+    /// clock.
+    pub fn replay(mut self, replay: BodyReplay) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Get the current time, corrected for the skew of the local clock.
+    fn now(&self) -> SystemTime {
+        let offset = self.offset.load(Ordering::Relaxed);
+        if offset >= 0 {
+            SystemTime::now() + Duration::from_secs(offset as u64)
+        } else {
+            SystemTime::now() - Duration::from_secs(-offset as u64)
+        }
+    }
+
+    /// Set the date, version and authorization headers of a request.
+    fn authorize(
+        &self,
+        req: &mut Request,
+        content_length: u64,
+        date: SystemTime,
+    ) -> Result<(), Exception> {
+        let date = HttpDate::from(date).to_string();
+        let headers = req.headers_mut();
+        headers.insert("x-ms-date", date.parse()?);
+        if !headers.contains_key("x-ms-version") {
+            headers.insert("x-ms-version", HeaderValue::from_static(DEFAULT_VERSION));
+        }
+
+        let signature = self.sign(req, content_length);
+        let mut authorization: HeaderValue =
+            format!("SharedKey {}:{}", self.account, signature).parse()?;
+        authorization.set_sensitive(true);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, authorization);
+        Ok(())
+    }
+
+    /// Compute the signature of a request.
+    fn sign(&self, req: &Request, content_length: u64) -> String {
+        let headers = req.headers();
+        let header = |name: header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("")
+        };
+
+        let mut string_to_sign = String::new();
+        string_to_sign.push_str(req.method().as_str());
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::CONTENT_ENCODING));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::CONTENT_LANGUAGE));
+        string_to_sign.push('\n');
+        if content_length > 0 {
+            string_to_sign.push_str(&content_length.to_string());
+        }
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::HeaderName::from_static("content-md5")));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::CONTENT_TYPE));
+        // `Date` is left empty in favour of `x-ms-date`.
+        string_to_sign.push_str("\n\n");
+        string_to_sign.push_str(header(header::IF_MODIFIED_SINCE));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::IF_MATCH));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::IF_NONE_MATCH));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::IF_UNMODIFIED_SINCE));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(header(header::RANGE));
+        string_to_sign.push('\n');
+        string_to_sign.push_str(&canonicalized_headers(headers));
+        string_to_sign.push_str(&self.canonicalized_resource(req.uri()));
+
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.key).unwrap();
+        mac.input(string_to_sign.as_bytes());
+        base64::encode(&mac.result().code())
+    }
+
+    /// The account name, path and sorted query parameters of the request.
+    fn canonicalized_resource(&self, uri: &http::Uri) -> String {
+        let mut resource = format!("/{}{}", self.account, uri.path());
+        let mut params: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let query = uri.query().unwrap_or("");
+        // Parameters are percent-decoded, but unlike in forms a `+` isn't a space.
+        let decode = |part: &str| percent_decode_str(part).decode_utf8_lossy().into_owned();
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (name, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => (param, ""),
+            };
+            params
+                .entry(decode(name).to_lowercase())
+                .or_default()
+                .push(decode(value));
+        }
+        for (name, mut values) in params {
+            values.sort();
+            resource.push_str(&format!("\n{}:{}", name, values.join(",")));
+        }
+        resource
+    }
+}
+
+/// The `x-ms-` headers of a request, lowercased and sorted, one per line.
+fn canonicalized_headers(headers: &http::HeaderMap) -> String {
+    let mut names: Vec<_> = headers
+        .keys()
+        .filter(|name| name.as_str().starts_with("x-ms-"))
+        .collect();
+    names.sort_by_key(|name| name.as_str());
+
+    let mut canonicalized = String::new();
+    for name in names {
+        let values: Vec<_> = headers
+            .get_all(name)
+            .iter()
+            .map(|value| value.to_str().unwrap_or("").trim())
+            .collect();
+        canonicalized.push_str(&format!("{}:{}\n", name, values.join(",")));
+    }
+    canonicalized
+}
+
+impl fmt::Debug for SharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedKey")
+            .field("account", &self.account)
+            .field("key", &"<hidden>")
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for SharedKey {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let header_length = req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok());
+            let content_length = match header_length.or_else(|| req.body().len()) {
+                Some(len) => len,
+                None => {
+                    let mut body = Vec::new();
+                    req.body_mut().read_to_end(&mut body).await?;
+                    let len = body.len() as u64;
+                    *req.body_mut() = Body::from(body);
+                    len
+                }
+            };
+            if header_length.is_none() && content_length > 0 {
+                req.headers_mut()
+                    .insert(header::CONTENT_LENGTH, content_length.into());
+            }
+
+            // Keep what's needed to send the request again if it's rejected because of skew.
+            let (parts, body) = req.into_parts();
+            let (body, replay) = Replay::prepare(body, &parts.extensions, self.replay).await?;
+            let mut copy = http::Request::new(Body::empty());
+            *copy.method_mut() = parts.method.clone();
+            *copy.uri_mut() = parts.uri.clone();
+            *copy.headers_mut() = parts.headers.clone();
+            copy_extensions(&parts.extensions, copy.extensions_mut());
+            let mut req = http::Request::from_parts(parts, body);
+
+            let date = self.now();
+            self.authorize(&mut req, content_length, date)?;
+            let mut res = next.run(req, client.clone()).await?;
+            if res.status() != StatusCode::FORBIDDEN {
+                return Ok(res);
+            }
+            let server_date = match res
+                .headers()
+                .get(header::DATE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<HttpDate>().ok())
+            {
+                Some(server_date) => SystemTime::from(server_date),
+                None => return Ok(res),
+            };
+            if seconds_between(server_date, date).abs() <= MAX_SKEW.as_secs() as i64 {
+                return Ok(res);
+            }
+
+            // The request was most likely rejected because of the skew, correct the clock for
+            // this and later requests.
+            let offset = seconds_between(server_date, SystemTime::now());
+            self.offset.store(offset, Ordering::Relaxed);
+            log::warn!(
+                "correcting clock skew of {}s with the Azure Storage service",
+                offset
+            );
+
+            match replay.body() {
+                Some(body) => {
+                    let mut req = copy;
+                    *req.body_mut() = body;
+                    self.authorize(&mut req, content_length, self.now())?;
+                    let status = AttemptReason::Status(StatusCode::FORBIDDEN);
+                    let earlier = attempt::take(&mut res, status);
+                    let mut res = next.run(req, client).await?;
+                    attempt::prepend(&mut res, earlier);
+                    Ok(res)
+                }
+                None => Ok(res),
+            }
+        })
+    }
+}
+
+/// Get the number of seconds from `earlier` to `later`, which is negative if `later` is earlier.
+fn seconds_between(later: SystemTime, earlier: SystemTime) -> i64 {
+    match later.duration_since(earlier) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+/// Middleware attaching a shared access signature to the query string of requests.
+///
+/// Requests that already carry a `sig` query parameter are passed through unchanged.
+#[derive(Clone)]
+pub struct Sas {
+    token: String,
+}
+
+impl Sas {
+    /// Create a new instance from a SAS token, with or without the leading `?`.
+    pub fn new(token: impl Into<String>) -> Self {
+        let token = token.into();
+        let token = token.trim_start_matches('?').to_string();
+        Self { token }
+    }
+}
+
+impl fmt::Debug for Sas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sas").field("token", &"<hidden>").finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Sas {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let query = req.uri().query().unwrap_or("");
+            let signed =
+                url::form_urlencoded::parse(query.as_bytes()).any(|(name, _)| name == "sig");
+            if !signed && !self.token.is_empty() {
+                let uri = match query {
+                    "" => format!("{}?{}", req.uri(), self.token),
+                    _ => format!("{}&{}", req.uri(), self.token),
+                };
+                *req.uri_mut() = uri.parse()?;
+            }
+            next.run(req, client).await
+        })
+    }
+}
@@ -0,0 +1,756 @@
+//! Caching responses.
+//!
+//! The [`Cache`] middleware stores responses to `GET` requests, and answers later requests for
+//! the same URL from the cache for as long as the response is fresh, following the rules for a
+//! private cache in RFC 7234:
+//!
+//! - Responses are fresh for the `max-age` of their `Cache-Control` header, or until their
+//!   `Expires` header. Responses with a `Last-Modified` header but neither of these are fresh for
+//!   a tenth of the time since they were last modified, up to a day.
+//! - Responses and requests with `Cache-Control: no-store` aren't stored. The `no-cache`,
+//!   `max-age`, `min-fresh`, `max-stale` and `only-if-cached` request directives are honored.
+//! - Stale responses, and responses with `Cache-Control: no-cache`, are revalidated with a
+//!   conditional request, using their `ETag` and `Last-Modified` headers. When the server
+//!   responds with `304 Not Modified`, the stored response is updated with the headers of the
+//!   `304` response and returned.
+//! - Stored responses are only used for requests with the same values of the request headers
+//!   named by the `Vary` header of the response. Each combination of values gets a variant of
+//!   its own, so a response that varies on `Accept-Language` is stored once for every language
+//!   that was requested. Responses with `Vary: *` aren't stored.
+//!
+//! Responses to requests with an `Authorization` header or a [`Tenant`] are stored under keys of
+//! their own for every credential and tenant, and are only returned for requests with the same
+//! credentials and tenant. Clients sharing a cache, like a client and the clients scoped from
+//! it, never get responses meant for other credentials. This only works for credentials that are
+//! set by the time a request reaches the cache: the cache set with `ScopedBuilder::cache` runs
+//! after the other middleware of the client, so credentials set by middleware like `BearerAuth`
+//! or `TenantCredentials` are taken into account. A `Cache` pushed as middleware must come after
+//! the middleware setting credentials. Responses to requests with credentials that change for
+//! every request, like signatures, are never reused.
+//!
+//! Successful `POST`, `PUT`, `PATCH` and `DELETE` requests, and requests with other unsafe
+//! methods, invalidate the stored responses for their URL, including all variants, and for the
+//! URLs of the `Location` and `Content-Location` headers of their response on the same origin, as
+//! required by section 4.4 of RFC 7234. A `GET` request after a write then fetches the resource
+//! again.
+//!
+//! Requests with a `Range` or a conditional header of their own, and requests marked with
+//! `Request::isolated`, bypass the cache. Responses that pass through the cache have a
+//! [`CacheStatus`] extension, see `Response::ext`.
+//!
+//! Set a cache on a client with `ScopedBuilder::cache`. Responses are stored in memory by
+//! default, and can be stored on disk, or elsewhere, with another [`CacheStorage`].
+//!
+//! The cache can be inspected and purged through the `Cache` it was set up with, or through
+//! `Client::cache`, for example to drop stored responses after changing a resource.
+//!
+//! [`Cache`]: struct.Cache.html
+//! [`CacheStatus`]: enum.CacheStatus.html
+//! [`CacheStorage`]: trait.CacheStorage.html
+//! [`Tenant`]: ../tenant/struct.Tenant.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::cache::{Cache, CacheStatus, DiskStorage};
+//!
+//! let cache = Cache::with_storage(DiskStorage::new("/var/cache/my-app")?);
+//! let client = surf::Client::new().scoped().cache(cache).build();
+//!
+//! let res = client.get("https://httpbin.org/cache/60").await?;
+//! let res = client.get("https://httpbin.org/cache/60").await?;
+//! assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Hit));
+//!
+//! client.put("https://httpbin.org/anything/1").body_string("updated".into()).await?;
+//! client.cache().unwrap().purge("https://httpbin.org/anything/1");
+//! println!("{:?}", client.cache().unwrap().stats());
+//! # Ok(()) }
+//! ```
+
+mod storage;
+
+pub use storage::{CacheStorage, DiskStorage, Entry, MemoryStorage};
+
+use storage::fnv1a;
+
+use crate::headers::HttpDate;
+use crate::middleware::tenant::Tenant;
+use crate::middleware::{Body, HttpClient, Isolated, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use http::header::{self, HeaderMap, HeaderValue};
+use http::{Method, StatusCode};
+use url::Url;
+
+use std::fmt::Write;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The longest time a response is fresh for based on its `Last-Modified` header.
+const MAX_HEURISTIC_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The largest number of seconds in an `Age` header or `Cache-Control` directive, larger values
+/// are capped to it (RFC 7234, section 1.2.1).
+const MAX_DELTA_SECONDS: u64 = 1 << 31;
+
+/// Request headers that make a request bypass the cache.
+const BYPASS_HEADERS: [header::HeaderName; 6] = [
+    header::RANGE,
+    header::IF_MATCH,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_UNMODIFIED_SINCE,
+    header::IF_RANGE,
+];
+
+/// Middleware caching responses.
+///
+/// The cache is a shared handle: clones use the same storage.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    storage: Arc<dyn CacheStorage>,
+    max_entry_size: usize,
+    stats: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    revalidations: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Cache {
+    /// Create a new instance, storing responses in memory with the default `MemoryStorage`.
+    pub fn new() -> Self {
+        Self::with_storage(MemoryStorage::default())
+    }
+
+    /// Create a new instance, storing responses in `storage`.
+    pub fn with_storage(storage: impl CacheStorage) -> Self {
+        Self {
+            storage: Arc::new(storage),
+            max_entry_size: 1024 * 1024,
+            stats: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Set the size of the largest response body that is stored, defaults to 1 MiB.
+    pub fn max_entry_size(mut self, size: usize) -> Self {
+        self.max_entry_size = size;
+        self
+    }
+
+    /// Get the response stored for a URL.
+    ///
+    /// URLs are compared as they were requested. When several variants of a response are stored,
+    /// this returns the variant that was stored last. Responses to requests with credentials
+    /// aren't returned, they're stored under other keys.
+    pub fn get(&self, url: &str) -> Option<Entry> {
+        self.storage.get(url)
+    }
+
+    /// Get the keys of all stored responses, as listed by `CacheStorage::keys`.
+    ///
+    /// The keys are the URLs of the responses. Responses to requests with credentials have a
+    /// `#credentials-` suffix, and the other variants of responses varying on request headers
+    /// have a `#vary-` suffix.
+    pub fn keys(&self) -> Vec<String> {
+        self.storage.keys()
+    }
+
+    /// Remove the stored responses for a URL, including all of their variants and the responses
+    /// to requests with credentials.
+    ///
+    /// Call this after changing the resource at the URL, so the next request fetches it again.
+    pub fn purge(&self, url: &str) {
+        self.purge_urls(&[url]);
+    }
+
+    /// Remove the stored responses for all URLs starting with `prefix`.
+    pub fn purge_prefix(&self, prefix: &str) {
+        self.purge_keys(|key| key.starts_with(prefix));
+    }
+
+    /// Remove the stored responses for which `predicate` returns `true`, given their key and the
+    /// stored response.
+    pub fn purge_where(&self, predicate: impl Fn(&str, &Entry) -> bool) {
+        for key in self.storage.keys() {
+            if let Some(entry) = self.storage.get(&key) {
+                if predicate(&key, &entry) {
+                    self.storage.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Remove all stored responses.
+    pub fn clear(&self) {
+        self.purge_keys(|_| true);
+    }
+
+    /// Remove the stored responses whose key matches, without loading them.
+    fn purge_keys(&self, matches: impl Fn(&str) -> bool) {
+        for key in self.storage.keys() {
+            if matches(&key) {
+                self.storage.remove(&key);
+            }
+        }
+    }
+
+    /// Remove the stored responses for URLs, listing the stored keys once to find their variants.
+    fn purge_urls(&self, urls: &[&str]) {
+        // Request URIs have no fragment, so only keys derived from a URL start with it and `#`.
+        let variants: Vec<_> = urls.iter().map(|url| format!("{}#", url)).collect();
+        self.purge_keys(|key| variants.iter().any(|prefix| key.starts_with(prefix)));
+        for url in urls {
+            self.storage.remove(url);
+        }
+    }
+
+    /// Get how many requests were answered from the cache, and how many weren't.
+    ///
+    /// Requests bypassing the cache aren't counted.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.stats.hits.load(Ordering::SeqCst),
+            revalidations: self.stats.revalidations.load(Ordering::SeqCst),
+            misses: self.stats.misses.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Purge the stored responses for a URL a request changed the resource at, and for the URLs
+    /// on the same origin its response refers to.
+    fn invalidate(&self, url: &Url, headers: &HeaderMap) {
+        let mut urls = vec![url.clone()];
+        for name in &[header::LOCATION, header::CONTENT_LOCATION] {
+            let location = headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| url.join(value).ok());
+            if let Some(location) = location {
+                if location.origin() == url.origin() {
+                    urls.push(location);
+                }
+            }
+        }
+        let urls: Vec<_> = urls.iter().map(Url::as_str).collect();
+        self.purge_urls(&urls);
+    }
+
+    fn count(&self, status: CacheStatus) {
+        let counter = match status {
+            CacheStatus::Hit => &self.stats.hits,
+            CacheStatus::Revalidated => &self.stats.revalidations,
+            CacheStatus::Miss => &self.stats.misses,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Store a response, if it can be stored, and return it with its body read.
+    async fn store(
+        &self,
+        key: &str,
+        req_headers: &HeaderMap,
+        res: Response,
+        request_time: SystemTime,
+        response_time: SystemTime,
+    ) -> Result<Response, Exception> {
+        if let Some(len) = content_length(res.headers()) {
+            if len > self.max_entry_size as u64 {
+                return Ok(res);
+            }
+        }
+
+        let (parts, mut body) = res.into_parts();
+        let mut bytes = Vec::new();
+        (&mut body)
+            .take(self.max_entry_size as u64 + 1)
+            .read_to_end(&mut bytes)
+            .await?;
+        if bytes.len() > self.max_entry_size {
+            let body = Body::from_reader(Cursor::new(bytes).chain(body));
+            return Ok(http::Response::from_parts(parts, body));
+        }
+
+        let entry = Entry {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: bytes.clone(),
+            vary: vary(&parts.headers, req_headers),
+            request_time,
+            response_time,
+        };
+        self.put(key, entry);
+        Ok(http::Response::from_parts(parts, Body::from(bytes)))
+    }
+
+    /// Find the stored response for a request, and the key it's stored under.
+    ///
+    /// The variant of a response that was stored last is stored under the URL, and the other
+    /// variants under keys of their own, see `variant_key`.
+    fn lookup(&self, key: &str, headers: &HeaderMap) -> Option<(String, Entry)> {
+        let entry = self.storage.get(key)?;
+        if varies_like(&entry, headers) {
+            return Some((key.to_string(), entry));
+        }
+        let vary: Vec<_> = entry
+            .vary
+            .iter()
+            .map(|(name, _)| (name.clone(), header_value(headers, name)))
+            .collect();
+        let key = variant_key(key, &vary);
+        let entry = self.storage.get(&key)?;
+        Some((key, entry)).filter(|(_, entry)| varies_like(entry, headers))
+    }
+
+    /// Store a new response under the URL, moving the other variant stored there to its own key.
+    fn put(&self, key: &str, entry: Entry) {
+        if let Some(previous) = self.storage.get(key) {
+            if !previous.vary.is_empty() && previous.vary != entry.vary {
+                self.storage
+                    .put(&variant_key(key, &previous.vary), previous);
+            }
+        }
+        if !entry.vary.is_empty() {
+            self.storage.remove(&variant_key(key, &entry.vary));
+        }
+        self.storage.put(key, entry);
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The number of requests a `Cache` handled, by how it handled them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    hits: u64,
+    revalidations: u64,
+    misses: u64,
+}
+
+impl CacheStats {
+    /// Get the number of requests answered with a fresh stored response.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Get the number of requests answered with a stored response that was revalidated.
+    pub fn revalidations(&self) -> u64 {
+        self.revalidations
+    }
+
+    /// Get the number of requests answered with a response from the server.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+/// How the cache handled a request, stored in the extensions of responses passing through a
+/// `Cache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The response is a stored response that was fresh.
+    Hit,
+    /// The response is a stored response that the server confirmed is still valid.
+    Revalidated,
+    /// The response was received from the server.
+    Miss,
+}
+
+impl<C: HttpClient> Middleware<C> for Cache {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if !is_safe(req.method()) {
+                let url = Url::parse(&req.uri().to_string()).ok();
+                let res = next.run(req, client).await?;
+                if let Some(url) = url {
+                    if res.status().is_success() || res.status().is_redirection() {
+                        self.invalidate(&url, res.headers());
+                    }
+                }
+                return Ok(res);
+            }
+
+            let bypass = req.method() != Method::GET
+                || req.extensions().get::<Isolated>().is_some()
+                || BYPASS_HEADERS
+                    .iter()
+                    .any(|name| req.headers().contains_key(name));
+            if bypass {
+                return next.run(req, client).await;
+            }
+
+            let key = request_key(&req);
+            let directives = Directives::request(req.headers());
+            let stored = if directives.no_store {
+                None
+            } else {
+                self.lookup(&key, req.headers())
+            };
+            let now = SystemTime::now();
+            if let Some((_, entry)) = &stored {
+                if !directives.no_cache && is_fresh(entry, &directives, now) {
+                    self.count(CacheStatus::Hit);
+                    return Ok(to_response(entry, CacheStatus::Hit, now));
+                }
+            }
+            if directives.only_if_cached {
+                let mut res = http::Response::new(Body::empty());
+                *res.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+                res.extensions_mut().insert(CacheStatus::Miss);
+                self.count(CacheStatus::Miss);
+                return Ok(res);
+            }
+
+            // Revalidate the stored response, if it has validators.
+            let stored = stored.filter(|(_, entry)| {
+                let etag = entry.headers.get(header::ETAG);
+                let last_modified = entry.headers.get(header::LAST_MODIFIED);
+                if let Some(etag) = etag {
+                    req.headers_mut()
+                        .insert(header::IF_NONE_MATCH, etag.clone());
+                }
+                if let Some(last_modified) = last_modified {
+                    req.headers_mut()
+                        .insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+                }
+                etag.is_some() || last_modified.is_some()
+            });
+
+            let req_headers = req.headers().clone();
+            let request_time = SystemTime::now();
+            let res = next.run(req, client).await?;
+            let response_time = SystemTime::now();
+
+            let mut res = match stored {
+                Some((stored_key, mut entry)) if res.status() == StatusCode::NOT_MODIFIED => {
+                    update(&mut entry, res.headers(), request_time, response_time);
+                    if !directives.no_store && is_storable(entry.status, &entry.headers) {
+                        self.storage.put(&stored_key, entry.clone());
+                    } else {
+                        self.storage.remove(&stored_key);
+                    }
+                    self.count(CacheStatus::Revalidated);
+                    return Ok(to_response(&entry, CacheStatus::Revalidated, response_time));
+                }
+                _ if !directives.no_store && is_storable(res.status(), res.headers()) => {
+                    self.store(&key, &req_headers, res, request_time, response_time)
+                        .await?
+                }
+                _ => res,
+            };
+            res.extensions_mut().insert(CacheStatus::Miss);
+            self.count(CacheStatus::Miss);
+            Ok(res)
+        })
+    }
+}
+
+/// The `Cache-Control` directives of a request or response.
+#[derive(Debug, Default)]
+struct Directives {
+    no_store: bool,
+    no_cache: bool,
+    must_revalidate: bool,
+    only_if_cached: bool,
+    max_age: Option<u64>,
+    min_fresh: Option<u64>,
+    /// `Some(None)` allows responses that are stale by any amount.
+    max_stale: Option<Option<u64>>,
+}
+
+impl Directives {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+        for value in headers.get_all(header::CACHE_CONTROL) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for directive in value.split(',') {
+                let mut parts = directive.splitn(2, '=');
+                let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+                let seconds = parts
+                    .next()
+                    .and_then(|value| delta_seconds(value.trim().trim_matches('"')));
+                match &*name {
+                    "no-store" => directives.no_store = true,
+                    "no-cache" => directives.no_cache = true,
+                    "must-revalidate" => directives.must_revalidate = true,
+                    "only-if-cached" => directives.only_if_cached = true,
+                    // Invalid values mean the response is stale.
+                    "max-age" => directives.max_age = Some(seconds.unwrap_or(0)),
+                    "min-fresh" => directives.min_fresh = seconds,
+                    "max-stale" => directives.max_stale = Some(seconds),
+                    _ => {}
+                }
+            }
+        }
+        directives
+    }
+
+    /// Parse the directives of a request, falling back to `Pragma: no-cache`.
+    fn request(headers: &HeaderMap) -> Self {
+        let mut directives = Self::parse(headers);
+        if !headers.contains_key(header::CACHE_CONTROL) {
+            directives.no_cache = headers
+                .get_all(header::PRAGMA)
+                .iter()
+                .any(|value| value.to_str().ok() == Some("no-cache"));
+        }
+        directives
+    }
+}
+
+/// Check whether a method is safe, so requests with it don't change the resource.
+fn is_safe(method: &Method) -> bool {
+    [Method::GET, Method::HEAD, Method::OPTIONS, Method::TRACE].contains(method)
+}
+
+/// Check whether a response can be stored.
+fn is_storable(status: StatusCode, headers: &HeaderMap) -> bool {
+    let directives = Directives::parse(headers);
+    if directives.no_store || vary_names(headers).any(|name| name == "*") {
+        return false;
+    }
+    // Temporary redirects are only stored when they say how long they are fresh for.
+    let explicit = directives.max_age.is_some() || headers.contains_key(header::EXPIRES);
+    let temporary_redirect = status == StatusCode::FOUND
+        || status == StatusCode::TEMPORARY_REDIRECT
+        || status == StatusCode::PERMANENT_REDIRECT;
+    let understood = is_heuristically_cacheable(status) || (explicit && temporary_redirect);
+    if !understood {
+        return false;
+    }
+    // Responses that are never fresh are only useful when they can be revalidated.
+    headers.contains_key(header::ETAG)
+        || headers.contains_key(header::LAST_MODIFIED)
+        || freshness_lifetime(status, headers) > Duration::from_secs(0)
+}
+
+/// Check whether responses with a status may be stored without explicit freshness information.
+fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    [200, 203, 204, 300, 301, 404, 405, 410, 414, 501].contains(&status.as_u16())
+}
+
+/// Get how long a response is fresh for after it was generated.
+fn freshness_lifetime(status: StatusCode, headers: &HeaderMap) -> Duration {
+    if let Some(max_age) = Directives::parse(headers).max_age {
+        return Duration::from_secs(max_age);
+    }
+    let date = parse_date(headers, header::DATE);
+    if headers.contains_key(header::EXPIRES) {
+        // Invalid dates, such as "0", mean the response has expired.
+        return match (date, parse_date(headers, header::EXPIRES)) {
+            (Some(date), Some(expires)) => expires.duration_since(date).unwrap_or_default(),
+            _ => Duration::from_secs(0),
+        };
+    }
+    match (date, parse_date(headers, header::LAST_MODIFIED)) {
+        (Some(date), Some(last_modified)) if is_heuristically_cacheable(status) => {
+            let age = date.duration_since(last_modified).unwrap_or_default();
+            (age / 10).min(MAX_HEURISTIC_LIFETIME)
+        }
+        _ => Duration::from_secs(0),
+    }
+}
+
+/// Get the age of a stored response (RFC 7234, section 4.2.3).
+fn current_age(entry: &Entry, now: SystemTime) -> Duration {
+    let date = parse_date(&entry.headers, header::DATE).unwrap_or(entry.response_time);
+    let apparent_age = entry.response_time.duration_since(date).unwrap_or_default();
+    let age_value = entry
+        .headers
+        .get(header::AGE)
+        .and_then(|value| delta_seconds(value.to_str().ok()?.trim()))
+        .map(Duration::from_secs)
+        .unwrap_or_default();
+    let response_delay = entry
+        .response_time
+        .duration_since(entry.request_time)
+        .unwrap_or_default();
+    let corrected_initial_age = apparent_age.max(age_value.saturating_add(response_delay));
+    let resident_time = now.duration_since(entry.response_time).unwrap_or_default();
+    corrected_initial_age.saturating_add(resident_time)
+}
+
+/// Parse a number of seconds, capping it to `MAX_DELTA_SECONDS`.
+fn delta_seconds(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    // Only digits are left, so parsing can only fail by overflowing.
+    let seconds = value.parse().unwrap_or(MAX_DELTA_SECONDS);
+    Some(seconds.min(MAX_DELTA_SECONDS))
+}
+
+/// Check whether a stored response may be used without revalidating it.
+fn is_fresh(entry: &Entry, request: &Directives, now: SystemTime) -> bool {
+    let response = Directives::parse(&entry.headers);
+    if response.no_cache {
+        return false;
+    }
+    let lifetime = freshness_lifetime(entry.status, &entry.headers);
+    let age = current_age(entry, now);
+    if let Some(max_age) = request.max_age {
+        if age > Duration::from_secs(max_age) {
+            return false;
+        }
+    }
+    if let Some(min_fresh) = request.min_fresh {
+        return lifetime > age.saturating_add(Duration::from_secs(min_fresh));
+    }
+    if lifetime > age {
+        return true;
+    }
+    match request.max_stale {
+        Some(_) if response.must_revalidate => false,
+        Some(Some(max_stale)) => age - lifetime <= Duration::from_secs(max_stale),
+        Some(None) => true,
+        None => false,
+    }
+}
+
+/// Update a stored response with the headers of a `304 Not Modified` response.
+fn update(
+    entry: &mut Entry,
+    headers: &HeaderMap,
+    request_time: SystemTime,
+    response_time: SystemTime,
+) {
+    for name in headers.keys() {
+        if name == header::CONTENT_LENGTH || name == header::TRANSFER_ENCODING {
+            continue;
+        }
+        entry.headers.remove(name);
+        for value in headers.get_all(name) {
+            entry.headers.append(name, value.clone());
+        }
+    }
+    entry.request_time = request_time;
+    entry.response_time = response_time;
+}
+
+/// Create a response from a stored response.
+fn to_response(entry: &Entry, status: CacheStatus, now: SystemTime) -> Response {
+    let mut res = http::Response::new(Body::from(entry.body.clone()));
+    *res.status_mut() = entry.status;
+    *res.headers_mut() = entry.headers.clone();
+    let age = HeaderValue::from(current_age(entry, now).as_secs());
+    res.headers_mut().insert(header::AGE, age);
+    res.extensions_mut().insert(status);
+    res
+}
+
+/// Get the key the response to a request is stored under.
+///
+/// Requests with credentials get a key of their own for every credential and tenant.
+fn request_key(req: &Request) -> String {
+    let mut credentials = String::new();
+    for value in req.headers().get_all(header::AUTHORIZATION) {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        let _ = writeln!(credentials, "authorization: {}", value);
+    }
+    if let Some(tenant) = req.extensions().get::<Tenant>() {
+        let _ = writeln!(credentials, "tenant: {}", tenant.key());
+    }
+    let url = req.uri().to_string();
+    if credentials.is_empty() {
+        return url;
+    }
+    format!("{}#credentials-{:016x}", url, fnv1a(credentials.as_bytes()))
+}
+
+/// Get the lowercase names in the `Vary` headers of a response.
+fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = String> + '_ {
+    headers
+        .get_all(header::VARY)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+}
+
+/// Get the values of the request headers named by the `Vary` header of a response.
+fn vary(res_headers: &HeaderMap, req_headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    vary_names(res_headers)
+        .map(|name| {
+            let value = header_value(req_headers, &name);
+            (name, value)
+        })
+        .collect()
+}
+
+/// Get the key a variant of a response is stored under while another variant is stored under
+/// the URL, from the values of the request headers it varies on.
+fn variant_key(key: &str, vary: &[(String, Option<String>)]) -> String {
+    let mut values = String::new();
+    for (name, value) in vary {
+        let _ = match value {
+            Some(value) => writeln!(values, "{}: {}", name, value),
+            None => writeln!(values, "{}", name),
+        };
+    }
+    // Request URIs have no fragment, so this can't be the URL of another request.
+    format!("{}#vary-{:016x}", key, fnv1a(values.as_bytes()))
+}
+
+/// Check whether a request has the same values as the request of a stored response, for the
+/// headers named by its `Vary` header.
+fn varies_like(entry: &Entry, headers: &HeaderMap) -> bool {
+    entry
+        .vary
+        .iter()
+        .all(|(name, value)| header_value(headers, name) == *value)
+}
+
+/// Get all values of a header, joined by commas.
+///
+/// The whitespace around the elements of lists is normalized, so `en,de` and `en, de` match.
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<_> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+fn parse_date(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    let value = headers.get(name)?.to_str().ok()?;
+    value.parse::<HttpDate>().ok().map(SystemTime::from)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,344 @@
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use http::StatusCode;
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Storage for the responses of a `Cache`.
+///
+/// Entries are stored under the URL of the request, followed by a `#credentials-` suffix for
+/// requests with credentials. When several variants of a response that varies on request headers
+/// are stored, the other variants are stored under that key followed by a `#vary-` suffix.
+/// Implementations are free to drop entries at any time, for example to stay within a size limit.
+pub trait CacheStorage: fmt::Debug + Send + Sync + 'static {
+    /// Get the entry stored under `key`.
+    fn get(&self, key: &str) -> Option<Entry>;
+
+    /// Store an entry under `key`, replacing any entry stored under it.
+    fn put(&self, key: &str, entry: Entry);
+
+    /// Remove the entry stored under `key`.
+    fn remove(&self, key: &str);
+
+    /// Get the keys of all stored entries.
+    ///
+    /// This is used to purge entries by prefix or predicate, and to find the other variants of a
+    /// response when purging it. Storages that can't list their keys can keep the default, which
+    /// returns no keys, so only entries stored under a URL itself are purged.
+    fn keys(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A response stored in a cache.
+#[derive(Clone)]
+pub struct Entry {
+    pub(super) status: StatusCode,
+    pub(super) headers: HeaderMap,
+    pub(super) body: Vec<u8>,
+    /// The request headers named by the `Vary` header of the response, and their values.
+    pub(super) vary: Vec<(String, Option<String>)>,
+    /// When the request was sent.
+    pub(super) request_time: SystemTime,
+    /// When the response was received.
+    pub(super) response_time: SystemTime,
+}
+
+impl Entry {
+    /// Get the status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Get when the response was received, or last revalidated.
+    pub fn stored_at(&self) -> SystemTime {
+        self.response_time
+    }
+
+    /// Get the approximate number of bytes the entry takes up.
+    pub fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        self.body.len() + headers
+    }
+
+    /// Encode the entry, for storages that store bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, value)| json!([name.as_str(), String::from_utf8_lossy(value.as_bytes())]))
+            .collect();
+        let vary: Vec<_> = self
+            .vary
+            .iter()
+            .map(|(name, value)| json!([name, value]))
+            .collect();
+        let meta = json!({
+            "status": self.status.as_u16(),
+            "headers": headers,
+            "vary": vary,
+            "request_time": millis(self.request_time),
+            "response_time": millis(self.response_time),
+        });
+        let mut bytes = meta.to_string().into_bytes();
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Decode an entry encoded with `Entry::encode`.
+    ///
+    /// Returns `None` if the bytes aren't a valid entry.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let split = bytes.iter().position(|&byte| byte == b'\n')?;
+        let meta: Value = serde_json::from_slice(&bytes[..split]).ok()?;
+
+        let mut headers = HeaderMap::new();
+        for header in meta.get("headers")?.as_array()? {
+            let name: HeaderName = header.get(0)?.as_str()?.parse().ok()?;
+            let value: HeaderValue = header.get(1)?.as_str()?.parse().ok()?;
+            headers.append(name, value);
+        }
+        let mut vary = vec![];
+        for header in meta.get("vary")?.as_array()? {
+            let name = header.get(0)?.as_str()?.to_string();
+            let value = header.get(1)?.as_str().map(str::to_string);
+            vary.push((name, value));
+        }
+        let time = |key| Some(UNIX_EPOCH + Duration::from_millis(meta.get(key)?.as_u64()?));
+        Some(Self {
+            status: StatusCode::from_u16(meta.get("status")?.as_u64()? as u16).ok()?,
+            headers,
+            body: bytes[split + 1..].to_vec(),
+            vary,
+            request_time: time("request_time")?,
+            response_time: time("response_time")?,
+        })
+    }
+}
+
+fn millis(time: SystemTime) -> u64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() * 1000 + u64::from(since_epoch.subsec_millis())
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("body", &format_args!("{} bytes", self.body.len()))
+            .field("vary", &self.vary)
+            .field("response_time", &self.response_time)
+            .finish()
+    }
+}
+
+/// In-memory storage, dropping the least recently used entries to stay within a size limit.
+#[derive(Debug)]
+pub struct MemoryStorage {
+    capacity: usize,
+    state: Mutex<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// The entries, and when they were last used.
+    entries: HashMap<String, (Entry, u64)>,
+    size: usize,
+    clock: u64,
+}
+
+impl MemoryStorage {
+    /// Create a new instance, storing up to `capacity` bytes of entries, as counted by
+    /// `Entry::size`.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(Lru::default()),
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    /// Create a new instance storing up to 16 MiB.
+    fn default() -> Self {
+        Self::new(16 * 1024 * 1024)
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<Entry> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let (entry, used) = state.entries.get_mut(key)?;
+        *used = clock;
+        Some(entry.clone())
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((old, _)) = state.entries.remove(key) {
+            state.size -= old.size();
+        }
+        let size = entry.size();
+        if size > self.capacity {
+            return;
+        }
+        while state.size + size > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            match oldest.and_then(|key| state.entries.remove(&key)) {
+                Some((old, _)) => state.size -= old.size(),
+                None => break,
+            }
+        }
+        state.clock += 1;
+        let clock = state.clock;
+        state.size += size;
+        state.entries.insert(key.to_string(), (entry, clock));
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((old, _)) = state.entries.remove(key) {
+            state.size -= old.size();
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.entries.keys().cloned().collect()
+    }
+}
+
+/// Storage in a directory on disk, with a file per entry.
+///
+/// The size of the directory isn't limited. Entries are written to a temporary file first, and
+/// then renamed, so several processes can share a directory.
+#[derive(Debug)]
+pub struct DiskStorage {
+    dir: PathBuf,
+}
+
+/// A counter making the names of temporary files unique within the process.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+impl DiskStorage {
+    /// Create a new instance storing entries in `dir`, creating the directory if it doesn't
+    /// exist.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(key.as_bytes())))
+    }
+
+    fn write(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        // Entries start with their key, to tell them apart when keys hash to the same file.
+        let mut bytes = key.as_bytes().to_vec();
+        bytes.push(b'\n');
+        bytes.extend(entry.encode());
+
+        let path = self.path(key);
+        let temp = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, bytes)?;
+        if let Err(err) = fs::rename(&temp, &path) {
+            let _ = fs::remove_file(&temp);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl CacheStorage for DiskStorage {
+    fn get(&self, key: &str) -> Option<Entry> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        let split = bytes.iter().position(|&byte| byte == b'\n')?;
+        if &bytes[..split] != key.as_bytes() {
+            return None;
+        }
+        Entry::decode(&bytes[split + 1..])
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        if let Err(err) = self.write(key, &entry) {
+            log::debug!("failed to store cache entry for {}: {}", key, err);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let path = self.path(key);
+        if read_key(&path).as_deref() == Some(key) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut keys = Vec::new();
+        for entry in entries.filter_map(Result::ok) {
+            // Skip temporary files, which have an extension.
+            if entry.path().extension().is_some() {
+                continue;
+            }
+            if let Some(key) = read_key(&entry.path()) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
+
+/// Read the key an entry file starts with, without reading the entry.
+fn read_key(path: &Path) -> Option<String> {
+    let mut line = Vec::new();
+    let file = fs::File::open(path).ok()?;
+    io::BufReader::new(file).read_until(b'\n', &mut line).ok()?;
+    if line.pop() != Some(b'\n') {
+        return None;
+    }
+    String::from_utf8(line).ok()
+}
+
+/// Hash bytes with 64-bit FNV-1a, which is stable across releases, unlike the standard hasher.
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
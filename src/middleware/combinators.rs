@@ -0,0 +1,261 @@
+//! Middleware combinators.
+
+use super::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use http::Method;
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Run two middleware in sequence, `first` wrapping `second`.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::{chain, logger};
+///
+/// let stack = chain(logger::new(), logger::new());
+/// let client = surf::Client::new().scoped().middleware(stack).build();
+/// ```
+pub fn chain<A, B>(first: A, second: B) -> Chain<A, B> {
+    Chain {
+        first,
+        second: Arc::new(second),
+    }
+}
+
+/// Apply a middleware only to requests matching `predicate`.
+///
+/// Other requests skip straight to the rest of the chain. The [`host`], [`path_prefix`] and
+/// [`method`] functions create common predicates.
+///
+/// [`host`]: fn.host.html
+/// [`path_prefix`]: fn.path_prefix.html
+/// [`method`]: fn.method.html
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::{host, logger, when};
+///
+/// let client = surf::Client::new()
+///     .scoped()
+///     .middleware(when(host("api.example.com"), logger::new()))
+///     .middleware(when(|req: &surf::middleware::Request| req.uri().port_part().is_some(), logger::new()))
+///     .build();
+/// ```
+pub fn when<P, M>(predicate: P, middleware: M) -> When<P, M>
+where
+    P: Fn(&Request) -> bool + Send + Sync + 'static,
+{
+    When {
+        predicate,
+        middleware,
+    }
+}
+
+/// Create a predicate matching requests to `host`, ignoring ASCII case.
+pub fn host(host: impl Into<String>) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let host = host.into();
+    move |req| {
+        req.uri()
+            .host()
+            .map(|h| h.eq_ignore_ascii_case(&host))
+            .unwrap_or(false)
+    }
+}
+
+/// Create a predicate matching requests to an origin pattern.
+///
+/// A pattern is a host, optionally preceded by a scheme and followed by a port, such as
+/// `api.example.com`, `https://api.example.com` or `localhost:8080`. A host of the form
+/// `*.example.com` matches any subdomain of `example.com`, but not `example.com` itself. Parts
+/// left out of the pattern match any value. Hosts are compared ignoring ASCII case.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::{logger, origin, when};
+///
+/// let internal = when(origin("https://*.internal.example.com"), logger::new());
+/// ```
+pub fn origin(pattern: &str) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let (scheme, rest) = match pattern.find("://") {
+        Some(i) => (Some(pattern[..i].to_ascii_lowercase()), &pattern[i + 3..]),
+        None => (None, pattern),
+    };
+    let (host, port) = match rest.rfind(':') {
+        Some(i)
+            if !rest[i + 1..].is_empty() && rest[i + 1..].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (&rest[..i], rest[i + 1..].parse::<u16>().ok())
+        }
+        _ => (rest, None),
+    };
+    let host = host.to_ascii_lowercase();
+
+    move |req| {
+        let uri = req.uri();
+        let req_scheme = uri.scheme_str().unwrap_or("http");
+        if let Some(scheme) = &scheme {
+            if !req_scheme.eq_ignore_ascii_case(scheme) {
+                return false;
+            }
+        }
+        if let Some(port) = port {
+            let default_port = match req_scheme {
+                "https" => Some(443),
+                "http" => Some(80),
+                _ => None,
+            };
+            if uri.port_u16().or(default_port) != Some(port) {
+                return false;
+            }
+        }
+        let req_host = match uri.host() {
+            Some(req_host) => req_host.to_ascii_lowercase(),
+            None => return false,
+        };
+        if host.starts_with("*.") {
+            req_host.ends_with(&host[1..])
+        } else {
+            req_host == host
+        }
+    }
+}
+
+/// Create a predicate matching requests whose path starts with `prefix`.
+pub fn path_prefix(prefix: impl Into<String>) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let prefix = prefix.into();
+    move |req| req.uri().path().starts_with(&prefix)
+}
+
+/// Create a predicate matching requests with `method`.
+pub fn method(method: Method) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    move |req| req.method() == method
+}
+
+/// Two middleware run in sequence, created by [`chain`].
+///
+/// [`chain`]: fn.chain.html
+pub struct Chain<A, B> {
+    first: A,
+    second: Arc<B>,
+}
+
+impl<A: fmt::Debug, B: fmt::Debug> fmt::Debug for Chain<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chain")
+            .field("first", &self.first)
+            .field("second", &self.second)
+            .finish()
+    }
+}
+
+impl<C, A, B> Middleware<C> for Chain<A, B>
+where
+    C: HttpClient,
+    A: Middleware<C>,
+    B: Middleware<C>,
+{
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let second: Arc<dyn Middleware<C>> = self.second.clone();
+            let rest: Vec<_> = Some(second)
+                .into_iter()
+                .chain(next.next_middleware.iter().cloned())
+                .collect();
+            let next = Next::new(&rest, next.endpoint);
+            self.first.handle(req, client, next).await
+        })
+    }
+}
+
+/// A middleware applied conditionally, created by [`when`].
+///
+/// [`when`]: fn.when.html
+pub struct When<P, M> {
+    predicate: P,
+    middleware: M,
+}
+
+impl<P, M: fmt::Debug> fmt::Debug for When<P, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("When")
+            .field("middleware", &self.middleware)
+            .finish()
+    }
+}
+
+impl<C, P, M> Middleware<C> for When<P, M>
+where
+    C: HttpClient,
+    P: Fn(&Request) -> bool + Send + Sync + 'static,
+    M: Middleware<C>,
+{
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        if (self.predicate)(&req) {
+            self.middleware.handle(req, client, next)
+        } else {
+            next.run(req, client)
+        }
+    }
+}
+
+/// One of two middleware, chosen when the stack is built.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::decompression_limit::DecompressionLimit;
+/// use surf::middleware::{logger, Either};
+///
+/// let verbose = std::env::var("VERBOSE").is_ok();
+/// let mw = if verbose {
+///     Either::Left(logger::new())
+/// } else {
+///     Either::Right(DecompressionLimit::new())
+/// };
+/// let client = surf::Client::new().scoped().middleware(mw).build();
+/// ```
+#[derive(Debug, Clone)]
+pub enum Either<A, B> {
+    /// The first middleware.
+    Left(A),
+    /// The second middleware.
+    Right(B),
+}
+
+impl<C, A, B> Middleware<C> for Either<A, B>
+where
+    C: HttpClient,
+    A: Middleware<C>,
+    B: Middleware<C>,
+{
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        match self {
+            Either::Left(middleware) => middleware.handle(req, client, next),
+            Either::Right(middleware) => middleware.handle(req, client, next),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! Storing cookies between requests.
+//!
+//! A [`CookieJar`] stores the cookies set by `Set-Cookie` response headers, and attaches the
+//! cookies matching a request's URL to the request's `Cookie` header. The `Domain`, `Path`,
+//! `Secure`, `Expires` and `Max-Age` attributes are honored as described in RFC 6265, with the
+//! exception that the public suffix list isn't consulted: a cookie for a `Domain` such as
+//! `co.uk` is accepted from `example.co.uk`.
+//!
+//! Set a jar on a client with `ScopedBuilder::cookie_jar`. The jar is a shared handle, so a clone
+//! kept by the caller can be used to inspect or seed the cookies while the client uses them.
+//! Requests marked with `Request::isolated` neither send nor store cookies.
+//!
+//! Jars can be saved to and loaded from a JSON file, to keep sessions across restarts of an
+//! application. Cookies without an expiry time are saved as well.
+//!
+//! [`CookieJar`]: struct.CookieJar.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::cookies::CookieJar;
+//!
+//! let jar = CookieJar::load("cookies.json").unwrap_or_default();
+//! let client = surf::Client::new().scoped().cookie_jar(jar.clone()).build();
+//!
+//! client.get("https://httpbin.org/cookies/set?session=chashu").await?;
+//! for cookie in jar.cookies() {
+//!     println!("{}={} ({})", cookie.name(), cookie.value(), cookie.domain());
+//! }
+//! jar.save("cookies.json")?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Isolated, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, COOKIE, SET_COOKIE};
+use serde_json::{json, Value};
+use url::Url;
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A cookie stored in a `CookieJar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received from `url`.
+    ///
+    /// Returns `None` if the header is malformed, or sets a cookie for a domain `url` doesn't
+    /// belong to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::middleware::cookies::Cookie;
+    /// use surf::url::Url;
+    ///
+    /// let url = Url::parse("https://www.example.com/account/login").unwrap();
+    /// let cookie = Cookie::parse("session=chashu; Domain=example.com; Secure", &url).unwrap();
+    /// assert_eq!(cookie.domain(), "example.com");
+    /// assert_eq!(cookie.path(), "/account");
+    /// assert!(cookie.secure());
+    /// ```
+    pub fn parse(header: &str, url: &Url) -> Option<Self> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut parts = header.split(';');
+        let (name, value) = split_pair(parts.next()?)?;
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path()),
+            secure: false,
+            http_only: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attr in parts {
+            let (key, value) = split_pair(attr).unwrap_or((attr.trim(), ""));
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_match(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_owned(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "expires" => {
+                    if let Ok(expires) = httpdate::parse_http_date(value) {
+                        cookie.expires = Some(expires);
+                    }
+                }
+                "max-age" => {
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        max_age = Some(seconds);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // `Max-Age` takes precedence over `Expires`.
+        match max_age {
+            Some(seconds) if seconds <= 0 => cookie.expires = Some(UNIX_EPOCH),
+            Some(seconds) => cookie.expires = Some(after(SystemTime::now(), seconds as u64)),
+            None => {}
+        }
+        Some(cookie)
+    }
+
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the domain of the cookie.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Check whether the cookie is only sent to its exact domain, rather than to subdomains too.
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    /// Get the path of the cookie.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Check whether the cookie is only sent over `https`.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Check whether the cookie was set with the `HttpOnly` attribute.
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// Get the time the cookie expires, if it does.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    /// Check whether the cookie has expired.
+    fn is_expired(&self, now: SystemTime) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+
+    /// Check whether the cookie should be sent with a request to `url`.
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        let domain = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        let secure = match url.scheme() {
+            "https" | "wss" => true,
+            _ => !self.secure,
+        };
+        domain && secure && path_match(url.path(), &self.path)
+    }
+
+    /// Check whether this cookie replaces `other`.
+    fn replaces(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn to_json(&self) -> Value {
+        let expires = self
+            .expires
+            .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+            .map(|expires| expires.as_secs());
+        json!({
+            "name": self.name,
+            "value": self.value,
+            "domain": self.domain,
+            "host_only": self.host_only,
+            "path": self.path,
+            "secure": self.secure,
+            "http_only": self.http_only,
+            "expires": expires,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let string = |key: &str| value.get(key)?.as_str().map(String::from);
+        let flag = |key: &str| value.get(key).and_then(Value::as_bool).unwrap_or(false);
+        Some(Cookie {
+            name: string("name")?,
+            value: string("value")?,
+            domain: string("domain")?,
+            host_only: flag("host_only"),
+            path: string("path")?,
+            secure: flag("secure"),
+            http_only: flag("http_only"),
+            expires: value
+                .get("expires")
+                .and_then(Value::as_u64)
+                .map(|secs| after(UNIX_EPOCH, secs)),
+        })
+    }
+}
+
+/// Get the time `seconds` after `time`, capped to the end of the year 9999, the latest date
+/// cookies can expire at (RFC 6265, section 5.2.2).
+fn after(time: SystemTime, seconds: u64) -> SystemTime {
+    let latest = UNIX_EPOCH + Duration::from_secs(253_402_300_799);
+    match time.checked_add(Duration::from_secs(seconds)) {
+        Some(time) => time.min(latest),
+        None => latest,
+    }
+}
+
+/// Split a `key=value` pair, trimming whitespace.
+fn split_pair(pair: &str) -> Option<(&str, &str)> {
+    let mut parts = pair.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    Some((key, value))
+}
+
+/// Get the default path of a cookie set by a response to a request for `path`.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(end) => path[..end].to_owned(),
+    }
+}
+
+/// Check whether `host` belongs to `domain`.
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    // Subdomains of IP addresses don't exist.
+    host.parse::<IpAddr>().is_err()
+        && host.ends_with(domain)
+        && host[..host.len() - domain.len()].ends_with('.')
+}
+
+/// Check whether a request for `path` is within the cookie path `prefix`.
+fn path_match(path: &str, prefix: &str) -> bool {
+    path == prefix
+        || (path.starts_with(prefix)
+            && (prefix.ends_with('/') || path[prefix.len()..].starts_with('/')))
+}
+
+/// Middleware storing cookies between requests.
+///
+/// Cloning a jar creates a new handle to the same cookies.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+impl CookieJar {
+    /// Create a new, empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a cookie, replacing any cookie with the same name, domain and path.
+    ///
+    /// Expired cookies remove the cookie they replace, without being stored.
+    pub fn insert(&self, cookie: Cookie) {
+        let now = SystemTime::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|stored| !cookie.replaces(stored) && !stored.is_expired(now));
+        if !cookie.is_expired(now) {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Store a cookie from a `Set-Cookie` header value, as if it was received from `url`.
+    ///
+    /// This is useful to seed a jar with a session obtained elsewhere. Returns `false` if the
+    /// header couldn't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::middleware::cookies::CookieJar;
+    /// use surf::url::Url;
+    ///
+    /// let jar = CookieJar::new();
+    /// let url = Url::parse("https://example.com/").unwrap();
+    /// assert!(jar.set_cookie(&url, "session=chashu; Path=/"));
+    /// let cookies = jar.matching(&url.join("/account").unwrap());
+    /// assert_eq!(cookies[0].value(), "chashu");
+    /// ```
+    pub fn set_cookie(&self, url: &Url, header: &str) -> bool {
+        match Cookie::parse(header, url) {
+            Some(cookie) => {
+                self.insert(cookie);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get all cookies that haven't expired.
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = SystemTime::now();
+        let cookies = self.cookies.lock().unwrap();
+        cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the cookies that would be sent with a request to `url`, longest path first.
+    pub fn matching(&self, url: &Url) -> Vec<Cookie> {
+        let mut cookies: Vec<_> = self
+            .cookies()
+            .into_iter()
+            .filter(|cookie| cookie.matches(url))
+            .collect();
+        cookies.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        cookies
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Serialize the cookies that haven't expired to JSON.
+    pub fn to_json(&self) -> String {
+        let cookies: Vec<_> = self.cookies().iter().map(Cookie::to_json).collect();
+        Value::Array(cookies).to_string()
+    }
+
+    /// Create a jar from cookies serialized with `to_json`.
+    ///
+    /// Cookies that have expired since they were serialized are dropped.
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        let cookies = value
+            .as_array()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "expected an array"))?;
+        let jar = Self::new();
+        for cookie in cookies {
+            let cookie = Cookie::from_json(cookie)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid cookie"))?;
+            jar.insert(cookie);
+        }
+        Ok(jar)
+    }
+
+    /// Save the cookies that haven't expired to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    /// Load a jar from a file written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for CookieJar {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if req.extensions().get::<Isolated>().is_some() {
+                return next.run(req, client).await;
+            }
+            let url = match Url::parse(&req.uri().to_string()) {
+                Ok(url) if url.host().is_some() => url,
+                _ => return next.run(req, client).await,
+            };
+
+            let cookies = self.matching(&url);
+            if !cookies.is_empty() {
+                let mut pairs: Vec<_> = cookies
+                    .iter()
+                    .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                    .collect();
+                // Keep cookies set on the request itself.
+                if let Some(existing) = req.headers().get(COOKIE).and_then(|v| v.to_str().ok()) {
+                    pairs.insert(0, existing.to_owned());
+                }
+                let header = pairs.join("; ");
+                if let Ok(value) = HeaderValue::from_str(&header) {
+                    req.headers_mut().insert(COOKIE, value);
+                }
+            }
+
+            let res = next.run(req, client).await?;
+            for header in res.headers().get_all(SET_COOKIE) {
+                if let Ok(header) = header.to_str() {
+                    self.set_cookie(&url, header);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
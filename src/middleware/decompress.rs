@@ -0,0 +1,257 @@
+//! Decompressing response bodies.
+//!
+//! The [`Decompress`] middleware asks servers for compressed responses with an `Accept-Encoding`
+//! header, and decompresses `gzip`, `deflate` and `br` bodies as they're read, so `recv_bytes`,
+//! `recv_json` and friends see the decoded body. This is mostly useful with backends that don't
+//! decode bodies themselves; the `curl` backend already decodes the codings it was built with,
+//! and this middleware then only handles the rest.
+//!
+//! Decoded responses lose their `Content-Encoding` and `Content-Length` headers, which are kept in
+//! an `OriginalHeaders` extension. Responses with a coding that isn't supported are returned
+//! unchanged. A request can opt out by setting the [`Identity`] extension, in which case no
+//! `Accept-Encoding` header is added and its response is left alone.
+//!
+//! To limit the size of decompressed bodies, add a `DecompressionLimit` before this middleware.
+//!
+//! [`Decompress`]: struct.Decompress.html
+//! [`Identity`]: struct.Identity.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::decompress::{Decompress, Identity};
+//!
+//! let client = surf::Client::new().scoped().middleware(Decompress::new()).build();
+//! let string = client.get("https://httpbin.org/brotli").recv_string().await?;
+//!
+//! let raw = client
+//!     .get("https://httpbin.org/gzip")
+//!     .set_ext(Identity)
+//!     .recv_bytes()
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::NoBody;
+use crate::middleware::transform::{map_body, Transform};
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::pipeline::content_decoded;
+use crate::Exception;
+
+use brotli_decompressor::DecompressorWriter;
+use flate2::write::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING};
+
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+
+/// The size of the brotli decoder's internal buffer.
+const BROTLI_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Middleware decompressing response bodies.
+#[derive(Debug, Clone, Default)]
+pub struct Decompress {
+    _priv: (),
+}
+
+impl Decompress {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Marker to opt a request out of decompression.
+///
+/// Set it on a request with `Request::set_ext`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Identity;
+
+impl<C: HttpClient> Middleware<C> for Decompress {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if req.extensions().get::<Identity>().is_some() {
+                return next.run(req, client).await;
+            }
+            if !req.headers().contains_key(ACCEPT_ENCODING) {
+                req.headers_mut().insert(
+                    ACCEPT_ENCODING,
+                    HeaderValue::from_static("gzip, deflate, br"),
+                );
+            }
+
+            let mut res = next.run(req, client).await?;
+            if res.extensions().get::<NoBody>().is_some() {
+                return Ok(res);
+            }
+            let decoder = match res.headers().get(CONTENT_ENCODING) {
+                Some(value) => match Decoder::new(value) {
+                    Some(decoder) => decoder,
+                    None => return Ok(res),
+                },
+                None => return Ok(res),
+            };
+            content_decoded(&mut res);
+            Ok(map_body(res, decoder))
+        })
+    }
+}
+
+/// A transform removing the content codings of a body.
+struct Decoder {
+    /// The decoders, in the order they're applied: the reverse of the order the codings are
+    /// listed in the `Content-Encoding` header.
+    stages: Vec<Stage>,
+}
+
+impl Decoder {
+    /// Create a decoder for a `Content-Encoding` value, if all of its codings are supported.
+    ///
+    /// Returns `None` for bodies without any coding.
+    fn new(value: &HeaderValue) -> Option<Self> {
+        let mut stages = Vec::new();
+        for coding in value.to_str().ok()?.split(',').map(str::trim) {
+            let stage = match coding.to_ascii_lowercase().as_str() {
+                "identity" | "" => continue,
+                "gzip" | "x-gzip" => Stage::Gzip(GzDecoder::new(Vec::new())),
+                "deflate" => Stage::Deflate(None),
+                "br" => Stage::Brotli(Box::new(DecompressorWriter::new(
+                    Vec::new(),
+                    BROTLI_BUFFER_SIZE,
+                ))),
+                _ => return None,
+            };
+            stages.push(stage);
+        }
+        if stages.is_empty() {
+            return None;
+        }
+        stages.reverse();
+        Some(Self { stages })
+    }
+}
+
+impl Transform for Decoder {
+    fn transform(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        let mut buf = input.to_vec();
+        for stage in &mut self.stages {
+            buf = stage.write(&buf)?;
+        }
+        output.extend_from_slice(&buf);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        // Finishing a stage can flush output, which the following stages still need to decode.
+        let mut buf = Vec::new();
+        for stage in &mut self.stages {
+            let mut flushed = stage.write(&buf)?;
+            flushed.extend(stage.finish()?);
+            buf = flushed;
+        }
+        output.extend_from_slice(&buf);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("stages", &self.stages.len())
+            .finish()
+    }
+}
+
+/// A decoder for a single content coding, writing to a buffer.
+enum Stage {
+    Gzip(GzDecoder<Vec<u8>>),
+    /// `deflate` is meant to be zlib-wrapped, but some servers send raw deflate data, which is
+    /// told apart from the first bytes.
+    Deflate(Option<DeflateStage>),
+    Brotli(Box<DecompressorWriter<Vec<u8>>>),
+}
+
+enum DeflateStage {
+    Zlib(ZlibDecoder<Vec<u8>>),
+    Raw(DeflateDecoder<Vec<u8>>),
+}
+
+impl Stage {
+    /// Decode a chunk, returning the output that's available so far.
+    fn write(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        match self {
+            Stage::Gzip(decoder) => {
+                decoder.write_all(input)?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+            Stage::Deflate(decoder) => {
+                let decoder = decoder.get_or_insert_with(|| {
+                    if is_zlib(input) {
+                        DeflateStage::Zlib(ZlibDecoder::new(Vec::new()))
+                    } else {
+                        DeflateStage::Raw(DeflateDecoder::new(Vec::new()))
+                    }
+                });
+                match decoder {
+                    DeflateStage::Zlib(decoder) => {
+                        decoder.write_all(input)?;
+                        Ok(mem::take(decoder.get_mut()))
+                    }
+                    DeflateStage::Raw(decoder) => {
+                        decoder.write_all(input)?;
+                        Ok(mem::take(decoder.get_mut()))
+                    }
+                }
+            }
+            Stage::Brotli(decoder) => {
+                decoder.write_all(input)?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Finish decoding, returning the remaining output.
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        match self {
+            Stage::Gzip(decoder) => {
+                decoder.try_finish()?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+            Stage::Deflate(None) => Ok(Vec::new()),
+            Stage::Deflate(Some(DeflateStage::Zlib(decoder))) => {
+                decoder.try_finish()?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+            Stage::Deflate(Some(DeflateStage::Raw(decoder))) => {
+                decoder.try_finish()?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+            Stage::Brotli(decoder) => {
+                decoder.close()?;
+                Ok(mem::take(decoder.get_mut()))
+            }
+        }
+    }
+}
+
+/// Check whether deflate data starts with a zlib header (RFC 1950).
+fn is_zlib(input: &[u8]) -> bool {
+    match input {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        // A single byte can't tell, assume the standard format.
+        _ => true,
+    }
+}
@@ -0,0 +1,202 @@
+//! Protection against decompression bombs.
+//!
+//! Backends and the `Decompress` middleware transparently decompress responses sent with a
+//! `Content-Encoding`, so a small response can expand into an enormous body. The [`DecompressionLimit`] middleware watches
+//! decoded bodies as they're read, and fails the read once the body grows past an absolute cap,
+//! or past a multiple of its encoded `Content-Length`.
+//!
+//! Reads fail with an `io::Error` of kind `InvalidData` wrapping a [`LimitExceeded`], which can be
+//! recovered with `io::Error::get_ref` and `downcast_ref`.
+//!
+//! [`DecompressionLimit`]: struct.DecompressionLimit.html
+//! [`LimitExceeded`]: struct.LimitExceeded.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::decompression_limit::{DecompressionLimit, LimitExceeded};
+//!
+//! let limit = DecompressionLimit::new().max_ratio(50).max_size(64 * 1024 * 1024);
+//! let client = surf::Client::new().scoped().middleware(limit).build();
+//! let mut res = client.get("https://httpbin.org/gzip").await?;
+//! match res.body_bytes().await {
+//!     Ok(body) => println!("{} bytes", body.len()),
+//!     Err(err) => match err.get_ref().and_then(|err| err.downcast_ref::<LimitExceeded>()) {
+//!         Some(exceeded) => println!("refusing to decompress: {}", exceeded),
+//!         None => return Err(err.into()),
+//!     },
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::transform::{map_body, Transform};
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::pipeline::OriginalHeaders;
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderName, CONTENT_ENCODING, CONTENT_LENGTH};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Decoded bodies smaller than this are never considered bombs, whatever their ratio.
+const MIN_RATIO_CHECK: u64 = 1024 * 1024;
+
+/// Middleware limiting the decoded size of compressed response bodies.
+#[derive(Debug, Clone)]
+pub struct DecompressionLimit {
+    max_ratio: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Default for DecompressionLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecompressionLimit {
+    /// Create a new instance with a maximum ratio of `100` and no absolute cap.
+    pub fn new() -> Self {
+        Self {
+            max_ratio: Some(100),
+            max_size: None,
+        }
+    }
+
+    /// Set the maximum ratio between the decoded size and the encoded `Content-Length`.
+    ///
+    /// The ratio is only enforced once a body has decoded to more than 1 MiB, and only for
+    /// responses with a `Content-Length`.
+    pub fn max_ratio(mut self, ratio: u64) -> Self {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    /// Set the maximum decoded size of a body, in bytes.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// The decoded size allowed for a body with the given encoded length.
+    fn limit(&self, encoded_len: Option<u64>) -> Option<u64> {
+        let ratio_limit = match (self.max_ratio, encoded_len) {
+            (Some(ratio), Some(len)) => Some(len.saturating_mul(ratio).max(MIN_RATIO_CHECK)),
+            _ => None,
+        };
+        match (ratio_limit, self.max_size) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for DecompressionLimit {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let res = next.run(req, client).await?;
+            // Bodies decoded by the backend or by other middleware have their original headers
+            // kept aside.
+            let (encoding, encoded_len) = match res.extensions().get::<OriginalHeaders>() {
+                Some(original) => (
+                    original.content_encoding().map(str::to_owned),
+                    original.content_length(),
+                ),
+                None => (
+                    header(&res, CONTENT_ENCODING),
+                    header(&res, CONTENT_LENGTH).and_then(|value| value.parse().ok()),
+                ),
+            };
+            let encoded = encoding.map(|value| value != "identity").unwrap_or(false);
+            if !encoded {
+                return Ok(res);
+            }
+
+            match self.limit(encoded_len) {
+                Some(limit) => Ok(map_body(
+                    res,
+                    Limit {
+                        limit,
+                        encoded_len,
+                        decoded_len: 0,
+                    },
+                )),
+                None => Ok(res),
+            }
+        })
+    }
+}
+
+/// Get the value of a header of a response as a string.
+fn header(res: &Response, name: HeaderName) -> Option<String> {
+    let value = res.headers().get(name)?.to_str().ok()?;
+    Some(value.to_owned())
+}
+
+/// A transform passing the body through until it exceeds its limit.
+#[derive(Debug)]
+struct Limit {
+    limit: u64,
+    encoded_len: Option<u64>,
+    decoded_len: u64,
+}
+
+impl Transform for Limit {
+    fn transform(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        self.decoded_len += input.len() as u64;
+        if self.decoded_len > self.limit {
+            let err = LimitExceeded {
+                limit: self.limit,
+                encoded_len: self.encoded_len,
+            };
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+        output.extend_from_slice(input);
+        Ok(())
+    }
+}
+
+/// The error returned when a decoded body exceeds its limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitExceeded {
+    limit: u64,
+    encoded_len: Option<u64>,
+}
+
+impl LimitExceeded {
+    /// Get the number of decoded bytes the body was allowed.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Get the encoded `Content-Length` of the body, if it was known.
+    pub fn encoded_len(&self) -> Option<u64> {
+        self.encoded_len
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.encoded_len {
+            Some(len) => write!(
+                f,
+                "decompressed body exceeds {} bytes ({} bytes encoded)",
+                self.limit, len
+            ),
+            None => write!(f, "decompressed body exceeds {} bytes", self.limit),
+        }
+    }
+}
+
+impl Error for LimitExceeded {}
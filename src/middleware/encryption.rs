@@ -0,0 +1,177 @@
+//! End-to-end body encryption.
+//!
+//! The `Encryption` middleware encrypts request bodies before they're sent, and decrypts the
+//! bodies of responses that carry encrypted content, using a user-supplied [`Cipher`]. This enables client-side
+//! encryption for services that shouldn't see plaintext, like object storage.
+//!
+//! Authenticated encryption can only verify a message once all of it has been seen, so bodies
+//! are buffered in memory while they're encrypted or decrypted. Plaintext is never handed out
+//! before it has been authenticated. Bodies written with `Request::body_writer` are only complete
+//! once the response is read, so they can't be encrypted, and requests with one are rejected.
+//!
+//! Because the server only ever sees ciphertext, the request `Content-Type` is replaced with
+//! `application/octet-stream`, and the original value is sent in a metadata header instead.
+//! Object stores return that header along with the stored object, which is how encrypted
+//! responses are recognized: only successful responses carrying the metadata header are
+//! decrypted, and its value is restored as the response `Content-Type`. Other responses,
+//! including `204 No Content` and `206 Partial Content`, are passed through untouched.
+//!
+//! [`Cipher`]: trait.Cipher.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::encryption::{Cipher, Encryption};
+//! use std::io;
+//!
+//! /// A cipher wrapping an AEAD implementation.
+//! #[derive(Debug)]
+//! struct MyCipher;
+//!
+//! impl Cipher for MyCipher {
+//!     fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+//!         // Generate a nonce, seal `plaintext`, and prepend the nonce.
+//! #       Ok(plaintext.to_vec())
+//!     }
+//!
+//!     fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+//!         // Split off the nonce and open the rest.
+//! #       Ok(ciphertext.to_vec())
+//!     }
+//! }
+//!
+//! let client = surf::Client::new()
+//!     .scoped()
+//!     .middleware(Encryption::new(MyCipher).metadata_header("x-amz-meta-content-type"))
+//!     .build();
+//! client
+//!     .put("https://bucket.example.com/notes.txt")
+//!     .body_string("secret".to_string())
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::Duplex;
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use http::StatusCode;
+
+use std::fmt;
+use std::io;
+
+/// The default header used to carry the plaintext `Content-Type`.
+const DEFAULT_METADATA_HEADER: &str = "x-encrypted-content-type";
+
+/// An authenticated encryption scheme applied to whole bodies.
+///
+/// Implementations are responsible for nonce management: a fresh nonce must be used for every
+/// call to `encrypt`, and is usually stored alongside the ciphertext.
+pub trait Cipher: Send + Sync + 'static {
+    /// Encrypt a body.
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decrypt and authenticate a body.
+    ///
+    /// This must return an error if the ciphertext fails to authenticate.
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Middleware encrypting request bodies and decrypting response bodies.
+pub struct Encryption<T: Cipher> {
+    cipher: T,
+    metadata_header: HeaderName,
+}
+
+impl<T: Cipher> Encryption<T> {
+    /// Create a new instance.
+    pub fn new(cipher: T) -> Self {
+        Self {
+            cipher,
+            metadata_header: HeaderName::from_static(DEFAULT_METADATA_HEADER),
+        }
+    }
+
+    /// Set the header carrying the plaintext `Content-Type`.
+    ///
+    /// Defaults to `X-Encrypted-Content-Type`. Object stores usually require a prefix for user
+    /// metadata, such as `x-amz-meta-`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `name` isn't a valid header name.
+    pub fn metadata_header(mut self, name: &str) -> Self {
+        self.metadata_header = name.parse().unwrap();
+        self
+    }
+}
+
+impl<T: Cipher> fmt::Debug for Encryption<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption")
+            .field("cipher", &"<hidden>")
+            .field("metadata_header", &self.metadata_header)
+            .finish()
+    }
+}
+
+impl<C: HttpClient, T: Cipher> Middleware<C> for Encryption<T> {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if req.extensions().get::<Duplex>().is_some() {
+                let msg = "bodies written while the response is read can't be encrypted";
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg).into());
+            }
+            let mut plaintext = Vec::new();
+            req.body_mut().read_to_end(&mut plaintext).await?;
+            if !plaintext.is_empty() {
+                let ciphertext = self.cipher.encrypt(&plaintext)?;
+                let octet_stream = HeaderValue::from_static("application/octet-stream");
+                let headers = req.headers_mut();
+                let mime = headers.remove(CONTENT_TYPE);
+                // Always sent, so the stored object is recognized as encrypted when fetched.
+                headers.insert(
+                    self.metadata_header.clone(),
+                    mime.unwrap_or_else(|| octet_stream.clone()),
+                );
+                headers.insert(CONTENT_TYPE, octet_stream);
+                headers.insert(CONTENT_LENGTH, ciphertext.len().into());
+                *req.body_mut() = Body::from(ciphertext);
+            }
+
+            let mut res = next.run(req, client).await?;
+            let mime = match res.headers().get(&self.metadata_header) {
+                Some(mime) if is_whole_body(res.status()) => mime.clone(),
+                _ => return Ok(res),
+            };
+
+            let mut ciphertext = Vec::new();
+            res.body_mut().read_to_end(&mut ciphertext).await?;
+            if ciphertext.is_empty() {
+                return Ok(res);
+            }
+            let plaintext = self.cipher.decrypt(&ciphertext)?;
+            let headers = res.headers_mut();
+            headers.insert(CONTENT_TYPE, mime);
+            headers.insert(CONTENT_LENGTH, plaintext.len().into());
+            *res.body_mut() = Body::from(plaintext);
+            Ok(res)
+        })
+    }
+}
+
+/// Whether a response with this status carries a whole body that can be authenticated.
+fn is_whole_body(status: StatusCode) -> bool {
+    status.is_success() && status != StatusCode::NO_CONTENT && status != StatusCode::PARTIAL_CONTENT
+}
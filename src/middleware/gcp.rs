@@ -0,0 +1,286 @@
+//! Google Cloud service account authorization.
+//!
+//! The [`ServiceAccount`] middleware obtains OAuth 2.0 access tokens for a Google service account
+//! and sends them as bearer tokens. Tokens are cached and refreshed shortly before they expire.
+//!
+//! Tokens can be obtained in two ways:
+//! - From a service account JSON key, by exchanging a signed JWT at the key's token endpoint.
+//! - From the metadata server, when running on Google Cloud with an attached service account or
+//!   workload identity.
+//!
+//! [`ServiceAccount`]: struct.ServiceAccount.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::gcp::ServiceAccount;
+//!
+//! let account = ServiceAccount::from_key_file("service-account.json")?
+//!     .scope("https://www.googleapis.com/auth/devstorage.read_only");
+//! let client = surf::Client::new().scoped().middleware(account).build();
+//! let buckets = client
+//!     .get("https://storage.googleapis.com/storage/v1/b?project=my-project")
+//!     .recv_string()
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use futures::prelude::*;
+use http::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde_json::{json, Value};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The metadata server endpoint returning tokens for the default service account.
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// How long before expiry a cached token is refreshed.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The lifetime requested for tokens obtained with a service account key, and assumed for
+/// tokens without an `expires_in`.
+const TOKEN_LIFETIME: u64 = 3600;
+
+/// Middleware authorizing requests as a Google service account.
+pub struct ServiceAccount {
+    source: Source,
+    scopes: Vec<String>,
+    token: Mutex<Option<Token>>,
+}
+
+enum Source {
+    Key {
+        client_email: String,
+        token_uri: String,
+        key_pair: Box<RsaKeyPair>,
+    },
+    Metadata,
+}
+
+#[derive(Clone)]
+struct Token {
+    value: HeaderValue,
+    expires: Instant,
+}
+
+impl ServiceAccount {
+    /// Create a new instance from the contents of a service account JSON key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::ErrorKind::InvalidData` error if the key can't be parsed.
+    pub fn from_key_json(json: &str) -> io::Result<Self> {
+        let key: Value = serde_json::from_str(json)?;
+        let field = |name: &str| {
+            key.get(name).and_then(Value::as_str).ok_or_else(|| {
+                let msg = format!("service account key is missing `{}`", name);
+                io::Error::new(io::ErrorKind::InvalidData, msg)
+            })
+        };
+        let key_pair = RsaKeyPair::from_pkcs8(&pem_to_der(field("private_key")?)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let token_uri = field("token_uri")
+            .unwrap_or("https://oauth2.googleapis.com/token")
+            .to_string();
+
+        Ok(Self::new(Source::Key {
+            client_email: field("client_email")?.to_string(),
+            token_uri,
+            key_pair: Box::new(key_pair),
+        }))
+    }
+
+    /// Create a new instance from a service account JSON key file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn from_key_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_key_json(&fs::read_to_string(path)?)
+    }
+
+    /// Create a new instance obtaining tokens from the metadata server.
+    ///
+    /// This works on Google Cloud instances with an attached service account, and on GKE with
+    /// workload identity.
+    pub fn metadata_server() -> Self {
+        Self::new(Source::Metadata)
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            scopes: vec![],
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Add an OAuth scope to request tokens for.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Get a valid token, refreshing it if needed.
+    async fn token<C: HttpClient>(&self, client: C, next: Next<'_, C>) -> Result<Token, Exception> {
+        // Holding the lock while refreshing makes concurrent requests wait for the refresh,
+        // rather than each refreshing the token themselves.
+        let mut cached = self.token.lock().await;
+        if let Some(token) = &*cached {
+            if token.expires > Instant::now() + REFRESH_MARGIN {
+                return Ok(token.clone());
+            }
+        }
+
+        let req = match &self.source {
+            Source::Key {
+                client_email,
+                token_uri,
+                key_pair,
+            } => {
+                let assertion = sign_jwt(client_email, token_uri, &self.scopes, key_pair)?;
+                let body = serde_urlencoded::to_string([
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                    ("assertion", &assertion),
+                ])?;
+                http::Request::post(token_uri.as_str())
+                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .body(Body::from(body.into_bytes()))?
+            }
+            Source::Metadata => {
+                let mut uri = METADATA_TOKEN_URL.to_string();
+                if !self.scopes.is_empty() {
+                    uri.push_str("?scopes=");
+                    uri.push_str(&self.scopes.join(","));
+                }
+                http::Request::get(uri)
+                    .header("Metadata-Flavor", "Google")
+                    .body(Body::empty())?
+            }
+        };
+
+        let now = Instant::now();
+        let mut res = next.run(req, client).await?;
+        let mut body = Vec::new();
+        res.body_mut().read_to_end(&mut body).await?;
+        if !res.status().is_success() {
+            let msg = format!(
+                "token request failed with status {}: {}",
+                res.status(),
+                String::from_utf8_lossy(&body)
+            );
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg).into());
+        }
+
+        let body: Value = serde_json::from_slice(&body)?;
+        let access_token = body
+            .get("access_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing access_token"))?;
+        let expires_in = body
+            .get("expires_in")
+            .and_then(Value::as_u64)
+            .unwrap_or(TOKEN_LIFETIME);
+        let mut value: HeaderValue = format!("Bearer {}", access_token).parse()?;
+        value.set_sensitive(true);
+        let token = Token {
+            value,
+            expires: now
+                .checked_add(Duration::from_secs(expires_in))
+                .unwrap_or_else(|| now + Duration::from_secs(TOKEN_LIFETIME)),
+        };
+        *cached = Some(token.clone());
+        Ok(token)
+    }
+}
+
+/// Create a signed JWT asserting the service account's identity.
+fn sign_jwt(
+    client_email: &str,
+    token_uri: &str,
+    scopes: &[String],
+    key_pair: &RsaKeyPair,
+) -> Result<String, Exception> {
+    let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let header = json!({ "alg": "RS256", "typ": "JWT" });
+    let claims = json!({
+        "iss": client_email,
+        "scope": scopes.join(" "),
+        "aud": token_uri,
+        "iat": iat,
+        "exp": iat + TOKEN_LIFETIME,
+    });
+
+    let mut jwt = encode_segment(&header.to_string());
+    jwt.push('.');
+    jwt.push_str(&encode_segment(&claims.to_string()));
+
+    let mut signature = vec![0; key_pair.public_modulus_len()];
+    key_pair
+        .sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            jwt.as_bytes(),
+            &mut signature,
+        )
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to sign JWT"))?;
+    jwt.push('.');
+    jwt.push_str(&base64::encode_config(&signature, base64::URL_SAFE_NO_PAD));
+    Ok(jwt)
+}
+
+fn encode_segment(segment: &str) -> String {
+    base64::encode_config(segment, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decode a PEM encoded private key.
+fn pem_to_der(pem: &str) -> io::Result<Vec<u8>> {
+    let encoded: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    base64::decode(&encoded).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+impl fmt::Debug for ServiceAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            Source::Key { client_email, .. } => client_email.as_str(),
+            Source::Metadata => "metadata server",
+        };
+        f.debug_struct("ServiceAccount")
+            .field("source", &source)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for ServiceAccount {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let token = self.token(client.clone(), next).await?;
+            req.headers_mut().insert(AUTHORIZATION, token.value);
+            next.run(req, client).await
+        })
+    }
+}
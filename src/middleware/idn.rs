@@ -0,0 +1,79 @@
+//! Internationalized domain names.
+//!
+//! URLs with non-ASCII hosts are converted to their ASCII (punycode) form when a request is
+//! created, so they can be used like any other URL:
+//!
+//! ```
+//! use surf::url::Url;
+//!
+//! let url = Url::parse("https://bücher.example/katalog")?;
+//! assert_eq!(url.host_str(), Some("xn--bcher-kva.example"));
+//! # Ok::<(), surf::url::ParseError>(())
+//! ```
+//!
+//! Internationalized names can be used to spoof other domains with lookalike characters. The
+//! [`RejectIdn`] middleware fails every request whose host contains an internationalized label,
+//! for applications that have no use for them.
+//!
+//! [`RejectIdn`]: struct.RejectIdn.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::idn::RejectIdn;
+//!
+//! let client = surf::Client::new().scoped().middleware(RejectIdn::new()).build();
+//! assert!(client.get("https://аpple.com").await.is_err());
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+
+use std::io;
+
+/// Middleware rejecting requests to internationalized domain names.
+///
+/// Requests fail with an `io::ErrorKind::InvalidInput` error if any label of the host starts
+/// with the punycode prefix `xn--`.
+#[derive(Debug, Clone, Default)]
+pub struct RejectIdn {
+    _priv: (),
+}
+
+impl RejectIdn {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Check whether a host contains a punycode label.
+fn is_idn(host: &str) -> bool {
+    host.split('.')
+        .any(|label| label.len() >= 4 && label[..4].eq_ignore_ascii_case("xn--"))
+}
+
+impl<C: HttpClient> Middleware<C> for RejectIdn {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if let Some(host) = req.uri().host() {
+                if is_idn(host) {
+                    let msg = format!("internationalized domain name `{}` rejected", host);
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, msg).into());
+                }
+            }
+            next.run(req, client).await
+        })
+    }
+}
@@ -0,0 +1,225 @@
+//! Structured request logs.
+//!
+//! The [`JsonLog`] middleware writes one JSON object per line for every completed request, ready
+//! to be shipped to log aggregators without parsing free-form text:
+//!
+//! ```text
+//! {"timestamp":1571230000123,"method":"GET","url":"https://httpbin.org/get","status":200,"duration_ms":212,"sent":112,"received":620}
+//! ```
+//!
+//! A request completes once its response body has been read to the end, or dropped. Requests
+//! that fail before a response arrives are logged with an `error` instead of a `status`.
+//!
+//! Other middleware can add fields to the record, such as the number of retries or whether a
+//! response came from a cache, by inserting [`LogFields`] into the response extensions.
+//!
+//! [`JsonLog`]: struct.JsonLog.html
+//! [`LogFields`]: struct.LogFields.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::json_log::JsonLog;
+//!
+//! let client = surf::Client::new()
+//!     .scoped()
+//!     .middleware(JsonLog::new(std::io::stderr()))
+//!     .build();
+//! let body = client.get("https://httpbin.org/get").recv_string().await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::{Exception, TransferSize};
+
+use futures::future::BoxFuture;
+use futures::io::AsyncRead;
+use serde_json::{Map, Value};
+
+use std::fmt;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Extra fields for the log record of a request.
+///
+/// Middleware running inside `JsonLog` can insert this into the response extensions to annotate
+/// the record. Fields with the same name as a built-in field replace it.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::json_log::LogFields;
+///
+/// let mut fields = LogFields::new();
+/// fields.insert("cache", "hit");
+/// fields.insert("retries", 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LogFields(Map<String, Value>);
+
+impl LogFields {
+    /// Create a new, empty instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a field, replacing any previous value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    /// Add fields to the response extensions, merging them with any fields already there.
+    pub fn merge_into(self, res: &mut Response) {
+        match res.extensions_mut().get_mut::<LogFields>() {
+            Some(fields) => fields.0.extend(self.0),
+            None => {
+                res.extensions_mut().insert(self);
+            }
+        }
+    }
+}
+
+type Writer = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Middleware writing a JSON record for every completed request.
+#[derive(Clone)]
+pub struct JsonLog {
+    writer: Writer,
+}
+
+impl JsonLog {
+    /// Create a new instance writing records to `writer`, one per line.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+}
+
+impl fmt::Debug for JsonLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLog")
+            .field("writer", &"<writer>")
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for JsonLog {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            let mut record = Map::new();
+            record.insert("timestamp".into(), timestamp.into());
+            record.insert("method".into(), req.method().as_str().into());
+            record.insert("url".into(), req.uri().to_string().into());
+
+            let res = match next.run(req, client).await {
+                Ok(res) => res,
+                Err(err) => {
+                    record.insert("error".into(), err.to_string().into());
+                    record.insert("duration_ms".into(), elapsed_ms(start).into());
+                    write_record(&self.writer, record);
+                    return Err(err);
+                }
+            };
+
+            record.insert("status".into(), res.status().as_u16().into());
+            let (mut parts, body) = res.into_parts();
+            let pending = Pending {
+                writer: self.writer.clone(),
+                record,
+                start,
+                size: parts.extensions.get::<TransferSize>().cloned(),
+                fields: parts.extensions.remove::<LogFields>(),
+            };
+            let body = Body::from_reader(Completion {
+                body,
+                pending: Some(pending),
+            });
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+fn write_record(writer: &Writer, record: Map<String, Value>) {
+    let mut line = Value::Object(record).to_string();
+    line.push('\n');
+    let mut writer = writer.lock().unwrap();
+    if let Err(err) = writer.write_all(line.as_bytes()) {
+        log::warn!("failed to write request log: {}", err);
+    }
+}
+
+/// The part of a record that's only known once the body has been read.
+struct Pending {
+    writer: Writer,
+    record: Map<String, Value>,
+    start: Instant,
+    size: Option<TransferSize>,
+    fields: Option<LogFields>,
+}
+
+impl Pending {
+    fn finish(mut self) {
+        self.record
+            .insert("duration_ms".into(), elapsed_ms(self.start).into());
+        if let Some(size) = &self.size {
+            self.record.insert("sent".into(), size.sent().into());
+            self.record
+                .insert("received".into(), size.received().into());
+        }
+        if let Some(fields) = self.fields {
+            self.record.extend(fields.0);
+        }
+        write_record(&self.writer, self.record);
+    }
+}
+
+/// A body that writes the record once it's read to the end or dropped.
+struct Completion {
+    body: Body,
+    pending: Option<Pending>,
+}
+
+impl AsyncRead for Completion {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        if read == 0 && !buf.is_empty() {
+            if let Some(pending) = self.pending.take() {
+                pending.finish();
+            }
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish();
+        }
+    }
+}
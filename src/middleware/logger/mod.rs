@@ -1,5 +1,19 @@
 //! Logging middleware.
 //!
+//! The [`Logger`] logs a record when a request is sent, and one when its response arrives, or
+//! when it fails. How much of the request and response is logged is set by its [`Verbosity`]:
+//! the request and status lines, the headers too, or the bodies as well, up to a size limit.
+//!
+//! Records are logged with the `log` crate by default, and can be emitted as `tracing` events
+//! instead with the `tracing` feature. Responses with a server error status are logged as
+//! errors, responses with a client error status as warnings, and everything else as info.
+//!
+//! The values of the `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` headers
+//! are redacted, and more headers can be redacted with `Logger::redact`.
+//!
+//! [`Logger`]: struct.Logger.html
+//! [`Verbosity`]: enum.Verbosity.html
+//!
 //! # Examples
 //!
 //! ```
@@ -11,20 +25,41 @@
 //! dbg!(res.body_string().await?);
 //! # Ok(()) }
 //! ```
+//!
+//! Logging the headers and the first kilobyte of the bodies of all requests of a client:
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::logger::{Logger, Verbosity};
+//!
+//! let logger = Logger::new()
+//!     .verbosity(Verbosity::Body)
+//!     .max_body_size(1024)
+//!     .redact("x-api-key");
+//! let client = surf::Client::new().middleware(logger);
+//! let res = client.get("https://httpbin.org/get").await?;
+//! # Ok(()) }
+//! ```
 
-#[cfg(target_arch = "wasm32")]
-mod wasm;
+use crate::http_client::{Body, HttpClient};
+use crate::middleware::{Middleware, Next, Request, Response};
 
-#[cfg(target_arch = "wasm32")]
-use wasm::Logger;
+use futures::future::BoxFuture;
+use futures::io::AsyncRead;
+use http::header::{self, HeaderMap, HeaderName};
 
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
 #[cfg(not(target_arch = "wasm32"))]
-mod native;
+use std::time::Instant;
 
-#[cfg(not(target_arch = "wasm32"))]
-use native::Logger;
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-/// Create a new instance.
+/// Create a new instance, logging the request and status lines with the `log` crate.
 ///
 /// # Examples
 ///
@@ -40,3 +75,365 @@ use native::Logger;
 pub fn new() -> Logger {
     Logger::new()
 }
+
+/// How much of requests and responses a `Logger` logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Log the method and URL of requests, and the status and duration of responses.
+    Line,
+    /// Log the headers of requests and responses as well.
+    Headers,
+    /// Log the headers and bodies of requests and responses as well.
+    ///
+    /// Bodies are logged once they have been sent or read, up to `Logger::max_body_size`
+    /// bytes, in separate records.
+    Body,
+}
+
+/// Where a `Logger` sends its records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Log records with the `log` crate, with the fields as key-value pairs.
+    Log,
+    /// Emit records as `tracing` events, with the fields as event fields.
+    #[cfg(feature = "tracing")]
+    Tracing,
+}
+
+/// Middleware logging requests and responses, see the [module documentation](index.html).
+#[derive(Debug, Clone)]
+pub struct Logger {
+    verbosity: Verbosity,
+    backend: Backend,
+    max_body_size: usize,
+    redacted: Vec<HeaderName>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Logger {
+    /// Create a new instance, logging the request and status lines with the `log` crate.
+    pub fn new() -> Self {
+        Self {
+            verbosity: Verbosity::Line,
+            backend: Backend::Log,
+            max_body_size: 4096,
+            redacted: vec![
+                header::AUTHORIZATION,
+                header::PROXY_AUTHORIZATION,
+                header::COOKIE,
+                header::SET_COOKIE,
+            ],
+        }
+    }
+
+    /// Set how much of requests and responses is logged.
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set where records are sent.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set how many bytes of each body are logged with `Verbosity::Body`, defaults to 4 KiB.
+    ///
+    /// Longer bodies are truncated in the log, but still sent and received in full.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Redact the value of a header, such as a header carrying an API key.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `name` isn't a valid header name.
+    pub fn redact(mut self, name: &str) -> Self {
+        self.redacted
+            .push(name.parse().expect("invalid header name"));
+        self
+    }
+
+    /// Format headers for the log, redacting the values of sensitive headers.
+    fn headers(&self, headers: &HeaderMap) -> Option<String> {
+        if self.verbosity < Verbosity::Headers {
+            return None;
+        }
+        let mut out = String::new();
+        for (name, value) in headers {
+            if !out.is_empty() {
+                out.push_str(", ");
+            }
+            if self.redacted.contains(name) {
+                let _ = write!(out, "{}: [redacted]", name);
+            } else {
+                let _ = write!(
+                    out,
+                    "{}: {}",
+                    name,
+                    String::from_utf8_lossy(value.as_bytes())
+                );
+            }
+        }
+        Some(out)
+    }
+
+    /// Log a body once it has been read, if bodies are logged.
+    fn capture(&self, body: Body, level: log::Level, id: usize, msg: &'static str) -> Body {
+        if self.verbosity < Verbosity::Body || !enabled(self.backend, level) {
+            return body;
+        }
+        Body::from_reader(Capture {
+            body,
+            logged: Vec::new(),
+            size: 0,
+            max_size: self.max_body_size,
+            record: Some((self.backend, level, id, msg)),
+        })
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Logger {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, crate::Exception>> {
+        Box::pin(async move {
+            // Formatting the request for the log is wasted work if nothing gets logged, which
+            // would otherwise add to every request.
+            if !enabled(self.backend, log::Level::Error) {
+                return next.run(req, client).await;
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            let start_time = Instant::now();
+            let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+            let req = if enabled(self.backend, log::Level::Info) {
+                let uri = req.uri().to_string();
+                let fields = Fields {
+                    id,
+                    method: Some(req.method().as_str()),
+                    uri: Some(&uri),
+                    headers: self.headers(req.headers()),
+                    ..Fields::default()
+                };
+                emit(self.backend, log::Level::Info, "sending request", &fields);
+                let (parts, body) = req.into_parts();
+                let body = self.capture(body, log::Level::Info, id, "request body");
+                http::Request::from_parts(parts, body)
+            } else {
+                req
+            };
+
+            let res = match next.run(req, client).await {
+                Ok(res) => res,
+                Err(err) => {
+                    let fields = Fields {
+                        id,
+                        error: Some(err.to_string()),
+                        ..Fields::default()
+                    };
+                    emit(self.backend, log::Level::Error, "request failed", &fields);
+                    return Err(err);
+                }
+            };
+
+            let status = res.status();
+            let level = if status.is_server_error() {
+                log::Level::Error
+            } else if status.is_client_error() {
+                log::Level::Warn
+            } else {
+                log::Level::Info
+            };
+            if !enabled(self.backend, level) {
+                return Ok(res);
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            let elapsed = Some(format!("{:?}", start_time.elapsed()));
+            #[cfg(target_arch = "wasm32")]
+            let elapsed = None;
+            let fields = Fields {
+                id,
+                status: Some(status.as_u16()),
+                elapsed,
+                headers: self.headers(res.headers()),
+                ..Fields::default()
+            };
+            emit(self.backend, level, "request completed", &fields);
+            let (parts, body) = res.into_parts();
+            let body = self.capture(body, level, id, "response body");
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// The fields of a log record.
+#[derive(Debug, Default)]
+struct Fields<'a> {
+    id: usize,
+    method: Option<&'a str>,
+    uri: Option<&'a str>,
+    status: Option<u16>,
+    elapsed: Option<String>,
+    headers: Option<String>,
+    size: Option<u64>,
+    body: Option<String>,
+    error: Option<String>,
+}
+
+impl log::kv::Source for Fields<'_> {
+    fn visit<'kvs>(
+        &'kvs self,
+        visitor: &mut dyn log::kv::Visitor<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        visitor.visit_pair("req.id".into(), self.id.into())?;
+        if let Some(method) = self.method {
+            visitor.visit_pair("req.method".into(), method.into())?;
+        }
+        if let Some(uri) = self.uri {
+            visitor.visit_pair("req.uri".into(), uri.into())?;
+        }
+        if let Some(status) = self.status {
+            visitor.visit_pair("req.status".into(), status.into())?;
+        }
+        if let Some(elapsed) = &self.elapsed {
+            visitor.visit_pair("elapsed".into(), elapsed.as_str().into())?;
+        }
+        if let Some(headers) = &self.headers {
+            visitor.visit_pair("headers".into(), headers.as_str().into())?;
+        }
+        if let Some(size) = self.size {
+            visitor.visit_pair("body.size".into(), size.into())?;
+        }
+        if let Some(body) = &self.body {
+            visitor.visit_pair("body".into(), body.as_str().into())?;
+        }
+        if let Some(error) = &self.error {
+            visitor.visit_pair("error".into(), error.as_str().into())?;
+        }
+        Ok(())
+    }
+}
+
+/// Check whether records of a level are logged.
+fn enabled(backend: Backend, level: log::Level) -> bool {
+    match backend {
+        Backend::Log => level <= log::STATIC_MAX_LEVEL && level <= log::max_level(),
+        #[cfg(feature = "tracing")]
+        Backend::Tracing => match level {
+            log::Level::Error => tracing::enabled!(tracing::Level::ERROR),
+            log::Level::Warn => tracing::enabled!(tracing::Level::WARN),
+            _ => tracing::enabled!(tracing::Level::INFO),
+        },
+    }
+}
+
+fn emit(backend: Backend, level: log::Level, msg: &str, fields: &Fields<'_>) {
+    if !enabled(backend, level) {
+        return;
+    }
+    match backend {
+        Backend::Log => log::logger().log(
+            &log::Record::builder()
+                .args(format_args!("{}", msg))
+                .key_values(fields)
+                .level(level)
+                .target(module_path!())
+                .module_path(Some(module_path!()))
+                .file(Some(file!()))
+                .line(Some(line!()))
+                .build(),
+        ),
+        #[cfg(feature = "tracing")]
+        Backend::Tracing => {
+            macro_rules! event {
+                ($level:expr) => {
+                    tracing::event!(
+                        $level,
+                        req.id = fields.id,
+                        req.method = fields.method,
+                        req.uri = fields.uri,
+                        req.status = fields.status,
+                        elapsed = fields.elapsed.as_deref(),
+                        headers = fields.headers.as_deref(),
+                        body.size = fields.size,
+                        body = fields.body.as_deref(),
+                        error = fields.error.as_deref(),
+                        "{}",
+                        msg
+                    )
+                };
+            }
+            match level {
+                log::Level::Error => event!(tracing::Level::ERROR),
+                log::Level::Warn => event!(tracing::Level::WARN),
+                _ => event!(tracing::Level::INFO),
+            }
+        }
+    }
+}
+
+/// A body keeping the first bytes read from it, and logging them once it ends or is dropped.
+struct Capture {
+    body: Body,
+    logged: Vec<u8>,
+    size: u64,
+    max_size: usize,
+    /// Where, at which level, and as what to log the body, taken once it was logged.
+    record: Option<(Backend, log::Level, usize, &'static str)>,
+}
+
+impl Capture {
+    fn finish(&mut self) {
+        if let Some((backend, level, id, msg)) = self.record.take() {
+            let fields = Fields {
+                id,
+                body: Some(String::from_utf8_lossy(&self.logged).into_owned()),
+                size: Some(self.size),
+                ..Fields::default()
+            };
+            emit(backend, level, msg, &fields);
+        }
+    }
+}
+
+impl AsyncRead for Capture {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let n = match Pin::new(&mut this.body).poll_read(cx, buf) {
+            Poll::Ready(Ok(n)) => n,
+            other => return other,
+        };
+        if n == 0 {
+            this.finish();
+        } else {
+            let keep = n.min(this.max_size.saturating_sub(this.logged.len()));
+            this.logged.extend_from_slice(&buf[..keep]);
+            this.size += n as u64;
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,235 @@
+//! Request metrics.
+//!
+//! The [`Metrics`] middleware records metrics about the requests passing through it with the
+//! [`metrics`] facade, so they're exported by whichever recorder the application installed, such
+//! as the Prometheus exporter. With the default `http_client` prefix, it records:
+//!
+//! - `http_client_requests_total`: a counter of the completed requests.
+//! - `http_client_requests_in_flight`: a gauge of the requests waiting for a response.
+//! - `http_client_request_duration_seconds`: a histogram of the time until the response headers
+//!   arrived, or the request failed.
+//! - `http_client_request_body_bytes_total` and `http_client_response_body_bytes_total`:
+//!   counters of the body bytes sent and received, recorded once the response body has been read
+//!   or dropped.
+//!
+//! All metrics are labeled with the `host` and `method` of the request. The request counter and
+//! the histogram are also labeled with the `status` class of the response, one of `1xx` through
+//! `5xx`, or `error` for requests that failed without a response.
+//!
+//! Without a recorder installed, recording a metric does nothing.
+//!
+//! [`Metrics`]: struct.Metrics.html
+//! [`metrics`]: https://docs.rs/metrics
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::metrics::Metrics;
+//!
+//! let client = surf::Client::new().middleware(Metrics::new().prefix("api_client"));
+//! let res = client.get("https://httpbin.org/get").await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::{Exception, TransferSize};
+
+use ::metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use ::metrics::{Counter, Gauge, Unit};
+use futures::future::BoxFuture;
+use futures::io::AsyncRead;
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Once};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Middleware recording request metrics, see the [module documentation](index.html).
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    requests: String,
+    in_flight: String,
+    duration: String,
+    request_bytes: String,
+    response_bytes: String,
+    described: Arc<Once>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Create a new instance, with the `http_client` prefix.
+    pub fn new() -> Self {
+        Self {
+            requests: String::new(),
+            in_flight: String::new(),
+            duration: String::new(),
+            request_bytes: String::new(),
+            response_bytes: String::new(),
+            described: Arc::new(Once::new()),
+        }
+        .prefix("http_client")
+    }
+
+    /// Set the prefix of the metric names, defaults to `http_client`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.requests = format!("{}_requests_total", prefix);
+        self.in_flight = format!("{}_requests_in_flight", prefix);
+        self.duration = format!("{}_request_duration_seconds", prefix);
+        self.request_bytes = format!("{}_request_body_bytes_total", prefix);
+        self.response_bytes = format!("{}_response_body_bytes_total", prefix);
+        self.described = Arc::new(Once::new());
+        self
+    }
+
+    /// Describe the metrics to the recorder, which must be installed by the time the first
+    /// request is sent.
+    fn describe(&self) {
+        self.described.call_once(|| {
+            describe_counter!(
+                self.requests.clone(),
+                Unit::Count,
+                "HTTP requests completed"
+            );
+            describe_gauge!(
+                self.in_flight.clone(),
+                Unit::Count,
+                "HTTP requests waiting for a response"
+            );
+            describe_histogram!(
+                self.duration.clone(),
+                Unit::Seconds,
+                "Time until the response headers of HTTP requests arrived"
+            );
+            describe_counter!(
+                self.request_bytes.clone(),
+                Unit::Bytes,
+                "HTTP request body bytes sent"
+            );
+            describe_counter!(
+                self.response_bytes.clone(),
+                Unit::Bytes,
+                "HTTP response body bytes received"
+            );
+        });
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Metrics {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            self.describe();
+            let host = req.uri().host().unwrap_or_default().to_string();
+            let method = req.method().to_string();
+
+            let in_flight = InFlight::new(gauge!(
+                self.in_flight.clone(),
+                "host" => host.clone(),
+                "method" => method.clone()
+            ));
+            let started = Instant::now();
+            let res = next.run(req, client).await;
+            let elapsed = started.elapsed();
+            drop(in_flight);
+
+            let status = match &res {
+                Ok(res) => format!("{}xx", res.status().as_u16() / 100),
+                Err(_) => "error".to_string(),
+            };
+            let labels = [("host", host), ("method", method), ("status", status)];
+            counter!(self.requests.clone(), &labels).increment(1);
+            histogram!(self.duration.clone(), &labels).record(elapsed.as_secs_f64());
+
+            let (parts, body) = res?.into_parts();
+            let size = match parts.extensions.get::<TransferSize>() {
+                Some(size) => size.clone(),
+                None => return Ok(http::Response::from_parts(parts, body)),
+            };
+            let labels = &labels[..2];
+            let body = Body::from_reader(Measured {
+                body,
+                counters: Some(Counters {
+                    request: counter!(self.request_bytes.clone(), labels),
+                    response: counter!(self.response_bytes.clone(), labels),
+                    size,
+                }),
+            });
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// A body recording the bytes transferred once it's read to the end or dropped.
+struct Measured {
+    body: Body,
+    counters: Option<Counters>,
+}
+
+struct Counters {
+    request: Counter,
+    response: Counter,
+    size: TransferSize,
+}
+
+impl Measured {
+    fn finish(&mut self) {
+        if let Some(counters) = self.counters.take() {
+            counters
+                .request
+                .increment(counters.size.request_body_bytes());
+            counters
+                .response
+                .increment(counters.size.response_body_bytes());
+        }
+    }
+}
+
+impl AsyncRead for Measured {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        if read == 0 && !buf.is_empty() {
+            self.finish();
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl Drop for Measured {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Counts a request as in flight until it's dropped, so requests that are canceled aren't
+/// counted forever.
+struct InFlight(Gauge);
+
+impl InFlight {
+    fn new(gauge: Gauge) -> Self {
+        gauge.increment(1.0);
+        Self(gauge)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.decrement(1.0);
+    }
+}
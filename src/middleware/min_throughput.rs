@@ -0,0 +1,163 @@
+//! Protection against slow responses.
+//!
+//! A server can keep a response open forever by trickling a few bytes at a time, which an
+//! absolute timeout only catches by also cutting off legitimately long downloads. The
+//! [`MinThroughput`] middleware instead fails a response body read once the body arrives slower
+//! than a minimum rate, measured over a window.
+//!
+//! Only time spent waiting on the network counts towards the window, so a caller that reads
+//! the body slowly doesn't trip the watchdog.
+//!
+//! [`MinThroughput`]: struct.MinThroughput.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use std::time::Duration;
+//! use surf::middleware::min_throughput::MinThroughput;
+//!
+//! // Fail if less than 1 KiB/s arrives over 30 seconds.
+//! let watchdog = MinThroughput::new(1024, Duration::from_secs(30));
+//! let client = surf::Client::new().scoped().middleware(watchdog).build();
+//! let image = client.get("https://example.com/debian.iso").recv_bytes().await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures_timer::Delay;
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Middleware failing response bodies that arrive too slowly.
+#[derive(Debug, Clone)]
+pub struct MinThroughput {
+    bytes_per_sec: u64,
+    window: Duration,
+}
+
+impl MinThroughput {
+    /// Create a new instance requiring at least `bytes_per_sec`, averaged over `window`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `window` is zero.
+    pub fn new(bytes_per_sec: u64, window: Duration) -> Self {
+        assert!(window > Duration::from_secs(0), "window must not be zero");
+        Self {
+            bytes_per_sec,
+            window,
+        }
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for MinThroughput {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let res = next.run(req, client).await?;
+            let (parts, body) = res.into_parts();
+            let body = Body::from_reader(Watchdog::new(body, self.clone()));
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// A reader enforcing a minimum throughput on a body.
+struct Watchdog {
+    body: Body,
+    config: MinThroughput,
+    received: u64,
+    waited: Duration,
+    waiting_since: Option<Instant>,
+    delay: Option<Delay>,
+}
+
+impl Watchdog {
+    fn new(body: Body, config: MinThroughput) -> Self {
+        Self {
+            body,
+            config,
+            received: 0,
+            waited: Duration::from_secs(0),
+            waiting_since: None,
+            delay: None,
+        }
+    }
+
+    /// Fail if the current window is below the minimum rate, or start a new window.
+    fn check(&mut self, waited: Duration) -> io::Result<()> {
+        let required = (self.config.bytes_per_sec as f64 * waited.as_secs_f64()) as u64;
+        if self.received < required {
+            let msg = format!(
+                "received {} bytes in {:?}, below the minimum of {} bytes/s",
+                self.received, waited, self.config.bytes_per_sec
+            );
+            return Err(io::Error::new(io::ErrorKind::TimedOut, msg));
+        }
+        self.received = 0;
+        self.waited = Duration::from_secs(0);
+        self.delay = None;
+        Ok(())
+    }
+}
+
+impl AsyncRead for Watchdog {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.body).poll_read(cx, buf) {
+            Poll::Ready(Ok(read)) => {
+                if let Some(since) = this.waiting_since.take() {
+                    this.waited += since.elapsed();
+                }
+                this.delay = None;
+                this.received += read as u64;
+                if read > 0 && this.waited >= this.config.window {
+                    this.check(this.waited)?;
+                }
+                Poll::Ready(Ok(read))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => loop {
+                let since = *this.waiting_since.get_or_insert_with(Instant::now);
+                let remaining = this.config.window.checked_sub(this.waited);
+                let delay = this.delay.get_or_insert_with(|| {
+                    Delay::new(remaining.unwrap_or_else(|| Duration::from_secs(0)))
+                });
+                if Pin::new(delay).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.check(this.waited + since.elapsed())?;
+                this.waiting_since = Some(Instant::now());
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("config", &self.config)
+            .field("received", &self.received)
+            .field("waited", &self.waited)
+            .finish()
+    }
+}
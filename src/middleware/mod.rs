@@ -45,16 +45,124 @@
 //!     })
 //! }
 //! ```
+//!
+//! # Auxiliary requests
+//!
+//! Middleware gets the client the request is sent with, and can clone it to make requests of its
+//! own, such as refreshing a token or fetching `robots.txt`. Clones share the connection pool of
+//! the client, and cloning is cheap. Create the request with [`sub_request`], so it gets the
+//! client settings of the request that caused it, such as its timeout.
+//!
+//! `next` can be used more than once. Running it sends the auxiliary request through the
+//! remaining middleware and the backend, but not through the current middleware, so it can't
+//! recurse. [`Next::endpoint`] skips the remaining middleware as well.
+//!
+//! [`sub_request`]: fn.sub_request.html
+//! [`Next::endpoint`]: struct.Next.html#method.endpoint
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use http::Method;
+//! use surf::middleware::{sub_request, HttpClient, Middleware, Next, Request, Response};
+//!
+//! /// Fetch `robots.txt` before every request.
+//! #[derive(Debug)]
+//! pub struct Robots;
+//!
+//! impl<C: HttpClient> Middleware<C> for Robots {
+//!     fn handle<'a>(
+//!         &'a self,
+//!         req: Request,
+//!         client: C,
+//!         next: Next<'a, C>,
+//!     ) -> BoxFuture<'a, Result<Response, surf::Exception>> {
+//!         Box::pin(async move {
+//!             let scheme = req.uri().scheme_str().unwrap_or("https");
+//!             let host = req.uri().host().unwrap_or_default();
+//!             let uri = format!("{}://{}/robots.txt", scheme, host).parse()?;
+//!             let robots = sub_request(&req, Method::GET, uri);
+//!             let res = next.endpoint().run(robots, client.clone()).await?;
+//!             println!("robots.txt: {}", res.status());
+//!             next.run(req, client).await
+//!         })
+//!     }
+//! }
+//! ```
 
 #[doc(inline)]
-pub use crate::http_client::{Body, HttpClient, Request, Response};
+pub use crate::http_client::{
+    Body, DangerAcceptInvalidCerts, HttpClient, Isolated, Request, Response,
+};
+pub use combinators::{chain, host, method, origin, path_prefix, when, Chain, Either, When};
+pub use rate_limit::RateLimit;
+
+mod combinators;
+pub(crate) mod replay;
 
+#[cfg(feature = "middleware-audit")]
+pub mod audit;
+#[cfg(feature = "azure")]
+pub mod azure;
+pub mod cache;
+pub mod cookies;
+#[cfg(feature = "middleware-decompress")]
+pub mod decompress;
+pub mod decompression_limit;
+pub mod encryption;
+#[cfg(feature = "gcp")]
+pub mod gcp;
+pub mod idn;
+pub mod json_log;
 pub mod logger;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod min_throughput;
+pub mod normalize;
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
+pub mod rate_limit;
+pub mod redirect;
+pub mod retry;
+pub mod ssrf;
+pub mod tenant;
+pub mod token;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod transform;
+pub mod validate;
 
+use crate::http_client::{copy_extensions, BodyFactory};
 use crate::Exception;
 use futures::future::BoxFuture;
 use std::sync::Arc;
 
+/// Create an auxiliary request on behalf of another request, with an empty body.
+///
+/// The new request gets the client settings of `parent`, such as its timeout, pipeline and proxy
+/// credentials, but none of its headers.
+///
+/// See the [module documentation](index.html#auxiliary-requests) for more information.
+///
+/// # Examples
+///
+/// ```
+/// use http::Method;
+/// use surf::middleware::{sub_request, Body, Request};
+///
+/// let parent = Request::new(Body::empty());
+/// let req = sub_request(&parent, Method::GET, "https://httpbin.org/robots.txt".parse().unwrap());
+/// assert_eq!(req.method(), Method::GET);
+/// ```
+pub fn sub_request(parent: &Request, method: http::Method, uri: http::Uri) -> Request {
+    let mut req = Request::new(Body::empty());
+    *req.method_mut() = method;
+    *req.uri_mut() = uri;
+    copy_extensions(parent.extensions(), req.extensions_mut());
+    // The factory recreates the body of the parent, not of this request.
+    req.extensions_mut().remove::<BodyFactory>();
+    req
+}
+
 /// Middleware that wraps around remaining middleware chain.
 pub trait Middleware<C: HttpClient>: 'static + Send + Sync {
     /// Asynchronously handle the request, and return a response.
@@ -120,6 +228,15 @@ impl<'a, C: HttpClient> Next<'a, C> {
         }
     }
 
+    /// Skip the remaining middleware, so requests are sent straight to the backend.
+    ///
+    /// Responses still pass through the response pipeline. This is meant for auxiliary requests,
+    /// see the [module documentation](index.html#auxiliary-requests).
+    pub fn endpoint(mut self) -> Self {
+        self.next_middleware = &[];
+        self
+    }
+
     /// Asynchronously execute the remaining middleware chain.
     pub fn run(mut self, req: Request, client: C) -> BoxFuture<'a, Result<Response, Exception>> {
         if let Some((current, next)) = self.next_middleware.split_first() {
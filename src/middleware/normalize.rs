@@ -0,0 +1,185 @@
+//! URL normalization.
+//!
+//! Different spellings of the same URL, like `HTTP://Example.com:80/a/./b/../c?b=2&a=1` and
+//! `http://example.com/a/c?a=1&b=2`, should usually be treated as the same resource: crawlers
+//! shouldn't fetch both, and caches shouldn't store both. A [`Normalizer`] rewrites URLs into a
+//! canonical form, either directly with [`Normalizer::normalize`] to compute keys, or for every
+//! request when used as middleware.
+//!
+//! Parsing a URL already lowercases the scheme and the host of `http` and `https` URLs, removes
+//! default ports and resolves `.` and `..` segments. On top of that, a `Normalizer`:
+//!
+//! - decodes percent-encoded unreserved characters (`%7E` becomes `~`) and uppercases the hex
+//!   digits of the remaining escapes,
+//! - removes the fragment and an empty query,
+//! - optionally sorts the query parameters,
+//! - optionally adds or removes a trailing slash.
+//!
+//! [`Normalizer`]: struct.Normalizer.html
+//! [`Normalizer::normalize`]: struct.Normalizer.html#method.normalize
+//!
+//! # Examples
+//!
+//! ```
+//! use surf::middleware::normalize::{Normalizer, TrailingSlash};
+//! use surf::url::Url;
+//!
+//! let normalizer = Normalizer::new()
+//!     .sort_query(true)
+//!     .trailing_slash(TrailingSlash::Remove);
+//! let url = Url::parse("HTTP://Example.com:80/a/./b/../%7euser/?b=2&a=1#top")?;
+//! assert_eq!(normalizer.normalize(&url).as_str(), "http://example.com/a/~user?a=1&b=2");
+//! # Ok::<(), surf::url::ParseError>(())
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use url::Url;
+
+/// How trailing slashes in paths are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Leave paths as they are.
+    Keep,
+    /// Add a trailing slash to paths that don't have one.
+    Add,
+    /// Remove the trailing slash from paths other than `/`.
+    Remove,
+}
+
+/// Rewrites URLs into a canonical form.
+///
+/// When used as middleware, the URL of every request is normalized before it's sent.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    trailing_slash: TrailingSlash,
+    sort_query: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Normalizer {
+    /// Create a new instance that keeps trailing slashes and the order of query parameters.
+    pub fn new() -> Self {
+        Self {
+            trailing_slash: TrailingSlash::Keep,
+            sort_query: false,
+        }
+    }
+
+    /// Set how trailing slashes are handled.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Set whether query parameters are sorted by name and value.
+    ///
+    /// The order of parameters matters to some servers, so this is disabled by default.
+    pub fn sort_query(mut self, sort: bool) -> Self {
+        self.sort_query = sort;
+        self
+    }
+
+    /// Normalize a URL.
+    pub fn normalize(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        url.set_fragment(None);
+
+        if !url.cannot_be_a_base() {
+            let mut path = normalize_escapes(url.path());
+            match self.trailing_slash {
+                TrailingSlash::Keep => {}
+                TrailingSlash::Add => {
+                    if !path.ends_with('/') {
+                        path.push('/');
+                    }
+                }
+                TrailingSlash::Remove => {
+                    while path.len() > 1 && path.ends_with('/') {
+                        path.pop();
+                    }
+                }
+            }
+            url.set_path(&path);
+        }
+
+        let query = url.query().map(|query| {
+            let mut params: Vec<String> = query
+                .split('&')
+                .filter(|param| !param.is_empty())
+                .map(normalize_escapes)
+                .collect();
+            if self.sort_query {
+                params.sort_by(|a, b| {
+                    let split = |param: &str| {
+                        let mut parts = param.splitn(2, '=');
+                        (
+                            parts.next().unwrap_or("").to_string(),
+                            parts.next().map(str::to_string),
+                        )
+                    };
+                    split(a).cmp(&split(b))
+                });
+            }
+            params.join("&")
+        });
+        match query {
+            Some(query) if !query.is_empty() => url.set_query(Some(&query)),
+            _ => url.set_query(None),
+        }
+        url
+    }
+}
+
+/// Decode escaped unreserved characters, and uppercase the remaining escapes.
+fn normalize_escapes(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    output.push(byte as char);
+                } else {
+                    output.push_str(&format!("%{:02X}", byte));
+                }
+                i += 3;
+            }
+            _ => {
+                // Multi-byte characters are always percent-encoded in a parsed URL, so every
+                // remaining byte is ASCII.
+                output.push(bytes[i] as char);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+impl<C: HttpClient> Middleware<C> for Normalizer {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let url = Url::parse(&req.uri().to_string())?;
+            *req.uri_mut() = self.normalize(&url).as_str().parse()?;
+            next.run(req, client).await
+        })
+    }
+}
@@ -0,0 +1,287 @@
+//! OAuth 2.0 access tokens, refreshed with a refresh token.
+//!
+//! The [`OAuth2`] middleware sends an access token as a bearer token, and obtains new access
+//! tokens with the refresh token grant (RFC 6749, section 6) at the token endpoint of the
+//! authorization server. Access tokens are refreshed shortly before they expire, and when a
+//! request is rejected with `401 Unauthorized`, in which case `GET` and `HEAD` requests, and
+//! requests with a body set by `Request::replayable_body`, are retried once with the new token.
+//! Refreshes are single-flighted: concurrent requests needing a new token wait for a single
+//! refresh.
+//!
+//! Authorization servers may issue a new refresh token with each access token, replacing the old
+//! one. Use `OAuth2::on_refresh` to store it, so it can be used again after a restart.
+//!
+//! Token requests are sent through the middleware after this one, with the client settings of
+//! the request that needed the token.
+//!
+//! [`OAuth2`]: struct.OAuth2.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::oauth2::OAuth2;
+//!
+//! let auth = OAuth2::new("https://auth.example.com/oauth/token", "my-client")
+//!     .client_secret("hunter2")
+//!     .refresh_token("tGzv3JOkF0XG5Qx2TlKWIA")
+//!     .on_refresh(|refresh_token| println!("new refresh token: {}", refresh_token));
+//! let client = surf::Client::new().scoped().middleware(auth).build();
+//! let res = client.get("https://api.example.com/me").await?;
+//! # Ok(()) }
+//! ```
+
+use crate::attempt::{self, AttemptReason};
+use crate::middleware::token::{authorize, Retry, Token};
+use crate::middleware::{sub_request, Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use futures::prelude::*;
+use http::header::{HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use http::{Method, StatusCode};
+use serde_json::Value;
+use url::form_urlencoded;
+
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// Middleware sending OAuth 2.0 access tokens, refreshing them with a refresh token.
+pub struct OAuth2 {
+    token_url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: Vec<String>,
+    refresh_ahead: Duration,
+    on_refresh: Option<OnRefresh>,
+    state: Mutex<State>,
+}
+
+type OnRefresh = Box<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    access_token: Option<Token>,
+    refresh_token: Option<String>,
+}
+
+impl OAuth2 {
+    /// Create a new instance, for a client registered with the authorization server whose token
+    /// endpoint is at `token_url`.
+    ///
+    /// Set a refresh token with `OAuth2::refresh_token` to obtain access tokens with.
+    pub fn new(token_url: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            token_url: token_url.into(),
+            client_id: client_id.into(),
+            client_secret: None,
+            scopes: vec![],
+            refresh_ahead: Duration::from_secs(60),
+            on_refresh: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Set the secret of a confidential client.
+    ///
+    /// The client authenticates to the token endpoint with HTTP Basic authentication. Public
+    /// clients, without a secret, send their client ID with the token request instead.
+    pub fn client_secret(mut self, secret: impl Into<String>) -> Self {
+        self.client_secret = Some(secret.into());
+        self
+    }
+
+    /// Set the refresh token to obtain access tokens with.
+    pub fn refresh_token(mut self, token: impl Into<String>) -> Self {
+        self.state.get_mut().refresh_token = Some(token.into());
+        self
+    }
+
+    /// Set an access token to use until it expires, for example one that was just obtained
+    /// together with the refresh token.
+    pub fn access_token(mut self, token: Token) -> Self {
+        self.state.get_mut().access_token = Some(token);
+        self
+    }
+
+    /// Add a scope to request access tokens for.
+    ///
+    /// By default, access tokens have the scope of the refresh token. Scopes can only narrow it.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Set how long before expiry access tokens are refreshed, defaults to 60 seconds.
+    pub fn refresh_ahead(mut self, margin: Duration) -> Self {
+        self.refresh_ahead = margin;
+        self
+    }
+
+    /// Call a function with the new refresh token, whenever the authorization server issues one.
+    pub fn on_refresh(mut self, f: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_refresh = Some(Box::new(f));
+        self
+    }
+
+    /// Get a valid access token, refreshing it if needed.
+    ///
+    /// `req` is the request to send to the token endpoint, without its URL and body yet.
+    async fn token<C: HttpClient>(
+        &self,
+        req: Request,
+        client: C,
+        next: Next<'_, C>,
+    ) -> Result<Token, Exception> {
+        // Holding the lock while refreshing makes concurrent requests wait for the refresh,
+        // rather than each refreshing the token themselves.
+        let mut state = self.state.lock().await;
+        if let Some(token) = &state.access_token {
+            if !token.expires_within(self.refresh_ahead) {
+                return Ok(token.clone());
+            }
+        }
+
+        let refresh_token = state.refresh_token.as_ref().ok_or_else(|| {
+            let msg = "no refresh token to obtain an access token with";
+            io::Error::new(io::ErrorKind::PermissionDenied, msg)
+        })?;
+        let req = self.refresh_request(req, refresh_token)?;
+        let now = Instant::now();
+        let mut res = next.run(req, client).await?;
+        let mut body = Vec::new();
+        res.body_mut().read_to_end(&mut body).await?;
+        if !res.status().is_success() {
+            return Err(refresh_error(res.status(), &body).into());
+        }
+
+        let body: Value = serde_json::from_slice(&body)?;
+        let access_token = body
+            .get("access_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing access_token"))?;
+        let mut token = Token::new(access_token);
+        if let Some(expires_in) = body.get("expires_in").and_then(Value::as_u64) {
+            token = token.expires_at(now + Duration::from_secs(expires_in));
+        }
+        if let Some(refresh_token) = body.get("refresh_token").and_then(Value::as_str) {
+            if state.refresh_token.as_deref() != Some(refresh_token) {
+                state.refresh_token = Some(refresh_token.to_string());
+                if let Some(on_refresh) = &self.on_refresh {
+                    on_refresh(refresh_token);
+                }
+            }
+        }
+        state.access_token = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Turn a request into a refresh token grant request.
+    fn refresh_request(&self, mut req: Request, refresh_token: &str) -> Result<Request, Exception> {
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "refresh_token");
+        form.append_pair("refresh_token", refresh_token);
+        if !self.scopes.is_empty() {
+            form.append_pair("scope", &self.scopes.join(" "));
+        }
+        if self.client_secret.is_none() {
+            form.append_pair("client_id", &self.client_id);
+        }
+
+        *req.uri_mut() = self.token_url.parse()?;
+        *req.body_mut() = Body::from(form.finish().into_bytes());
+        let headers = req.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        if let Some(secret) = &self.client_secret {
+            // The credentials are form encoded before being joined (RFC 6749, section 2.3.1).
+            let encode =
+                |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+            let credentials = encode(&self.client_id) + ":" + &encode(secret);
+            let mut value: HeaderValue =
+                format!("Basic {}", base64::encode(&credentials)).parse()?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(req)
+    }
+
+    /// Drop an access token that was rejected, unless it has been replaced already.
+    async fn invalidate(&self, rejected: &Token) {
+        let mut state = self.state.lock().await;
+        if state.access_token.as_ref() == Some(rejected) {
+            state.access_token = None;
+        }
+    }
+}
+
+/// Create an error for a failed token request, including the OAuth error code if there is one.
+fn refresh_error(status: StatusCode, body: &[u8]) -> io::Error {
+    let error: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+    let msg = match error.get("error").and_then(Value::as_str) {
+        Some(code) => match error.get("error_description").and_then(Value::as_str) {
+            Some(description) => format!("token refresh failed: {}: {}", code, description),
+            None => format!("token refresh failed: {}", code),
+        },
+        None => format!(
+            "token refresh failed with status {}: {}",
+            status,
+            String::from_utf8_lossy(body)
+        ),
+    };
+    io::Error::new(io::ErrorKind::PermissionDenied, msg)
+}
+
+impl fmt::Debug for OAuth2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuth2")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.client_id)
+            .field("scopes", &self.scopes)
+            .field("refresh_ahead", &self.refresh_ahead)
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for OAuth2 {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let retry = Retry::new(&req);
+            let token_req = sub_request(&req, Method::POST, Default::default());
+            let token = self.token(token_req, client.clone(), next).await?;
+            authorize(&mut req, &token)?;
+            let mut res = next.run(req, client.clone()).await?;
+            if res.status() != StatusCode::UNAUTHORIZED {
+                return Ok(res);
+            }
+
+            self.invalidate(&token).await;
+            match retry {
+                Some(retry) => {
+                    let mut req = retry.into_request();
+                    let token_req = sub_request(&req, Method::POST, Default::default());
+                    let token = self.token(token_req, client.clone(), next).await?;
+                    authorize(&mut req, &token)?;
+                    let status = AttemptReason::Status(StatusCode::UNAUTHORIZED);
+                    let earlier = attempt::take(&mut res, status);
+                    let mut res = next.run(req, client).await?;
+                    attempt::prepend(&mut res, earlier);
+                    Ok(res)
+                }
+                None => Ok(res),
+            }
+        })
+    }
+}
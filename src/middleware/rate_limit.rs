@@ -0,0 +1,314 @@
+//! Client-side rate limiting.
+//!
+//! The [`RateLimit`] middleware keeps requests within a [`Rate`], across all requests and for
+//! every host separately. Limits are enforced with token buckets: a bucket holds up to `burst`
+//! tokens, refilled at the rate of the limit, and every request takes a token from the global
+//! bucket and from the bucket of its host.
+//!
+//! Requests exceeding a limit wait until they're within it, in the order they were sent. Call
+//! [`RateLimit::reject`] to fail them with a [`RateLimited`] error instead, or
+//! [`RateLimit::max_wait`] to only wait up to a maximum delay.
+//!
+//! Clones of the middleware share their buckets, so a clone limits the requests passing through
+//! the original as well.
+//!
+//! [`RateLimit`]: struct.RateLimit.html
+//! [`Rate`]: struct.Rate.html
+//! [`RateLimit::reject`]: struct.RateLimit.html#method.reject
+//! [`RateLimit::max_wait`]: struct.RateLimit.html#method.max_wait
+//! [`RateLimited`]: struct.RateLimited.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::rate_limit::{Rate, RateLimit};
+//!
+//! let limit = RateLimit::new()
+//!     .global(Rate::per_second(50))
+//!     .per_host(Rate::per_second(5).burst(10))
+//!     .host("api.github.com", Rate::per_minute(30));
+//! let client = surf::Client::new().middleware(limit);
+//! for page in 1..=100 {
+//!     let url = format!("https://api.github.com/repositories?page={}", page);
+//!     let res = client.get(url).await?;
+//!     println!("{}", res.status());
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures_timer::Delay;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of host buckets kept before full ones are discarded.
+const MAX_IDLE_HOSTS: usize = 1024;
+
+/// A number of requests allowed in a period of time.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use surf::middleware::rate_limit::Rate;
+///
+/// let rate = Rate::new(100, Duration::from_secs(10)).burst(20);
+/// assert_eq!(rate.requests(), 100);
+/// assert_eq!(rate.burst_size(), 20);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    requests: u32,
+    period: Duration,
+    burst: u32,
+}
+
+impl Rate {
+    /// Allow `requests` requests every `period`, in bursts of up to `requests` requests.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `requests` or `period` is zero.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        assert!(requests > 0, "requests must not be zero");
+        assert!(period > Duration::from_secs(0), "period must not be zero");
+        Self {
+            requests,
+            period,
+            burst: requests,
+        }
+    }
+
+    /// Allow `requests` requests every second.
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Allow `requests` requests every minute.
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Set the number of requests that can be sent at once after a quiet period, defaults to the
+    /// number of requests per period.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `burst` is zero.
+    pub fn burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "burst must not be zero");
+        self.burst = burst;
+        self
+    }
+
+    /// Get the number of requests allowed every period.
+    pub fn requests(&self) -> u32 {
+        self.requests
+    }
+
+    /// Get the period.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Get the number of requests that can be sent at once.
+    pub fn burst_size(&self) -> u32 {
+        self.burst
+    }
+
+    /// Get the number of tokens added to a bucket every second.
+    fn per_sec(&self) -> f64 {
+        f64::from(self.requests) / self.period.as_secs_f64()
+    }
+}
+
+/// Middleware limiting the rate of requests, see the [module documentation](index.html).
+#[derive(Debug, Clone, Default)]
+pub struct RateLimit {
+    global: Option<Rate>,
+    per_host: Option<Rate>,
+    hosts: HashMap<String, Rate>,
+    max_wait: Option<Duration>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    global: Option<Bucket>,
+    hosts: HashMap<String, Bucket>,
+}
+
+impl RateLimit {
+    /// Create a new instance without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the rate of all requests.
+    pub fn global(mut self, rate: Rate) -> Self {
+        self.global = Some(rate);
+        self
+    }
+
+    /// Limit the rate of requests to every host, unless the host has a limit of its own.
+    pub fn per_host(mut self, rate: Rate) -> Self {
+        self.per_host = Some(rate);
+        self
+    }
+
+    /// Limit the rate of requests to a host, such as `api.example.com`.
+    pub fn host(mut self, host: &str, rate: Rate) -> Self {
+        self.hosts.insert(host.to_ascii_lowercase(), rate);
+        self
+    }
+
+    /// Fail requests exceeding a limit with a `RateLimited` error, instead of waiting until they
+    /// are within it.
+    pub fn reject(self) -> Self {
+        self.max_wait(Duration::from_secs(0))
+    }
+
+    /// Only wait up to `max` for a request to be within the limits, failing it with a
+    /// `RateLimited` error if it would need to wait longer.
+    ///
+    /// By default requests wait as long as needed.
+    pub fn max_wait(mut self, max: Duration) -> Self {
+        self.max_wait = Some(max);
+        self
+    }
+
+    /// Take a token for a request to a host, returning how long to wait before sending it.
+    fn acquire(&self, host: &str) -> Result<Duration, RateLimited> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let buckets = &mut *buckets;
+
+        let global = match self.global {
+            Some(rate) => Some(buckets.global.get_or_insert_with(|| Bucket::new(rate, now))),
+            None => None,
+        };
+        let host_rate = self.hosts.get(host).or(self.per_host.as_ref());
+        if host_rate.is_some() && buckets.hosts.len() >= MAX_IDLE_HOSTS {
+            buckets.hosts.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let host = match host_rate {
+            Some(rate) => Some(
+                buckets
+                    .hosts
+                    .entry(host.to_string())
+                    .or_insert_with(|| Bucket::new(*rate, now)),
+            ),
+            None => None,
+        };
+
+        let mut wait = Duration::from_secs(0);
+        for bucket in global.iter().chain(host.iter()) {
+            wait = wait.max(bucket.wait(now));
+        }
+        if let Some(max) = self.max_wait {
+            if wait > max {
+                return Err(RateLimited { retry_after: wait });
+            }
+        }
+        for bucket in global.into_iter().chain(host) {
+            bucket.take(now);
+        }
+        Ok(wait)
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for RateLimit {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let host = req.uri().host().unwrap_or_default().to_ascii_lowercase();
+            let wait = self.acquire(&host)?;
+            if wait > Duration::from_secs(0) {
+                log::debug!("rate limited request to {}, waiting {:?}", host, wait);
+                Delay::new(wait).await;
+            }
+            next.run(req, client).await
+        })
+    }
+}
+
+/// A token bucket.
+///
+/// Tokens are taken even when the bucket is empty, so the number of tokens goes negative and
+/// every waiting request waits for the tokens taken before it.
+#[derive(Debug)]
+struct Bucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: f64::from(rate.burst),
+            updated: now,
+        }
+    }
+
+    /// Get the number of tokens at `now`.
+    fn tokens(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * self.rate.per_sec()).min(f64::from(self.rate.burst))
+    }
+
+    /// Get how long a request taking a token at `now` needs to wait.
+    fn wait(&self, now: Instant) -> Duration {
+        let missing = 1.0 - self.tokens(now);
+        if missing <= 0.0 {
+            return Duration::from_secs(0);
+        }
+        Duration::from_secs_f64(missing / self.rate.per_sec())
+    }
+
+    fn take(&mut self, now: Instant) {
+        self.tokens = self.tokens(now) - 1.0;
+        self.updated = now;
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        self.tokens(now) >= f64::from(self.rate.burst)
+    }
+}
+
+/// The error returned for requests exceeding a limit of the `RateLimit` middleware, if they
+/// aren't allowed to wait that long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl RateLimited {
+    /// Get how long until the request would be within the limits.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limit exceeded, retry after {:?}", self.retry_after)
+    }
+}
+
+impl Error for RateLimited {}
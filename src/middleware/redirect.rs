@@ -0,0 +1,414 @@
+//! Following redirects.
+//!
+//! The [`Redirect`] middleware follows `3xx` responses with a `Location` header, according to a
+//! [`RedirectPolicy`]. Use `Client::redirect_policy` to follow redirects for all requests of a
+//! client. `303 See Other` responses, and `301 Moved Permanently` and `302 Found` responses to
+//! `POST` requests, are followed with a `GET` request without a body, unless the policy preserves
+//! the method. Other redirects are followed with the original method and body.
+//!
+//! By default the `Authorization`, `Cookie` and `Proxy-Authorization` headers are removed when
+//! following a redirect to another origin. Headers set by middleware that runs after the
+//! `Redirect` middleware are set again for every request; use `ScopedBuilder::middleware_for` to
+//! only send credentials to the origins they're meant for.
+//!
+//! # Replaying the body
+//!
+//! Following a `307 Temporary Redirect` or `308 Permanent Redirect` means sending the request
+//! body again, which isn't possible for a streaming body that has already been sent. The
+//! [`BodyReplay`] policy decides how to deal with this:
+//!
+//! - `BodyReplay::Buffer(limit)` (the default, with a limit of 64 KiB): bodies of up to `limit`
+//!   bytes are buffered in memory before the request is sent, so they can be sent again.
+//! - `BodyReplay::Factory`: only bodies set with `Request::replayable_body` are sent again.
+//! - `BodyReplay::Never`: bodies are never sent again.
+//!
+//! Bodies set with `Request::replayable_body` are always recreated rather than buffered. If a
+//! redirect requires sending a body that can't be replayed, the request fails with a
+//! [`RedirectError::BodyNotReplayable`] error instead of sending a request without it.
+//!
+//! Following stops with a [`RedirectLoop`] error once a redirect leads back to a URL that was
+//! already requested with the same method, or once the maximum number of redirects is exceeded.
+//! The error lists the URLs that were requested.
+//!
+//! Settings of the original request (such as the timeout) carry over to the redirected requests,
+//! but other extensions are only available to the first request.
+//!
+//! [`Redirect`]: struct.Redirect.html
+//! [`RedirectPolicy`]: struct.RedirectPolicy.html
+//! [`BodyReplay`]: enum.BodyReplay.html
+//! [`RedirectLoop`]: struct.RedirectLoop.html
+//! [`RedirectError::BodyNotReplayable`]: enum.RedirectError.html#variant.BodyNotReplayable
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::redirect::{BodyReplay, Redirect};
+//!
+//! let redirect = Redirect::new().replay(BodyReplay::Buffer(1024 * 1024));
+//! let client = surf::Client::new().scoped().middleware(redirect).build();
+//! let uri = "https://httpbin.org/redirect-to?url=/post&status_code=307";
+//! let res = client.post(uri).body_string("chashu".into()).await?;
+//! # Ok(()) }
+//! ```
+
+use crate::attempt::{self, AttemptReason};
+use crate::http_client::copy_extensions;
+use crate::middleware::replay::Replay;
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use http::header::{
+    AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+    PROXY_AUTHORIZATION, TRANSFER_ENCODING,
+};
+use http::{Method, StatusCode};
+use url::Url;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// How to deal with request bodies that need to be sent again to follow a redirect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyReplay {
+    /// Buffer bodies of up to the given number of bytes.
+    Buffer(usize),
+    /// Only send bodies set with `Request::replayable_body` again.
+    Factory,
+    /// Never send bodies again.
+    Never,
+}
+
+impl Default for BodyReplay {
+    fn default() -> Self {
+        BodyReplay::Buffer(64 * 1024)
+    }
+}
+
+/// An error following a redirect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectError {
+    /// The redirect requires sending the request body again, but the body can't be replayed.
+    BodyNotReplayable {
+        /// The status of the redirect.
+        status: StatusCode,
+        /// The location redirected to.
+        location: Url,
+    },
+}
+
+impl fmt::Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectError::BodyNotReplayable { status, location } => write!(
+                f,
+                "can't follow redirect ({}) to {}: the request body can't be replayed",
+                status, location
+            ),
+        }
+    }
+}
+
+impl Error for RedirectError {}
+
+/// An error returned when following redirects leads back to an earlier request, or exceeds the
+/// maximum number of redirects.
+///
+/// A redirect to a URL that was already requested with the same method is a cycle, and fails
+/// right away rather than after the maximum number of redirects.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::middleware::redirect::{RedirectLoop, RedirectPolicy};
+///
+/// let client = surf::Client::new().redirect_policy(RedirectPolicy::limited(3));
+/// match client.get("https://httpbin.org/redirect/5").await {
+///     Err(err) => match err.downcast_ref::<RedirectLoop>() {
+///         Some(err) => println!("gave up after {:?}", err.chain()),
+///         None => return Err(err),
+///     },
+///     Ok(res) => println!("{}", res.status()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectLoop {
+    chain: Vec<Url>,
+    cycle: bool,
+}
+
+impl RedirectLoop {
+    /// Get the URLs requested, starting with the original URL and ending with the location of
+    /// the redirect that wasn't followed.
+    pub fn chain(&self) -> &[Url] {
+        &self.chain
+    }
+
+    /// Check whether the last redirect led back to an earlier request, rather than exceeding
+    /// the maximum number of redirects.
+    pub fn is_cycle(&self) -> bool {
+        self.cycle
+    }
+}
+
+impl fmt::Display for RedirectLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cycle {
+            write!(f, "redirect loop: ")?;
+        } else {
+            write!(f, "too many redirects: ")?;
+        }
+        for (i, url) in self.chain.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", url)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RedirectLoop {}
+
+/// A policy for following redirects.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::middleware::redirect::{BodyReplay, RedirectPolicy};
+///
+/// let policy = RedirectPolicy::limited(5)
+///     .preserve_method(true)
+///     .replay(BodyReplay::Factory);
+/// let client = surf::Client::new().redirect_policy(policy);
+/// let res = client.get("https://httpbin.org/redirect/3").await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    max_redirects: usize,
+    replay: BodyReplay,
+    strip_sensitive_headers: bool,
+    preserve_method: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_redirects: 10,
+            replay: BodyReplay::default(),
+            strip_sensitive_headers: true,
+            preserve_method: false,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Create a new instance, following up to `10` redirects.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new instance that doesn't follow redirects.
+    pub fn none() -> Self {
+        Self::limited(0)
+    }
+
+    /// Create a new instance following up to `max` redirects.
+    pub fn limited(max: usize) -> Self {
+        Self::default().max_redirects(max)
+    }
+
+    /// Set the maximum number of redirects to follow, defaults to `10`.
+    ///
+    /// A request redirected more often fails with a `RedirectLoop` error. With a limit of `0`,
+    /// redirect responses are returned as they are.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Set how to deal with request bodies that need to be sent again.
+    pub fn replay(mut self, replay: BodyReplay) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Set whether to remove the `Authorization`, `Cookie` and `Proxy-Authorization` headers when
+    /// following a redirect to another origin, defaults to `true`.
+    ///
+    /// Once removed, the headers aren't sent again, even if a later redirect leads back to the
+    /// original origin.
+    pub fn strip_sensitive_headers(mut self, strip: bool) -> Self {
+        self.strip_sensitive_headers = strip;
+        self
+    }
+
+    /// Set whether to keep the method of `POST` requests when following `301 Moved Permanently`
+    /// and `302 Found` redirects, defaults to `false`.
+    ///
+    /// RFC 7231 allows either, but most user agents change the method to `GET`. `303 See Other`
+    /// redirects are always followed with `GET`.
+    pub fn preserve_method(mut self, preserve: bool) -> Self {
+        self.preserve_method = preserve;
+        self
+    }
+}
+
+/// Middleware following redirects.
+#[derive(Debug, Clone, Default)]
+pub struct Redirect {
+    policy: RedirectPolicy,
+}
+
+impl Redirect {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new instance following redirects according to `policy`.
+    pub fn with_policy(policy: RedirectPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Set the maximum number of redirects to follow, defaults to `10`.
+    ///
+    /// A request redirected more often fails with a `RedirectLoop` error. With a limit of `0`,
+    /// redirect responses are returned as they are.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.policy = self.policy.max_redirects(max);
+        self
+    }
+
+    /// Set how to deal with request bodies that need to be sent again.
+    pub fn replay(mut self, replay: BodyReplay) -> Self {
+        self.policy = self.policy.replay(replay);
+        self
+    }
+}
+
+/// Check whether two URLs have the same origin.
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+impl<C: HttpClient> Middleware<C> for Redirect {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let policy = &self.policy;
+            let (body, mut replay) =
+                Replay::prepare(body, &parts.extensions, policy.replay).await?;
+
+            let mut method = parts.method.clone();
+            let mut uri = parts.uri.clone();
+            let mut headers = parts.headers.clone();
+            let mut extensions = http::Extensions::new();
+            copy_extensions(&parts.extensions, &mut extensions);
+
+            let req = http::Request::from_parts(parts, body);
+            let mut res = next.run(req, client.clone()).await?;
+
+            let mut hops = vec![];
+            loop {
+                let status = res.status();
+                if policy.max_redirects == 0
+                    || !status.is_redirection()
+                    || status == StatusCode::NOT_MODIFIED
+                {
+                    break;
+                }
+                let (location, base) = match res
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| {
+                        let base = Url::parse(&uri.to_string()).ok()?;
+                        Some((base.join(location).ok()?, base))
+                    }) {
+                    Some(urls) => urls,
+                    None => break,
+                };
+
+                let downgrade = status == StatusCode::SEE_OTHER
+                    || (method == Method::POST
+                        && !policy.preserve_method
+                        && (status == StatusCode::MOVED_PERMANENTLY
+                            || status == StatusCode::FOUND));
+                let next_method = if downgrade && method != Method::HEAD {
+                    Method::GET
+                } else {
+                    method.clone()
+                };
+                if hops.is_empty() {
+                    hops.push((method.clone(), base.clone()));
+                }
+                let cycle = hops.contains(&(next_method.clone(), location.clone()));
+                hops.push((next_method.clone(), location.clone()));
+                if cycle || hops.len() > policy.max_redirects + 1 {
+                    let chain = hops.into_iter().map(|(_, url)| url).collect();
+                    return Err(RedirectLoop { chain, cycle }.into());
+                }
+
+                if policy.strip_sensitive_headers && !same_origin(&base, &location) {
+                    for name in &[AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+                        headers.remove(name);
+                    }
+                }
+
+                method = next_method;
+                let body = if downgrade {
+                    for name in &[
+                        CONTENT_TYPE,
+                        CONTENT_LENGTH,
+                        CONTENT_ENCODING,
+                        TRANSFER_ENCODING,
+                    ] {
+                        headers.remove(name);
+                    }
+                    replay = Replay::Bytes(Arc::new(vec![]));
+                    Body::empty()
+                } else {
+                    match replay.body() {
+                        Some(body) => body,
+                        None => {
+                            let err = RedirectError::BodyNotReplayable { status, location };
+                            return Err(err.into());
+                        }
+                    }
+                };
+
+                uri = match location.as_str().parse() {
+                    Ok(uri) => uri,
+                    Err(_) => break,
+                };
+                let mut req = http::Request::new(body);
+                *req.method_mut() = method.clone();
+                *req.uri_mut() = uri.clone();
+                *req.headers_mut() = headers.clone();
+                copy_extensions(&extensions, req.extensions_mut());
+                let earlier = attempt::take(&mut res, AttemptReason::Redirect(status));
+                res = next.run(req, client.clone()).await?;
+                attempt::prepend(&mut res, earlier);
+            }
+            Ok(res)
+        })
+    }
+}
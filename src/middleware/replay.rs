@@ -0,0 +1,69 @@
+//! Keeping request bodies around so they can be sent again.
+
+use super::redirect::BodyReplay;
+use crate::http_client::{BodyFactory, Duplex};
+use crate::middleware::Body;
+use crate::Exception;
+
+use futures::io::AsyncReadExt;
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// A request body that can be sent again.
+#[derive(Clone)]
+pub(crate) enum Replay {
+    Bytes(Arc<Vec<u8>>),
+    Factory(BodyFactory),
+    None,
+}
+
+impl Replay {
+    /// Prepare a body to be sent, returning it with a way to send it again according to `policy`.
+    ///
+    /// Bodies from a factory, set in `extensions`, are always recreated rather than buffered.
+    /// Bodies of duplex requests are never read ahead, so they can't be sent again.
+    pub(crate) async fn prepare(
+        body: Body,
+        extensions: &http::Extensions,
+        policy: BodyReplay,
+    ) -> Result<(Body, Replay), Exception> {
+        if extensions.get::<Duplex>().is_some() {
+            return Ok((body, Replay::None));
+        }
+        let factory = extensions.get::<BodyFactory>().cloned();
+        Ok(match (factory, policy) {
+            (Some(factory), _) => (body, Replay::Factory(factory)),
+            (None, BodyReplay::Buffer(limit)) => match buffer(body, limit).await? {
+                (body, Some(bytes)) => (body, Replay::Bytes(Arc::new(bytes))),
+                (body, None) => (body, Replay::None),
+            },
+            // Only a body known to be empty can be replayed, without reading ahead.
+            (None, _) if body.len() == Some(0) => (body, Replay::Bytes(Arc::new(vec![]))),
+            (None, _) => (body, Replay::None),
+        })
+    }
+
+    /// Create the body again, if possible.
+    pub(crate) fn body(&self) -> Option<Body> {
+        match self {
+            Replay::Bytes(bytes) => Some(Body::from(bytes.to_vec())),
+            Replay::Factory(BodyFactory(factory)) => Some(factory()),
+            Replay::None => None,
+        }
+    }
+}
+
+/// Read up to `limit` bytes of a body, returning the bytes if that's the entire body.
+async fn buffer(mut body: Body, limit: usize) -> Result<(Body, Option<Vec<u8>>), Exception> {
+    let mut buf = Vec::new();
+    (&mut body)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut buf)
+        .await?;
+    if buf.len() <= limit {
+        Ok((Body::from(buf.clone()), Some(buf)))
+    } else {
+        Ok((Body::from_reader(Cursor::new(buf).chain(body)), None))
+    }
+}
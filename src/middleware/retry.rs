@@ -0,0 +1,278 @@
+//! Retrying failed requests.
+//!
+//! The [`Retry`] middleware sends idempotent requests (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS`
+//! and `TRACE`) again when they fail with a connection error or a timeout, or when the response
+//! has one of the retried statuses: `429 Too Many Requests`, `502 Bad Gateway` and
+//! `503 Service Unavailable` by default. Other requests are passed through unchanged.
+//!
+//! Between attempts the middleware waits for an exponentially growing delay with random jitter,
+//! so that many clients failing at once don't retry at once. If the response has a
+//! `Retry-After` header, its delay is used instead. If that delay is longer than the maximum
+//! delay, the response is returned rather than waiting.
+//!
+//! Retrying means sending the request body again. Bodies are buffered or recreated according to
+//! a [`BodyReplay`] policy, like for redirects; if the body can't be replayed, the last response
+//! or error is returned. The attempts made are available from `Response::attempts`.
+//!
+//! [`Retry`]: struct.Retry.html
+//! [`BodyReplay`]: ../redirect/enum.BodyReplay.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use http::StatusCode;
+//! use std::time::Duration;
+//! use surf::middleware::retry::Retry;
+//!
+//! let retry = Retry::new()
+//!     .max_attempts(5)
+//!     .statuses(&[StatusCode::SERVICE_UNAVAILABLE, StatusCode::GATEWAY_TIMEOUT])
+//!     .max_delay(Duration::from_secs(10));
+//! let client = surf::Client::new().scoped().middleware(retry).build();
+//! let res = client.get("https://httpbin.org/status/503").await?;
+//! println!("gave up after {} attempts", res.attempts().len());
+//! # Ok(()) }
+//! ```
+
+use crate::attempt::{self, AttemptReason, Attempts};
+use crate::http_client::copy_extensions;
+use crate::middleware::redirect::BodyReplay;
+use crate::middleware::replay::Replay;
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::{Exception, TimeoutError};
+
+use futures::future::BoxFuture;
+use futures_timer::Delay;
+use http::header::RETRY_AFTER;
+use http::{Method, StatusCode};
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Middleware retrying idempotent requests.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    max_attempts: usize,
+    statuses: Vec<StatusCode>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    replay: BodyReplay,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ],
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(30),
+            replay: BodyReplay::default(),
+        }
+    }
+}
+
+impl Retry {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of attempts, including the first one, defaults to `3`.
+    pub fn max_attempts(mut self, max: usize) -> Self {
+        self.max_attempts = max;
+        self
+    }
+
+    /// Set the response statuses that are retried, defaults to `429`, `502` and `503`.
+    pub fn statuses(mut self, statuses: &[StatusCode]) -> Self {
+        self.statuses = statuses.to_vec();
+        self
+    }
+
+    /// Set the delay before the first retry, defaults to 200 milliseconds.
+    ///
+    /// The delay doubles for every following retry, and a random part of up to half of it is
+    /// taken off.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set the maximum delay between attempts, defaults to 30 seconds.
+    ///
+    /// A `Retry-After` header asking for a longer delay ends the retries.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set how to deal with request bodies that need to be sent again.
+    pub fn replay(mut self, replay: BodyReplay) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Get the delay before the retry following attempt `n`, counting from 1.
+    fn backoff(&self, n: usize) -> Duration {
+        let exp = (n - 1).min(31) as u32;
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << exp)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(n);
+        let jitter = (hasher.finish() % 1000) as u32;
+        delay - delay / 2 * jitter / 1000
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Retry {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if self.max_attempts <= 1 || !idempotent(req.method()) {
+                return next.run(req, client).await;
+            }
+
+            let (parts, body) = req.into_parts();
+            let (body, replay) = Replay::prepare(body, &parts.extensions, self.replay).await?;
+
+            let method = parts.method.clone();
+            let uri = parts.uri.clone();
+            let headers = parts.headers.clone();
+            let mut extensions = http::Extensions::new();
+            copy_extensions(&parts.extensions, &mut extensions);
+
+            let mut req = http::Request::from_parts(parts, body);
+            let mut earlier = Attempts::default();
+            let mut n = 1;
+            loop {
+                let started = Instant::now();
+                let res = next.run(req, client.clone()).await;
+                let (reason, retry_after) = match &res {
+                    Ok(res) if self.statuses.contains(&res.status()) => {
+                        (AttemptReason::Status(res.status()), retry_after(res))
+                    }
+                    Ok(_) => return finish(res, earlier),
+                    Err(err) => match transient(err) {
+                        Some(reason) => (reason, None),
+                        None => return finish(res, earlier),
+                    },
+                };
+                if n >= self.max_attempts {
+                    return finish(res, earlier);
+                }
+                let delay = match retry_after {
+                    Some(delay) if delay > self.max_delay => return finish(res, earlier),
+                    Some(delay) => delay,
+                    None => self.backoff(n),
+                };
+                let body = match replay.body() {
+                    Some(body) => body,
+                    None => return finish(res, earlier),
+                };
+
+                match res {
+                    Ok(mut res) => attempt::append(&mut earlier, attempt::take(&mut res, reason)),
+                    Err(_) => attempt::failed(&mut earlier, started, reason),
+                }
+                log::debug!("retrying {} {} in {:?}", method, uri, delay);
+                Delay::new(delay).await;
+
+                req = http::Request::new(body);
+                *req.method_mut() = method.clone();
+                *req.uri_mut() = uri.clone();
+                *req.headers_mut() = headers.clone();
+                copy_extensions(&extensions, req.extensions_mut());
+                n += 1;
+            }
+        })
+    }
+}
+
+/// Check whether sending a request with a method more than once has the same effect as once.
+pub(crate) fn idempotent(method: &Method) -> bool {
+    [
+        Method::GET,
+        Method::HEAD,
+        Method::PUT,
+        Method::DELETE,
+        Method::OPTIONS,
+        Method::TRACE,
+    ]
+    .contains(method)
+}
+
+/// Add the earlier attempts to the final result.
+fn finish(res: Result<Response, Exception>, earlier: Attempts) -> Result<Response, Exception> {
+    let mut res = res?;
+    if !earlier.is_empty() {
+        attempt::prepend(&mut res, earlier);
+    }
+    Ok(res)
+}
+
+/// Get the delay asked for by the `Retry-After` header of a response.
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Check whether an error is worth retrying, such as a connection error or a timeout.
+fn transient(err: &Exception) -> Option<AttemptReason> {
+    if err.is::<TimeoutError>() {
+        return Some(AttemptReason::Timeout);
+    }
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return match err.kind() {
+            io::ErrorKind::TimedOut => Some(AttemptReason::Timeout),
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => Some(AttemptReason::Error(err.to_string())),
+            _ => None,
+        };
+    }
+    #[cfg(all(feature = "curl-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<isahc::Error>() {
+            return match err {
+                isahc::Error::Timeout => Some(AttemptReason::Timeout),
+                isahc::Error::ConnectFailed
+                | isahc::Error::CouldntResolveHost
+                | isahc::Error::NoResponse
+                | isahc::Error::Io(_) => Some(AttemptReason::Error(err.to_string())),
+                _ => None,
+            };
+        }
+    }
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            if err.is_connect() {
+                return Some(AttemptReason::Error(err.to_string()));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,205 @@
+//! Protection against server-side request forgery.
+//!
+//! The [`SsrfGuard`] middleware rejects requests to hosts that resolve to private, loopback,
+//! link-local, multicast, reserved or unspecified addresses, or to IPv6 addresses embedding such
+//! an IPv4 address. This makes it safe(r) to make requests to URLs supplied
+//! by untrusted users, who could otherwise use them to reach internal services.
+//!
+//! Hosts are resolved by the middleware itself, and requests are rejected if _any_ of the
+//! resolved addresses isn't allowed. For `http` URLs the request is then pinned to the checked
+//! address, so a DNS server handing out a different address on the next lookup (DNS rebinding)
+//! can't redirect the request. `https` URLs can't be pinned without breaking certificate
+//! validation, and are resolved again by the backend.
+//!
+//! The backends don't follow redirects themselves. Redirects followed by a middleware registered
+//! before the guard pass through it again, and are checked like any other request.
+//!
+//! [`SsrfGuard`]: struct.SsrfGuard.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::ssrf::SsrfGuard;
+//!
+//! let client = surf::Client::new().scoped().middleware(SsrfGuard::new()).build();
+//! assert!(client.get("http://169.254.169.254/latest/meta-data/").await.is_err());
+//! assert!(client.get("http://localhost:8080/admin").await.is_err());
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use http::header::{HeaderValue, HOST};
+use http::uri::{Authority, Uri};
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::thread;
+
+/// Middleware rejecting requests to addresses that aren't publicly routable.
+///
+/// Rejected requests fail with an `io::ErrorKind::PermissionDenied` error.
+#[derive(Debug, Clone, Default)]
+pub struct SsrfGuard {
+    allowed: Vec<IpAddr>,
+}
+
+impl SsrfGuard {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow requests to an address that would otherwise be rejected.
+    pub fn allow(mut self, addr: IpAddr) -> Self {
+        self.allowed.push(addr);
+        self
+    }
+
+    /// Check whether requests to an address are allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::middleware::ssrf::SsrfGuard;
+    ///
+    /// let guard = SsrfGuard::new();
+    /// assert!(guard.is_allowed("93.184.216.34".parse()?));
+    /// assert!(!guard.is_allowed("10.0.0.1".parse()?));
+    /// assert!(!guard.is_allowed("::ffff:127.0.0.1".parse()?));
+    /// # Ok::<(), std::net::AddrParseError>(())
+    /// ```
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        self.allowed.contains(&addr) || is_public(addr)
+    }
+
+    fn check(&self, addr: IpAddr) -> io::Result<()> {
+        if self.is_allowed(addr) {
+            Ok(())
+        } else {
+            let msg = format!("requests to {} are not allowed", addr);
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, msg))
+        }
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for SsrfGuard {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let host = match req.uri().host() {
+                Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no host").into()),
+            };
+            if let Ok(addr) = host.parse::<IpAddr>() {
+                self.check(addr)?;
+                return next.run(req, client).await;
+            }
+
+            let https = req.uri().scheme_str() == Some("https");
+            let port = req.uri().port_u16().unwrap_or(if https { 443 } else { 80 });
+            let addrs = resolve(host.to_string(), port).await?;
+            for addr in &addrs {
+                self.check(addr.ip())?;
+            }
+
+            if req.uri().scheme_str() == Some("http") {
+                pin(&mut req, addrs[0])?;
+            }
+            next.run(req, client).await
+        })
+    }
+}
+
+/// Resolve a host on a background thread.
+async fn resolve(host: String, port: u16) -> io::Result<Vec<SocketAddr>> {
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let addrs = (host.as_str(), port)
+            .to_socket_addrs()
+            .map(|addrs| addrs.collect::<Vec<_>>());
+        let _ = sender.send(addrs);
+    });
+    let addrs = receiver
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::Interrupted, "lookup was cancelled"))??;
+    if addrs.is_empty() {
+        let msg = "host did not resolve to any address";
+        return Err(io::Error::new(io::ErrorKind::NotFound, msg));
+    }
+    Ok(addrs)
+}
+
+/// Point the request at a resolved address, keeping the original `Host` header.
+fn pin(req: &mut Request, addr: SocketAddr) -> io::Result<()> {
+    if !req.headers().contains_key(HOST) {
+        let authority = req.uri().authority_part().unwrap().as_str();
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        let value = HeaderValue::from_str(host).map_err(|_| invalid())?;
+        req.headers_mut().insert(HOST, value);
+    }
+    let mut parts = req.uri().clone().into_parts();
+    let authority: Authority = addr.to_string().parse().map_err(|_| invalid())?;
+    parts.authority = Some(authority);
+    *req.uri_mut() = Uri::from_parts(parts).map_err(|_| invalid())?;
+    Ok(())
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "invalid uri")
+}
+
+/// Check whether an address is publicly routable.
+fn is_public(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => is_public_v4(addr),
+        IpAddr::V6(addr) => is_public_v6(addr),
+    }
+}
+
+fn is_public_v4(addr: Ipv4Addr) -> bool {
+    let [a, b, c, _] = addr.octets();
+    !(addr.is_private()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_multicast()
+        || a == 0
+        // Reserved for future use, including the broadcast address.
+        || a >= 240
+        // Shared address space, used for carrier-grade NAT.
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments.
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking networks.
+        || (a == 198 && (b == 18 || b == 19)))
+}
+
+fn is_public_v6(addr: Ipv6Addr) -> bool {
+    let first = addr.segments()[0];
+    match addr.segments() {
+        // IPv4-mapped addresses, and IPv4 addresses translated by NAT64.
+        [0, 0, 0, 0, 0, 0xffff, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => {
+            let [.., a, b, c, d] = addr.octets();
+            return is_public_v4(Ipv4Addr::new(a, b, c, d));
+        }
+        _ => {}
+    }
+    !(addr.is_loopback()
+        || addr.is_unspecified()
+        || addr.is_multicast()
+        // Unique local addresses.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local and the deprecated site-local addresses.
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0)
+}
@@ -0,0 +1,197 @@
+//! Per-tenant credentials.
+//!
+//! The `TenantCredentials` middleware lets a single `Client` serve many tenants. Each request is
+//! tagged with a [`Tenant`] extension, and the middleware applies the credentials registered for
+//! that tenant. Requests for a tenant without registered credentials are rejected, so credentials
+//! are never applied to requests of another tenant.
+//!
+//! [`Tenant`]: struct.Tenant.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::tenant::{Bearer, Tenant, TenantCredentials};
+//!
+//! let credentials = TenantCredentials::new()
+//!     .tenant("chashu", Bearer::new("chashu-token"))
+//!     .tenant("nori", Bearer::new("nori-token"));
+//! let client = surf::Client::new().scoped().middleware(credentials).build();
+//!
+//! let res = client
+//!     .get("https://httpbin.org/bearer")
+//!     .set_ext(Tenant::new("chashu"))
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// The tenant a request is made on behalf of.
+///
+/// Set it on a request with `Request::set_ext`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tenant(String);
+
+impl Tenant {
+    /// Create a new instance.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+
+    /// Get the tenant key.
+    pub fn key(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Credentials that can be applied to a request.
+///
+/// This is implemented for closures taking a `&mut Request`, which makes it possible to sign
+/// requests or set arbitrary headers.
+pub trait Credentials: Send + Sync + 'static {
+    /// Apply the credentials to a request.
+    fn apply(&self, req: &mut Request);
+}
+
+impl<F> Credentials for F
+where
+    F: Fn(&mut Request) + Send + Sync + 'static,
+{
+    fn apply(&self, req: &mut Request) {
+        (self)(req)
+    }
+}
+
+/// Bearer token credentials, sent in the `Authorization` header.
+#[derive(Clone)]
+pub struct Bearer {
+    value: HeaderValue,
+}
+
+impl Bearer {
+    /// Create a new instance.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the token isn't a valid header value.
+    pub fn new(token: impl AsRef<str>) -> Self {
+        let mut value: HeaderValue = format!("Bearer {}", token.as_ref()).parse().unwrap();
+        value.set_sensitive(true);
+        Self { value }
+    }
+}
+
+impl Credentials for Bearer {
+    fn apply(&self, req: &mut Request) {
+        req.headers_mut().insert(AUTHORIZATION, self.value.clone());
+    }
+}
+
+impl fmt::Debug for Bearer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bearer")
+            .field("value", &"<hidden>")
+            .finish()
+    }
+}
+
+/// Credentials sent in a custom header, such as an API key.
+#[derive(Clone)]
+pub struct ApiKey {
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+impl ApiKey {
+    /// Create a new instance.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `name` isn't a valid header name, or `key` isn't a valid header value.
+    pub fn new(name: &'static str, key: impl AsRef<str>) -> Self {
+        let mut value: HeaderValue = key.as_ref().parse().unwrap();
+        value.set_sensitive(true);
+        Self {
+            name: name.parse().unwrap(),
+            value,
+        }
+    }
+}
+
+impl Credentials for ApiKey {
+    fn apply(&self, req: &mut Request) {
+        req.headers_mut()
+            .insert(self.name.clone(), self.value.clone());
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("name", &self.name)
+            .field("value", &"<hidden>")
+            .finish()
+    }
+}
+
+/// Middleware applying credentials based on the request's `Tenant`.
+///
+/// Requests without a `Tenant` are passed through unchanged. Requests for a tenant without
+/// registered credentials fail with an `io::ErrorKind::PermissionDenied` error.
+#[derive(Default)]
+pub struct TenantCredentials {
+    tenants: HashMap<String, Arc<dyn Credentials>>,
+}
+
+impl TenantCredentials {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the credentials for a tenant, replacing any previous credentials.
+    pub fn tenant(mut self, key: impl Into<String>, credentials: impl Credentials) -> Self {
+        self.tenants.insert(key.into(), Arc::new(credentials));
+        self
+    }
+}
+
+impl fmt::Debug for TenantCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantCredentials")
+            .field("tenants", &self.tenants.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for TenantCredentials {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            if let Some(tenant) = req.extensions().get::<Tenant>() {
+                let credentials = self.tenants.get(tenant.key()).cloned().ok_or_else(|| {
+                    let msg = format!("no credentials registered for tenant `{}`", tenant.key());
+                    io::Error::new(io::ErrorKind::PermissionDenied, msg)
+                })?;
+                credentials.apply(&mut req);
+            }
+            next.run(req, client).await
+        })
+    }
+}
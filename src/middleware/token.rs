@@ -0,0 +1,251 @@
+//! Bearer tokens from a pluggable source.
+//!
+//! The [`BearerAuth`] middleware sends tokens obtained from a [`TokenSource`] as bearer tokens.
+//! Tokens are cached, and refreshed shortly before they expire. Refreshes are single-flighted:
+//! concurrent requests needing a new token wait for a single call to the source.
+//!
+//! If a request is rejected with `401 Unauthorized`, the token it was sent with is dropped from
+//! the cache, and `GET` and `HEAD` requests, and requests with a body set by
+//! `Request::replayable_body`, are retried once with a new token.
+//!
+//! [`BearerAuth`]: struct.BearerAuth.html
+//! [`TokenSource`]: trait.TokenSource.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use std::time::Duration;
+//! use surf::middleware::token::{BearerAuth, Token};
+//!
+//! let auth = BearerAuth::new(|| async {
+//!     let token: serde_json::Value = surf::post("https://auth.example.com/token")
+//!         .recv_json()
+//!         .await?;
+//!     let value = token["access_token"].as_str().unwrap_or_default();
+//!     let expires_in = token["expires_in"].as_u64().unwrap_or(300);
+//!     Ok(Token::new(value).expires_in(Duration::from_secs(expires_in)))
+//! });
+//! let client = surf::Client::new().scoped().middleware(auth).build();
+//! let res = client.get("https://api.example.com/me").await?;
+//! # Ok(()) }
+//! ```
+
+use crate::attempt::{self, AttemptReason};
+use crate::http_client::{copy_extensions, BodyFactory};
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use futures::prelude::*;
+use http::header::{HeaderValue, AUTHORIZATION};
+use http::{Method, StatusCode};
+
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// A bearer token.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    value: String,
+    expires_at: Option<Instant>,
+}
+
+impl Token {
+    /// Create a new instance that never expires.
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            expires_at: None,
+        }
+    }
+
+    /// Set the time after which the token expires.
+    pub fn expires_at(mut self, at: Instant) -> Self {
+        self.expires_at = Some(at);
+        self
+    }
+
+    /// Set the time from now after which the token expires.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_at(Instant::now() + duration)
+    }
+
+    /// Get the token.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the time after which the token expires, if it does.
+    pub fn expiry(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    /// Check whether the token will have expired `margin` from now.
+    pub(super) fn expires_within(&self, margin: Duration) -> bool {
+        match self.expires_at {
+            Some(at) => Instant::now() + margin >= at,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("value", &"<hidden>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// A source of bearer tokens.
+///
+/// This is implemented for closures returning a future that resolves to a `Token`.
+pub trait TokenSource: Send + Sync + 'static {
+    /// Obtain a new token.
+    fn token(&self) -> BoxFuture<'_, Result<Token, Exception>>;
+}
+
+impl<F, Fut> TokenSource for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Token, Exception>> + Send + 'static,
+{
+    fn token(&self) -> BoxFuture<'_, Result<Token, Exception>> {
+        Box::pin((self)())
+    }
+}
+
+/// Middleware sending tokens from a `TokenSource` as bearer tokens.
+pub struct BearerAuth<S: TokenSource> {
+    source: S,
+    refresh_ahead: Duration,
+    cached: Mutex<Option<Token>>,
+}
+
+impl<S: TokenSource> BearerAuth<S> {
+    /// Create a new instance.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            refresh_ahead: Duration::from_secs(60),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Set how long before expiry tokens are refreshed, defaults to 60 seconds.
+    pub fn refresh_ahead(mut self, margin: Duration) -> Self {
+        self.refresh_ahead = margin;
+        self
+    }
+
+    /// Get a valid token, refreshing it if needed.
+    async fn token(&self) -> Result<Token, Exception> {
+        // Holding the lock while refreshing makes concurrent requests wait for the refresh,
+        // rather than each refreshing the token themselves.
+        let mut cached = self.cached.lock().await;
+        match &*cached {
+            Some(token) if !token.expires_within(self.refresh_ahead) => Ok(token.clone()),
+            _ => {
+                let token = self.source.token().await?;
+                *cached = Some(token.clone());
+                Ok(token)
+            }
+        }
+    }
+
+    /// Drop a token that was rejected from the cache, unless it has been replaced already.
+    async fn invalidate(&self, rejected: &Token) {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref() == Some(rejected) {
+            *cached = None;
+        }
+    }
+}
+
+impl<S: TokenSource> fmt::Debug for BearerAuth<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerAuth")
+            .field("refresh_ahead", &self.refresh_ahead)
+            .finish()
+    }
+}
+
+/// Set the `Authorization` header of a request.
+pub(super) fn authorize(req: &mut Request, token: &Token) -> Result<(), Exception> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {}", token.value()))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid token"))?;
+    value.set_sensitive(true);
+    req.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+/// A copy of a request, to send it again with a new token.
+pub(super) struct Retry {
+    req: Request,
+    factory: Option<BodyFactory>,
+}
+
+impl Retry {
+    /// Copy a request, if it has no body or its body can be recreated.
+    pub(super) fn new(req: &Request) -> Option<Self> {
+        let factory = req.extensions().get::<BodyFactory>().cloned();
+        if factory.is_none() && req.method() != Method::GET && req.method() != Method::HEAD {
+            return None;
+        }
+        let mut copy = http::Request::new(Body::empty());
+        *copy.method_mut() = req.method().clone();
+        *copy.uri_mut() = req.uri().clone();
+        *copy.headers_mut() = req.headers().clone();
+        copy_extensions(req.extensions(), copy.extensions_mut());
+        Some(Self { req: copy, factory })
+    }
+
+    /// Get the request, with its body recreated.
+    pub(super) fn into_request(self) -> Request {
+        let mut req = self.req;
+        if let Some(BodyFactory(factory)) = self.factory {
+            *req.body_mut() = factory();
+        }
+        req
+    }
+}
+
+impl<C: HttpClient, S: TokenSource> Middleware<C> for BearerAuth<S> {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let retry = Retry::new(&req);
+            let token = self.token().await?;
+            authorize(&mut req, &token)?;
+            let mut res = next.run(req, client.clone()).await?;
+            if res.status() != StatusCode::UNAUTHORIZED {
+                return Ok(res);
+            }
+
+            self.invalidate(&token).await;
+            match retry {
+                Some(retry) => {
+                    let mut req = retry.into_request();
+                    let token = self.token().await?;
+                    authorize(&mut req, &token)?;
+                    let status = AttemptReason::Status(StatusCode::UNAUTHORIZED);
+                    let earlier = attempt::take(&mut res, status);
+                    let mut res = next.run(req, client).await?;
+                    attempt::prepend(&mut res, earlier);
+                    Ok(res)
+                }
+                None => Ok(res),
+            }
+        })
+    }
+}
@@ -0,0 +1,346 @@
+//! Distributed tracing.
+//!
+//! The [`Trace`] middleware creates a `tracing` span for every request, and propagates the trace
+//! to the server with the W3C Trace Context `traceparent` and `tracestate` headers. The span has
+//! the attributes of the OpenTelemetry semantic conventions for HTTP clients, so a
+//! `tracing-opentelemetry` layer exports it as a client span:
+//!
+//! - `http.request.method`, `url.full`, `server.address` and `server.port` when the request is
+//!   sent.
+//! - `http.response.status_code` when the response arrives, or `error` if the request fails.
+//! - `http.request.body.size` and `http.response.body.size` once the response body has been read
+//!   or dropped, which is when the span ends.
+//!
+//! Requests continue the trace of a [`TraceContext`] in their extensions, such as the context of
+//! the incoming request a server is handling, and start a new trace otherwise. Every request gets
+//! a span ID of its own, which is sent as the parent ID in the `traceparent` header, and recorded
+//! as the `span_id` attribute of the span along with the `trace_id`. The span ID is random,
+//! unless `Trace::span_id` reads the ID an OpenTelemetry layer assigned to the span. The context
+//! sent is stored in the extensions of the response.
+//!
+//! [`Trace`]: struct.Trace.html
+//! [`TraceContext`]: struct.TraceContext.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::trace::{Trace, TraceContext};
+//!
+//! let client = surf::Client::new().middleware(Trace::new());
+//!
+//! // The headers of the request being handled.
+//! let incoming = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", None);
+//! let res = client
+//!     .get("https://httpbin.org/headers")
+//!     .set_ext(incoming.unwrap_or_else(TraceContext::new))
+//!     .await?;
+//! println!("{:?}", res.ext::<TraceContext>());
+//! # Ok(()) }
+//! ```
+
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::{Exception, TransferSize};
+
+use futures::future::BoxFuture;
+use futures::io::AsyncRead;
+use http::header::HeaderValue;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The W3C Trace Context of a request: the trace it belongs to, and the span it was sent from.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::trace::TraceContext;
+///
+/// let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+/// let context = TraceContext::parse(header, Some("congo=t61rcWkgMzE")).unwrap();
+/// assert_eq!(context.trace_id(), 0x4bf92f3577b34da6a3ce929d0e0e4736);
+/// assert!(context.is_sampled());
+/// assert_eq!(context.to_string(), header);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+    flags: u8,
+    state: Option<String>,
+}
+
+impl TraceContext {
+    /// Create a new instance, starting a new sampled trace.
+    pub fn new() -> Self {
+        let trace_id = u128::from(random_id()) << 64 | u128::from(random_id());
+        Self {
+            trace_id,
+            span_id: random_id(),
+            flags: 1,
+            state: None,
+        }
+    }
+
+    /// Parse the values of the `traceparent` and `tracestate` headers.
+    ///
+    /// Returns `None` if `traceparent` isn't valid.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = hex(parts.next()?, 2)?;
+        let trace_id = hex(parts.next()?, 32)?;
+        let span_id = hex(parts.next()?, 16)?;
+        let flags = hex(parts.next()?, 2)?;
+        // Later versions may add fields, but version 00 has none.
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return None;
+        }
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id: span_id as u64,
+            flags: flags as u8,
+            state: tracestate
+                .map(str::trim)
+                .filter(|state| !state.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// Get the ID of the trace.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Get the ID of the span.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Returns `true` if the caller may have recorded the trace.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+
+    /// Get the vendor-specific trace state, the value of the `tracestate` header.
+    pub fn trace_state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /// Create the context of a new span in the same trace.
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: random_id(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Format the context as the value of a `traceparent` header.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+/// Parse a lowercase hexadecimal field of a fixed length.
+fn hex(field: &str, len: usize) -> Option<u128> {
+    let valid = field.len() == len
+        && field
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte));
+    if !valid {
+        return None;
+    }
+    u128::from_str_radix(field, 16).ok()
+}
+
+/// Generate a random, non-zero ID.
+fn random_id() -> u64 {
+    loop {
+        let id = RandomState::new().build_hasher().finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+type SpanIdFn = Arc<dyn Fn(&Span) -> Option<u64> + Send + Sync>;
+
+/// Middleware creating a span for every request, and propagating its trace, see the
+/// [module documentation](index.html).
+#[derive(Clone, Default)]
+pub struct Trace {
+    span_id: Option<SpanIdFn>,
+}
+
+impl Trace {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the span ID to send in the `traceparent` header from the span of the request.
+    ///
+    /// A `tracing-opentelemetry` layer exports the span with an ID of its own, rather than the
+    /// random ID surf generates, so the spans of the server aren't parented to the exported
+    /// span. surf doesn't depend on OpenTelemetry, so pass a function reading the ID the layer
+    /// assigned:
+    ///
+    /// ```ignore
+    /// use opentelemetry::trace::TraceContextExt;
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let trace = Trace::new().span_id(|span| {
+    ///     let id = span.context().span().span_context().span_id();
+    ///     Some(u64::from_be_bytes(id.to_bytes()))
+    /// });
+    /// ```
+    ///
+    /// The random ID is sent if the function returns `None` or `0`. The trace ID always comes
+    /// from the `TraceContext` of the request, so that should be the context the layer continues.
+    pub fn span_id<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Span) -> Option<u64> + Send + Sync + 'static,
+    {
+        self.span_id = Some(Arc::new(f));
+        self
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trace")
+            .field("span_id", &self.span_id.is_some())
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Trace {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            let mut context = match req.extensions().get::<TraceContext>() {
+                Some(parent) => parent.child(),
+                None => TraceContext::new(),
+            };
+            let uri = req.uri();
+            let span = tracing::info_span!(
+                "HTTP request",
+                otel.name = req.method().as_str(),
+                otel.kind = "client",
+                otel.status_code = Empty,
+                http.request.method = req.method().as_str(),
+                url.full = %uri,
+                server.address = uri.host(),
+                server.port = uri.port_u16(),
+                http.response.status_code = Empty,
+                http.request.body.size = Empty,
+                http.response.body.size = Empty,
+                error = Empty,
+                trace_id = %format_args!("{:032x}", context.trace_id),
+                span_id = Empty,
+            );
+            let span_id = self.span_id.as_ref().and_then(|span_id| span_id(&span));
+            if let Some(span_id) = span_id.filter(|&span_id| span_id != 0) {
+                context.span_id = span_id;
+            }
+            let span_id = format!("{:016x}", context.span_id);
+            span.record("span_id", span_id.as_str());
+
+            let traceparent = HeaderValue::from_str(&context.to_string()).unwrap();
+            req.headers_mut().insert("traceparent", traceparent);
+            let tracestate = context
+                .trace_state()
+                .and_then(|state| HeaderValue::from_str(state).ok());
+            match tracestate {
+                Some(state) => req.headers_mut().insert("tracestate", state),
+                None => req.headers_mut().remove("tracestate"),
+            };
+
+            let res = match next.run(req, client).instrument(span.clone()).await {
+                Ok(res) => res,
+                Err(err) => {
+                    span.record("otel.status_code", "ERROR");
+                    span.record("error", tracing::field::display(&err));
+                    return Err(err);
+                }
+            };
+
+            let status = res.status();
+            span.record("http.response.status_code", status.as_u16());
+            if status.is_server_error() {
+                span.record("otel.status_code", "ERROR");
+            }
+            let (mut parts, body) = res.into_parts();
+            let size = parts.extensions.get::<TransferSize>().cloned();
+            parts.extensions.insert(context);
+            let body = Body::from_reader(Traced {
+                body,
+                span: Some((span, size)),
+            });
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// A body ending its span once it's read to the end or dropped.
+struct Traced {
+    body: Body,
+    span: Option<(Span, Option<TransferSize>)>,
+}
+
+impl Traced {
+    fn finish(&mut self) {
+        // Taking the span out ends it, whether or not the sizes are known.
+        if let Some((span, Some(size))) = self.span.take() {
+            span.record("http.request.body.size", size.request_body_bytes());
+            span.record("http.response.body.size", size.response_body_bytes());
+        }
+    }
+}
+
+impl AsyncRead for Traced {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        if read == 0 && !buf.is_empty() {
+            self.finish();
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl Drop for Traced {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,175 @@
+//! Lazy response body transformations.
+//!
+//! Middleware that needs to change a response body (decrypting, decompressing, stripping an
+//! envelope) can wrap the body with a [`Transform`] instead of buffering it. Transforms run chunk
+//! by chunk as the body is read, so several of them compose without any middleware holding the
+//! entire body in memory.
+//!
+//! [`Transform`]: trait.Transform.html
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use surf::middleware::transform::map_body;
+//! use surf::middleware::{HttpClient, Middleware, Next, Request, Response};
+//! use std::io;
+//!
+//! /// Uppercase every response body.
+//! #[derive(Debug)]
+//! pub struct Shout;
+//!
+//! impl<C: HttpClient> Middleware<C> for Shout {
+//!     fn handle<'a>(
+//!         &'a self,
+//!         req: Request,
+//!         client: C,
+//!         next: Next<'a, C>,
+//!     ) -> BoxFuture<'a, Result<Response, surf::Exception>> {
+//!         Box::pin(async move {
+//!             let res = next.run(req, client).await?;
+//!             Ok(map_body(res, |input: &[u8], output: &mut Vec<u8>| {
+//!                 output.extend(input.iter().map(u8::to_ascii_uppercase));
+//!                 Ok::<(), io::Error>(())
+//!             }))
+//!         })
+//!     }
+//! }
+//! ```
+
+use crate::middleware::{Body, Response};
+
+use futures::io::AsyncRead;
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The size of the chunks read from the wrapped body.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A stateful, chunk-wise transformation of a byte stream.
+///
+/// This is implemented for closures taking an input chunk and an output buffer, for
+/// transformations that don't need to flush any state at the end of the stream.
+pub trait Transform: Send + Unpin + 'static {
+    /// Transform a chunk of input, appending the result to `output`.
+    ///
+    /// Transforms may hold on to input until they've seen enough of it, and emit it in a later
+    /// call or in `finish`.
+    fn transform(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Flush any remaining output once the input is exhausted.
+    fn finish(&mut self, _output: &mut Vec<u8>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F> Transform for F
+where
+    F: FnMut(&[u8], &mut Vec<u8>) -> io::Result<()> + Send + Unpin + 'static,
+{
+    fn transform(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        (self)(input, output)
+    }
+}
+
+/// A reader that applies a `Transform` to a body as it's read.
+pub struct TransformReader<T: Transform> {
+    body: Body,
+    transform: T,
+    /// Scratch space for reading chunks of the wrapped body.
+    chunk: Vec<u8>,
+    /// Transformed bytes that haven't been read yet, starting at `pos`.
+    output: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<T: Transform> TransformReader<T> {
+    /// Create a new instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), surf::Exception> { futures::executor::block_on(async {
+    /// use futures::prelude::*;
+    /// use surf::middleware::transform::TransformReader;
+    /// use surf::middleware::Body;
+    ///
+    /// let body = Body::from(b"chashu".to_vec());
+    /// let mut reader = TransformReader::new(body, |input: &[u8], output: &mut Vec<u8>| {
+    ///     output.extend(input.iter().rev());
+    ///     Ok(())
+    /// });
+    /// let mut string = String::new();
+    /// reader.read_to_string(&mut string).await?;
+    /// assert_eq!(string, "uhsahc");
+    /// # Ok(()) }) }
+    /// ```
+    pub fn new(body: Body, transform: T) -> Self {
+        Self {
+            body,
+            transform,
+            chunk: vec![0; CHUNK_SIZE],
+            output: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<T: Transform> AsyncRead for TransformReader<T> {
+    #[allow(missing_doc_code_examples)]
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            if this.pos < this.output.len() {
+                let len = buf.len().min(this.output.len() - this.pos);
+                buf[..len].copy_from_slice(&this.output[this.pos..this.pos + len]);
+                this.pos += len;
+                return Poll::Ready(Ok(len));
+            }
+            if this.done {
+                return Poll::Ready(Ok(0));
+            }
+
+            this.output.clear();
+            this.pos = 0;
+            let read = futures::ready!(Pin::new(&mut this.body).poll_read(cx, &mut this.chunk))?;
+            if read == 0 {
+                this.transform.finish(&mut this.output)?;
+                this.done = true;
+            } else {
+                this.transform
+                    .transform(&this.chunk[..read], &mut this.output)?;
+            }
+        }
+    }
+}
+
+impl<T: Transform> fmt::Debug for TransformReader<T> {
+    #[allow(missing_doc_code_examples)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransformReader")
+            .field("body", &self.body)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// Wrap the body of a response with a `Transform`.
+///
+/// The transformed body generally has a different length, so the `Content-Length` header is
+/// removed.
+pub fn map_body(res: Response, transform: impl Transform) -> Response {
+    let (mut parts, body) = res.into_parts();
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    let body = Body::from_reader(TransformReader::new(body, transform));
+    http::Response::from_parts(parts, body)
+}
@@ -0,0 +1,222 @@
+//! Validating requests before they're sent.
+//!
+//! The [`Validate`] middleware checks every request against a set of rules, and rejects requests
+//! that break one of them with a [`PolicyError`], before anything is sent. This lets platform
+//! teams enforce conventions, such as every request carrying a tenant header, on all requests
+//! made through a shared client.
+//!
+//! Set the rules for all requests of a client with `ScopedBuilder::validate`. Rules see the
+//! request as it was built by the caller, before any middleware of the client runs.
+//!
+//! [`Validate`]: struct.Validate.html
+//! [`PolicyError`]: enum.PolicyError.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::middleware::validate::{PolicyError, Validate};
+//!
+//! let validate = Validate::new()
+//!     .require_header("x-tenant")
+//!     .max_body_size(1024 * 1024)
+//!     .rule(|req: &surf::middleware::Request| match req.uri().scheme_str() {
+//!         Some("https") => Ok(()),
+//!         _ => Err(PolicyError::Rejected("only https is allowed".into())),
+//!     });
+//! let client = surf::Client::new().scoped().validate(validate).build();
+//!
+//! let err = client.get("https://httpbin.org/get").await.unwrap_err();
+//! assert!(err.downcast_ref::<PolicyError>().is_some());
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::Duplex;
+use crate::middleware::{Body, HttpClient, Middleware, Next, Request, Response};
+use crate::Exception;
+
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncReadExt};
+use http::header::HeaderName;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// An error rejecting a request that breaks a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// A required header is missing.
+    MissingHeader(HeaderName),
+    /// The request body is larger than the limit, in bytes.
+    BodyTooLarge {
+        /// The maximum body size.
+        limit: u64,
+    },
+    /// The request was rejected by a rule.
+    Rejected(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::MissingHeader(name) => write!(f, "missing required header {}", name),
+            PolicyError::BodyTooLarge { limit } => {
+                write!(f, "request body is larger than {} bytes", limit)
+            }
+            PolicyError::Rejected(reason) => write!(f, "request rejected: {}", reason),
+        }
+    }
+}
+
+impl Error for PolicyError {}
+
+/// A rule requests have to follow.
+///
+/// This is implemented for closures taking a `&Request`.
+pub trait Rule: Send + Sync + 'static {
+    /// Check a request, returning an error if it breaks the rule.
+    fn check(&self, req: &Request) -> Result<(), PolicyError>;
+}
+
+impl<F> Rule for F
+where
+    F: Fn(&Request) -> Result<(), PolicyError> + Send + Sync + 'static,
+{
+    fn check(&self, req: &Request) -> Result<(), PolicyError> {
+        (self)(req)
+    }
+}
+
+/// Middleware rejecting requests that break a rule.
+#[derive(Clone, Default)]
+pub struct Validate {
+    rules: Vec<Arc<dyn Rule>>,
+    max_body_size: Option<u64>,
+}
+
+impl Validate {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule.
+    ///
+    /// Rules are checked in the order they were added, the first broken rule rejects the request.
+    pub fn rule(mut self, rule: impl Rule) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    /// Require requests to have a header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the name isn't a valid header name.
+    pub fn require_header(self, name: &str) -> Self {
+        let name: HeaderName = name.parse().unwrap();
+        self.rule(move |req: &Request| {
+            if req.headers().contains_key(&name) {
+                Ok(())
+            } else {
+                Err(PolicyError::MissingHeader(name.clone()))
+            }
+        })
+    }
+
+    /// Reject requests with a body larger than `limit` bytes.
+    ///
+    /// Bodies are buffered in memory, up to the limit, to find their size before the request is
+    /// sent. Bodies written with `Request::body_writer` are only complete once the response is
+    /// read, so they're counted as they're sent instead, and the request fails once they exceed
+    /// the limit.
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.max_body_size = Some(limit);
+        self
+    }
+}
+
+impl fmt::Debug for Validate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validate")
+            .field("rules", &self.rules.len())
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+impl<C: HttpClient> Middleware<C> for Validate {
+    #[allow(missing_doc_code_examples)]
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        client: C,
+        next: Next<'a, C>,
+    ) -> BoxFuture<'a, Result<Response, Exception>> {
+        Box::pin(async move {
+            for rule in &self.rules {
+                rule.check(&req)?;
+            }
+            if let Some(limit) = self.max_body_size {
+                if req.extensions().get::<Duplex>().is_some() {
+                    // The body is only complete once the response is read, so it can't be
+                    // buffered, and is checked as it's sent instead.
+                    let exceeded = Arc::new(AtomicBool::new(false));
+                    let body = std::mem::replace(req.body_mut(), Body::empty());
+                    *req.body_mut() = Body::from_reader(Limited {
+                        body,
+                        limit,
+                        read: 0,
+                        exceeded: exceeded.clone(),
+                    });
+                    return match next.run(req, client).await {
+                        Err(_) if exceeded.load(Ordering::SeqCst) => {
+                            Err(PolicyError::BodyTooLarge { limit }.into())
+                        }
+                        res => res,
+                    };
+                }
+                let mut buf = Vec::new();
+                let body = std::mem::replace(req.body_mut(), Body::empty());
+                body.take(limit + 1).read_to_end(&mut buf).await?;
+                if buf.len() as u64 > limit {
+                    return Err(PolicyError::BodyTooLarge { limit }.into());
+                }
+                *req.body_mut() = Body::from(buf);
+            }
+            next.run(req, client).await
+        })
+    }
+}
+
+/// A body failing once more than `limit` bytes were read from it.
+struct Limited {
+    body: Body,
+    limit: u64,
+    read: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl AsyncRead for Limited {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        self.read += read as u64;
+        if self.read > self.limit {
+            self.exceeded.store(true, Ordering::SeqCst);
+            let limit = self.limit;
+            let err = PolicyError::BodyTooLarge { limit };
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+        }
+        Poll::Ready(Ok(read))
+    }
+}
@@ -0,0 +1,280 @@
+//! Downloading from mirrors.
+//!
+//! Package managers and updaters often fetch the same artifact from one of several mirrors.
+//! [`Mirrors`] picks the fastest of them by racing `HEAD` requests, downloads from it, and falls
+//! back to the next fastest mirror when a download fails halfway. Fallbacks resume where the
+//! failed download stopped with a range request, or skip the bytes that were already received if
+//! the mirror doesn't support ranges. The download can be verified against a SHA-256 checksum.
+//!
+//! [`Mirrors`]: struct.Mirrors.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::mirror::Mirrors;
+//! use surf::url::Url;
+//!
+//! let mirrors = vec![
+//!     Url::parse("https://mirror1.example.com/release.tar.gz")?,
+//!     Url::parse("https://mirror2.example.com/release.tar.gz")?,
+//! ];
+//! let mut file = Vec::new();
+//! Mirrors::new(surf::Client::new(), mirrors)
+//!     .sha256("b5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c")
+//!     .download(&mut file)
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::{Client, Exception, Response};
+
+use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+use http::StatusCode;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::io;
+use std::time::Duration;
+
+/// The size of the buffer used to copy response bodies.
+const BUF_SIZE: usize = 16 * 1024;
+
+/// Downloads an artifact from the fastest of several mirrors.
+#[derive(Debug)]
+pub struct Mirrors<C: HttpClient> {
+    client: Client<C>,
+    urls: Vec<Url>,
+    sha256: Option<String>,
+    probe_timeout: Duration,
+}
+
+impl<C: HttpClient> Mirrors<C> {
+    /// Create a new instance, sending requests through `client`.
+    pub fn new(client: Client<C>, urls: impl IntoIterator<Item = Url>) -> Self {
+        Self {
+            client,
+            urls: urls.into_iter().collect(),
+            sha256: None,
+            probe_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Verify the download against a hex encoded SHA-256 checksum.
+    pub fn sha256(mut self, checksum: impl AsRef<str>) -> Self {
+        self.sha256 = Some(checksum.as_ref().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Set how long to wait for mirrors to respond to the `HEAD` request ranking them, defaults
+    /// to 5 seconds.
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Rank the mirrors by racing `HEAD` requests to all of them.
+    ///
+    /// Mirrors are returned in the order they responded with a successful status. Mirrors that
+    /// failed or didn't respond within the probe timeout follow, in their original order, as a
+    /// last resort.
+    pub async fn rank(&self) -> Vec<Url> {
+        let mut probes: FuturesUnordered<_> = self
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                let req = self.client.head(url.as_str()).timeout(self.probe_timeout);
+                async move {
+                    let ok = match req.await {
+                        Ok(res) => res.status().is_success(),
+                        Err(err) => {
+                            log::debug!("mirror {} failed to respond: {}", url, err);
+                            false
+                        }
+                    };
+                    (index, ok)
+                }
+            })
+            .collect();
+
+        let mut ranked = vec![];
+        let mut failed = vec![];
+        while let Some((index, ok)) = probes.next().await {
+            if ok {
+                ranked.push(index);
+            } else {
+                failed.push(index);
+            }
+        }
+        failed.sort();
+        ranked
+            .into_iter()
+            .chain(failed)
+            .map(|index| self.urls[index].clone())
+            .collect()
+    }
+
+    /// Download the artifact into `writer`, and return its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last mirror if the download fails on all of them, and the error
+    /// of `writer` if writing fails. Returns a `ChecksumMismatch` error if the download doesn't
+    /// match the checksum. The data written to `writer` should be discarded after an error.
+    pub async fn download(&self, mut writer: impl AsyncWrite + Unpin) -> Result<u64, Exception> {
+        let mut hasher = Sha256::new();
+        let mut offset = 0;
+        let mut last_error = None;
+
+        for url in self.rank().await {
+            let res = match self.get(&url, offset).await {
+                Ok(res) => res,
+                Err(err) => {
+                    log::debug!("mirror {} failed: {}", url, err);
+                    last_error = Some(err);
+                    continue;
+                }
+            };
+            match copy(res, &mut writer, &mut hasher, &mut offset).await {
+                Ok(()) => {
+                    writer.flush().await?;
+                    return self.verify(hasher).map(|()| offset);
+                }
+                Err(Failure::Write(err)) => return Err(err.into()),
+                Err(Failure::Read(err)) => {
+                    log::debug!("download from {} failed at {}: {}", url, offset, err);
+                    last_error = Some(err.into());
+                }
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no mirrors").into()))
+    }
+
+    /// Request the artifact from a mirror, starting at `offset`.
+    async fn get(&self, url: &Url, offset: u64) -> Result<Response, Exception> {
+        let mut req = self.client.get(url.as_str());
+        if offset > 0 {
+            // Not `Request::range_from`, since a response with the whole body is fine too.
+            req = req.set_header("Range", format!("bytes={}-", offset));
+        }
+        let mut res = req.await?.error_for_status()?;
+        if offset == 0 {
+            return Ok(res);
+        }
+
+        if res.status() == StatusCode::PARTIAL_CONTENT {
+            let start = res
+                .content_range()
+                .and_then(|range| range.range())
+                .map(|range| range.start);
+            if start != Some(offset) {
+                let msg = "mirror responded with a different range";
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg).into());
+            }
+        } else {
+            // The mirror ignored the range, skip what was received already.
+            let mut buf = vec![0; BUF_SIZE];
+            let mut skipped = 0;
+            while skipped < offset {
+                let len = buf.len().min((offset - skipped) as usize);
+                let read = res.read(&mut buf[..len]).await?;
+                if read == 0 {
+                    let msg = "artifact is shorter on this mirror";
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg).into());
+                }
+                skipped += read as u64;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Check the checksum of the download, if there is one.
+    fn verify(&self, hasher: Sha256) -> Result<(), Exception> {
+        let expected = match &self.sha256 {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        let mut actual = String::new();
+        for byte in hasher.result().iter() {
+            write!(actual, "{:02x}", byte).unwrap();
+        }
+        if actual == *expected {
+            Ok(())
+        } else {
+            Err(ChecksumMismatch {
+                expected: expected.clone(),
+                actual,
+            }
+            .into())
+        }
+    }
+}
+
+/// The reason copying a response body failed.
+enum Failure {
+    /// Reading the body failed, another mirror can be tried.
+    Read(io::Error),
+    /// Writing the body failed.
+    Write(io::Error),
+}
+
+/// Copy a response body into `writer`, hashing it and counting the bytes written in `offset`.
+async fn copy(
+    mut res: Response,
+    writer: &mut (impl AsyncWrite + Unpin),
+    hasher: &mut Sha256,
+    offset: &mut u64,
+) -> Result<(), Failure> {
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        let len = res.read(&mut buf).await.map_err(Failure::Read)?;
+        if len == 0 {
+            return Ok(());
+        }
+        writer
+            .write_all(&buf[..len])
+            .await
+            .map_err(Failure::Write)?;
+        hasher.input(&buf[..len]);
+        *offset += len as u64;
+    }
+}
+
+/// The error returned when a download doesn't match its checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    expected: String,
+    actual: String,
+}
+
+impl ChecksumMismatch {
+    /// Get the expected checksum.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// Get the checksum of the download.
+    pub fn actual(&self) -> &str {
+        &self.actual
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "checksum mismatch: expected sha256 {}, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
@@ -0,0 +1,140 @@
+//! Memory-mapped file bodies.
+//!
+//! Mapping a file lets the kernel page it in as it's sent, instead of copying it into a buffer
+//! on the heap first, and makes sending it again free. Mapping is only used on 64-bit Unix
+//! platforms, where the address space is large enough for any file; elsewhere, and when mapping
+//! fails, the file is read into memory instead.
+//!
+//! A mapping reflects changes other processes make to the file, so the contents are only valid
+//! as long as nothing writes to the file, which callers of `Contents::open` must ensure.
+
+use futures::io::AsyncRead;
+
+use std::fs::File;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// A read-only mapping of a whole file.
+struct Mapping {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The mapping is read-only and never changes address, so it can be shared between threads.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Map a file, returning `None` if that isn't supported or fails.
+    ///
+    /// The file must not be modified or truncated while the mapping exists.
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    unsafe fn new(file: &File) -> Option<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = file.metadata().ok()?.len() as usize;
+        // Empty mappings aren't allowed.
+        if len == 0 {
+            return None;
+        }
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        Some(Self {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    unsafe fn new(_file: &File) -> Option<Self> {
+        None
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// The contents of a file, either mapped or read into memory.
+#[derive(Clone)]
+pub(crate) struct Contents(Arc<Source>);
+
+enum Source {
+    Mapped(Mapping),
+    Read(Vec<u8>),
+}
+
+impl Contents {
+    /// Map a file, or read it if it can't be mapped.
+    ///
+    /// The file must not be modified or truncated while the contents, or a reader over them,
+    /// exist.
+    pub(crate) unsafe fn open(file: &mut File) -> io::Result<Self> {
+        let source = match Mapping::new(file) {
+            Some(mapping) => Source::Mapped(mapping),
+            None => {
+                let mut buf = Vec::new();
+                io::Read::read_to_end(file, &mut buf)?;
+                Source::Read(buf)
+            }
+        };
+        Ok(Contents(Arc::new(source)))
+    }
+
+    /// Create a reader over the contents.
+    pub(crate) fn reader(&self) -> Reader {
+        Reader {
+            contents: self.clone(),
+            pos: 0,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match &*self.0 {
+            Source::Mapped(mapping) => mapping.as_slice(),
+            Source::Read(buf) => buf,
+        }
+    }
+}
+
+/// A reader over the contents of a file.
+pub(crate) struct Reader {
+    contents: Contents,
+    pos: usize,
+}
+
+impl AsyncRead for Reader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let rest = &this.contents.as_slice()[this.pos..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        this.pos += len;
+        Poll::Ready(Ok(len))
+    }
+}
@@ -0,0 +1,267 @@
+//! Multipart bodies.
+//!
+//! Build `multipart/form-data`, `multipart/related` or `multipart/mixed` bodies, and send them
+//! with `Request::body_multipart`. Parts are written in the order they're added, can carry
+//! arbitrary headers (such as `Content-ID` or `Content-Transfer-Encoding`), and can themselves be
+//! multipart bodies.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::multipart::{Multipart, Part};
+//!
+//! let form = Multipart::form_data()
+//!     .part(Part::text("chashu").name("name"))
+//!     .part(Part::bytes(vec![0xff, 0xd8]).name("photo").file_name("chashu.jpg"));
+//! let res = surf::post("https://httpbin.org/post").body_multipart(form).await?;
+//! # Ok(()) }
+//! ```
+//!
+//! An MTOM/XOP message is a `multipart/related` body whose root part references the others by
+//! `Content-ID`:
+//!
+//! ```
+//! use surf::multipart::{Multipart, Part};
+//!
+//! let envelope = r#"<soap:Envelope>...</soap:Envelope>"#;
+//! let message = Multipart::related()
+//!     .param("type", "application/xop+xml")
+//!     .param("start", "<root@surf>")
+//!     .part(
+//!         Part::text(envelope)
+//!             .content_type("application/xop+xml; charset=UTF-8")
+//!             .content_id("<root@surf>"),
+//!     )
+//!     .part(
+//!         Part::bytes(vec![1, 2, 3])
+//!             .content_type("application/octet-stream")
+//!             .content_id("<data@surf>")
+//!             .header("Content-Transfer-Encoding", "binary"),
+//!     );
+//! assert!(message.content_type().starts_with("multipart/related; boundary="));
+//! ```
+
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A multipart body.
+#[derive(Debug, Clone)]
+pub struct Multipart {
+    subtype: &'static str,
+    boundary: String,
+    params: Vec<(String, String)>,
+    parts: Vec<Part>,
+}
+
+impl Multipart {
+    /// Create a new `multipart/form-data` body.
+    pub fn form_data() -> Self {
+        Self::new("form-data")
+    }
+
+    /// Create a new `multipart/related` body.
+    pub fn related() -> Self {
+        Self::new("related")
+    }
+
+    /// Create a new `multipart/mixed` body.
+    pub fn mixed() -> Self {
+        Self::new("mixed")
+    }
+
+    fn new(subtype: &'static str) -> Self {
+        Self {
+            subtype,
+            boundary: boundary(),
+            params: vec![],
+            parts: vec![],
+        }
+    }
+
+    /// Add a parameter to the `Content-Type`, such as `type` or `start` for `multipart/related`.
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    /// Append a part.
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    /// Insert a part at `index`, shifting all parts after it.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `index` is greater than the number of parts.
+    pub fn insert(mut self, index: usize, part: Part) -> Self {
+        self.parts.insert(index, part);
+        self
+    }
+
+    /// Get the parts, in the order they'll be written.
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    /// Get the boundary separating the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Get the `Content-Type` of the body, including the boundary.
+    pub fn content_type(&self) -> String {
+        let mut mime = format!("multipart/{}; boundary={}", self.subtype, self.boundary);
+        for (name, value) in &self.params {
+            mime.push_str(&format!("; {}=\"{}\"", name, value.replace('"', "\\\"")));
+        }
+        mime
+    }
+
+    /// Encode the body.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for part in &self.parts {
+            buf.extend_from_slice(b"--");
+            buf.extend_from_slice(self.boundary.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+            for (name, value) in part.headers.iter() {
+                buf.extend_from_slice(name.as_str().as_bytes());
+                buf.extend_from_slice(b": ");
+                buf.extend_from_slice(value.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+            buf.extend_from_slice(b"\r\n");
+            buf.extend_from_slice(&part.body);
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf.extend_from_slice(b"--");
+        buf.extend_from_slice(self.boundary.as_bytes());
+        buf.extend_from_slice(b"--\r\n");
+        buf
+    }
+}
+
+/// A single part of a multipart body.
+#[derive(Debug, Clone)]
+pub struct Part {
+    headers: HeaderMap,
+    body: Vec<u8>,
+    name: Option<String>,
+    file_name: Option<String>,
+}
+
+impl Part {
+    /// Create a part from bytes.
+    pub fn bytes(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            body: body.into(),
+            name: None,
+            file_name: None,
+        }
+    }
+
+    /// Create a `text/plain` part from a string.
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::bytes(body.into().into_bytes()).content_type("text/plain; charset=utf-8")
+    }
+
+    /// Create a part containing a nested multipart body.
+    pub fn multipart(body: Multipart) -> Self {
+        let mime = body.content_type();
+        Self::bytes(body.to_bytes()).content_type(mime)
+    }
+
+    /// Set the form field name, for `multipart/form-data` bodies.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self.set_disposition();
+        self
+    }
+
+    /// Set the file name, for `multipart/form-data` bodies.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self.set_disposition();
+        self
+    }
+
+    /// Set the `Content-Type` of the part.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the value isn't a valid header value.
+    pub fn content_type(self, mime: impl AsRef<str>) -> Self {
+        self.header(CONTENT_TYPE.as_str(), mime)
+    }
+
+    /// Set the `Content-ID` of the part, used to reference it from other parts.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the value isn't a valid header value.
+    pub fn content_id(self, id: impl AsRef<str>) -> Self {
+        self.header("Content-ID", id)
+    }
+
+    /// Set a header of the part, replacing any previous value.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `name` isn't a valid header name, or `value` isn't a valid header value.
+    pub fn header(mut self, name: &str, value: impl AsRef<str>) -> Self {
+        let name: HeaderName = name.parse().unwrap();
+        let value: HeaderValue = value.as_ref().parse().unwrap();
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Get the headers of the part.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of the part.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    fn set_disposition(&mut self) {
+        let mut disposition = String::from("form-data");
+        if let Some(name) = &self.name {
+            disposition.push_str(&format!("; name=\"{}\"", escape(name)));
+        }
+        if let Some(file_name) = &self.file_name {
+            disposition.push_str(&format!("; filename=\"{}\"", escape(file_name)));
+        }
+        self.headers
+            .insert(CONTENT_DISPOSITION, disposition.parse().unwrap());
+    }
+}
+
+/// Escape a quoted parameter value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Generate a boundary that's unlikely to appear in any part.
+fn boundary() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::SeqCst));
+    let first = hasher.finish();
+    hasher.write_u64(first);
+    format!("surf-{:016x}{:016x}", first, hasher.finish())
+}
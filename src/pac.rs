@@ -0,0 +1,1216 @@
+//! Proxy auto-config (PAC) files.
+//!
+//! Corporate networks often tell clients which proxy to use with a PAC file: a JavaScript file
+//! defining a `FindProxyForURL(url, host)` function, which returns the proxies to try for a URL,
+//! such as `"PROXY proxy.corp.example.com:8080; DIRECT"`. Windows and macOS fetch the file from
+//! the URL configured by the network administrator, and route the traffic of browsers and
+//! system services through the proxies it returns.
+//!
+//! Set a [`Pac`] on a client with `ClientBuilder::pac`, and the file is evaluated for every
+//! request that none of the proxies set with `ClientBuilder::proxy` apply to. The request is
+//! sent through the first proxy the file returns that surf supports, or directly if that's
+//! `DIRECT`. Requests for which the file fails use the proxies set in the environment instead.
+//! Only the curl backend uses PAC files.
+//!
+//! [`Pac`]: struct.Pac.html
+//!
+//! # Supported scripts
+//!
+//! surf doesn't embed a JavaScript engine. PAC files are evaluated by an interpreter for the
+//! subset of JavaScript they are written in in practice:
+//!
+//! - function declarations, `var` declarations and assignments, `if` and `else`, and `return`;
+//! - string and number literals, `true`, `false`, `null` and `undefined`;
+//! - the operators `==`, `!=`, `===`, `!==`, `<`, `>`, `<=`, `>=`, `&&`, `||`, `!`, `+`, `-`,
+//!   `*`, `/`, `%` and `? :`;
+//! - the `length` of strings, and their methods `charAt`, `endsWith`, `includes`, `indexOf`,
+//!   `lastIndexOf`, `startsWith`, `substr`, `substring`, `toLowerCase`, `toUpperCase` and
+//!   `trim`;
+//! - the PAC functions `alert`, `convert_addr`, `dnsDomainIs`, `dnsDomainLevels`, `dnsResolve`,
+//!   `isInNet`, `isPlainHostName`, `isResolvable`, `localHostOrDomainIs`, `myIpAddress` and
+//!   `shExpMatch`.
+//!
+//! Files using anything else, such as loops or regular expressions, fail to parse, and calling
+//! the time functions `dateRange`, `timeRange` and `weekdayRange` fails when the file is
+//! evaluated.
+//!
+//! The DNS functions resolve host names with the system resolver, blocking the task sending the
+//! request, like browsers block while evaluating PAC files. The URLs of `https` requests are
+//! passed to the file without their path and query, like Chrome does, so the file can't see
+//! more of them than the proxy can.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::pac::Pac;
+//!
+//! let pac = Pac::fetch(&surf::Client::new(), "http://wpad.corp.example.com/wpad.dat").await?;
+//! let client = surf::Client::builder().pac(pac).build();
+//! let res = client.get("https://intranet.corp.example.com").await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::proxy::Proxy;
+use crate::{Client, Exception};
+
+use url::Url;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+
+/// The number of steps after which evaluating a script is aborted.
+const MAX_STEPS: usize = 100_000;
+
+/// The maximum depth of nested function calls.
+const MAX_DEPTH: usize = 64;
+
+/// The maximum depth of nested statements and expressions.
+const MAX_NESTING: usize = 32;
+
+/// A parsed proxy auto-config file.
+///
+/// Clones share the parsed script.
+#[derive(Clone)]
+pub struct Pac {
+    program: Arc<Program>,
+}
+
+impl Pac {
+    /// Parse a PAC file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file uses JavaScript that isn't supported, see the
+    /// [module documentation](index.html#supported-scripts), or doesn't define
+    /// `FindProxyForURL`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::pac::Pac;
+    /// use surf::url::Url;
+    ///
+    /// let pac = Pac::parse(r#"
+    ///     function FindProxyForURL(url, host) {
+    ///         if (isPlainHostName(host) || dnsDomainIs(host, ".corp.example.com"))
+    ///             return "DIRECT";
+    ///         return "PROXY proxy.corp.example.com:8080; DIRECT";
+    ///     }
+    /// "#)?;
+    ///
+    /// let proxy = pac.proxy(&Url::parse("https://httpbin.org/get")?)?;
+    /// assert_eq!(proxy.unwrap().url().as_str(), "http://proxy.corp.example.com:8080/");
+    /// assert!(pac.proxy(&Url::parse("http://wiki.corp.example.com/")?)?.is_none());
+    /// # Ok::<(), surf::Exception>(())
+    /// ```
+    pub fn parse(script: &str) -> Result<Self, PacError> {
+        let tokens = tokenize(script)?;
+        let program = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        }
+        .program()?;
+        if !program.functions.contains_key("FindProxyForURL") {
+            return Err(PacError::new(None, "missing FindProxyForURL function"));
+        }
+        Ok(Self {
+            program: Arc::new(program),
+        })
+    }
+
+    /// Fetch a PAC file and parse it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the response has an error status, or if the
+    /// file can't be parsed.
+    pub async fn fetch<C: HttpClient>(
+        client: &Client<C>,
+        url: impl AsRef<str>,
+    ) -> Result<Self, Exception> {
+        let mut res = client.get(url).await?.error_for_status()?;
+        let script = res.body_string().await?;
+        Ok(Self::parse(&script)?)
+    }
+
+    /// Call `FindProxyForURL` for a URL, returning the proxies it lists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script fails, for example by calling an unsupported function.
+    pub fn find_proxy_for_url(&self, url: &Url) -> Result<String, PacError> {
+        let host = url.host_str().unwrap_or_default();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let mut url = url.clone();
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        url.set_fragment(None);
+        if url.scheme() == "https" || url.scheme() == "wss" {
+            url.set_path("/");
+            url.set_query(None);
+        }
+        let args = vec![
+            Value::String(url.as_str().to_string()),
+            Value::String(host.to_string()),
+        ];
+
+        let mut interpreter = Interpreter {
+            program: &self.program,
+            frames: vec![HashMap::new()],
+            steps: 0,
+        };
+        if let Some(value) = interpreter.block(&self.program.statements)? {
+            log::debug!("PAC script returned {} at the top level", value);
+        }
+        let result = interpreter.call("FindProxyForURL", args)?;
+        Ok(result.to_string())
+    }
+
+    /// Get the proxy to send a request to a URL through, or `None` to connect directly.
+    ///
+    /// This is the first entry returned by `FindProxyForURL` that surf supports: `DIRECT`,
+    /// `PROXY` or `HTTP` for HTTP proxies, `HTTPS` for HTTP proxies connected to over TLS,
+    /// `SOCKS` or `SOCKS4` for SOCKS4a proxies, and `SOCKS5` for SOCKS5 proxies resolving host
+    /// names. Requests aren't retried through the next entry when a proxy can't be reached. If
+    /// there is no supported entry, the request is sent directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script fails, for example by calling an unsupported function.
+    pub fn proxy(&self, url: &Url) -> Result<Option<Proxy>, PacError> {
+        let result = self.find_proxy_for_url(url)?;
+        for entry in result.split(';') {
+            let mut parts = entry.split_whitespace();
+            let kind = parts.next().unwrap_or_default().to_ascii_uppercase();
+            let addr = parts.next().unwrap_or_default();
+            let scheme = match kind.as_str() {
+                "DIRECT" => return Ok(None),
+                "PROXY" | "HTTP" => "http",
+                "HTTPS" => "https",
+                "SOCKS" | "SOCKS4" => "socks4a",
+                "SOCKS5" => "socks5h",
+                "" => continue,
+                _ => {
+                    log::debug!("skipping unsupported PAC entry {:?}", entry.trim());
+                    continue;
+                }
+            };
+            match Proxy::parse(addr, scheme, None) {
+                Some(proxy) => return Ok(Some(proxy)),
+                None => log::debug!("skipping invalid PAC entry {:?}", entry.trim()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl fmt::Debug for Pac {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut functions: Vec<_> = self.program.functions.keys().collect();
+        functions.sort();
+        f.debug_struct("Pac")
+            .field("functions", &functions)
+            .finish()
+    }
+}
+
+/// The error returned when a PAC file can't be parsed or evaluated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacError {
+    line: Option<usize>,
+    message: String,
+}
+
+impl PacError {
+    fn new(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Get the line of the script the error is on, if it's a parse error.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl fmt::Display for PacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "PAC script, line {}: {}", line, self.message),
+            None => write!(f, "PAC script: {}", self.message),
+        }
+    }
+}
+
+impl Error for PacError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Number(f64),
+    Punct(&'static str),
+    End,
+}
+
+/// Punctuators, longest first so they are matched greedily.
+const PUNCTS: &[&str] = &[
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "+=", "(", ")", "{", "}", "[", "]", ",", ";",
+    ".", "+", "-", "*", "/", "%", "!", "<", ">", "=", "?", ":",
+];
+
+/// Split a script into tokens, and the lines they are on.
+fn tokenize(script: &str) -> Result<Vec<(Token, usize)>, PacError> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut rest = script;
+    loop {
+        let trimmed = rest.trim_start();
+        line += rest[..rest.len() - trimmed.len()].matches('\n').count();
+        rest = trimmed;
+        if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            continue;
+        }
+        if rest.starts_with("/*") {
+            let end = rest
+                .find("*/")
+                .ok_or_else(|| PacError::new(Some(line), "unterminated comment"))?;
+            line += rest[..end].matches('\n').count();
+            rest = &rest[end + 2..];
+            continue;
+        }
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        let (token, len) = if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_string()), len)
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| PacError::new(Some(line), "invalid number"))?;
+            (Token::Number(number), len)
+        } else if c == '"' || c == '\'' {
+            let (string, len) =
+                string(rest, c).ok_or_else(|| PacError::new(Some(line), "unterminated string"))?;
+            (Token::String(string), len)
+        } else {
+            match PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+                Some(punct) => (Token::Punct(punct), punct.len()),
+                None => {
+                    let msg = format!("unexpected character {:?}", c);
+                    return Err(PacError::new(Some(line), msg));
+                }
+            }
+        };
+        tokens.push((token, line));
+        rest = &rest[len..];
+    }
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+/// Read a string literal quoted with `quote`, returning its value and length in the script.
+fn string(s: &str, quote: char) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                '0' => value.push('\0'),
+                '\n' => {}
+                c => value.push(c),
+            },
+            '\n' => return None,
+            c if c == quote => return Some((value, i + 1)),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// A parsed script.
+#[derive(Debug)]
+struct Program {
+    functions: HashMap<String, Function>,
+    /// The statements outside of functions, run before `FindProxyForURL` is called.
+    statements: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Var(Vec<(String, Option<Expr>)>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    Return(Option<Expr>),
+    Block(Vec<Stmt>),
+    Expr(Expr),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Assign(String, Option<BinaryOp>, Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Method(Box<Expr>, String, Vec<Expr>),
+    Property(Box<Expr>, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    StrictEq,
+    StrictNe,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+/// Words that can't be used as names.
+const KEYWORDS: &[&str] = &[
+    "break", "case", "const", "continue", "do", "else", "false", "for", "function", "if", "let",
+    "new", "null", "return", "switch", "this", "true", "typeof", "var", "while",
+];
+
+/// A recursive descent parser for the supported subset of JavaScript.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// The depth of the statements and expressions being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> PacError {
+        PacError::new(Some(self.tokens[self.pos].1), message)
+    }
+
+    fn unexpected(&self) -> PacError {
+        let msg = match self.peek() {
+            Token::Ident(name) => format!("unexpected `{}`", name),
+            Token::String(_) => "unexpected string".to_string(),
+            Token::Number(_) => "unexpected number".to_string(),
+            Token::Punct(punct) => format!("unexpected `{}`", punct),
+            Token::End => "unexpected end of script".to_string(),
+        };
+        self.error(msg)
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        match self.peek() {
+            Token::Punct(found) => *found == punct,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Ident(name) => name == keyword,
+            _ => false,
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), PacError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Parse a nested statement or expression, failing when they're nested too deeply to be
+    /// parsed, or evaluated, without running out of stack.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, PacError>,
+    ) -> Result<T, PacError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn name(&mut self) -> Result<String, PacError> {
+        match self.peek() {
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn program(mut self) -> Result<Program, PacError> {
+        let mut functions = HashMap::new();
+        let mut statements = vec![];
+        while *self.peek() != Token::End {
+            if self.is_keyword("function") {
+                self.pos += 1;
+                let name = self.name()?;
+                let function = self.function()?;
+                functions.insert(name, function);
+            } else {
+                statements.push(self.statement()?);
+            }
+        }
+        Ok(Program {
+            functions,
+            statements,
+        })
+    }
+
+    fn function(&mut self) -> Result<Function, PacError> {
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            params.push(self.name()?);
+        }
+        self.expect("{")?;
+        let body = self.block()?;
+        Ok(Function { params, body })
+    }
+
+    /// Parse the statements of a block, after its opening brace.
+    fn block(&mut self) -> Result<Vec<Stmt>, PacError> {
+        let mut statements = vec![];
+        while !self.eat("}") {
+            if self.is_keyword("function") {
+                return Err(self.error("nested functions aren't supported"));
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, PacError> {
+        self.nested(Self::statement_inner)
+    }
+
+    fn statement_inner(&mut self) -> Result<Stmt, PacError> {
+        let keyword = match self.peek() {
+            Token::Ident(name) => name.clone(),
+            _ => String::new(),
+        };
+        let stmt = match keyword.as_str() {
+            "var" | "let" | "const" => {
+                self.pos += 1;
+                let mut vars = vec![];
+                loop {
+                    let name = self.name()?;
+                    let value = if self.eat("=") {
+                        Some(self.expr()?)
+                    } else {
+                        None
+                    };
+                    vars.push((name, value));
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                Stmt::Var(vars)
+            }
+            "if" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let condition = self.expr()?;
+                self.expect(")")?;
+                let then = Box::new(self.statement()?);
+                let otherwise = if self.is_keyword("else") {
+                    self.pos += 1;
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+                return Ok(Stmt::If(condition, then, otherwise));
+            }
+            "return" => {
+                self.pos += 1;
+                if self.is_punct(";") || self.is_punct("}") {
+                    Stmt::Return(None)
+                } else {
+                    Stmt::Return(Some(self.expr()?))
+                }
+            }
+            "break" | "case" | "continue" | "do" | "for" | "new" | "switch" | "this" | "typeof"
+            | "while" => {
+                return Err(self.error(format!("`{}` isn't supported", keyword)));
+            }
+            _ if self.eat("{") => return Ok(Stmt::Block(self.block()?)),
+            _ if self.eat(";") => return Ok(Stmt::Block(vec![])),
+            _ => Stmt::Expr(self.expr()?),
+        };
+        // Semicolons are optional, as they mostly are in JavaScript.
+        self.eat(";");
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, PacError> {
+        self.nested(Self::expr_inner)
+    }
+
+    fn expr_inner(&mut self) -> Result<Expr, PacError> {
+        let following = self.tokens.get(self.pos + 1).map(|(token, _)| token);
+        let assignment = match (self.peek(), following) {
+            (Token::Ident(_), Some(Token::Punct("="))) => Some(None),
+            (Token::Ident(_), Some(Token::Punct("+="))) => Some(Some(BinaryOp::Add)),
+            _ => None,
+        };
+        if let Some(op) = assignment {
+            let name = self.name()?;
+            self.pos += 1;
+            let value = self.expr()?;
+            return Ok(Expr::Assign(name, op, Box::new(value)));
+        }
+
+        let condition = self.or()?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.expr()?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn or(&mut self) -> Result<Expr, PacError> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, PacError> {
+        let mut expr = self.binary(0)?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.binary(0)?));
+        }
+        Ok(expr)
+    }
+
+    /// Parse binary operators, from the `level` of precedence upwards.
+    fn binary(&mut self, level: usize) -> Result<Expr, PacError> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("===", BinaryOp::StrictEq),
+                ("!==", BinaryOp::StrictNe),
+            ],
+            &[
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+        let ops = match LEVELS.get(level) {
+            Some(ops) => ops,
+            None => return self.unary(),
+        };
+        let mut expr = self.binary(level + 1)?;
+        while let Some((_, op)) = ops.iter().find(|(punct, _)| self.is_punct(punct)) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            expr = Expr::Binary(*op, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, PacError> {
+        if self.eat("!") {
+            let expr = self.nested(Self::unary)?;
+            Ok(Expr::Not(Box::new(expr)))
+        } else if self.eat("-") {
+            let expr = self.nested(Self::unary)?;
+            Ok(Expr::Negate(Box::new(expr)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, PacError> {
+        let mut expr = self.primary()?;
+        while self.eat(".") {
+            let name = match self.next() {
+                Token::Ident(name) => name,
+                _ => return Err(self.unexpected()),
+            };
+            expr = if self.eat("(") {
+                Expr::Method(Box::new(expr), name, self.args()?)
+            } else {
+                Expr::Property(Box::new(expr), name)
+            };
+        }
+        Ok(expr)
+    }
+
+    /// Parse the arguments of a call, after the opening parenthesis.
+    fn args(&mut self) -> Result<Vec<Expr>, PacError> {
+        let mut args = vec![];
+        while !self.eat(")") {
+            if !args.is_empty() {
+                self.expect(",")?;
+            }
+            args.push(self.expr()?);
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, PacError> {
+        let value = match self.peek() {
+            Token::String(string) => Value::String(string.clone()),
+            Token::Number(number) => Value::Number(*number),
+            Token::Ident(name) if name == "true" => Value::Bool(true),
+            Token::Ident(name) if name == "false" => Value::Bool(false),
+            Token::Ident(name) if name == "null" => Value::Null,
+            Token::Ident(name) if name == "undefined" => Value::Undefined,
+            Token::Ident(_) => {
+                let name = self.name()?;
+                if self.eat("(") {
+                    return Ok(Expr::Call(name, self.args()?));
+                }
+                return Ok(Expr::Variable(name));
+            }
+            Token::Punct("(") => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(")")?;
+                return Ok(expr);
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        Ok(Expr::Literal(value))
+    }
+}
+
+/// A JavaScript value.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Undefined | Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(number) => *number != 0.0 && !number.is_nan(),
+            Value::String(string) => !string.is_empty(),
+        }
+    }
+
+    fn to_number(&self) -> f64 {
+        match self {
+            Value::Undefined => f64::NAN,
+            Value::Null => 0.0,
+            Value::Bool(value) => f64::from(u8::from(*value)),
+            Value::Number(number) => *number,
+            Value::String(string) => match string.trim() {
+                "" => 0.0,
+                string => string.parse().unwrap_or(f64::NAN),
+            },
+        }
+    }
+
+    /// Convert the value to an integer, as used for string indices.
+    fn to_integer(&self) -> i64 {
+        let number = self.to_number();
+        if number.is_nan() {
+            0
+        } else {
+            number.trunc() as i64
+        }
+    }
+
+    fn is_nullish(&self) -> bool {
+        *self == Value::Undefined || *self == Value::Null
+    }
+
+    /// Compare two values with `==`.
+    fn loose_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (a, b) if a.is_nullish() || b.is_nullish() => a.is_nullish() && b.is_nullish(),
+            (Value::String(a), Value::String(b)) => a == b,
+            (a, b) => a.to_number() == b.to_number(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Undefined => f.write_str("undefined"),
+            Value::Null => f.write_str("null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(number) if number.is_nan() => f.write_str("NaN"),
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e21 => {
+                write!(f, "{}", *number as i128)
+            }
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(string) => f.write_str(string),
+        }
+    }
+}
+
+/// The state of an evaluation of a script.
+struct Interpreter<'a> {
+    program: &'a Program,
+    /// The global variables, followed by the local variables of the function being called.
+    frames: Vec<HashMap<String, Value>>,
+    steps: usize,
+}
+
+impl Interpreter<'_> {
+    fn step(&mut self) -> Result<(), PacError> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(PacError::new(None, "script takes too long"));
+        }
+        Ok(())
+    }
+
+    /// Run statements, returning the value of a `return` statement.
+    fn block(&mut self, statements: &[Stmt]) -> Result<Option<Value>, PacError> {
+        for stmt in statements {
+            if let Some(value) = self.statement(stmt)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<Option<Value>, PacError> {
+        self.step()?;
+        match stmt {
+            Stmt::Var(vars) => {
+                for (name, value) in vars {
+                    let value = match value {
+                        Some(value) => self.expr(value)?,
+                        None => Value::Undefined,
+                    };
+                    self.frames.last_mut().unwrap().insert(name.clone(), value);
+                }
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.expr(condition)?.is_truthy() {
+                    return self.statement(then);
+                } else if let Some(otherwise) = otherwise {
+                    return self.statement(otherwise);
+                }
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Value::Undefined,
+                };
+                return Ok(Some(value));
+            }
+            Stmt::Block(statements) => return self.block(statements),
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Value, PacError> {
+        self.step()?;
+        let value = match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Variable(name) => {
+                let local = self.frames.last().unwrap().get(name);
+                match local.or_else(|| self.frames[0].get(name)) {
+                    Some(value) => value.clone(),
+                    None => {
+                        let msg = format!("`{}` isn't defined", name);
+                        return Err(PacError::new(None, msg));
+                    }
+                }
+            }
+            Expr::Assign(name, op, value) => {
+                let mut value = self.expr(value)?;
+                if let Some(op) = op {
+                    let current = self.expr(&Expr::Variable(name.clone()))?;
+                    value = binary(*op, current, value);
+                }
+                // Assigning to an undeclared variable creates a global one.
+                let frame = if self.frames.last().unwrap().contains_key(name) {
+                    self.frames.len() - 1
+                } else {
+                    0
+                };
+                self.frames[frame].insert(name.clone(), value.clone());
+                value
+            }
+            Expr::Not(value) => Value::Bool(!self.expr(value)?.is_truthy()),
+            Expr::Negate(value) => Value::Number(-self.expr(value)?.to_number()),
+            Expr::Binary(op, left, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                binary(*op, left, right)
+            }
+            Expr::And(left, right) => {
+                let left = self.expr(left)?;
+                if !left.is_truthy() {
+                    return Ok(left);
+                }
+                self.expr(right)?
+            }
+            Expr::Or(left, right) => {
+                let left = self.expr(left)?;
+                if left.is_truthy() {
+                    return Ok(left);
+                }
+                self.expr(right)?
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.expr(condition)?.is_truthy() {
+                    self.expr(then)?
+                } else {
+                    self.expr(otherwise)?
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<_, _>>()?;
+                self.call(name, args)?
+            }
+            Expr::Method(target, name, args) => {
+                let target = self.expr(target)?;
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<_, _>>()?;
+                method(&target, name, &args)?
+            }
+            Expr::Property(target, name) => match (self.expr(target)?, name.as_str()) {
+                (Value::String(string), "length") => Value::Number(string.chars().count() as f64),
+                (target, _) if target.is_nullish() => {
+                    let msg = format!("can't read `{}` of {}", name, target);
+                    return Err(PacError::new(None, msg));
+                }
+                _ => Value::Undefined,
+            },
+        };
+        Ok(value)
+    }
+
+    /// Call a function of the script, or a PAC function.
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, PacError> {
+        let function = match self.program.functions.get(name) {
+            Some(function) => function,
+            None => return builtin(name, &args),
+        };
+        if self.frames.len() > MAX_DEPTH {
+            return Err(PacError::new(None, "too much recursion"));
+        }
+        let mut args = args.into_iter();
+        let locals = function
+            .params
+            .iter()
+            .map(|param| (param.clone(), args.next().unwrap_or(Value::Undefined)))
+            .collect();
+        self.frames.push(locals);
+        let result = self.block(&function.body);
+        self.frames.pop();
+        Ok(result?.unwrap_or(Value::Undefined))
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    use BinaryOp::*;
+
+    let number = |f: fn(f64, f64) -> f64| Value::Number(f(left.to_number(), right.to_number()));
+    let compare = |f: fn(Ordering) -> bool| {
+        let ordering = match (&left, &right) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) => a.to_number().partial_cmp(&b.to_number()),
+        };
+        Value::Bool(ordering.map(f) == Some(true))
+    };
+    match op {
+        Add => match (&left, &right) {
+            (Value::String(_), _) | (_, Value::String(_)) => {
+                Value::String(format!("{}{}", left, right))
+            }
+            _ => number(|a, b| a + b),
+        },
+        Sub => number(|a, b| a - b),
+        Mul => number(|a, b| a * b),
+        Div => number(|a, b| a / b),
+        Rem => number(|a, b| a % b),
+        Eq => Value::Bool(left.loose_eq(&right)),
+        Ne => Value::Bool(!left.loose_eq(&right)),
+        StrictEq => Value::Bool(left == right),
+        StrictNe => Value::Bool(left != right),
+        Lt => compare(|ordering| ordering == Ordering::Less),
+        Gt => compare(|ordering| ordering == Ordering::Greater),
+        Le => compare(|ordering| ordering != Ordering::Greater),
+        Ge => compare(|ordering| ordering != Ordering::Less),
+    }
+}
+
+/// Call a string method.
+fn method(target: &Value, name: &str, args: &[Value]) -> Result<Value, PacError> {
+    let string = match target {
+        Value::String(string) => string,
+        _ => {
+            let msg = format!("`{}` can only be called on strings", name);
+            return Err(PacError::new(None, msg));
+        }
+    };
+    let chars: Vec<char> = string.chars().collect();
+    let len = chars.len() as i64;
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Undefined);
+    let index = |i: usize, default: i64| match arg(i) {
+        Value::Undefined => default,
+        value => value.to_integer().max(0).min(len),
+    };
+    let slice =
+        |start: i64, end: i64| -> String { chars[start as usize..end as usize].iter().collect() };
+
+    let value = match name {
+        "charAt" => {
+            let i = arg(0).to_integer();
+            match chars.get(i as usize) {
+                Some(c) if i >= 0 => Value::String(c.to_string()),
+                _ => Value::String(String::new()),
+            }
+        }
+        "endsWith" => Value::Bool(string.ends_with(&arg(0).to_string())),
+        "includes" => Value::Bool(string.contains(&arg(0).to_string())),
+        "indexOf" | "lastIndexOf" => {
+            let needle: Vec<char> = arg(0).to_string().chars().collect();
+            let matches = |i: &i64| chars[*i as usize..].starts_with(&needle);
+            let found = if name == "indexOf" {
+                (index(1, 0)..=len).find(matches)
+            } else {
+                (0..=index(1, len)).rev().find(matches)
+            };
+            Value::Number(found.unwrap_or(-1) as f64)
+        }
+        "startsWith" => Value::Bool(string.starts_with(&arg(0).to_string())),
+        "substr" => {
+            let start = arg(0).to_integer();
+            let start = if start < 0 {
+                (len + start).max(0)
+            } else {
+                start.min(len)
+            };
+            let count = match arg(1) {
+                Value::Undefined => len,
+                count => count.to_integer().max(0),
+            };
+            Value::String(slice(start, start.saturating_add(count).min(len)))
+        }
+        "substring" => {
+            let (start, end) = (index(0, 0), index(1, len));
+            Value::String(slice(start.min(end), start.max(end)))
+        }
+        "toLowerCase" => Value::String(string.to_lowercase()),
+        "toUpperCase" => Value::String(string.to_uppercase()),
+        "trim" => Value::String(string.trim().to_string()),
+        _ => {
+            let msg = format!("unsupported method `{}`", name);
+            return Err(PacError::new(None, msg));
+        }
+    };
+    Ok(value)
+}
+
+/// Call a PAC function.
+fn builtin(name: &str, args: &[Value]) -> Result<Value, PacError> {
+    let arg = |i: usize| args.get(i).map(Value::to_string).unwrap_or_default();
+    let value = match name {
+        "alert" => {
+            log::debug!("PAC script: {}", arg(0));
+            Value::Undefined
+        }
+        "convert_addr" => match arg(0).parse::<Ipv4Addr>() {
+            Ok(ip) => Value::Number(f64::from(u32::from(ip))),
+            Err(_) => Value::Number(0.0),
+        },
+        "dnsDomainIs" => {
+            let host = arg(0).to_ascii_lowercase();
+            Value::Bool(host.ends_with(&arg(1).to_ascii_lowercase()))
+        }
+        "dnsDomainLevels" => Value::Number(arg(0).matches('.').count() as f64),
+        "dnsResolve" => match resolve(&arg(0)) {
+            Some(ip) => Value::String(ip.to_string()),
+            None => Value::Null,
+        },
+        "isInNet" => {
+            let net = arg(1).parse::<Ipv4Addr>().ok();
+            let mask = arg(2).parse::<Ipv4Addr>().ok();
+            let in_net = match (resolve(&arg(0)), net, mask) {
+                (Some(ip), Some(net), Some(mask)) => {
+                    let mask = u32::from(mask);
+                    u32::from(ip) & mask == u32::from(net) & mask
+                }
+                _ => false,
+            };
+            Value::Bool(in_net)
+        }
+        "isPlainHostName" => Value::Bool(!arg(0).contains('.')),
+        "isResolvable" => Value::Bool(resolve(&arg(0)).is_some()),
+        "localHostOrDomainIs" => {
+            let host = arg(0).to_ascii_lowercase();
+            let domain = arg(1).to_ascii_lowercase();
+            let is = host == domain
+                || (!host.contains('.')
+                    && domain.starts_with(&host)
+                    && domain[host.len()..].starts_with('.'));
+            Value::Bool(is)
+        }
+        "myIpAddress" => Value::String(my_ip_address().to_string()),
+        "shExpMatch" => Value::Bool(sh_exp_match(&arg(0), &arg(1))),
+        _ => {
+            let msg = format!("unsupported function `{}`", name);
+            return Err(PacError::new(None, msg));
+        }
+    };
+    Ok(value)
+}
+
+/// Resolve a host name to an IPv4 address, as the DNS functions of PAC files only support
+/// IPv4.
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    if let Ok(ip) = host.parse() {
+        return Some(ip);
+    }
+    let addrs = (host, 0).to_socket_addrs().ok()?;
+    addrs.into_iter().find_map(|addr| match addr.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    })
+}
+
+/// Get the IPv4 address of the interface used to reach the internet.
+fn my_ip_address() -> Ipv4Addr {
+    // Connecting a UDP socket picks a route without sending any packets.
+    let local = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(198, 51, 100, 1), 53))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip());
+    match local {
+        Ok(IpAddr::V4(ip)) if !ip.is_unspecified() => ip,
+        _ => Ipv4Addr::LOCALHOST,
+    }
+}
+
+/// Match a string against a shell expression, in which `*` matches any characters and `?`
+/// matches a single one.
+fn sh_exp_match(string: &str, pattern: &str) -> bool {
+    let string: Vec<char> = string.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // The positions at which the last `*` was seen, to backtrack to.
+    let (mut s, mut p) = (0, 0);
+    let mut star = None;
+    while s < string.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, s));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == string[s] => {
+                s += 1;
+                p += 1;
+            }
+            _ => match star {
+                Some((star_p, star_s)) => {
+                    p = star_p + 1;
+                    s = star_s + 1;
+                    star = Some((star_p, star_s + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,114 @@
+//! JSON Patch documents.
+//!
+//! A [`JsonPatch`] is a list of operations changing a JSON document, as defined by RFC 6902.
+//! Send it with `Request::body_json_patch`. Partial updates that only set and remove object
+//! members are easier to express as a JSON Merge Patch, which is the changed part of the
+//! document itself, see `Request::body_merge_patch`.
+//!
+//! Operations refer to locations in the document with JSON Pointers, such as `/tags/0`. Escape
+//! keys containing a `/` or `~` with [`escape`].
+//!
+//! [`JsonPatch`]: struct.JsonPatch.html
+//! [`escape`]: fn.escape.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use serde_json::json;
+//! use surf::patch::JsonPatch;
+//!
+//! let patch = JsonPatch::new()
+//!     .test("/version", json!(3))
+//!     .replace("/name", json!("chashu"))
+//!     .add("/tags/-", json!("cat"))
+//!     .remove("/owner");
+//! let res = surf::patch("https://example.com/pets/1")
+//!     .body_json_patch(&patch)?
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+/// A JSON Patch document, see the [module documentation](index.html).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonPatch {
+    ops: Vec<Value>,
+}
+
+impl JsonPatch {
+    /// Create a new instance without operations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value at `path`, inserting it into an array, or setting an object member.
+    pub fn add(self, path: &str, value: Value) -> Self {
+        self.push(json!({ "op": "add", "path": path, "value": value }))
+    }
+
+    /// Remove the value at `path`.
+    pub fn remove(self, path: &str) -> Self {
+        self.push(json!({ "op": "remove", "path": path }))
+    }
+
+    /// Replace the value at `path`, which must exist.
+    pub fn replace(self, path: &str, value: Value) -> Self {
+        self.push(json!({ "op": "replace", "path": path, "value": value }))
+    }
+
+    /// Move the value at `from` to `path`.
+    pub fn move_from(self, from: &str, path: &str) -> Self {
+        self.push(json!({ "op": "move", "from": from, "path": path }))
+    }
+
+    /// Copy the value at `from` to `path`.
+    pub fn copy_from(self, from: &str, path: &str) -> Self {
+        self.push(json!({ "op": "copy", "from": from, "path": path }))
+    }
+
+    /// Check that the value at `path` is equal to `value`, failing the whole patch otherwise.
+    ///
+    /// This makes patches safe to apply to a document that may have changed.
+    pub fn test(self, path: &str, value: Value) -> Self {
+        self.push(json!({ "op": "test", "path": path, "value": value }))
+    }
+
+    fn push(mut self, op: Value) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Get the operations.
+    pub fn ops(&self) -> &[Value] {
+        &self.ops
+    }
+
+    /// Returns `true` if the patch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl Serialize for JsonPatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.ops)
+    }
+}
+
+/// Escape a key for use in a JSON Pointer.
+///
+/// # Examples
+///
+/// ```
+/// use surf::patch::escape;
+///
+/// let path = format!("/headers/{}", escape("a/b~c"));
+/// assert_eq!(path, "/headers/a~1b~0c");
+/// ```
+pub fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
@@ -0,0 +1,386 @@
+//! The response processing pipeline.
+//!
+//! Every response passes through the same stages, in the same order, before it's handed to any
+//! middleware:
+//!
+//! 1. __Transfer decoding__: the backend removes the transfer coding (such as `chunked`), and the
+//!    `Transfer-Encoding` header is removed since it no longer describes the body.
+//! 2. __Content decoding__: compressed bodies are decompressed. The `curl` and `window.fetch`
+//!    backends decode the content coding themselves, other decoders can be added as hooks.
+//! 3. __Charset decoding__: if enabled with [`Pipeline::decode_charset`], text bodies in a
+//!    supported charset are transcoded to UTF-8 and their `Content-Type` is updated to match.
+//!
+//! Whenever a stage rewrites the body, the `Content-Encoding` and `Content-Length` headers are
+//! updated to describe the new body. The original values are kept in an [`OriginalHeaders`]
+//! extension, and middleware that decodes bodies itself should use [`content_decoded`] to do the
+//! same.
+//!
+//! Hooks can be added to each stage to inspect or rewrite the response. The hooks of a stage run
+//! in the order they were added, before the built-in handling of that stage, so they can also
+//! take over from it (for example to decode a charset that isn't supported out of the box).
+//!
+//! [`Pipeline::decode_charset`]: struct.Pipeline.html#method.decode_charset
+//! [`OriginalHeaders`]: struct.OriginalHeaders.html
+//! [`content_decoded`]: fn.content_decoded.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::pipeline::{Pipeline, Stage};
+//!
+//! let pipeline = Pipeline::new()
+//!     .decode_charset(true)
+//!     .hook(Stage::Content, |res: &mut surf::middleware::Response| {
+//!         log::debug!("content encoding: {:?}", res.headers().get("content-encoding"));
+//!         Ok(())
+//!     });
+//! let client = surf::Client::new().scoped().pipeline(pipeline).build();
+//! let string = client.get("https://example.com").recv_string().await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::{NoBody, Response};
+use crate::middleware::transform::{map_body, Transform};
+use crate::Exception;
+
+use http::header::{
+    HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING,
+};
+use mime::Mime;
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// A stage of the response processing pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Removing the transfer coding.
+    Transfer,
+    /// Removing the content coding.
+    Content,
+    /// Transcoding text to UTF-8.
+    Charset,
+}
+
+/// A hook running at a stage of the pipeline.
+///
+/// This is implemented for closures taking a `&mut Response`.
+pub trait Hook: Send + Sync + 'static {
+    /// Inspect or rewrite the response.
+    fn run(&self, res: &mut Response) -> Result<(), Exception>;
+}
+
+impl<F> Hook for F
+where
+    F: Fn(&mut Response) -> Result<(), Exception> + Send + Sync + 'static,
+{
+    fn run(&self, res: &mut Response) -> Result<(), Exception> {
+        (self)(res)
+    }
+}
+
+/// The response processing pipeline of a client.
+///
+/// Set it on a client with `ScopedBuilder::pipeline`.
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    hooks: Vec<(Stage, Arc<dyn Hook>)>,
+    charset: bool,
+}
+
+impl Pipeline {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a hook to a stage.
+    pub fn hook(mut self, stage: Stage, hook: impl Hook) -> Self {
+        self.hooks.push((stage, Arc::new(hook)));
+        self
+    }
+
+    /// Transcode text bodies to UTF-8, defaults to `false`.
+    ///
+    /// Bodies are only transcoded if their `Content-Type` names one of the supported charsets:
+    /// `us-ascii`, `iso-8859-1` (decoded as `windows-1252`, like browsers do), `windows-1252`,
+    /// `utf-16`, `utf-16le` and `utf-16be`. Invalid input is replaced with `U+FFFD`.
+    pub fn decode_charset(mut self, enabled: bool) -> Self {
+        self.charset = enabled;
+        self
+    }
+
+    /// Run all stages on a response.
+    pub(crate) fn run(&self, mut res: Response) -> Result<Response, Exception> {
+        self.run_hooks(Stage::Transfer, &mut res)?;
+        res.headers_mut().remove(TRANSFER_ENCODING);
+
+        self.run_hooks(Stage::Content, &mut res)?;
+
+        self.run_hooks(Stage::Charset, &mut res)?;
+        if self.charset && res.extensions().get::<NoBody>().is_none() {
+            res = decode_charset(res);
+        }
+        Ok(res)
+    }
+
+    fn run_hooks(&self, stage: Stage, res: &mut Response) -> Result<(), Exception> {
+        for (_, hook) in self.hooks.iter().filter(|(s, _)| *s == stage) {
+            hook.run(res)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages: Vec<_> = self.hooks.iter().map(|(stage, _)| stage).collect();
+        f.debug_struct("Pipeline")
+            .field("hooks", &stages)
+            .field("charset", &self.charset)
+            .finish()
+    }
+}
+
+/// The framing headers a response was received with, before its body was rewritten.
+///
+/// Stored in the extensions of responses whose body was decoded, get it with
+/// `Response::ext::<OriginalHeaders>()`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::pipeline::OriginalHeaders;
+///
+/// let res = surf::get("https://httpbin.org/gzip").await?;
+/// assert_eq!(res.header("Content-Encoding"), None);
+/// let original = res.ext::<OriginalHeaders>().unwrap();
+/// assert_eq!(original.content_encoding(), Some("gzip"));
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OriginalHeaders {
+    content_encoding: Option<HeaderValue>,
+    content_length: Option<HeaderValue>,
+}
+
+impl OriginalHeaders {
+    /// Get the original `Content-Encoding`.
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.content_encoding
+            .as_ref()
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Get the original `Content-Length`.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+            .as_ref()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+    }
+}
+
+/// Keep the original framing headers of a response, unless they were kept already.
+fn keep_original(res: &mut Response) {
+    if res.extensions().get::<OriginalHeaders>().is_none() {
+        let original = OriginalHeaders {
+            content_encoding: res.headers().get(CONTENT_ENCODING).cloned(),
+            content_length: res.headers().get(CONTENT_LENGTH).cloned(),
+        };
+        res.extensions_mut().insert(original);
+    }
+}
+
+/// Update the headers of a response after its content coding has been removed.
+///
+/// This removes the `Content-Encoding` and `Content-Length` headers, which described the encoded
+/// body, and keeps their original values in an `OriginalHeaders` extension.
+///
+/// # Examples
+///
+/// ```
+/// use surf::middleware::{Body, Response};
+/// use surf::pipeline::{content_decoded, OriginalHeaders};
+///
+/// let mut res = Response::new(Body::empty());
+/// res.headers_mut().insert("content-encoding", "gzip".parse().unwrap());
+/// res.headers_mut().insert("content-length", "20".parse().unwrap());
+/// content_decoded(&mut res);
+///
+/// assert!(res.headers().get("content-encoding").is_none());
+/// let original = res.extensions().get::<OriginalHeaders>().unwrap();
+/// assert_eq!(original.content_encoding(), Some("gzip"));
+/// assert_eq!(original.content_length(), Some(20));
+/// ```
+pub fn content_decoded(res: &mut Response) {
+    keep_original(res);
+    res.headers_mut().remove(CONTENT_ENCODING);
+    res.headers_mut().remove(CONTENT_LENGTH);
+}
+
+/// The charsets that can be transcoded to UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Windows1252,
+    Utf16Le,
+    Utf16Be,
+    /// UTF-16 with a byte order mark, defaulting to little endian.
+    Utf16,
+}
+
+impl Charset {
+    fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "us-ascii" | "ascii" | "iso-8859-1" | "iso8859-1" | "latin1" | "l1"
+            | "windows-1252" | "cp1252" => Some(Charset::Windows1252),
+            "utf-16le" => Some(Charset::Utf16Le),
+            "utf-16be" => Some(Charset::Utf16Be),
+            "utf-16" => Some(Charset::Utf16),
+            _ => None,
+        }
+    }
+}
+
+/// Transcode the body of a response to UTF-8, if it's in a supported charset.
+fn decode_charset(mut res: Response) -> Response {
+    let mime: Mime = match res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+    {
+        Some(mime) => mime,
+        None => return res,
+    };
+    let charset = match mime
+        .get_param(mime::CHARSET)
+        .and_then(|charset| Charset::from_label(charset.as_str()))
+    {
+        Some(charset) => charset,
+        None => return res,
+    };
+
+    let mut content_type = format!("{}/{}", mime.type_(), mime.subtype());
+    if let Some(suffix) = mime.suffix() {
+        content_type.push('+');
+        content_type.push_str(suffix.as_str());
+    }
+    for (name, value) in mime.params().filter(|(name, _)| *name != mime::CHARSET) {
+        content_type.push_str(&format!("; {}={}", name, value));
+    }
+    content_type.push_str("; charset=utf-8");
+    if let Ok(value) = HeaderValue::from_str(&content_type) {
+        res.headers_mut().insert(CONTENT_TYPE, value);
+    }
+    keep_original(&mut res);
+    res.headers_mut().remove(CONTENT_LENGTH);
+
+    map_body(
+        res,
+        Decoder {
+            charset,
+            pending: Vec::new(),
+        },
+    )
+}
+
+/// Code points of the `windows-1252` bytes `0x80` to `0x9f`.
+const WINDOWS_1252: [u16; 32] = [
+    0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008d, 0x017d, 0x008f, 0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014,
+    0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178,
+];
+
+/// A streaming charset decoder.
+struct Decoder {
+    charset: Charset,
+    /// Input that doesn't form a complete character yet.
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn decode_utf16(&mut self, output: &mut Vec<u8>, last: bool) {
+        if self.charset == Charset::Utf16 {
+            if self.pending.len() < 2 && !last {
+                return;
+            }
+            self.charset = match self.pending.get(..2) {
+                Some([0xfe, 0xff]) => Charset::Utf16Be,
+                _ => Charset::Utf16Le,
+            };
+            if let Some([0xfe, 0xff]) | Some([0xff, 0xfe]) = self.pending.get(..2) {
+                self.pending.drain(..2);
+            }
+        }
+        let big_endian = self.charset == Charset::Utf16Be;
+        let mut units: Vec<u16> = self
+            .pending
+            .chunks_exact(2)
+            .map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                }
+            })
+            .collect();
+        let mut rest = self.pending.split_off(units.len() * 2);
+        // Keep a trailing high surrogate until its pair arrives.
+        if !last {
+            if let Some(&unit) = units.last() {
+                if (0xd800..0xdc00).contains(&unit) {
+                    units.pop();
+                    let bytes = self.pending.split_off(units.len() * 2);
+                    rest.splice(0..0, bytes);
+                }
+            }
+        }
+        self.pending = rest;
+        let mut buf = [0; 4];
+        for c in std::char::decode_utf16(units.iter().cloned()) {
+            let c = c.unwrap_or(std::char::REPLACEMENT_CHARACTER);
+            output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        if last && !self.pending.is_empty() {
+            self.pending.clear();
+            let c = std::char::REPLACEMENT_CHARACTER;
+            output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+}
+
+impl Transform for Decoder {
+    fn transform(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        match self.charset {
+            Charset::Windows1252 => {
+                let mut buf = [0; 4];
+                for &byte in input {
+                    let c = match byte {
+                        0x80..=0x9f => WINDOWS_1252[usize::from(byte - 0x80)],
+                        _ => u16::from(byte),
+                    };
+                    let c = std::char::from_u32(u32::from(c)).unwrap();
+                    output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            Charset::Utf16 | Charset::Utf16Le | Charset::Utf16Be => {
+                self.pending.extend_from_slice(input);
+                self.decode_utf16(output, false);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        if self.charset != Charset::Windows1252 {
+            self.decode_utf16(output, true);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Conditional requests that only change a resource if it wasn't changed by someone else.
+
+use http::StatusCode;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::headers::{ETag, HttpDate};
+
+/// The validators of a version of a resource: its entity tag and when it was last modified.
+///
+/// Get them from the response a resource was fetched with with `Response::validators`, and send
+/// them with `Request::precondition` to only update the resource if it's still at that version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    etag: Option<ETag>,
+    last_modified: Option<HttpDate>,
+}
+
+impl Validators {
+    /// Create a new instance.
+    pub fn new(etag: Option<ETag>, last_modified: Option<HttpDate>) -> Self {
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    /// Get the entity tag of the version.
+    pub fn etag(&self) -> Option<&ETag> {
+        self.etag.as_ref()
+    }
+
+    /// Get when the version was last modified.
+    pub fn last_modified(&self) -> Option<HttpDate> {
+        self.last_modified
+    }
+
+    /// Returns `true` if there are no validators, so the version can't be told apart from other
+    /// versions.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Marks a request with an `If-Match` or `If-Unmodified-Since` header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Requested;
+
+impl Requested {
+    /// Check whether a response status means the precondition failed.
+    pub(crate) fn failed(self, status: StatusCode) -> bool {
+        status == StatusCode::PRECONDITION_FAILED
+    }
+}
+
+/// The error returned when a server refuses a conditional request with `412 Precondition Failed`,
+/// because the resource was changed since it was fetched.
+///
+/// Requests with a precondition set by `Request::precondition`, `Request::if_match` or
+/// `Request::if_unmodified_since` fail with this error, so conflicting updates can be told apart
+/// from other errors. Fetch the resource again, and reapply the update to the new version.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::PreconditionFailed;
+///
+/// let url = "https://example.com/documents/1";
+/// let res = surf::get(url).await?;
+/// let validators = res.validators();
+///
+/// match surf::put(url).precondition(&validators).body_string("new".into()).await {
+///     Err(err) if err.is::<PreconditionFailed>() => println!("changed by someone else"),
+///     res => println!("{}", res?.status()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreconditionFailed {
+    current: Validators,
+}
+
+impl PreconditionFailed {
+    pub(crate) fn new(current: Validators) -> Self {
+        Self { current }
+    }
+
+    /// Get the validators of the current version of the resource, if the server sent them with
+    /// the `412` response.
+    pub fn current(&self) -> &Validators {
+        &self.current
+    }
+}
+
+impl fmt::Display for PreconditionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("precondition failed, the resource was changed")
+    }
+}
+
+impl Error for PreconditionFailed {}
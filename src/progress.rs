@@ -0,0 +1,226 @@
+//! Transfer progress.
+//!
+//! `Request::progress` returns a [`Progress`] handle before the request is sent. It's a `Stream`
+//! of [`ProgressEvent`]s, which makes it easy to drive a progress bar from an event loop while
+//! the request runs elsewhere.
+//!
+//! Events are coalesced: a slow consumer only sees the latest progress, never a backlog. The
+//! stream ends once the response body has been read to the end or dropped, or when the request
+//! fails. When a request is sent again, for example after a redirect, the counts start over.
+//!
+//! [`Progress`]: struct.Progress.html
+//! [`ProgressEvent`]: struct.ProgressEvent.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use futures::prelude::*;
+//!
+//! let mut req = surf::get("https://httpbin.org/bytes/100000");
+//! let progress = req.progress().for_each(|event| {
+//!     match event.receive_total() {
+//!         Some(total) => println!("{} of {} bytes", event.received(), total),
+//!         None => println!("{} bytes", event.received()),
+//!     }
+//!     future::ready(())
+//! });
+//! let (body, ()) = future::join(req.recv_bytes(), progress).await;
+//! let body = body?;
+//! # Ok(()) }
+//! ```
+
+use futures::io::AsyncRead;
+use futures::stream::Stream;
+use http::header::CONTENT_LENGTH;
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::http_client::{self, Body, NoBody};
+
+/// The progress of a request at some point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressEvent {
+    sent: u64,
+    send_total: Option<u64>,
+    received: u64,
+    receive_total: Option<u64>,
+}
+
+impl ProgressEvent {
+    /// Get the number of request body bytes sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Get the size of the request body, if it's known.
+    pub fn send_total(&self) -> Option<u64> {
+        self.send_total
+    }
+
+    /// Get the number of response body bytes received so far.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Get the size of the response body, if the server sent a `Content-Length` that still
+    /// applies after decoding.
+    pub fn receive_total(&self) -> Option<u64> {
+        self.receive_total
+    }
+}
+
+/// A stream of progress events of a request.
+///
+/// Get it with `Request::progress`.
+#[derive(Debug)]
+pub struct Progress {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    event: ProgressEvent,
+    /// Whether `event` changed since it was last yielded.
+    changed: bool,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl Progress {
+    /// Create a new stream, and the reporter updating it.
+    pub(crate) fn new() -> (Self, Reporter) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let reporter = Reporter(Arc::new(Handle(shared.clone())));
+        (Self { shared }, reporter)
+    }
+}
+
+impl Stream for Progress {
+    type Item = ProgressEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.changed {
+            shared.changed = false;
+            return Poll::Ready(Some(shared.event));
+        }
+        if shared.done {
+            return Poll::Ready(None);
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// The sending side of a `Progress` stream, stored in the extensions of a request.
+///
+/// The stream ends once all clones have been dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct Reporter(Arc<Handle>);
+
+#[derive(Debug)]
+struct Handle(Arc<Mutex<Shared>>);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let mut shared = self.0.lock().unwrap();
+        shared.done = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Reporter {
+    fn update(&self, f: impl FnOnce(&mut ProgressEvent)) {
+        let mut shared = (self.0).0.lock().unwrap();
+        f(&mut shared.event);
+        shared.changed = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Start reporting the progress of a request, counting its body as it's sent.
+    pub(crate) fn track_request(&self, req: http_client::Request) -> http_client::Request {
+        let (parts, body) = req.into_parts();
+        let total = body.len().or_else(|| content_length(&parts.headers));
+        self.update(|event| {
+            *event = ProgressEvent {
+                send_total: total,
+                ..ProgressEvent::default()
+            }
+        });
+        let body = Body::from_reader(Reporting {
+            body,
+            reporter: Some(self.clone()),
+            response: false,
+        });
+        http::Request::from_parts(parts, body)
+    }
+
+    /// Report the progress of a response, counting its body as it's read.
+    pub(crate) fn track_response(&self, res: http_client::Response) -> http_client::Response {
+        let total = match res.extensions().get::<NoBody>() {
+            Some(_) => Some(0),
+            None => content_length(res.headers()),
+        };
+        self.update(|event| {
+            event.received = 0;
+            event.receive_total = total;
+        });
+        if total == Some(0) {
+            return res;
+        }
+        let (parts, body) = res.into_parts();
+        let body = Body::from_reader(Reporting {
+            body,
+            reporter: Some(self.clone()),
+            response: true,
+        });
+        http::Response::from_parts(parts, body)
+    }
+}
+
+/// Parse the `Content-Length` header of a message.
+fn content_length(headers: &http::HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// A body reporting the bytes read from it, until it ends.
+struct Reporting {
+    body: Body,
+    /// Dropped at the end of the body, so the stream can end without waiting for the body.
+    reporter: Option<Reporter>,
+    response: bool,
+}
+
+impl AsyncRead for Reporting {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        let response = self.response;
+        if let Some(reporter) = &self.reporter {
+            if read > 0 {
+                reporter.update(|event| {
+                    if response {
+                        event.received += read as u64;
+                    } else {
+                        event.sent += read as u64;
+                    }
+                });
+            } else if response {
+                self.reporter = None;
+            }
+        }
+        Poll::Ready(Ok(read))
+    }
+}
@@ -0,0 +1,573 @@
+//! Proxies.
+//!
+//! Set proxies for the requests of a client with `ClientBuilder::proxy`. A proxy can apply to
+//! requests of a single scheme (`Proxy::http`, `Proxy::https` and `Proxy::ftp`), or to all
+//! requests (`Proxy::all` and `Proxy::socks5`). Each request is sent through the first proxy of the client
+//! that applies to it, skipping proxies whose no-proxy list matches the host.
+//!
+//! # Environment variables
+//!
+//! Requests that no proxy of the client applies to use the proxies set in the environment, the
+//! same way as curl:
+//!
+//! - `http_proxy`: the proxy for `http` requests. The upper case `HTTP_PROXY` is ignored when
+//!   running as a CGI script, because a client can set it through the `Proxy` request header.
+//! - `https_proxy` or `HTTPS_PROXY`: the proxy for `https` requests.
+//! - `ftp_proxy` or `FTP_PROXY`: the proxy for `ftp` requests.
+//! - `all_proxy` or `ALL_PROXY`: the proxy for requests that neither of the above apply to.
+//! - `no_proxy` or `NO_PROXY`: a comma separated list of hosts to connect to directly.
+//!
+//! # System settings
+//!
+//! With the `system-proxy` feature, requests that neither the proxies of the client nor the
+//! environment apply to use the proxies set in the system settings, so desktop apps work behind
+//! corporate proxies without being configured:
+//!
+//! - on Windows, the proxy server and exceptions set in the Internet Options of the current
+//!   user, which WinHTTP reports with `WinHttpGetIEProxyConfigForCurrentUser`;
+//! - on macOS, the HTTP, HTTPS and SOCKS proxies and the exceptions of the network settings, as
+//!   reported by the SystemConfiguration framework.
+//!
+//! The settings are read when the client is created. Exceptions for host names and domains,
+//! such as `*.example.com`, are supported, while exceptions for address ranges, such as `10.*`,
+//! and for all plain host names are ignored. PAC files set in the system settings aren't
+//! evaluated automatically: get their URL with `system_auto_config_url`, and evaluate them with
+//! the `pac` feature. On other platforms, desktop environments set the environment variables
+//! instead, and there are no system settings to read.
+//!
+//! # SOCKS5
+//!
+//! `Proxy::socks5` creates a SOCKS5 proxy, such as Tor or an SSH tunnel opened with
+//! `ssh -D 1080 host`. Host names are resolved by the proxy rather than locally, so requests
+//! don't leak the hosts they go to through DNS; see `Proxy::remote_dns`. Proxies that require
+//! authentication take a username and password with `Proxy::basic_auth`.
+//!
+//! # FTP
+//!
+//! `ftp://` URLs can be requested through an HTTP proxy, which fetches the resource over FTP and
+//! returns it in an HTTP response, like curl does. This is how legacy FTP servers are often
+//! reached in corporate networks. The request is sent to the proxy with the URL in absolute form,
+//! such as `GET ftp://ftp.example.com/pub/file.txt HTTP/1.1`. Only HTTP proxies set with
+//! `Proxy::ftp` or `Proxy::all` are used for `ftp` requests, and sending one fails if there is no
+//! such proxy, since surf doesn't speak FTP itself. The `curl` backend still needs a libcurl built
+//! with FTP support to accept `ftp://` URLs, as most distributions ship it.
+//!
+//! # Stream isolation
+//!
+//! Tor isolates streams that authenticate to its SOCKS port with different credentials onto
+//! different circuits (`IsolateSOCKSAuth`, enabled by default). Setting per-request credentials
+//! with `Request::proxy_credentials` makes it possible to keep the traffic of different logical
+//! identities from being linked by a shared exit node.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::proxy::Proxy;
+//!
+//! let client = surf::ClientBuilder::new()
+//!     .proxy(Proxy::http("proxy.internal:3128").no_proxy("localhost, .internal"))
+//!     .proxy(Proxy::https("https://proxy.internal:3129").basic_auth("chashu", "tofu"))
+//!     .build();
+//! let res = client.get("https://httpbin.org/get").await?;
+//! # Ok(()) }
+//! ```
+//!
+//! Sending requests through an SSH tunnel:
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::proxy::Proxy;
+//!
+//! let client = surf::ClientBuilder::new()
+//!     .proxy(Proxy::socks5("127.0.0.1:1080"))
+//!     .build();
+//! let res = client.get("http://intranet.example.com").await?;
+//! # Ok(()) }
+//! ```
+//!
+//! Isolating Tor streams:
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::proxy::Proxy;
+//!
+//! let client = surf::ClientBuilder::new()
+//!     .proxy(Proxy::socks5("127.0.0.1:9050"))
+//!     .build();
+//! let alice = client.get("https://check.torproject.org").proxy_credentials("alice", "");
+//! let bob = client.get("https://check.torproject.org").proxy_credentials("bob", "");
+//! let (alice, bob) = futures::future::try_join(alice, bob).await?;
+//! # Ok(()) }
+//! ```
+
+use url::Url;
+
+use std::env;
+
+/// A proxy to send requests through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proxy {
+    url: Url,
+    /// The scheme of the requests the proxy applies to, or `None` for all requests.
+    scheme: Option<&'static str>,
+    no_proxy: Vec<String>,
+}
+
+impl Proxy {
+    /// Create a proxy for `http` requests, from a URL or a `host:port` address of an HTTP proxy.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the address isn't a valid URL with a host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::proxy::Proxy;
+    ///
+    /// let proxy = Proxy::http("10.0.0.1:3128");
+    /// assert_eq!(proxy.url().as_str(), "http://10.0.0.1:3128/");
+    /// ```
+    pub fn http(addr: impl AsRef<str>) -> Self {
+        Self::new(addr.as_ref(), "http", Some("http"))
+    }
+
+    /// Create a proxy for `https` requests, from a URL or a `host:port` address of an HTTP proxy.
+    ///
+    /// The proxy tunnels the TLS connection to the server, so it can't read the requests. Pass
+    /// an `https://` URL to connect to the proxy itself over TLS.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the address isn't a valid URL with a host.
+    pub fn https(addr: impl AsRef<str>) -> Self {
+        Self::new(addr.as_ref(), "http", Some("https"))
+    }
+
+    /// Create a proxy for `ftp` requests, from a URL or a `host:port` address of an HTTP proxy.
+    ///
+    /// See the [module documentation](index.html#ftp) for more information.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the address isn't a valid URL with a host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::proxy::Proxy;
+    /// use surf::url::Url;
+    ///
+    /// let proxy = Proxy::ftp("proxy.internal:3128");
+    /// assert!(proxy.applies_to(&Url::parse("ftp://ftp.example.com/pub/").unwrap()));
+    /// ```
+    pub fn ftp(addr: impl AsRef<str>) -> Self {
+        Self::new(addr.as_ref(), "http", Some("ftp"))
+    }
+
+    /// Create a proxy for all requests, from a URL or a `host:port` address of an HTTP proxy.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the address isn't a valid URL with a host.
+    pub fn all(addr: impl AsRef<str>) -> Self {
+        Self::new(addr.as_ref(), "http", None)
+    }
+
+    /// Create a SOCKS5 proxy for all requests, from a `host:port` address, or a `socks5://` or
+    /// `socks5h://` URL.
+    ///
+    /// Host names are resolved by the proxy, unless a `socks5://` URL is passed, see
+    /// `Proxy::remote_dns`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the address isn't a valid URL with a host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::proxy::Proxy;
+    ///
+    /// let proxy = Proxy::socks5("127.0.0.1:9050");
+    /// assert_eq!(proxy.url().as_str(), "socks5h://127.0.0.1:9050");
+    /// ```
+    pub fn socks5(addr: impl AsRef<str>) -> Self {
+        Self::new(addr.as_ref(), "socks5h", None)
+    }
+
+    /// Create a proxy from an address, using `default_scheme` if it isn't a URL.
+    fn new(addr: &str, default_scheme: &str, scheme: Option<&'static str>) -> Self {
+        Self::parse(addr, default_scheme, scheme).expect("invalid proxy address")
+    }
+
+    /// Parse a proxy address, using `default_scheme` if it isn't a URL.
+    pub(crate) fn parse(
+        addr: &str,
+        default_scheme: &str,
+        scheme: Option<&'static str>,
+    ) -> Option<Self> {
+        let url = if addr.contains("://") {
+            Url::parse(addr)
+        } else {
+            Url::parse(&format!("{}://{}", default_scheme, addr))
+        };
+        let url = url.ok().filter(|url| url.has_host())?;
+        Some(Self {
+            url,
+            scheme,
+            no_proxy: vec![],
+        })
+    }
+
+    /// Authenticate to the proxy with a username and password.
+    ///
+    /// HTTP proxies get the credentials in a `Proxy-Authorization` header, SOCKS5 proxies
+    /// through username/password authentication (RFC 1929). Credentials set with
+    /// `Request::proxy_credentials` take precedence.
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        // Setting credentials only fails for URLs without a host, which can't be proxies.
+        let _ = self.url.set_username(username);
+        let _ = self.url.set_password(Some(password));
+        self
+    }
+
+    /// Set whether a SOCKS5 proxy resolves host names, defaults to `true` for `Proxy::socks5`.
+    ///
+    /// Resolving names locally leaks the hosts that are visited to the local DNS resolver, and
+    /// fails for names only the proxy can resolve, such as Tor's `.onion` addresses. This has no
+    /// effect on HTTP proxies, which always resolve names themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::proxy::Proxy;
+    ///
+    /// let proxy = Proxy::socks5("127.0.0.1:1080").remote_dns(false);
+    /// assert_eq!(proxy.url().as_str(), "socks5://127.0.0.1:1080");
+    /// ```
+    pub fn remote_dns(mut self, enabled: bool) -> Self {
+        let scheme = match (self.url.scheme(), enabled) {
+            ("socks5", true) => "socks5h",
+            ("socks5h", false) => "socks5",
+            _ => return self,
+        };
+        // Both schemes are non-special, so switching between them can't fail.
+        let _ = self.url.set_scheme(scheme);
+        self
+    }
+
+    /// Connect to some hosts directly, given as a comma separated list like the `no_proxy`
+    /// environment variable.
+    ///
+    /// Each entry matches a host name or IP address, and the subdomains of a host name. A
+    /// leading dot is ignored, and `*` matches all hosts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use surf::proxy::Proxy;
+    /// use surf::url::Url;
+    ///
+    /// let proxy = Proxy::all("10.0.0.1:3128").no_proxy("localhost, .example.com");
+    /// assert!(proxy.applies_to(&Url::parse("https://httpbin.org/").unwrap()));
+    /// assert!(!proxy.applies_to(&Url::parse("http://api.example.com/").unwrap()));
+    /// ```
+    pub fn no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy.extend(
+            hosts
+                .split(',')
+                .map(|host| host.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|host| !host.is_empty()),
+        );
+        self
+    }
+
+    /// Get the URL of the proxy.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Check whether a request to a URL is sent through the proxy.
+    pub fn applies_to(&self, url: &Url) -> bool {
+        if let Some(scheme) = self.scheme {
+            if url.scheme() != scheme {
+                return false;
+            }
+        }
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+            None => return false,
+        };
+        let host = host.to_ascii_lowercase();
+        !self.no_proxy.iter().any(|entry| {
+            entry == "*"
+                || host == *entry
+                || (host.ends_with(entry.as_str())
+                    && host[..host.len() - entry.len()].ends_with('.'))
+        })
+    }
+
+    /// Check whether this is an HTTP proxy, which can fetch `ftp` URLs.
+    pub(crate) fn is_http(&self) -> bool {
+        let scheme = self.url.scheme();
+        scheme == "http" || scheme == "https"
+    }
+
+    /// Get the URL of the proxy, including credentials.
+    pub(crate) fn url_with(&self, credentials: &Credentials) -> Url {
+        let mut url = self.url.clone();
+        // Setting credentials only fails for URLs without a host, which can't be proxies.
+        let _ = url.set_username(&credentials.username);
+        let _ = url.set_password(Some(&credentials.password));
+        url
+    }
+}
+
+/// Read the proxies set in the environment.
+pub(crate) fn from_env() -> Vec<Proxy> {
+    let var = |name: &str| {
+        env::var(name)
+            .ok()
+            .or_else(|| env::var(name.to_ascii_uppercase()).ok())
+            .filter(|value| !value.trim().is_empty())
+    };
+    let no_proxy = var("no_proxy").unwrap_or_default();
+    let http = match env::var_os("REQUEST_METHOD") {
+        // A CGI script gets the `Proxy` header of the request as `HTTP_PROXY`.
+        Some(_) => env::var("http_proxy").ok(),
+        None => var("http_proxy"),
+    };
+
+    [
+        (http, Some("http")),
+        (var("https_proxy"), Some("https")),
+        (var("ftp_proxy"), Some("ftp")),
+        (var("all_proxy"), None),
+    ]
+    .iter()
+    // Invalid values are ignored rather than failing to create a client.
+    .filter_map(|(addr, scheme)| Proxy::parse(addr.as_ref()?, "http", *scheme))
+    .map(|proxy| proxy.no_proxy(&no_proxy))
+    .collect()
+}
+
+/// Read the proxies set in the system settings.
+#[cfg(feature = "system-proxy")]
+pub(crate) fn from_system() -> Vec<Proxy> {
+    system::proxies()
+}
+
+/// Get the URL of the proxy auto-config (PAC) file set in the system settings, if any.
+///
+/// "Automatically detect settings" (WPAD) isn't supported. This always returns `None` on
+/// platforms other than Windows and macOS.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Some(url) = surf::proxy::system_auto_config_url() {
+///     println!("the system proxies are configured by {}", url);
+/// }
+/// ```
+#[cfg(feature = "system-proxy")]
+pub fn system_auto_config_url() -> Option<String> {
+    system::auto_config_url()
+}
+
+/// Convert a proxy exception of the system settings to a `no_proxy` entry, if it's supported.
+#[cfg(all(feature = "system-proxy", any(windows, target_os = "macos")))]
+fn no_proxy_entry(exception: &str) -> Option<&str> {
+    let entry = exception.trim();
+    let entry = entry.strip_prefix("*.").unwrap_or(entry);
+    let supported = !entry.is_empty() && !entry.contains(|c| c == '*' || c == '/' || c == '<');
+    Some(entry).filter(|_| supported)
+}
+
+/// Reading the Internet Options on Windows.
+#[cfg(all(feature = "system-proxy", windows))]
+mod system {
+    use super::{no_proxy_entry, Proxy};
+
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    const INTERNET_SETTINGS: &str = r"Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+
+    fn settings() -> Option<RegKey> {
+        RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey(INTERNET_SETTINGS)
+            .ok()
+    }
+
+    pub(super) fn proxies() -> Vec<Proxy> {
+        let settings = match settings() {
+            Some(settings) => settings,
+            None => return vec![],
+        };
+        let enabled: u32 = settings.get_value("ProxyEnable").unwrap_or(0);
+        let server: String = settings.get_value("ProxyServer").unwrap_or_default();
+        if enabled == 0 || server.trim().is_empty() {
+            return vec![];
+        }
+        let overrides: String = settings.get_value("ProxyOverride").unwrap_or_default();
+        let no_proxy: Vec<_> = overrides.split(';').filter_map(no_proxy_entry).collect();
+        parse_server(&server)
+            .into_iter()
+            .map(|proxy| proxy.no_proxy(&no_proxy.join(",")))
+            .collect()
+    }
+
+    /// Parse the proxy server setting, which is either a single proxy for all requests, or a
+    /// list like `http=proxy:3128;https=proxy:3129;socks=proxy:1080`.
+    fn parse_server(server: &str) -> Vec<Proxy> {
+        if !server.contains('=') {
+            return Proxy::parse(server.trim(), "http", None)
+                .into_iter()
+                .collect();
+        }
+        let proxies = server.split(';').filter_map(|entry| {
+            let mut parts = entry.splitn(2, '=');
+            let kind = parts.next()?.trim().to_ascii_lowercase();
+            let addr = parts.next()?.trim();
+            match kind.as_str() {
+                "http" => Proxy::parse(addr, "http", Some("http")),
+                "https" => Proxy::parse(addr, "http", Some("https")),
+                "ftp" => Proxy::parse(addr, "http", Some("ftp")),
+                // Internet Options only support SOCKS4 proxies.
+                "socks" => Proxy::parse(addr, "socks4a", None),
+                _ => None,
+            }
+        });
+        let mut proxies: Vec<_> = proxies.collect();
+        // The SOCKS proxy applies to all requests, so it comes after the others.
+        proxies.sort_by_key(|proxy| !proxy.is_http());
+        proxies
+    }
+
+    pub(super) fn auto_config_url() -> Option<String> {
+        let url: String = settings()?.get_value("AutoConfigURL").ok()?;
+        Some(url).filter(|url| !url.trim().is_empty())
+    }
+}
+
+/// Reading the network settings on macOS.
+#[cfg(all(feature = "system-proxy", target_os = "macos"))]
+mod system {
+    use super::{no_proxy_entry, Proxy};
+
+    use system_configuration::core_foundation::array::CFArray;
+    use system_configuration::core_foundation::base::{CFType, TCFType};
+    use system_configuration::core_foundation::dictionary::CFDictionary;
+    use system_configuration::core_foundation::number::CFNumber;
+    use system_configuration::core_foundation::string::CFString;
+    use system_configuration::dynamic_store::SCDynamicStoreBuilder;
+
+    type Settings = CFDictionary<CFString, CFType>;
+
+    fn settings() -> Option<Settings> {
+        SCDynamicStoreBuilder::new("surf").build().get_proxies()
+    }
+
+    fn number(settings: &Settings, key: &'static str) -> Option<i64> {
+        let value = settings.find(CFString::from_static_string(key))?;
+        value.downcast::<CFNumber>()?.to_i64()
+    }
+
+    fn string(settings: &Settings, key: &'static str) -> Option<String> {
+        let value = settings.find(CFString::from_static_string(key))?;
+        Some(value.downcast::<CFString>()?.to_string())
+    }
+
+    /// Get the address of a proxy, if it's enabled.
+    ///
+    /// The keys of a proxy's settings start with its kind, such as `HTTPEnable`, `HTTPProxy` and
+    /// `HTTPPort`.
+    fn addr(settings: &Settings, keys: [&'static str; 3]) -> Option<String> {
+        let [enable, proxy, port] = keys;
+        if number(settings, enable) != Some(1) {
+            return None;
+        }
+        let host = string(settings, proxy).filter(|host| !host.is_empty())?;
+        match number(settings, port) {
+            Some(port) => Some(format!("{}:{}", host, port)),
+            None => Some(host),
+        }
+    }
+
+    pub(super) fn proxies() -> Vec<Proxy> {
+        let settings = match settings() {
+            Some(settings) => settings,
+            None => return vec![],
+        };
+        let mut no_proxy = vec![];
+        let exceptions = settings
+            .find(CFString::from_static_string("ExceptionsList"))
+            .and_then(|exceptions| exceptions.downcast::<CFArray>());
+        for exception in exceptions.iter().flat_map(|exceptions| exceptions.iter()) {
+            // The exceptions are strings, and the array holds a reference to them.
+            let exception = unsafe { CFType::wrap_under_get_rule(*exception) };
+            if let Some(exception) = exception.downcast::<CFString>() {
+                no_proxy.extend(no_proxy_entry(&exception.to_string()).map(str::to_string));
+            }
+        }
+
+        let proxies = [
+            (
+                addr(&settings, ["HTTPEnable", "HTTPProxy", "HTTPPort"]),
+                "http",
+                Some("http"),
+            ),
+            (
+                addr(&settings, ["HTTPSEnable", "HTTPSProxy", "HTTPSPort"]),
+                "http",
+                Some("https"),
+            ),
+            (
+                addr(&settings, ["SOCKSEnable", "SOCKSProxy", "SOCKSPort"]),
+                "socks5h",
+                None,
+            ),
+        ];
+        proxies
+            .iter()
+            .filter_map(|(addr, default_scheme, scheme)| {
+                Proxy::parse(addr.as_ref()?, default_scheme, *scheme)
+            })
+            .map(|proxy| proxy.no_proxy(&no_proxy.join(",")))
+            .collect()
+    }
+
+    pub(super) fn auto_config_url() -> Option<String> {
+        let settings = settings()?;
+        if number(&settings, "ProxyAutoConfigEnable") != Some(1) {
+            return None;
+        }
+        string(&settings, "ProxyAutoConfigURLString").filter(|url| !url.is_empty())
+    }
+}
+
+/// Other platforms have no system settings.
+#[cfg(all(feature = "system-proxy", not(any(windows, target_os = "macos"))))]
+mod system {
+    use super::Proxy;
+
+    pub(super) fn proxies() -> Vec<Proxy> {
+        vec![]
+    }
+
+    pub(super) fn auto_config_url() -> Option<String> {
+        None
+    }
+}
+
+/// Proxy credentials for a single request, stored in the request's extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct Credentials {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
@@ -0,0 +1,263 @@
+//! Editing query strings.
+//!
+//! [`QueryPairs`] is a mutable view of the query string of a request, created by
+//! `Request::query_pairs`. Unlike `Request::set_query` and `Request::merge_query`, which
+//! serialize a struct, it can express repeated names, names without a value, and the encoding
+//! quirks some servers expect. The query string is rewritten when the view is dropped.
+//!
+//! By default names and values are encoded as `application/x-www-form-urlencoded`: everything
+//! except ASCII letters, digits and `*-._` is percent-encoded, and spaces become `+`. Use
+//! [`QueryPairs::spaces`] and [`QueryPairs::keep`] to change this.
+//!
+//! [`QueryPairs`]: struct.QueryPairs.html
+//! [`QueryPairs::spaces`]: struct.QueryPairs.html#method.spaces
+//! [`QueryPairs::keep`]: struct.QueryPairs.html#method.keep
+//!
+//! # Examples
+//!
+//! ```
+//! use surf::query::Spaces;
+//!
+//! let mut req = surf::get("https://example.com/search?q=cats&tag=a&tag=a");
+//! req.query_pairs()
+//!     .spaces(Spaces::Percent)
+//!     .keep(",")
+//!     .dedup()
+//!     .set("q", "cute cats")
+//!     .append("fields", "name,age")
+//!     .append_name("pretty");
+//! assert_eq!(
+//!     req.url().query(),
+//!     Some("q=cute%20cats&tag=a&fields=name,age&pretty")
+//! );
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::Request;
+
+use std::fmt;
+
+/// How to encode spaces in names and values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spaces {
+    /// Encode spaces as `+`, the default.
+    Plus,
+    /// Encode spaces as `%20`.
+    Percent,
+}
+
+/// A mutable view of the query string of a request.
+///
+/// The query string is rewritten from the pairs when the view is dropped. If no pairs are left,
+/// the query string is removed.
+pub struct QueryPairs<'a, C: HttpClient> {
+    req: &'a mut Request<C>,
+    pairs: Vec<(String, Option<String>)>,
+    spaces: Spaces,
+    keep: String,
+}
+
+impl<'a, C: HttpClient> QueryPairs<'a, C> {
+    /// Create a view of the query string of a request, decoding its pairs.
+    pub(crate) fn new(req: &'a mut Request<C>) -> Self {
+        let pairs = req
+            .url()
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                let name = decode(parts.next().unwrap_or(""));
+                (name, parts.next().map(decode))
+            })
+            .collect();
+        Self {
+            req,
+            pairs,
+            spaces: Spaces::Plus,
+            keep: String::new(),
+        }
+    }
+
+    /// Set how to encode spaces, defaults to `Spaces::Plus`.
+    pub fn spaces(&mut self, spaces: Spaces) -> &mut Self {
+        self.spaces = spaces;
+        self
+    }
+
+    /// Leave the given characters unencoded, such as `,` for servers expecting comma separated
+    /// lists.
+    ///
+    /// Only ASCII characters can be left unencoded. `&`, `=` and `#` are always encoded, since
+    /// leaving them would change the meaning of the query string.
+    pub fn keep(&mut self, chars: &str) -> &mut Self {
+        self.keep.extend(
+            chars
+                .chars()
+                .filter(|c| c.is_ascii() && !"&=#+% ".contains(*c)),
+        );
+        self
+    }
+
+    /// Append a pair.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.pairs.push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Append a name without a value, such as `pretty` in `?pretty`.
+    pub fn append_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.pairs.push((name.into(), None));
+        self
+    }
+
+    /// Replace all pairs with a name by a single pair, at the position of the first one.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        let name = name.into();
+        let value = Some(value.into());
+        match self.pairs.iter().position(|(n, _)| *n == name) {
+            Some(index) => {
+                self.pairs[index].1 = value;
+                let mut i = 0;
+                self.pairs.retain(|(n, _)| {
+                    let keep = *n != name || i == index;
+                    i += 1;
+                    keep
+                });
+            }
+            None => self.pairs.push((name, value)),
+        }
+        self
+    }
+
+    /// Remove all pairs with a name.
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.pairs.retain(|(n, _)| n != name);
+        self
+    }
+
+    /// Remove pairs that repeat an earlier pair with the same name and value.
+    pub fn dedup(&mut self) -> &mut Self {
+        let mut seen = Vec::new();
+        self.pairs.retain(|pair| {
+            if seen.contains(pair) {
+                false
+            } else {
+                seen.push(pair.clone());
+                true
+            }
+        });
+        self
+    }
+
+    /// Remove all pairs.
+    pub fn clear(&mut self) -> &mut Self {
+        self.pairs.clear();
+        self
+    }
+
+    /// Get the value of the first pair with a name.
+    ///
+    /// Returns `Some("")` for a name without a value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_deref().unwrap_or(""))
+    }
+
+    /// Get the values of all pairs with a name, in order.
+    pub fn get_all<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'b str> + 'b {
+        self.pairs
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, value)| value.as_deref().unwrap_or(""))
+    }
+
+    /// Iterate over the pairs, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.pairs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    /// Get the number of pairs.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Check whether there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Percent-encode a name or value.
+    fn encode(&self, input: &str, out: &mut String) {
+        for byte in input.bytes() {
+            match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => {
+                    out.push(byte as char)
+                }
+                b' ' if self.spaces == Spaces::Plus => out.push('+'),
+                _ if byte.is_ascii() && self.keep.contains(byte as char) => out.push(byte as char),
+                _ => out.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+    }
+}
+
+impl<C: HttpClient> Drop for QueryPairs<'_, C> {
+    fn drop(&mut self) {
+        let mut query = String::new();
+        for (i, (name, value)) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                query.push('&');
+            }
+            self.encode(name, &mut query);
+            if let Some(value) = value {
+                query.push('=');
+                self.encode(value, &mut query);
+            }
+        }
+        let query = if query.is_empty() {
+            None
+        } else {
+            Some(query.as_str())
+        };
+        self.req.replace_query(query);
+    }
+}
+
+impl<C: HttpClient> fmt::Debug for QueryPairs<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryPairs")
+            .field("pairs", &self.pairs)
+            .field("spaces", &self.spaces)
+            .field("keep", &self.keep)
+            .finish()
+    }
+}
+
+/// Decode a name or value of an `application/x-www-form-urlencoded` query string.
+fn decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let hex = |i: usize| {
+        let digits = bytes.get(i..i + 2)?;
+        u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    };
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], hex(i + 1)) {
+            (b'+', _) => decoded.push(b' '),
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
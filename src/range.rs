@@ -0,0 +1,132 @@
+//! Byte range requests.
+
+use http::StatusCode;
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// The range of a request set with `Request::range` or `Request::range_from`, stored in the
+/// request's extensions.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Requested {
+    pub(crate) start: u64,
+    pub(crate) end: Option<u64>,
+}
+
+impl Requested {
+    /// Get the value of the `Range` header.
+    pub(crate) fn header(&self) -> String {
+        match self.end {
+            Some(end) => format!("bytes={}-{}", self.start, end - 1),
+            None => format!("bytes={}-", self.start),
+        }
+    }
+
+    /// Check whether a response status means the server ignored the range.
+    ///
+    /// A `200 OK` response to a request for the whole body is what was asked for.
+    pub(crate) fn ignored_by(&self, status: StatusCode) -> bool {
+        status == StatusCode::OK && !(self.start == 0 && self.end.is_none())
+    }
+}
+
+/// The error returned when a server responds to a range request with the whole body.
+///
+/// Servers may ignore the `Range` header of a request, for example because they don't support
+/// ranges for a resource, and respond with `200 OK` instead of `206 Partial Content`. Requests
+/// with a range set by `Request::range` or `Request::range_from` fail with this error then,
+/// rather than returning a body that doesn't start where it was expected to.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::RangeIgnored;
+///
+/// match surf::get("https://httpbin.org/range/1024").range(512..1024).await {
+///     Err(err) if err.is::<RangeIgnored>() => println!("download again from the start"),
+///     res => println!("{:?}", res?.content_range()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeIgnored {
+    _priv: (),
+}
+
+impl RangeIgnored {
+    pub(crate) fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl fmt::Display for RangeIgnored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("server ignored the range of the request")
+    }
+}
+
+impl Error for RangeIgnored {}
+
+/// The `Content-Range` of a response, see `Response::content_range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentRange {
+    range: Option<Range<u64>>,
+    complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse a `Content-Range` header value in bytes.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if !value.get(..6)?.eq_ignore_ascii_case("bytes ") {
+            return None;
+        }
+        let mut parts = value[6..].trim_start().splitn(2, '/');
+        let (range, complete_length) = (parts.next()?, parts.next()?);
+        let complete_length = match complete_length {
+            "*" => None,
+            length => Some(length.parse().ok()?),
+        };
+        let range = match range {
+            // An unsatisfied range, which is only valid with a known length.
+            "*" => {
+                complete_length?;
+                None
+            }
+            range => {
+                let mut bounds = range.splitn(2, '-');
+                let first: u64 = bounds.next()?.parse().ok()?;
+                let last: u64 = bounds.next()?.parse().ok()?;
+                if first > last {
+                    return None;
+                }
+                if let Some(length) = complete_length {
+                    if last >= length {
+                        return None;
+                    }
+                }
+                Some(first..last.checked_add(1)?)
+            }
+        };
+        Some(Self {
+            range,
+            complete_length,
+        })
+    }
+
+    /// Get the range of the body, with an exclusive end.
+    ///
+    /// This is `None` for `416 Range Not Satisfiable` responses, which only tell the length of
+    /// the whole body.
+    pub fn range(&self) -> Option<Range<u64>> {
+        self.range.clone()
+    }
+
+    /// Get the length of the whole body, if the server knows it.
+    pub fn complete_length(&self) -> Option<u64> {
+        self.complete_length
+    }
+}
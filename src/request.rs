@@ -5,9 +5,27 @@ use mime::Mime;
 use serde::Serialize;
 use url::Url;
 
-use crate::headers::Headers;
-use crate::http_client::{self, Body, HttpClient};
-use crate::middleware::{Middleware, Next};
+use crate::attempt::{self, AttemptReason, Attempts};
+use crate::codec::{self, Codecs};
+use crate::duplex::{self, BodyWriter};
+use crate::framing;
+use crate::headers::{ETag, Headers, HttpDate};
+use crate::http_client::{
+    self, Body, DrainOnDrop, Duplex, FreshConnection, HttpClient, Isolated, LenientFraming,
+    NormalizeText, Reused, Timeout,
+};
+use crate::middleware::redirect::BodyReplay;
+use crate::middleware::replay::Replay;
+use crate::middleware::{retry, Middleware, Next};
+use crate::multipart::Multipart;
+use crate::pipeline::Pipeline;
+use crate::precondition::{self, PreconditionFailed, Validators};
+use crate::progress::{Progress, Reporter};
+use crate::query::QueryPairs;
+use crate::range::{self, RangeIgnored};
+use crate::reuse::{self, Upload};
+use crate::timeout;
+use crate::transfer::TransferSize;
 use crate::Exception;
 use crate::Response;
 
@@ -20,6 +38,7 @@ use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "native-client")]
 use super::http_client::native::NativeClient;
@@ -61,7 +80,11 @@ impl Request<NativeClient> {
     /// # Ok(()) }
     /// ```
     pub fn new(method: http::Method, url: Url) -> Self {
-        Self::with_client(method, url, NativeClient::new())
+        lazy_static::lazy_static! {
+            /// The backend shared by requests that aren't created from a `Client`.
+            static ref SHARED: NativeClient = NativeClient::new();
+        }
+        Self::with_client(method, url, SHARED.clone())
     }
 }
 
@@ -109,6 +132,21 @@ impl<C: HttpClient> Request<C> {
         self
     }
 
+    /// Push shared middleware onto the middleware stack.
+    pub(crate) fn push_middleware(&mut self, mw: Arc<dyn Middleware<C>>) {
+        self.middleware.as_mut().unwrap().push(mw);
+    }
+
+    /// Get a mutable reference to the request headers.
+    pub(crate) fn headers_mut(&mut self) -> &mut http::HeaderMap {
+        self.req.as_mut().unwrap().headers_mut()
+    }
+
+    /// Get a mutable reference to the request extensions.
+    pub(crate) fn extensions_mut(&mut self) -> &mut http::Extensions {
+        self.req.as_mut().unwrap().extensions_mut()
+    }
+
     /// Get the URL querystring.
     ///
     /// # Examples
@@ -160,15 +198,72 @@ impl<C: HttpClient> Request<C> {
         query: &impl Serialize,
     ) -> Result<Self, serde_urlencoded::ser::Error> {
         let query = serde_urlencoded::to_string(query)?;
-        self.url.set_query(Some(&query));
-
-        let req = self.req.as_mut().unwrap();
-        let uri = req.uri_mut();
-        *uri = self.url.clone().into_string().parse().unwrap();
+        self.replace_query(Some(&query));
+        Ok(self)
+    }
 
+    /// Merge a serialized struct into the URL querystring.
+    ///
+    /// Unlike `Request::set_query`, pairs already in the query string are kept, unless the struct
+    /// has a field with the same name, which replaces them. Fields serialized as `None` are
+    /// skipped, leaving existing pairs of that name alone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Serialize)]
+    /// struct Page {
+    ///     page: u32,
+    ///     per_page: Option<u32>,
+    /// }
+    ///
+    /// let query = Page { page: 2, per_page: None };
+    /// let req = surf::get("https://httpbin.org/get?q=cats&page=1").merge_query(&query)?;
+    /// assert_eq!(req.url().query(), Some("q=cats&page=2"));
+    /// # Ok(()) }
+    /// ```
+    pub fn merge_query(
+        mut self,
+        query: &impl Serialize,
+    ) -> Result<Self, serde_urlencoded::ser::Error> {
+        let query = serde_urlencoded::to_string(query)?;
+        let mut pairs = self.query_pairs();
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            pairs.set(name, value);
+        }
+        drop(pairs);
         Ok(self)
     }
 
+    /// Get a mutable view of the query string, to add, remove or deduplicate pairs, and to
+    /// control how they're encoded.
+    ///
+    /// The query string is rewritten when the view is dropped. See the [query] submodule for more
+    /// information.
+    ///
+    /// [query]: query/index.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut req = surf::get("https://httpbin.org/get?page=1&page=1");
+    /// req.query_pairs().dedup().append("filter", "cats & dogs");
+    /// assert_eq!(req.url().query(), Some("page=1&filter=cats+%26+dogs"));
+    /// ```
+    pub fn query_pairs(&mut self) -> QueryPairs<'_, C> {
+        QueryPairs::new(self)
+    }
+
+    /// Replace the query string of the URL.
+    pub(crate) fn replace_query(&mut self, query: Option<&str>) {
+        self.url.set_query(query);
+        let req = self.req.as_mut().unwrap();
+        *req.uri_mut() = self.url.clone().into_string().parse().unwrap();
+    }
+
     /// Get an HTTP header.
     ///
     /// # Examples
@@ -224,284 +319,1095 @@ impl<C: HttpClient> Request<C> {
         Headers::new(self.req.as_mut().unwrap().headers_mut())
     }
 
-    /// Get the request HTTP method.
+    /// Request a range of the body, in bytes.
+    ///
+    /// This sets the `Range` header. A server that supports ranges responds with
+    /// `206 Partial Content` and a `Content-Range` header, see `Response::content_range`. If the
+    /// server ignores the range and responds with the whole body, the request fails with a
+    /// `RangeIgnored` error.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the range is empty.
     ///
     /// # Examples
     ///
     /// ```
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// use surf::http;
-    /// let req = surf::get("https://httpbin.org/get");
-    /// assert_eq!(req.method(), http::Method::GET);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let req = surf::get("https://httpbin.org/range/1024").range(0..512);
+    /// assert_eq!(req.header("Range"), Some("bytes=0-511"));
     /// # Ok(()) }
     /// ```
-    pub fn method(&self) -> &Method {
-        let req = self.req.as_ref().unwrap();
-        req.method()
+    pub fn range(self, range: std::ops::Range<u64>) -> Self {
+        assert!(range.start < range.end, "empty range");
+        self.set_range(range::Requested {
+            start: range.start,
+            end: Some(range.end),
+        })
     }
 
-    /// Get the request url.
+    /// Request the body from an offset to the end, for example to resume a download.
+    ///
+    /// See `Request::range`. A request from offset `0` accepts a `200 OK` response with the whole
+    /// body.
     ///
     /// # Examples
     ///
     /// ```
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// use surf::url::Url;
-    /// let req = surf::get("https://httpbin.org/get");
-    /// assert_eq!(req.url(), &Url::parse("https://httpbin.org/get")?);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let req = surf::get("https://httpbin.org/range/1024").range_from(512);
+    /// assert_eq!(req.header("Range"), Some("bytes=512-"));
     /// # Ok(()) }
     /// ```
-    pub fn url(&self) -> &Url {
-        &self.url
+    pub fn range_from(self, offset: u64) -> Self {
+        self.set_range(range::Requested {
+            start: offset,
+            end: None,
+        })
     }
 
-    /// Get the request MIME.
+    fn set_range(mut self, range: range::Requested) -> Self {
+        let value = http::HeaderValue::from_str(&range.header()).unwrap();
+        let req = self.req.as_mut().unwrap();
+        req.headers_mut().insert(http::header::RANGE, value);
+        req.extensions_mut().insert(range);
+        self
+    }
+
+    /// Only get the resource if it doesn't match an entity tag, usually the `ETag` of a version
+    /// fetched before.
     ///
-    /// Gets the `Content-Type` header and parses it to a `Mime` type.
+    /// This sets the `If-None-Match` header. If the resource still matches, the server responds
+    /// with `304 Not Modified` and no body, see `Response::etag`.
     ///
-    /// [Read more on MDN](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types)
+    /// # Examples
     ///
-    /// # Panics
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use surf::headers::ETag;
     ///
-    /// This method will panic if an invalid MIME type was set as a header. Use the [`set_header`]
-    /// method to bypass any checks.
+    /// let req = surf::get("https://httpbin.org/etag/v1").if_none_match(&ETag::strong("v1"));
+    /// assert_eq!(req.header("If-None-Match"), Some(r#""v1""#));
+    /// # Ok(()) }
+    /// ```
+    pub fn if_none_match(mut self, etag: &ETag) -> Self {
+        let value = http::HeaderValue::from_str(&etag.to_string()).unwrap();
+        let req = self.req.as_mut().unwrap();
+        req.headers_mut().insert(http::header::IF_NONE_MATCH, value);
+        self
+    }
+
+    /// Only change the resource if it matches an entity tag, usually the `ETag` of the version
+    /// the change was made to.
     ///
-    /// [`set_header`]: #method.set_header
+    /// This sets the `If-Match` header. If the resource doesn't match, because it was changed
+    /// since, the server responds with `412 Precondition Failed`, and the request fails with a
+    /// `PreconditionFailed` error. Weak tags never match.
     ///
     /// # Examples
     ///
     /// ```
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// use surf::mime;
-    /// let req = surf::post("https://httpbin.org/get")
-    ///     .set_mime(mime::TEXT_CSS);
-    /// assert_eq!(req.mime(), Some(mime::TEXT_CSS));
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use surf::headers::ETag;
+    ///
+    /// let req = surf::put("https://httpbin.org/put").if_match(&ETag::strong("v1"));
+    /// assert_eq!(req.header("If-Match"), Some(r#""v1""#));
     /// # Ok(()) }
     /// ```
-    pub fn mime(&self) -> Option<Mime> {
-        let header = self.header("Content-Type")?;
-        Some(header.parse().unwrap())
+    pub fn if_match(mut self, etag: &ETag) -> Self {
+        let value = http::HeaderValue::from_str(&etag.to_string()).unwrap();
+        let req = self.req.as_mut().unwrap();
+        req.headers_mut().insert(http::header::IF_MATCH, value);
+        req.extensions_mut().insert(precondition::Requested);
+        self
     }
 
-    /// Set the request MIME.
+    /// Only change the resource if it wasn't modified after a time, usually the `Last-Modified`
+    /// time of the version the change was made to.
     ///
-    /// [Read more on MDN](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types)
+    /// This sets the `If-Unmodified-Since` header. If the resource was modified since, the server
+    /// responds with `412 Precondition Failed`, and the request fails with a
+    /// `PreconditionFailed` error.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `time` is before 1970 or after 9999, which HTTP dates can't express.
+    pub fn if_unmodified_since(mut self, time: impl Into<HttpDate>) -> Self {
+        let value = http::HeaderValue::from_str(&time.into().to_string()).unwrap();
+        let req = self.req.as_mut().unwrap();
+        req.headers_mut()
+            .insert(http::header::IF_UNMODIFIED_SINCE, value);
+        req.extensions_mut().insert(precondition::Requested);
+        self
+    }
+
+    /// Only change the resource if it's still at the version it was fetched at, for optimistic
+    /// concurrency control.
+    ///
+    /// This sends the entity tag of the version with `Request::if_match` if it's strong, and
+    /// otherwise the time it was last modified with `Request::if_unmodified_since`. Requests for
+    /// versions without either are sent without a precondition, see `Validators::is_empty`.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// use surf::mime;
-    /// let req = surf::post("https://httpbin.org/get")
-    ///     .set_mime(mime::TEXT_CSS);
-    /// assert_eq!(req.mime(), Some(mime::TEXT_CSS));
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let url = "https://example.com/documents/1";
+    /// let mut res = surf::get(url).await?;
+    /// let validators = res.validators();
+    /// let document = res.body_string().await?;
+    ///
+    /// let res = surf::put(url)
+    ///     .precondition(&validators)
+    ///     .body_string(document.replace("draft", "final"))
+    ///     .await?;
     /// # Ok(()) }
     /// ```
-    pub fn set_mime(self, mime: Mime) -> Self {
-        self.set_header("Content-Type", format!("{}", mime))
+    pub fn precondition(self, validators: &Validators) -> Self {
+        match (validators.etag(), validators.last_modified()) {
+            (Some(etag), _) if !etag.is_weak() => self.if_match(etag),
+            (_, Some(time)) => self.if_unmodified_since(time),
+            _ => self,
+        }
     }
 
-    /// Pass an `AsyncRead` stream as the request body.
+    /// Only get the resource if it was modified after a time, usually the `Last-Modified` time
+    /// of a version fetched before.
     ///
-    /// # Mime
+    /// This sets the `If-Modified-Since` header. If the resource wasn't modified, the server
+    /// responds with `304 Not Modified` and no body, see `Response::last_modified`.
     ///
-    /// The encoding is set to `application/octet-stream`.
+    /// # Panics
+    ///
+    /// This will panic if `time` is before 1970 or after 9999, which HTTP dates can't express.
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let reader = surf::get("https://httpbin.org/get").await?;
-    /// let uri = "https://httpbin.org/post";
-    /// let res = surf::post(uri).body(reader).await?;
-    /// assert_eq!(res.status(), 200);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let time = UNIX_EPOCH + Duration::from_secs(784111777);
+    /// let req = surf::get("https://httpbin.org/cache").if_modified_since(time);
+    /// assert_eq!(req.header("If-Modified-Since"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
     /// # Ok(()) }
     /// ```
-    pub fn body<R>(mut self, reader: R) -> Self
-    where
-        R: AsyncRead + Unpin + Send + 'static,
-    {
-        *self.req.as_mut().unwrap().body_mut() = Box::new(reader).into();
-        self.set_mime(mime::APPLICATION_OCTET_STREAM)
+    pub fn if_modified_since(mut self, time: impl Into<HttpDate>) -> Self {
+        let value = http::HeaderValue::from_str(&time.into().to_string()).unwrap();
+        let req = self.req.as_mut().unwrap();
+        req.headers_mut()
+            .insert(http::header::IF_MODIFIED_SINCE, value);
+        self
     }
 
-    /// Pass JSON as the request body.
+    /// Get a request extension.
     ///
-    /// # Mime
+    /// Extensions are typed values that travel with the request through the middleware stack,
+    /// letting callers pass data to middleware.
     ///
-    /// The encoding is set to `application/json`.
+    /// # Examples
     ///
-    /// # Errors
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Debug, PartialEq)]
+    /// struct RequestId(u32);
     ///
-    /// This method will return an error if the provided data could not be serialized to JSON.
+    /// let req = surf::get("https://httpbin.org/get").set_ext(RequestId(7));
+    /// assert_eq!(req.ext::<RequestId>(), Some(&RequestId(7)));
+    /// # Ok(()) }
+    /// ```
+    pub fn ext<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.req.as_ref().unwrap().extensions().get()
+    }
+
+    /// Set a request extension, replacing any previous value of the same type.
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```
     /// # #[runtime::main]
     /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let uri = "https://httpbin.org/post";
-    /// let data = serde_json::json!({ "name": "chashu" });
-    /// let res = surf::post(uri).body_json(&data)?.await?;
-    /// assert_eq!(res.status(), 200);
+    /// #[derive(Debug, PartialEq)]
+    /// struct RequestId(u32);
+    ///
+    /// let req = surf::get("https://httpbin.org/get").set_ext(RequestId(7));
+    /// assert_eq!(req.ext::<RequestId>(), Some(&RequestId(7)));
     /// # Ok(()) }
     /// ```
-    pub fn body_json(mut self, json: &impl Serialize) -> serde_json::Result<Self> {
-        *self.req.as_mut().unwrap().body_mut() = serde_json::to_vec(json)?.into();
-        Ok(self.set_mime(mime::APPLICATION_JSON))
+    pub fn set_ext<T: Send + Sync + 'static>(mut self, val: T) -> Self {
+        self.extensions_mut().insert(val);
+        self
     }
 
-    /// Pass a string as the request body.
+    /// Get a stream of progress events for this request.
     ///
-    /// # Mime
+    /// The stream can be polled while the request is sent, see the [progress] submodule.
     ///
-    /// The encoding is set to `text/plain; charset=utf-8`.
+    /// [progress]: progress/index.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let uri = "https://httpbin.org/post";
-    /// let data = "hello world".to_string();
-    /// let res = surf::post(uri).body_string(data).await?;
-    /// assert_eq!(res.status(), 200);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use futures::prelude::*;
+    ///
+    /// let mut req = surf::post("https://httpbin.org/post").body_bytes(vec![0; 1 << 20]);
+    /// let progress = req.progress();
+    /// let (res, events) = future::join(req, progress.collect::<Vec<_>>()).await;
+    /// assert_eq!(events.last().unwrap().sent(), 1 << 20);
     /// # Ok(()) }
     /// ```
-    pub fn body_string(mut self, string: String) -> Self {
-        *self.req.as_mut().unwrap().body_mut() = string.into_bytes().into();
-        self.set_mime(mime::TEXT_PLAIN_UTF_8)
+    pub fn progress(&mut self) -> Progress {
+        let (progress, reporter) = Progress::new();
+        self.extensions_mut().insert(reporter);
+        progress
     }
 
-    /// Pass bytes as the request body.
+    /// Send the request without sharing any state with other requests.
     ///
-    /// # Mime
+    /// Isolated requests use a new connection, and don't use or update cached DNS results, TLS
+    /// sessions or cookies, making them hard to link to other requests made by the same client.
+    /// This is useful for privacy sensitive requests, such as update checks. See
+    /// [`middleware::Isolated`] for how middleware should treat them.
     ///
-    /// The encoding is set to `application/octet-stream`.
+    /// [`middleware::Isolated`]: middleware/struct.Isolated.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let uri = "https://httpbin.org/post";
-    /// let data = b"hello world";
-    /// let res = surf::post(uri).body_bytes(data).await?;
-    /// assert_eq!(res.status(), 200);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://example.com/updates.json").isolated().await?;
     /// # Ok(()) }
     /// ```
-    pub fn body_bytes(mut self, bytes: impl AsRef<[u8]>) -> Self {
-        *self.req.as_mut().unwrap().body_mut() = bytes.as_ref().to_owned().into();
-        self.set_mime(mime::APPLICATION_OCTET_STREAM)
+    pub fn isolated(self) -> Self {
+        self.set_ext(http_client::Isolated)
     }
 
-    /// Pass a file as the request body.
-    ///
-    /// # Mime
-    ///
-    /// The encoding is set based on the file extension using [`mime_guess`] if the operation was
-    /// successful. If `path` has no extension, or its extension has no known MIME type mapping,
-    /// then `None` is returned.
+    /// Accept any server certificate for this request, including expired, self-signed and
+    /// mismatched ones.
     ///
-    /// [`mime_guess`]: https://docs.rs/mime_guess
+    /// This turns off the protection TLS offers against impersonation of the server, so it's only
+    /// meant for development and testing, such as talking to a single service with a self-signed
+    /// certificate from a client that verifies certificates for every other request. Redirects
+    /// and auxiliary requests sent by middleware are verified as usual. The browser backend
+    /// can't skip verification, and fails these requests.
     ///
-    /// # Errors
+    /// See [`middleware::DangerAcceptInvalidCerts`] for how backends treat them.
     ///
-    /// This method will return an error if the file couldn't be read.
+    /// [`middleware::DangerAcceptInvalidCerts`]: middleware/struct.DangerAcceptInvalidCerts.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # #[runtime::main]
     /// # async fn main() -> Result<(), surf::Exception> {
-    /// let res = surf::post("https://httpbin.org/post")
-    ///     .body_file("README.md")?
+    /// let res = surf::get("https://self-signed.badssl.com/")
+    ///     .danger_accept_invalid_certs()
     ///     .await?;
-    /// assert_eq!(res.status(), 200);
     /// # Ok(()) }
     /// ```
-    pub fn body_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
-        let mime = mime_guess::from_path(&path).first_or_octet_stream();
-        let bytes = fs::read(path)?;
-        *self.req.as_mut().unwrap().body_mut() = bytes.into();
-        Ok(self.set_mime(mime))
+    pub fn danger_accept_invalid_certs(self) -> Self {
+        log::warn!(
+            "certificate verification is disabled for a request to {}",
+            self.url()
+        );
+        self.set_ext(http_client::DangerAcceptInvalidCerts)
     }
 
-    /// Pass a form as the request body.
-    ///
-    /// # Mime
+    /// Read up to `limit` bytes of the response body when the response is dropped unread.
     ///
-    /// The encoding is set to `application/x-www-form-urlencoded`.
-    ///
-    /// # Errors
-    ///
-    /// An error will be returned if the encoding failed.
+    /// Dropping a response before its body has been read closes the connection, since it can't
+    /// be used for another request until the rest of the body has been received. With this
+    /// setting, the part of the body that has already arrived is read and discarded when the
+    /// response is dropped. If that reaches the end of the body, the connection is returned to
+    /// the pool instead. Dropping never waits for more data; use `Response::consume` to wait for
+    /// the whole body.
     ///
     /// # Examples
     ///
-    /// ```
-    /// # use serde::{Deserialize, Serialize};
+    /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// #[derive(Serialize, Deserialize)]
-    /// struct Body {
-    ///     apples: u32
-    /// }
-    ///
-    /// let res = surf::post("https://httpbin.org/post")
-    ///     .body_form(&Body { apples: 7 })?
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://httpbin.org/get")
+    ///     .drain_on_drop(64 * 1024)
     ///     .await?;
-    /// assert_eq!(res.status(), 200);
+    /// println!("{}", res.status());
     /// # Ok(()) }
     /// ```
-    pub fn body_form(
-        mut self,
-        form: &impl Serialize,
-    ) -> Result<Self, serde_urlencoded::ser::Error> {
-        let query = serde_urlencoded::to_string(form)?;
-        self = self.body_string(query);
-        self = self.set_mime(mime::APPLICATION_WWW_FORM_URLENCODED);
-        Ok(self)
+    pub fn drain_on_drop(self, limit: u64) -> Self {
+        self.set_ext(DrainOnDrop(limit))
     }
 
-    /// Submit the request and get the response body as bytes.
+    /// Set the timeout for the request, replacing the client's timeout.
+    ///
+    /// The timeout covers sending the request and reading the whole response body. Once it
+    /// expires the request is cancelled, and it fails with a [`TimeoutError`]. Every attempt to
+    /// complete the request, such as a followed redirect, gets the full timeout.
+    ///
+    /// [`TimeoutError`]: struct.TimeoutError.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let bytes = surf::get("https://httpbin.org/get").recv_bytes().await?;
-    /// assert!(bytes.len() > 0);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use std::time::Duration;
+    ///
+    /// let string = surf::get("https://httpbin.org/get")
+    ///     .timeout(Duration::from_secs(5))
+    ///     .recv_string()
+    ///     .await?;
     /// # Ok(()) }
     /// ```
-    pub async fn recv_bytes(self) -> Result<Vec<u8>, Exception> {
-        let mut req = self.await?;
-        Ok(req.body_bytes().await?)
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.set_ext(http_client::Timeout(timeout))
     }
 
-    /// Submit the request and get the response body as a string.
+    /// Skip the checks for ambiguous framing on this request and its response.
+    ///
+    /// By default requests and responses with conflicting or malformed `Content-Length` and
+    /// `Transfer-Encoding` headers fail with a [`FramingError`], because they can be used to
+    /// smuggle requests past proxies. Only skip the checks for servers that are known to be
+    /// broken, and can be trusted.
+    ///
+    /// [`FramingError`]: struct.FramingError.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # #[runtime::main]
-    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    /// let string = surf::get("https://httpbin.org/get").recv_string().await?;
-    /// assert!(string.len() > 0);
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let string = surf::get("http://legacy.example.com/status")
+    ///     .lenient_framing()
+    ///     .recv_string()
+    ///     .await?;
     /// # Ok(()) }
     /// ```
-    pub async fn recv_string(self) -> Result<String, Exception> {
-        let mut req = self.await?;
-        Ok(req.body_string().await?)
+    pub fn lenient_framing(self) -> Self {
+        self.set_ext(LenientFraming)
     }
 
-    /// Submit the request and decode the response body from json into a struct.
+    /// Normalize the text of the response, when it's read with `Response::body_string` or
+    /// `Response::body_lines`.
+    ///
+    /// A byte order mark at the start of the body is removed, and `\r\n` line endings are
+    /// replaced with `\n`, so text fetched from different servers can be compared or hashed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let string = surf::get("https://example.com/notes.txt")
+    ///     .normalize_text()
+    ///     .recv_string()
+    ///     .await?;
+    /// assert!(!string.contains('\r'));
+    /// # Ok(()) }
+    /// ```
+    pub fn normalize_text(self) -> Self {
+        self.set_ext(NormalizeText)
+    }
+
+    /// Authenticate to the client's proxy with the given credentials for this request.
+    ///
+    /// Tor uses SOCKS credentials to isolate streams, see the [proxy] submodule. The credentials
+    /// replace those set with `Proxy::basic_auth`, and are ignored if the request isn't sent
+    /// through a proxy.
+    ///
+    /// [proxy]: proxy/index.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use surf::proxy::Proxy;
+    ///
+    /// let client = surf::ClientBuilder::new()
+    ///     .proxy(Proxy::socks5("127.0.0.1:9050"))
+    ///     .build();
+    /// let res = client
+    ///     .get("https://check.torproject.org")
+    ///     .proxy_credentials("identity-1", "")
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn proxy_credentials(
+        self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.set_ext(crate::proxy::Credentials {
+            username: username.into(),
+            password: password.into(),
+        })
+    }
+
+    /// Get the request HTTP method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::http;
+    /// let req = surf::get("https://httpbin.org/get");
+    /// assert_eq!(req.method(), http::Method::GET);
+    /// # Ok(()) }
+    /// ```
+    pub fn method(&self) -> &Method {
+        let req = self.req.as_ref().unwrap();
+        req.method()
+    }
+
+    /// Get the request url.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::url::Url;
+    /// let req = surf::get("https://httpbin.org/get");
+    /// assert_eq!(req.url(), &Url::parse("https://httpbin.org/get")?);
+    /// # Ok(()) }
+    /// ```
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the request MIME.
+    ///
+    /// Gets the `Content-Type` header and parses it to a `Mime` type.
+    ///
+    /// [Read more on MDN](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types)
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if an invalid MIME type was set as a header. Use the [`set_header`]
+    /// method to bypass any checks.
+    ///
+    /// [`set_header`]: #method.set_header
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::mime;
+    /// let req = surf::post("https://httpbin.org/get")
+    ///     .set_mime(mime::TEXT_CSS);
+    /// assert_eq!(req.mime(), Some(mime::TEXT_CSS));
+    /// # Ok(()) }
+    /// ```
+    pub fn mime(&self) -> Option<Mime> {
+        let header = self.header("Content-Type")?;
+        Some(header.parse().unwrap())
+    }
+
+    /// Set the request MIME.
+    ///
+    /// [Read more on MDN](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::mime;
+    /// let req = surf::post("https://httpbin.org/get")
+    ///     .set_mime(mime::TEXT_CSS);
+    /// assert_eq!(req.mime(), Some(mime::TEXT_CSS));
+    /// # Ok(()) }
+    /// ```
+    pub fn set_mime(self, mime: Mime) -> Self {
+        self.set_header("Content-Type", format!("{}", mime))
+    }
+
+    /// Pass an `AsyncRead` stream as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/octet-stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let reader = surf::get("https://httpbin.org/get").await?;
+    /// let uri = "https://httpbin.org/post";
+    /// let res = surf::post(uri).body(reader).await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body<R>(mut self, reader: R) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        *self.req.as_mut().unwrap().body_mut() = Box::new(reader).into();
+        self.set_mime(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Write the request body while the response is read.
+    ///
+    /// Returns the request along with a writer for its body, which is streamed to the server as
+    /// it's written and ends when the writer is closed or dropped. The response is available as
+    /// soon as the server sends it, while the body is still being written, which protocols where
+    /// the server answers parts of the body as they arrive rely on. Write the body concurrently
+    /// with awaiting the response, since the server may only respond once it has received part of
+    /// the body.
+    ///
+    /// The body isn't read ahead of sending it, so it can't be sent again: redirects that need
+    /// the body to be sent again fail, and failed requests aren't retried. Middleware needing the
+    /// whole body before sending the request can't have it: the validate middleware checks the
+    /// body size as it's sent, the audit middleware logs the body without a hash, and the
+    /// encryption middleware rejects the request.
+    ///
+    /// The curl backend hands out the response once the first bytes of its body arrive, rather
+    /// than as soon as its headers do. Browsers can't stream request bodies, so the browser
+    /// backend fails requests with a body writer.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/octet-stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use futures::future::join;
+    /// use futures::io::AsyncWriteExt;
+    /// use futures::stream::StreamExt;
+    ///
+    /// let (req, mut writer) = surf::post("https://example.com/echo").body_writer();
+    /// let writing = async move {
+    ///     for n in 0..3 {
+    ///         writer.write_all(format!("message {}\n", n).as_bytes()).await?;
+    ///     }
+    ///     writer.close().await
+    /// };
+    /// let reading = async move {
+    ///     let mut res = req.await?;
+    ///     let mut lines = res.body_lines(1024);
+    ///     while let Some(line) = lines.next().await {
+    ///         println!("{}", line?);
+    ///     }
+    ///     Ok::<_, surf::Exception>(())
+    /// };
+    /// let (written, read) = join(writing, reading).await;
+    /// written?;
+    /// read?;
+    /// # Ok(()) }
+    /// ```
+    pub fn body_writer(mut self) -> (Self, BodyWriter) {
+        let (body, writer) = duplex::pipe();
+        *self.req.as_mut().unwrap().body_mut() = body;
+        let req = self
+            .set_ext(Duplex)
+            .set_mime(mime::APPLICATION_OCTET_STREAM);
+        (req, writer)
+    }
+
+    /// Pass a function creating `AsyncRead` streams as the request body.
+    ///
+    /// Unlike with `body`, the body can be sent more than once. The function is called for every
+    /// time the body is sent, for example when following a `307 Temporary Redirect`.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/octet-stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use std::io::Cursor;
+    /// use std::sync::Arc;
+    ///
+    /// let data = Arc::new(vec![0; 1024 * 1024]);
+    /// let uri = "https://httpbin.org/redirect-to?url=/post&status_code=307";
+    /// let res = surf::post(uri)
+    ///     .replayable_body(move || Cursor::new(data.to_vec()))
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn replayable_body<F, R>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let factory = http_client::BodyFactory(Arc::new(move || Body::from_reader(factory())));
+        *self.req.as_mut().unwrap().body_mut() = (factory.0)();
+        self = self.set_ext(factory);
+        self.set_mime(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Pass JSON as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/json`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the provided data could not be serialized to JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let uri = "https://httpbin.org/post";
+    /// let data = serde_json::json!({ "name": "chashu" });
+    /// let res = surf::post(uri).body_json(&data)?.await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_json(mut self, json: &impl Serialize) -> serde_json::Result<Self> {
+        *self.req.as_mut().unwrap().body_mut() = serde_json::to_vec(json)?.into();
+        Ok(self.set_mime(mime::APPLICATION_JSON))
+    }
+
+    /// Pass a JSON Patch as the request body, usually for a `PATCH` request.
+    ///
+    /// Build the patch with a [`JsonPatch`], or pass any value serializing to a list of
+    /// operations.
+    ///
+    /// [`JsonPatch`]: patch/struct.JsonPatch.html
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/json-patch+json`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the provided data could not be serialized to JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use serde_json::json;
+    /// use surf::patch::JsonPatch;
+    ///
+    /// let patch = JsonPatch::new().replace("/name", json!("chashu"));
+    /// let req = surf::patch("https://httpbin.org/patch").body_json_patch(&patch)?;
+    /// assert_eq!(req.header("Content-Type"), Some("application/json-patch+json"));
+    /// # Ok(()) }
+    /// ```
+    pub fn body_json_patch(mut self, patch: &impl Serialize) -> serde_json::Result<Self> {
+        *self.req.as_mut().unwrap().body_mut() = serde_json::to_vec(patch)?.into();
+        Ok(self.set_header("Content-Type", "application/json-patch+json"))
+    }
+
+    /// Pass a JSON Merge Patch as the request body, usually for a `PATCH` request.
+    ///
+    /// A merge patch is a JSON object with the members to change: members set to `null` are
+    /// removed, and other members are set, merging objects recursively.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/merge-patch+json`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the provided data could not be serialized to JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let patch = serde_json::json!({ "name": "chashu", "owner": null });
+    /// let res = surf::patch("https://httpbin.org/patch").body_merge_patch(&patch)?.await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn body_merge_patch(mut self, patch: &impl Serialize) -> serde_json::Result<Self> {
+        *self.req.as_mut().unwrap().body_mut() = serde_json::to_vec(patch)?.into();
+        Ok(self.set_header("Content-Type", "application/merge-patch+json"))
+    }
+
+    /// Pass a protobuf message as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/x-protobuf`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Clone, PartialEq, prost::Message)]
+    /// struct Cat {
+    ///     #[prost(string, tag = "1")]
+    ///     name: String,
+    /// }
+    ///
+    /// let cat = Cat { name: "chashu".to_string() };
+    /// let res = surf::post("https://example.com/cats").body_protobuf(&cat).await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "protobuf")]
+    pub fn body_protobuf(mut self, message: &impl prost::Message) -> Self {
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        // Encoding only fails if the buffer is too small, and a `Vec` grows as needed.
+        message.encode(&mut buf).unwrap();
+        *self.req.as_mut().unwrap().body_mut() = buf.into();
+        self.set_header("Content-Type", "application/x-protobuf")
+    }
+
+    /// Pass MessagePack as the request body.
+    ///
+    /// Structs are encoded as maps with the names of their fields, which is what most
+    /// MessagePack APIs expect.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/msgpack`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the provided data could not be serialized to
+    /// MessagePack.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     name: String,
+    /// }
+    ///
+    /// let cat = Cat { name: "chashu".to_string() };
+    /// let res = surf::post("https://example.com/cats").body_msgpack(&cat)?.await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "msgpack")]
+    pub fn body_msgpack(
+        mut self,
+        value: &impl Serialize,
+    ) -> Result<Self, rmp_serde::encode::Error> {
+        *self.req.as_mut().unwrap().body_mut() = rmp_serde::to_vec_named(value)?.into();
+        Ok(self.set_header("Content-Type", "application/msgpack"))
+    }
+
+    /// Pass XML as the request body.
+    ///
+    /// The root element is named after the type of `value`, and fields become child elements,
+    /// or attributes when their name starts with `@`, following the conventions of `quick-xml`.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/xml`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the provided data could not be serialized to XML.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     #[serde(rename = "@id")]
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// // Sends `<Cat id="1"><name>chashu</name></Cat>`.
+    /// let cat = Cat { id: 1, name: "chashu".to_string() };
+    /// let res = surf::post("https://example.com/cats").body_xml(&cat)?.await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "xml")]
+    pub fn body_xml(mut self, value: &impl Serialize) -> Result<Self, quick_xml::DeError> {
+        *self.req.as_mut().unwrap().body_mut() =
+            quick_xml::se::to_string(value)?.into_bytes().into();
+        Ok(self.set_header("Content-Type", "application/xml"))
+    }
+
+    /// Pass a string as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `text/plain; charset=utf-8`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let uri = "https://httpbin.org/post";
+    /// let data = "hello world".to_string();
+    /// let res = surf::post(uri).body_string(data).await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_string(mut self, string: String) -> Self {
+        *self.req.as_mut().unwrap().body_mut() = string.into_bytes().into();
+        self.set_mime(mime::TEXT_PLAIN_UTF_8)
+    }
+
+    /// Pass bytes as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/octet-stream`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let uri = "https://httpbin.org/post";
+    /// let data = b"hello world";
+    /// let res = surf::post(uri).body_bytes(data).await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_bytes(mut self, bytes: impl AsRef<[u8]>) -> Self {
+        *self.req.as_mut().unwrap().body_mut() = bytes.as_ref().to_owned().into();
+        self.set_mime(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    /// Pass a file as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set based on the file extension using [`mime_guess`] if the operation was
+    /// successful. If `path` has no extension, or its extension has no known MIME type mapping,
+    /// then `None` is returned.
+    ///
+    /// [`mime_guess`]: https://docs.rs/mime_guess
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the file couldn't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::post("https://httpbin.org/post")
+    ///     .body_file("README.md")?
+    ///     .await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        let bytes = fs::read(path)?;
+        *self.req.as_mut().unwrap().body_mut() = bytes.into();
+        Ok(self.set_mime(mime))
+    }
+
+    /// Pass a memory-mapped file as the request body.
+    ///
+    /// Unlike `body_file`, the file isn't read into memory before it's sent, which saves copying
+    /// multi-gigabyte files. The body can be sent more than once, for example when following a
+    /// `307 Temporary Redirect`, without mapping the file again. Files are only mapped on 64-bit
+    /// Unix platforms; elsewhere, and if mapping fails, this behaves like `body_file`.
+    ///
+    /// This requires the `mmap` feature.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set based on the file extension using [`mime_guess`], falling back to
+    /// `application/octet-stream`.
+    ///
+    /// [`mime_guess`]: https://docs.rs/mime_guess
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the file couldn't be opened.
+    ///
+    /// # Safety
+    ///
+    /// The body is read from the mapped file, which other processes can still change. The
+    /// behavior is undefined if the file is modified while the request holds the mapping, which
+    /// is until the request and its response have been dropped, and the process is killed with
+    /// `SIGBUS` if the file is truncated. Only map files that nothing writes to while they're
+    /// sent.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// // The backup is complete, so nothing writes to it anymore.
+    /// let req = surf::put("https://example.com/backups/disk.img");
+    /// let res = unsafe { req.body_file_mapped("disk.img")? }.await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub unsafe fn body_file_mapped(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        let contents = crate::mmap::Contents::open(&mut fs::File::open(path)?)?;
+        let factory =
+            http_client::BodyFactory(Arc::new(move || Body::from_reader(contents.reader())));
+        *self.req.as_mut().unwrap().body_mut() = (factory.0)();
+        self = self.set_ext(factory);
+        Ok(self.set_mime(mime))
+    }
+
+    /// Pass a form as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to `application/x-www-form-urlencoded`.
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the encoding failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Body {
+    ///     apples: u32
+    /// }
+    ///
+    /// let req = surf::post("https://httpbin.org/post").body_form(&Body { apples: 7 })?;
+    /// assert_eq!(req.header("Content-Type"), Some("application/x-www-form-urlencoded"));
+    /// # Ok(()) }
+    /// ```
+    pub fn body_form(
+        mut self,
+        form: &impl Serialize,
+    ) -> Result<Self, serde_urlencoded::ser::Error> {
+        *self.req.as_mut().unwrap().body_mut() =
+            serde_urlencoded::to_string(form)?.into_bytes().into();
+        Ok(self.set_mime(mime::APPLICATION_WWW_FORM_URLENCODED))
+    }
+
+    /// Pass a multipart body as the request body.
+    ///
+    /// # Mime
+    ///
+    /// The encoding is set to the type of the multipart body, such as `multipart/form-data`,
+    /// including its boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::multipart::{Multipart, Part};
+    ///
+    /// let form = Multipart::form_data().part(Part::text("chashu").name("name"));
+    /// let res = surf::post("https://httpbin.org/post")
+    ///     .body_multipart(form)
+    ///     .await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_multipart(mut self, multipart: Multipart) -> Self {
+        *self.req.as_mut().unwrap().body_mut() = multipart.to_bytes().into();
+        self.set_header("Content-Type", multipart.content_type())
+    }
+
+    /// Encode a value as the request body, using the codec registered for its type.
+    ///
+    /// The `Content-Type` is set to the codec's content type. Codecs are registered on the
+    /// client with `ScopedBuilder::codecs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no codec is registered for `T`, or if encoding fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use serde::{Deserialize, Serialize};
+    /// use surf::codec::{Codecs, Json};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     name: String,
+    /// }
+    ///
+    /// let client = surf::Client::new()
+    ///     .scoped()
+    ///     .codecs(Codecs::new().register::<Cat>(Json))
+    ///     .build();
+    /// let cat = Cat { name: "chashu".to_string() };
+    /// let res = client.post("https://httpbin.org/post").body_as(&cat)?.await?;
+    /// assert_eq!(res.status(), 200);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_as<T: 'static>(mut self, value: &T) -> Result<Self, Exception> {
+        let req = self.req.as_mut().unwrap();
+        let codecs = req.extensions().get::<Arc<Codecs>>().cloned();
+        let codec = codecs
+            .as_ref()
+            .and_then(|codecs| codecs.encoder::<T>())
+            .ok_or_else(codec::missing::<T>)?;
+        *req.body_mut() = codec.encode(value)?.into();
+        let content_type = codec.content_type().to_string();
+        Ok(self.set_header("Content-Type", content_type))
+    }
+
+    /// Submit the request and get the response body as bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let bytes = surf::get("https://httpbin.org/get").recv_bytes().await?;
+    /// assert!(bytes.len() > 0);
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_bytes(self) -> Result<Vec<u8>, Exception> {
+        let mut req = self.await?;
+        Ok(req.body_bytes().await?)
+    }
+
+    /// Submit the request and get the response body as a string.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let string = surf::get("https://httpbin.org/get").recv_string().await?;
+    /// assert!(string.len() > 0);
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_string(self) -> Result<String, Exception> {
+        let mut req = self.await?;
+        Ok(req.body_string().await?)
+    }
+
+    /// Submit the request and decode the response body from json into a struct.
     ///
     /// # Examples
     ///
@@ -524,6 +1430,72 @@ impl<C: HttpClient> Request<C> {
         Ok(req.body_json::<T>().await?)
     }
 
+    /// Submit the request and decode the response body as a protobuf message.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Clone, PartialEq, prost::Message)]
+    /// struct Cat {
+    ///     #[prost(string, tag = "1")]
+    ///     name: String,
+    /// }
+    ///
+    /// let cat: Cat = surf::get("https://example.com/cats/1").recv_protobuf().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "protobuf")]
+    pub async fn recv_protobuf<T: prost::Message + Default>(self) -> Result<T, Exception> {
+        let mut res = self.await?;
+        Ok(res.body_protobuf::<T>().await?)
+    }
+
+    /// Submit the request and decode the response body from MessagePack into a struct.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     name: String,
+    /// }
+    ///
+    /// let cat: Cat = surf::get("https://example.com/cats/1").recv_msgpack().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "msgpack")]
+    pub async fn recv_msgpack<T: serde::de::DeserializeOwned>(self) -> Result<T, Exception> {
+        let mut res = self.await?;
+        Ok(res.body_msgpack::<T>().await?)
+    }
+
+    /// Submit the request and decode the response body from XML into a struct.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     name: String,
+    /// }
+    ///
+    /// let cat: Cat = surf::get("https://example.com/cats/1.xml").recv_xml().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "xml")]
+    pub async fn recv_xml<T: serde::de::DeserializeOwned>(self) -> Result<T, Exception> {
+        let mut res = self.await?;
+        Ok(res.body_xml::<T>().await?)
+    }
+
     /// Submit the request and decode the response body from form encoding into a struct.
     ///
     /// # Errors
@@ -531,7 +1503,7 @@ impl<C: HttpClient> Request<C> {
     /// Any I/O error encountered while reading the body is immediately returned
     /// as an `Err`.
     ///
-    /// If the body cannot be interpreted as valid json for the target type `T`,
+    /// If the body cannot be interpreted as valid form encoding for the target type `T`,
     /// an `Err` is returned.
     ///
     /// # Examples
@@ -554,6 +1526,35 @@ impl<C: HttpClient> Request<C> {
         Ok(req.body_form::<T>().await?)
     }
 
+    /// Submit the request and decode the response body with the codec registered for `T`.
+    ///
+    /// See `Response::body_as` for how the codec is chosen.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::codec::{Codecs, Json};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Ip {
+    ///     origin: String,
+    /// }
+    ///
+    /// let client = surf::Client::new()
+    ///     .scoped()
+    ///     .codecs(Codecs::new().register::<Ip>(Json))
+    ///     .build();
+    /// let Ip { origin } = client.get("https://httpbin.org/ip").recv_as().await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn recv_as<T: 'static>(self) -> Result<T, Exception> {
+        let mut res = self.await?;
+        res.body_as::<T>().await
+    }
+
     /// Get a HTTP request
     pub fn request(&self) -> Option<&http_client::Request> {
         self.req.as_ref()
@@ -570,13 +1571,36 @@ impl<C: HttpClient> Future for Request<C> {
             let client = self.client.take().unwrap();
             let middleware = self.middleware.take().unwrap();
             let req = self.req.take().unwrap();
+            let codecs = req.extensions().get::<Arc<Codecs>>().cloned();
+            let range = req.extensions().get::<range::Requested>().copied();
+            let precondition = req.extensions().get::<precondition::Requested>().copied();
+            let normalize_text = req.extensions().get::<NormalizeText>().copied();
 
             self.fut = Some(Box::pin(async move {
-                let next = Next::new(&middleware, &|req, client| {
-                    Box::pin(async move { client.send(req).await.map_err(|e| e.into()) })
-                });
-
-                let res = next.run(req, client).await?;
+                // Without middleware the request can be sent directly, without boxing `send`.
+                let mut res = if middleware.is_empty() {
+                    send(req, client).await?
+                } else {
+                    let next = Next::new(&middleware, &|req, client| Box::pin(send(req, client)));
+                    next.run(req, client).await?
+                };
+                if let Some(range) = range {
+                    if range.ignored_by(res.status()) {
+                        return Err(RangeIgnored::new().into());
+                    }
+                }
+                if let Some(precondition) = precondition {
+                    if precondition.failed(res.status()) {
+                        let current = Response::new(res).validators();
+                        return Err(PreconditionFailed::new(current).into());
+                    }
+                }
+                if let Some(codecs) = codecs {
+                    res.extensions_mut().insert(codecs);
+                }
+                if let Some(normalize_text) = normalize_text {
+                    res.extensions_mut().insert(normalize_text);
+                }
                 Ok(Response::new(res))
             }));
         }
@@ -585,6 +1609,125 @@ impl<C: HttpClient> Future for Request<C> {
     }
 }
 
+/// Send a request through the backend once it has passed through all middleware, and run the
+/// response through the pipeline.
+///
+/// Idempotent requests failing because the server closed the connection they were sent over
+/// before responding, which happens when it closes an idle keep-alive connection just as it's
+/// reused, are sent once more over a new connection. Only requests the backend sent over a
+/// connection that may have been reused are, and only empty bodies and bodies set with
+/// `Request::replayable_body` are sent again. Requests that aren't sent again fail with an
+/// `UploadInterrupted` error if the connection broke while their body was sent. See the `reuse`
+/// module.
+async fn send<C: HttpClient>(
+    req: http_client::Request,
+    client: C,
+) -> Result<http_client::Response, Exception> {
+    // Isolated requests already use a new connection.
+    if !retry::idempotent(req.method()) || req.extensions().get::<Isolated>().is_some() {
+        return send_tracked(req, client).await;
+    }
+    let (parts, body) = req.into_parts();
+    let (body, replay) = Replay::prepare(body, &parts.extensions, BodyReplay::Never).await?;
+
+    let method = parts.method.clone();
+    let uri = parts.uri.clone();
+    let headers = parts.headers.clone();
+    let mut extensions = http::Extensions::new();
+    http_client::copy_extensions(&parts.extensions, &mut extensions);
+
+    let started = Instant::now();
+    let reused = Reused::default();
+    let upload = Upload::default();
+    let mut req = http::Request::from_parts(parts, upload.track(body));
+    req.extensions_mut().insert(reused.clone());
+    let err = match send_once(req, client.clone()).await {
+        Err(err) if reused.get() && reuse::closed_before_response(&err) => err,
+        Err(err) => return Err(upload.error(err)),
+        res => return res,
+    };
+    let body = match replay.body() {
+        Some(body) => body,
+        None => return Err(upload.error(err)),
+    };
+    log::debug!(
+        "connection closed before a response to {} {} arrived, retrying over a new connection: {}",
+        method,
+        uri,
+        err
+    );
+
+    let mut req = http::Request::new(body);
+    *req.method_mut() = method;
+    *req.uri_mut() = uri;
+    *req.headers_mut() = headers;
+    *req.extensions_mut() = extensions;
+    req.extensions_mut().insert(FreshConnection);
+    let mut res = send_tracked(req, client).await?;
+    let mut earlier = Attempts::default();
+    attempt::failed(&mut earlier, started, AttemptReason::Error(err.to_string()));
+    attempt::prepend(&mut res, earlier);
+    Ok(res)
+}
+
+/// Send a request through the backend once, failing with an `UploadInterrupted` error if the
+/// connection broke while its body was sent.
+async fn send_tracked<C: HttpClient>(
+    req: http_client::Request,
+    client: C,
+) -> Result<http_client::Response, Exception> {
+    let upload = Upload::default();
+    let (parts, body) = req.into_parts();
+    let req = http::Request::from_parts(parts, upload.track(body));
+    send_once(req, client)
+        .await
+        .map_err(|err| upload.error(err))
+}
+
+/// Send a request through the backend, see `send`.
+async fn send_once<C: HttpClient>(
+    req: http_client::Request,
+    client: C,
+) -> Result<http_client::Response, Exception> {
+    let size = TransferSize::new();
+    let method = req.method().clone();
+    let pipeline = req.extensions().get::<Arc<Pipeline>>().cloned();
+    let timeout = req.extensions().get::<Timeout>().map(|timeout| timeout.0);
+    let strict = req.extensions().get::<LenientFraming>().is_none();
+    let drain = req.extensions().get::<DrainOnDrop>().copied();
+    let progress = req.extensions().get::<Reporter>().cloned();
+    if strict {
+        framing::check_request(&req)?;
+    }
+    let req = match &progress {
+        Some(progress) => progress.track_request(req),
+        None => req,
+    };
+    let started = Instant::now();
+    let fut = client.send(size.track_request(req));
+    let mut res = match timeout {
+        Some(timeout) => timeout::within(timeout, fut.err_into()).await?,
+        None => fut.await?,
+    };
+    if strict {
+        framing::check_response(&res)?;
+    }
+    attempt::record(&mut res, started);
+    http_client::strip_body(&method, &mut res);
+    if let Some(drain) = drain {
+        res.extensions_mut().insert(drain);
+    }
+    let res = size.track_response(res);
+    let res = match pipeline {
+        Some(pipeline) => pipeline.run(res)?,
+        None => Pipeline::default().run(res)?,
+    };
+    match progress {
+        Some(progress) => Ok(progress.track_response(res)),
+        None => Ok(res),
+    }
+}
+
 #[cfg(feature = "native-client")]
 impl<R: AsyncRead + Unpin + Send + 'static> TryFrom<http::Request<Box<R>>>
     for Request<NativeClient>
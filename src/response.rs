@@ -1,4 +1,8 @@
+use bytes::Bytes;
+use futures::io::BufReader;
 use futures::prelude::*;
+use futures::stream::{self, BoxStream};
+use futures::task;
 use http::status::StatusCode;
 use http::version::Version;
 use mime::Mime;
@@ -7,12 +11,22 @@ use serde::de::DeserializeOwned;
 use std::fmt;
 use std::io::{self, Error};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use crate::headers::Headers;
+use crate::attempt::Attempts;
+use crate::codec::{self, Codecs};
+use crate::headers::{ETag, Headers, HttpDate};
 use crate::http_client;
+use crate::precondition::Validators;
+use crate::range::ContentRange;
+use crate::status::{StatusClass, StatusMatch};
+use crate::transfer::TransferSize;
 use crate::Exception;
 
+/// The maximum size of the chunks yielded by `Response::body_stream`.
+const CHUNK_SIZE: usize = 16 * 1024;
+
 /// An HTTP response, returned by `Request`.
 pub struct Response {
     response: http_client::Response,
@@ -39,6 +53,80 @@ impl Response {
         self.response.status()
     }
 
+    /// Get the class of the response status.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::StatusClass;
+    ///
+    /// let res = surf::get("https://httpbin.org/get").await?;
+    /// assert_eq!(res.status_class(), StatusClass::Success);
+    /// # Ok(()) }
+    /// ```
+    pub fn status_class(&self) -> StatusClass {
+        StatusClass::of(self.status())
+    }
+
+    /// Start handling the response by its status, with a handler for responses with `status`.
+    ///
+    /// See [`StatusMatch`] for how handlers are chosen.
+    ///
+    /// [`StatusMatch`]: struct.StatusMatch.html
+    pub fn on_status<T, F, Fut>(self, status: impl PartialEq<StatusCode>, f: F) -> StatusMatch<T>
+    where
+        T: 'static,
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        StatusMatch::new(self).on_status(status, f)
+    }
+
+    /// Start handling the response by its status, with a handler for responses with a status of
+    /// `class`.
+    ///
+    /// See [`StatusMatch`] for how handlers are chosen.
+    ///
+    /// [`StatusMatch`]: struct.StatusMatch.html
+    pub fn on_class<T, F, Fut>(self, class: StatusClass, f: F) -> StatusMatch<T>
+    where
+        T: 'static,
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        StatusMatch::new(self).on_class(class, f)
+    }
+
+    /// Get the reason phrase sent by the server alongside the status code.
+    ///
+    /// Unlike `status().canonical_reason()`, this is the exact text from the status line, which
+    /// some APIs use to carry error details. Status codes without a registered meaning, such as
+    /// `499` or `599`, are supported as well.
+    ///
+    /// Returns `None` if the server sent no reason phrase, or if the backend doesn't expose it.
+    /// The curl backend currently never does.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let res = surf::get("https://httpbin.org/status/499").await?;
+    /// assert_eq!(res.status(), 499);
+    /// if let Some(reason) = res.reason_phrase() {
+    ///     println!("server said: {}", reason);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn reason_phrase(&self) -> Option<&str> {
+        self.response
+            .extensions()
+            .get::<http_client::ReasonPhrase>()
+            .map(|reason| reason.0.as_str())
+    }
+
     /// Get the HTTP protocol version.
     ///
     /// # Examples
@@ -72,6 +160,28 @@ impl Response {
         headers.get(key).map(|h| h.to_str().unwrap())
     }
 
+    /// Get a response extension.
+    ///
+    /// Extensions are typed values that travel with the response through the middleware stack,
+    /// letting middleware pass data to callers.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use surf::pipeline::OriginalHeaders;
+    ///
+    /// let res = surf::get("https://httpbin.org/gzip").await?;
+    /// if let Some(original) = res.ext::<OriginalHeaders>() {
+    ///     println!("decoded from {:?}", original.content_encoding());
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn ext<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.response.extensions().get()
+    }
+
     /// Get all headers.
     ///
     /// # Examples
@@ -114,6 +224,228 @@ impl Response {
         Some(header.parse().unwrap())
     }
 
+    /// Get the `Content-Range` of a `206 Partial Content` or `416 Range Not Satisfiable`
+    /// response.
+    ///
+    /// Returns `None` if the header is missing, isn't in bytes, or can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://httpbin.org/range/1024").range(0..512).await?;
+    /// let range = res.content_range().unwrap();
+    /// assert_eq!(range.range(), Some(0..512));
+    /// assert_eq!(range.complete_length(), Some(1024));
+    /// # Ok(()) }
+    /// ```
+    pub fn content_range(&self) -> Option<ContentRange> {
+        let value = self.response.headers().get(http::header::CONTENT_RANGE)?;
+        ContentRange::parse(value.to_str().ok()?)
+    }
+
+    /// Get the entity tag of the response, from the `ETag` header.
+    ///
+    /// Send it with `Request::if_none_match` to only get the resource again if it changed.
+    /// Returns `None` if the header is missing or can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://httpbin.org/etag/v1").await?;
+    /// let etag = res.etag().unwrap();
+    ///
+    /// let res = surf::get("https://httpbin.org/etag/v1").if_none_match(&etag).await?;
+    /// assert_eq!(res.status(), 304);
+    /// # Ok(()) }
+    /// ```
+    pub fn etag(&self) -> Option<ETag> {
+        let value = self.response.headers().get(http::header::ETAG)?;
+        value.to_str().ok()?.parse().ok()
+    }
+
+    /// Get when the resource was last modified, from the `Last-Modified` header.
+    ///
+    /// Send it with `Request::if_modified_since` to only get the resource again if it changed.
+    /// Returns `None` if the header is missing or can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://httpbin.org/cache").await?;
+    /// if let Some(last_modified) = res.last_modified() {
+    ///     let res = surf::get("https://httpbin.org/cache")
+    ///         .if_modified_since(last_modified)
+    ///         .await?;
+    ///     assert_eq!(res.status(), 304);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn last_modified(&self) -> Option<HttpDate> {
+        let value = self.response.headers().get(http::header::LAST_MODIFIED)?;
+        value.to_str().ok()?.parse().ok()
+    }
+
+    /// Get the validators of the version of the resource in the response, from its `ETag` and
+    /// `Last-Modified` headers.
+    ///
+    /// Send them with `Request::precondition` to only change the resource if it's still at this
+    /// version.
+    pub fn validators(&self) -> Validators {
+        Validators::new(self.etag(), self.last_modified())
+    }
+
+    /// Get the languages of the intended audience of the response, from the `Content-Language`
+    /// header.
+    ///
+    /// Returns an empty list if the header is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://example.com/greeting")
+    ///     .set_header("Accept-Language", "de, en;q=0.5")
+    ///     .await?;
+    /// if res.content_language().iter().any(|tag| tag.eq_ignore_ascii_case("de")) {
+    ///     println!("Guten Tag!");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn content_language(&self) -> Vec<String> {
+        self.response
+            .headers()
+            .get_all(http::header::CONTENT_LANGUAGE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// Returns `true` if the response carries a message body.
+    ///
+    /// Responses to `HEAD` requests, `1xx`, `204 No Content` and `304 Not Modified` responses, and
+    /// responses with `Content-Length: 0` never have a body. Their body is never read from the
+    /// connection, and reading it produces an empty buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// let res = surf::head("https://httpbin.org/get").await?;
+    /// assert!(!res.has_body());
+    /// # Ok(()) }
+    /// ```
+    pub fn has_body(&self) -> bool {
+        self.response
+            .extensions()
+            .get::<http_client::NoBody>()
+            .is_none()
+    }
+
+    /// Read and discard the rest of the body.
+    ///
+    /// Dropping a response before its body has been read closes the connection. Consuming the
+    /// body first lets the connection be reused for another request, which is worth it when
+    /// only the status or headers are needed, and the body is small. For large bodies,
+    /// reconnecting is cheaper. See also `Request::drain_on_drop`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let mut res = surf::get("https://httpbin.org/get").await?;
+    /// if res.status().is_success() {
+    ///     res.consume().await?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub async fn consume(&mut self) -> io::Result<()> {
+        let mut buf = [0; 8 * 1024];
+        while self.response.body_mut().read(&mut buf).await? > 0 {}
+        Ok(())
+    }
+
+    /// Turn a response with a `4xx` or `5xx` status into an error.
+    ///
+    /// The error has the kind `ErrorKind::Status`. The response, including its body, is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let mut res = surf::get("https://httpbin.org/status/404").await?.error_for_status()?;
+    /// println!("{}", res.body_string().await?);
+    /// # Ok(()) }
+    /// ```
+    pub fn error_for_status(self) -> Result<Self, crate::Error> {
+        let status = self.status();
+        if status.is_client_error() || status.is_server_error() {
+            let err = crate::error::StatusError(status);
+            Err(crate::Error::new(crate::ErrorKind::Status(status), err))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Get the number of bytes transferred for this request and response.
+    ///
+    /// See [`TransferSize`] for what is counted.
+    ///
+    /// [`TransferSize`]: struct.TransferSize.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let mut res = surf::get("https://httpbin.org/get").await?;
+    /// res.body_bytes().await?;
+    /// println!("received {} bytes", res.transfer_size().received());
+    /// # Ok(()) }
+    /// ```
+    pub fn transfer_size(&self) -> TransferSize {
+        self.response
+            .extensions()
+            .get::<TransferSize>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Get the attempts made to complete the request, including retries and followed redirects.
+    ///
+    /// See [`Attempts`] for more information.
+    ///
+    /// [`Attempts`]: struct.Attempts.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// let res = surf::get("https://httpbin.org/get").await?;
+    /// println!("{} attempt(s) in {:?}", res.attempts().len(), res.attempts().elapsed());
+    /// # Ok(()) }
+    /// ```
+    pub fn attempts(&self) -> Attempts {
+        self.response
+            .extensions()
+            .get::<Attempts>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Reads the entire request body into a byte buffer.
     ///
     /// This method can be called after the body has already been read, but will
@@ -139,10 +471,98 @@ impl Response {
         Ok(buf)
     }
 
+    /// Take the body as a stream of chunks, to process it as it arrives rather than buffering it.
+    ///
+    /// The stream ends after the whole body has been read, or after yielding an error. The
+    /// response is left with an empty body.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use futures::prelude::*;
+    ///
+    /// let mut res = surf::get("https://example.com/debian.iso").await?;
+    /// let mut chunks = res.body_stream();
+    /// let mut len = 0;
+    /// while let Some(chunk) = chunks.try_next().await? {
+    ///     len += chunk.len();
+    /// }
+    /// println!("received {} bytes", len);
+    /// # Ok(()) }
+    /// ```
+    pub fn body_stream(&mut self) -> BoxStream<'static, Result<Bytes, Exception>> {
+        let body = std::mem::replace(self.response.body_mut(), http_client::Body::empty());
+        stream::unfold(Some(body), |body| async move {
+            let mut body = body?;
+            let mut buf = vec![0; CHUNK_SIZE];
+            match body.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(len) => {
+                    buf.truncate(len);
+                    Some((Ok(Bytes::from(buf)), Some(body)))
+                }
+                Err(err) => Some((Err(err.into()), None)),
+            }
+        })
+        .boxed()
+    }
+
+    /// Take the body as a stream of lines, for plain text streaming endpoints such as log tails.
+    ///
+    /// Lines end with `\n` or `\r\n`, which isn't included. The last line doesn't need to end
+    /// with a newline. Lines are read as they arrive, and never buffered beyond `max_len` bytes:
+    /// a longer line yields an error instead, so a misbehaving server can't exhaust memory.
+    ///
+    /// The stream ends after the whole body has been read, or after yielding an error. The
+    /// response is left with an empty body.
+    ///
+    /// # Errors
+    ///
+    /// An `io::ErrorKind::InvalidData` error is yielded for a line that is longer than `max_len`
+    /// bytes or isn't valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), surf::Exception> {
+    /// use futures::prelude::*;
+    ///
+    /// let mut res = surf::get("https://example.com/logs?follow=1").await?;
+    /// let mut lines = res.body_lines(64 * 1024);
+    /// while let Some(line) = lines.try_next().await? {
+    ///     println!("{}", line);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn body_lines(&mut self, max_len: usize) -> BoxStream<'static, Result<String, Exception>> {
+        let body = std::mem::replace(self.response.body_mut(), http_client::Body::empty());
+        // Only the first line can start with a byte order mark.
+        let strip_bom = self.ext::<http_client::NormalizeText>().is_some();
+        let state = Some((BufReader::new(body), strip_bom));
+        stream::unfold(state, move |state| async move {
+            let (mut reader, strip_bom) = state?;
+            match read_line(&mut reader, max_len).await {
+                Ok(Some(mut line)) => {
+                    if strip_bom && line.starts_with('\u{feff}') {
+                        line.remove(0);
+                    }
+                    Some((Ok(line), Some((reader, false))))
+                }
+                Ok(None) => None,
+                Err(err) => Some((Err(err.into()), None)),
+            }
+        })
+        .boxed()
+    }
+
     /// Reads the entire request body into a string.
     ///
     /// This method can be called after the body has already been read, but will
-    /// produce an empty buffer.
+    /// produce an empty buffer. The text is normalized if the request was sent with
+    /// `Request::normalize_text`.
     ///
     /// # Errors
     ///
@@ -162,7 +582,12 @@ impl Response {
     /// ```
     pub async fn body_string(&mut self) -> Result<String, Exception> {
         let bytes = self.body_bytes().await?;
-        Ok(String::from_utf8(bytes).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?)
+        let string =
+            String::from_utf8(bytes).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        if self.ext::<http_client::NormalizeText>().is_some() {
+            return Ok(normalize_text(&string));
+        }
+        Ok(string)
     }
 
     /// Reads and deserialized the entire request body from json.
@@ -195,15 +620,16 @@ impl Response {
         Ok(serde_json::from_slice(&body_bytes).map_err(|_| std::io::ErrorKind::InvalidData)?)
     }
 
-    /// Reads and deserialized the entire request body from form encoding.
+    /// Reads and deserialized the entire response body from form encoding
+    /// (`application/x-www-form-urlencoded`).
     ///
     /// # Errors
     ///
     /// Any I/O error encountered while reading the body is immediately returned
     /// as an `Err`.
     ///
-    /// If the body cannot be interpreted as valid json for the target type `T`,
-    /// an `Err` is returned.
+    /// If the body cannot be interpreted as valid form encoding for the target type `T`,
+    /// an `Err` describing the problem is returned.
     ///
     /// # Examples
     ///
@@ -221,9 +647,207 @@ impl Response {
     /// # Ok(()) }
     /// ```
     pub async fn body_form<T: serde::de::DeserializeOwned>(&mut self) -> Result<T, Exception> {
-        use std::io::ErrorKind;
         let string = self.body_string().await?;
-        Ok(serde_urlencoded::from_str(&string).map_err(|_| Error::from(ErrorKind::InvalidData))?)
+        Ok(serde_urlencoded::from_str(&string)?)
+    }
+
+    /// Reads and decodes the entire response body as a protobuf message.
+    ///
+    /// # Errors
+    ///
+    /// Any I/O error encountered while reading the body is immediately returned as an `Err`.
+    ///
+    /// If the body isn't a valid encoding of `T`, an `Err` with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Clone, PartialEq, prost::Message)]
+    /// struct Cat {
+    ///     #[prost(string, tag = "1")]
+    ///     name: String,
+    /// }
+    ///
+    /// let mut res = surf::get("https://example.com/cats/1").await?;
+    /// let cat: Cat = res.body_protobuf().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "protobuf")]
+    pub async fn body_protobuf<T: prost::Message + Default>(&mut self) -> io::Result<T> {
+        let body = self.body_bytes().await?;
+        T::decode(&body[..]).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads and deserializes the entire response body from MessagePack.
+    ///
+    /// # Errors
+    ///
+    /// Any I/O error encountered while reading the body is immediately returned as an `Err`.
+    ///
+    /// If the body isn't valid MessagePack for the target type `T`, an `Err` with
+    /// `ErrorKind::InvalidData` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     name: String,
+    /// }
+    ///
+    /// let mut res = surf::get("https://example.com/cats/1").await?;
+    /// let cat: Cat = res.body_msgpack().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "msgpack")]
+    pub async fn body_msgpack<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        let body = self.body_bytes().await?;
+        rmp_serde::from_slice(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads and deserializes the entire response body from XML.
+    ///
+    /// The name of the root element isn't checked. Child elements and attributes map to fields,
+    /// with the names of attributes starting with `@`, following the conventions of `quick-xml`.
+    ///
+    /// # Errors
+    ///
+    /// Any I/O error encountered while reading the body is immediately returned as an `Err`.
+    ///
+    /// If the body isn't valid XML for the target type `T`, an `Err` with
+    /// `ErrorKind::InvalidData` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Cat {
+    ///     #[serde(rename = "@id")]
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// // Receives `<Cat id="1"><name>chashu</name></Cat>`.
+    /// let mut res = surf::get("https://example.com/cats/1.xml").await?;
+    /// let cat: Cat = res.body_xml().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "xml")]
+    pub async fn body_xml<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        let body = self.body_bytes().await?;
+        quick_xml::de::from_reader(&body[..])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Decode the body with the codec registered for `T`.
+    ///
+    /// The codec whose content type matches the response's `Content-Type` is used, falling back
+    /// to the first codec registered for `T`. Codecs are registered on the client with
+    /// `ScopedBuilder::codecs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no codec is registered for `T`, if reading the body fails, or if
+    /// decoding fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[runtime::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    /// use surf::codec::{Codecs, Json};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Ip {
+    ///     origin: String,
+    /// }
+    ///
+    /// let client = surf::Client::new()
+    ///     .scoped()
+    ///     .codecs(Codecs::new().register::<Ip>(Json))
+    ///     .build();
+    /// let mut res = client.get("https://httpbin.org/ip").await?;
+    /// let Ip { origin } = res.body_as().await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn body_as<T: 'static>(&mut self) -> Result<T, Exception> {
+        let codecs = self.response.extensions().get::<Arc<Codecs>>().cloned();
+        let content_type = self.header("Content-Type").map(|mime| mime.to_string());
+        let codec = codecs
+            .as_ref()
+            .and_then(|codecs| codecs.decoder::<T>(content_type.as_deref()))
+            .ok_or_else(codec::missing::<T>)?;
+        let body = self.body_bytes().await?;
+        codec.decode(&body)
+    }
+}
+
+/// Read a line of at most `max_len` bytes, without its line ending.
+///
+/// Returns `None` at the end of the body.
+/// Remove a leading byte order mark, and replace `\r\n` line endings with `\n`.
+fn normalize_text(string: &str) -> String {
+    let string = string.strip_prefix('\u{feff}').unwrap_or(string);
+    string.replace("\r\n", "\n")
+}
+
+/// Read a line of at most `max_len` bytes, without the line ending.
+pub(crate) async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_len: usize,
+) -> io::Result<Option<String>> {
+    let too_long = || io::Error::new(io::ErrorKind::InvalidData, "line too long");
+    // Leave room for the `\r\n` line ending.
+    let limit = (max_len as u64).saturating_add(2);
+    let mut line = Vec::new();
+    let read = reader.take(limit).read_until(b'\n', &mut line).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    } else if read as u64 == limit {
+        return Err(too_long());
+    }
+    if line.len() > max_len {
+        return Err(too_long());
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "line isn't valid UTF-8"))
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        let limit = match self.response.extensions().get::<http_client::DrainOnDrop>() {
+            Some(drain) if self.has_body() => drain.0,
+            _ => return,
+        };
+        // Only read what has already arrived, without waiting for more.
+        let mut cx = Context::from_waker(task::noop_waker_ref());
+        let mut buf = [0; 8 * 1024];
+        let mut read = 0;
+        while read <= limit {
+            // Read at most one byte past the limit, to tell whether the body ended.
+            let len = (limit - read).min(buf.len() as u64 - 1) as usize + 1;
+            match Pin::new(self.response.body_mut()).poll_read(&mut cx, &mut buf[..len]) {
+                Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) | Poll::Pending => break,
+                Poll::Ready(Ok(len)) => read += len as u64,
+            }
+        }
     }
 }
 
@@ -0,0 +1,180 @@
+//! Recovering from requests sent over a connection the server was closing.
+//!
+//! Servers close keep-alive connections that have been idle for a while. When a request is sent
+//! over a pooled connection just as the server closes it, the request fails with a reset
+//! connection, or without a response at all, even though the server never handled it. Idempotent
+//! requests that fail like that are sent once more, over a new connection, see `send` in the
+//! `request` module.
+//!
+//! Only requests the backend flagged with `Reused` are sent again, since a new connection failing
+//! like that isn't a reuse race. The curl backend can't tell which connection a request used, so
+//! it flags requests to hosts it has kept a connection open to. The hyper backend already retries
+//! requests failing on a reused connection itself, and doesn't flag any.
+//!
+//! Servers also close the connection when they reject a request before reading all of its body,
+//! such as with `413 Payload Too Large`. The response is returned when the backend reads it
+//! before it notices the broken connection, but it's lost otherwise, and the request fails with
+//! an `UploadInterrupted` error wrapping the error of the broken connection. Such requests
+//! aren't sent again to get the response, since the server may have acted on them. Sending an
+//! `Expect: 100-continue` header, which curl does by itself for large bodies, lets the server
+//! respond before the body is sent instead.
+//!
+//! An upload breaking on a reused connection looks the same as a request sent over a connection
+//! the server closed while it was idle: both fail with a broken pipe or a reset connection. So
+//! idempotent requests the backend flagged with `Reused` are still sent once more when their
+//! upload breaks, if their body can be sent again, which is safe since they're idempotent. Only
+//! requests that aren't sent again fail with an `UploadInterrupted` error.
+
+use crate::http_client::Body;
+use crate::Exception;
+
+use futures::io::AsyncRead;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Check whether a request failed because the connection it was sent over was closed before a
+/// response arrived.
+pub(crate) fn closed_before_response(err: &Exception) -> bool {
+    closed(&**err)
+}
+
+fn closed(err: &(dyn Error + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return match err.kind() {
+            io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => true,
+            // Backends wrap errors of their own in an `io::Error`.
+            _ => match err.get_ref() {
+                Some(inner) => closed(inner),
+                None => false,
+            },
+        };
+    }
+    #[cfg(all(feature = "curl-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<isahc::Error>() {
+            return match err {
+                isahc::Error::NoResponse => true,
+                isahc::Error::Io(err) => closed(err),
+                // isahc only keeps the description of other curl errors, so compare it with the
+                // descriptions of `CURLE_SEND_ERROR` and `CURLE_RECV_ERROR`.
+                isahc::Error::Curl(description) => [55, 56]
+                    .iter()
+                    .any(|&code| curl::Error::new(code).description() == description),
+                _ => false,
+            };
+        }
+    }
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    {
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            if err.is_canceled() || err.is_closed() {
+                return true;
+            }
+            return match err.source() {
+                Some(source) => closed(source),
+                None => false,
+            };
+        }
+    }
+    false
+}
+
+/// How far a request body was sent.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Upload(Arc<AtomicU8>);
+
+const STARTED: u8 = 1;
+const FINISHED: u8 = 2;
+
+impl Upload {
+    /// Track how far a body is read by the backend.
+    pub(crate) fn track(&self, body: Body) -> Body {
+        Body::from_reader(Tracked {
+            body,
+            upload: self.clone(),
+        })
+    }
+
+    /// Turn the error of a request into an `UploadInterrupted` error, if the connection was
+    /// closed after part of the body was sent, but not all of it.
+    pub(crate) fn error(&self, err: Exception) -> Exception {
+        if self.0.load(Ordering::SeqCst) == STARTED && closed_before_response(&err) {
+            UploadInterrupted { source: err }.into()
+        } else {
+            err
+        }
+    }
+}
+
+struct Tracked {
+    body: Body,
+    upload: Upload,
+}
+
+impl AsyncRead for Tracked {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        let state = match read {
+            0 if !buf.is_empty() => FINISHED,
+            0 => return Poll::Ready(Ok(0)),
+            _ => STARTED,
+        };
+        self.upload.0.fetch_max(state, Ordering::SeqCst);
+        Poll::Ready(Ok(read))
+    }
+}
+
+/// The error returned when the connection broke while the request body was being sent.
+///
+/// Servers close the connection like that when they reject a request before reading all of its
+/// body, for example because it's too large. The response explaining why was lost, the source of
+/// this error is the error of the broken connection. Send an `Expect: 100-continue` header to let
+/// the server reject the request before the body is sent, and get its response.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::UploadInterrupted;
+///
+/// let file = futures::io::AllowStdIo::new(std::fs::File::open("backup.tar")?);
+/// match surf::post("https://example.com/upload").body(file).await {
+///     Err(err) if err.is::<UploadInterrupted>() => println!("upload rejected: {}", err),
+///     res => println!("{}", res?.status()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct UploadInterrupted {
+    source: Exception,
+}
+
+impl fmt::Display for UploadInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "connection closed while sending the request body, the server may have rejected the request: {}",
+            self.source
+        )
+    }
+}
+
+impl Error for UploadInterrupted {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
@@ -0,0 +1,156 @@
+//! Branching on response statuses.
+
+use futures::future::BoxFuture;
+use http::StatusCode;
+
+use std::fmt;
+use std::future::Future;
+
+use crate::Response;
+
+/// The class of a response status, given by its first digit.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::StatusClass;
+///
+/// let res = surf::get("https://httpbin.org/status/503").await?;
+/// match res.status_class() {
+///     StatusClass::Success => println!("done"),
+///     StatusClass::ServerError => println!("try again later"),
+///     class => println!("unexpected {:?} response", class),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    /// `1xx`: the request was received, and is being processed.
+    Informational,
+    /// `2xx`: the request was successful.
+    Success,
+    /// `3xx`: further action is needed to complete the request.
+    Redirection,
+    /// `4xx`: the request was invalid.
+    ClientError,
+    /// `5xx`: the server failed to handle a valid request, and nonstandard statuses from `600`
+    /// up.
+    ServerError,
+}
+
+impl StatusClass {
+    /// Get the class of a status.
+    pub fn of(status: StatusCode) -> Self {
+        match status.as_u16() {
+            100..=199 => StatusClass::Informational,
+            200..=299 => StatusClass::Success,
+            300..=399 => StatusClass::Redirection,
+            400..=499 => StatusClass::ClientError,
+            _ => StatusClass::ServerError,
+        }
+    }
+}
+
+impl From<StatusCode> for StatusClass {
+    fn from(status: StatusCode) -> Self {
+        Self::of(status)
+    }
+}
+
+/// Handlers for the statuses of a response, created by `Response::on_status` or
+/// `Response::on_class`.
+///
+/// The first handler matching the status of the response is run, and `StatusMatch::otherwise`
+/// handles responses that no handler matched, so every response is handled.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use surf::StatusClass;
+///
+/// let res = surf::get("https://httpbin.org/anything/users/7").await?;
+/// let user: Option<String> = res
+///     .on_status(404, |_| async { Ok::<_, surf::Exception>(None) })
+///     .on_class(StatusClass::Success, |mut res| async move {
+///         Ok(Some(res.body_string().await?))
+///     })
+///     .otherwise(|res| async move { Err(format!("unexpected status {}", res.status()).into()) })
+///     .await?;
+/// # Ok(()) }
+/// ```
+pub struct StatusMatch<T> {
+    state: State<T>,
+}
+
+enum State<T> {
+    Unmatched(Response),
+    Matched(BoxFuture<'static, T>),
+}
+
+impl<T: 'static> StatusMatch<T> {
+    pub(crate) fn new(res: Response) -> Self {
+        Self {
+            state: State::Unmatched(res),
+        }
+    }
+
+    /// Handle responses with a status, such as `404` or `StatusCode::NOT_FOUND`.
+    pub fn on_status<F, Fut>(self, status: impl PartialEq<StatusCode>, f: F) -> Self
+    where
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        self.on(|res| status == res.status(), f)
+    }
+
+    /// Handle responses with a status of a class.
+    pub fn on_class<F, Fut>(self, class: StatusClass, f: F) -> Self
+    where
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        self.on(|res| res.status_class() == class, f)
+    }
+
+    fn on<F, Fut>(self, matches: impl FnOnce(&Response) -> bool, f: F) -> Self
+    where
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        match self.state {
+            State::Unmatched(res) if matches(&res) => Self {
+                state: State::Matched(Box::pin(f(res))),
+            },
+            state => Self { state },
+        }
+    }
+
+    /// Handle responses that no other handler matched, and get the output of the handler that
+    /// was run.
+    pub async fn otherwise<F, Fut>(self, f: F) -> T
+    where
+        F: FnOnce(Response) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        match self.state {
+            State::Unmatched(res) => f(res).await,
+            State::Matched(fut) => fut.await,
+        }
+    }
+}
+
+impl<T> fmt::Debug for StatusMatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match &self.state {
+            State::Unmatched(res) => format!("unmatched {}", res.status()),
+            State::Matched(_) => "matched".to_string(),
+        };
+        f.debug_struct("StatusMatch")
+            .field("state", &state)
+            .finish()
+    }
+}
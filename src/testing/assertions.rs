@@ -0,0 +1,166 @@
+//! Assertions on responses.
+//!
+//! These macros check a response and panic with a descriptive message when the check fails,
+//! like `assert_eq!`. The macros checking the body read it, so they have to be used in an async
+//! context, and the response has to be mutable.
+//!
+//! - `assert_status!(res, 200)`: check the status, given as a number or a `StatusCode`.
+//! - `assert_header!(res, "etag")`: check that a header is present.
+//! - `assert_header!(res, "content-type", "application/json")`: check the value of a header.
+//! - `assert_json_body!(res, json!({ ... }))`: check that the body is the given JSON value.
+//! - `assert_body_snapshot!(res, "tests/snapshots/name.txt")`: check the body against a snapshot
+//!   file, relative to the crate root.
+//!
+//! A snapshot file that doesn't exist is created from the body, so the first run of a test
+//! records the snapshot. Set the `SURF_UPDATE_SNAPSHOTS` environment variable to record all
+//! snapshots again after an intended change.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use serde_json::json;
+//! use surf::{assert_header, assert_json_body, assert_status};
+//!
+//! let mut res = surf::get("https://httpbin.org/json").await?;
+//! assert_status!(res, 200);
+//! assert_header!(res, "content-type", "application/json");
+//! assert_json_body!(res, json!({
+//!     "slideshow": {
+//!         "author": "Yours Truly",
+//!         "date": "date of publication",
+//!         "slides": [],
+//!         "title": "Sample Slide Show",
+//!     }
+//! }));
+//! # Ok(()) }
+//! ```
+
+use crate::Response;
+
+use serde_json::Value;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[doc(inline)]
+pub use crate::{assert_body_snapshot, assert_header, assert_json_body, assert_status};
+
+/// Check the status of a response.
+#[macro_export]
+macro_rules! assert_status {
+    ($res:expr, $status:expr) => {{
+        let actual = $res.status();
+        let expected = $status;
+        assert!(
+            actual == expected,
+            "unexpected response status: expected {}, got {}",
+            expected,
+            actual
+        );
+    }};
+}
+
+/// Check that a response has a header, optionally with a given value.
+#[macro_export]
+macro_rules! assert_header {
+    ($res:expr, $name:expr) => {{
+        assert!(
+            $res.header($name).is_some(),
+            "missing response header {}",
+            $name
+        );
+    }};
+    ($res:expr, $name:expr, $value:expr) => {{
+        match $res.header($name) {
+            Some(actual) => assert!(
+                actual == $value,
+                "unexpected value of response header {}: expected {:?}, got {:?}",
+                $name,
+                $value,
+                actual
+            ),
+            None => panic!("missing response header {}", $name),
+        }
+    }};
+}
+
+/// Check that the body of a response is a JSON value.
+#[macro_export]
+macro_rules! assert_json_body {
+    ($res:expr, $expected:expr) => {{
+        let actual = $crate::testing::assertions::json_body(&mut $res).await;
+        let expected = $expected;
+        assert!(
+            actual == expected,
+            "unexpected response body:\nexpected: {}\n     got: {}",
+            expected,
+            actual
+        );
+    }};
+}
+
+/// Check the body of a response against a snapshot file, relative to the crate root.
+#[macro_export]
+macro_rules! assert_body_snapshot {
+    ($res:expr, $path:expr) => {{
+        let path = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path);
+        let actual = $crate::testing::assertions::string_body(&mut $res).await;
+        if let Err(msg) = $crate::testing::assertions::check_snapshot(&path, &actual) {
+            panic!("{}", msg);
+        }
+    }};
+}
+
+/// Read the body of a response as JSON, panicking if that fails.
+#[doc(hidden)]
+pub async fn json_body(res: &mut Response) -> Value {
+    match res.body_json().await {
+        Ok(value) => value,
+        Err(e) => panic!("can't read the response body as JSON: {}", e),
+    }
+}
+
+/// Read the body of a response as a string, panicking if that fails.
+#[doc(hidden)]
+pub async fn string_body(res: &mut Response) -> String {
+    match res.body_string().await {
+        Ok(body) => body,
+        Err(e) => panic!("can't read the response body as a string: {}", e),
+    }
+}
+
+/// Compare a body against a snapshot file, recording the snapshot if needed.
+#[doc(hidden)]
+pub fn check_snapshot(path: &Path, actual: &str) -> Result<(), String> {
+    let update = env::var_os("SURF_UPDATE_SNAPSHOTS").is_some();
+    if update || !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+        }
+        return fs::write(path, actual)
+            .map_err(|e| format!("can't write snapshot {}: {}", path.display(), e));
+    }
+
+    let expected = fs::read_to_string(path)
+        .map_err(|e| format!("can't read snapshot {}: {}", path.display(), e))?;
+    if expected == actual {
+        return Ok(());
+    }
+    let line = expected
+        .lines()
+        .zip(actual.lines())
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+    Err(format!(
+        "response body doesn't match snapshot {} from line {}:\nexpected: {:?}\n     got: {:?}\n\
+         set SURF_UPDATE_SNAPSHOTS to record the snapshot again",
+        path.display(),
+        line + 1,
+        expected.lines().nth(line).unwrap_or(""),
+        actual.lines().nth(line).unwrap_or(""),
+    ))
+}
@@ -0,0 +1,295 @@
+//! A mock HTTP backend.
+//!
+//! [`MockClient`] answers requests with canned responses instead of sending them, so code using
+//! surf can be tested without a network. Register an [`Expectation`] for every request the code
+//! under test should send, matching its method and path, and optionally its headers and body.
+//! Each request gets the response of the first expectation that matches it, in the order they
+//! were registered. Requests that no expectation matches fail with an `io::Error`.
+//!
+//! At the end of a test, `MockClient::verify` checks that every request was matched, and that
+//! every expectation was met.
+//!
+//! [`MockClient`]: struct.MockClient.html
+//! [`Expectation`]: struct.Expectation.html
+//!
+//! # Examples
+//!
+//! ```
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use http::Method;
+//! use surf::testing::mock::{Expectation, MockClient, MockResponse};
+//!
+//! let mock = MockClient::new();
+//! mock.expect(
+//!     Expectation::new(Method::POST, "/users")
+//!         .header("content-type", "application/json")
+//!         .body(r#"{"name":"chashu"}"#)
+//!         .respond_with(MockResponse::new(201).body(r#"{"id":1}"#)),
+//! );
+//!
+//! let client = surf::Client::with_client(mock.clone());
+//! let res = client
+//!     .post("https://api.example.com/users")
+//!     .body_string(r#"{"name":"chashu"}"#.to_string())
+//!     .set_header("content-type", "application/json")
+//!     .await?;
+//! assert_eq!(res.status(), 201);
+//! mock.verify();
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::{Body, HttpClient, Request, Response};
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use http::header::{HeaderName, HeaderValue};
+use http::{Method, StatusCode};
+
+use std::fmt::{self, Write};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+type BodyPredicate = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// An HTTP backend answering requests with canned responses.
+///
+/// Clones share their expectations, so a clone can be passed to the client while the original is
+/// kept to verify the expectations.
+#[derive(Debug, Clone, Default)]
+pub struct MockClient {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    expectations: Vec<Expectation>,
+    /// The requests that no expectation matched.
+    unmatched: Vec<String>,
+}
+
+impl MockClient {
+    /// Create a new instance without expectations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an expectation.
+    pub fn expect(&self, expectation: Expectation) {
+        self.state.lock().unwrap().expectations.push(expectation);
+    }
+
+    /// Check that every request was matched, and every expectation was met.
+    ///
+    /// # Panics
+    ///
+    /// This will panic, listing the problems, if a request didn't match any expectation, or if
+    /// an expectation wasn't matched the expected number of times.
+    pub fn verify(&self) {
+        let state = self.state.lock().unwrap();
+        let mut problems = String::new();
+        for request in &state.unmatched {
+            writeln!(problems, "- unexpected request {}", request).unwrap();
+        }
+        for expectation in &state.expectations {
+            let met = match expectation.times {
+                Some(times) => expectation.calls == times,
+                None => expectation.calls > 0,
+            };
+            if !met {
+                let expected = match expectation.times {
+                    Some(times) => format!("{} times", times),
+                    None => "at least once".to_string(),
+                };
+                writeln!(
+                    problems,
+                    "- expected {} {} {}, got {}",
+                    expectation.method, expectation.path, expected, expectation.calls
+                )
+                .unwrap();
+            }
+        }
+        assert!(
+            problems.is_empty(),
+            "mock expectations failed:\n{}",
+            problems
+        );
+    }
+}
+
+impl HttpClient for MockClient {
+    type Error = io::Error;
+
+    fn send(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Self::Error>> {
+        let state = self.state.clone();
+        Box::pin(async move {
+            let mut body = Vec::new();
+            req.body_mut().read_to_end(&mut body).await?;
+
+            let mut state = state.lock().unwrap();
+            let expectation = state
+                .expectations
+                .iter_mut()
+                .find(|expectation| expectation.matches(&req, &body));
+            match expectation {
+                Some(expectation) => {
+                    expectation.calls += 1;
+                    Ok(expectation.response.to_response())
+                }
+                None => {
+                    let request = format!("{} {}", req.method(), req.uri());
+                    let msg = format!("no mock expectation matches {}", request);
+                    state.unmatched.push(request);
+                    Err(io::Error::new(io::ErrorKind::NotFound, msg))
+                }
+            }
+        })
+    }
+}
+
+/// A request a `MockClient` expects, and the response to it.
+pub struct Expectation {
+    method: Method,
+    path: String,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Vec<BodyPredicate>,
+    times: Option<usize>,
+    calls: usize,
+    response: MockResponse,
+}
+
+impl Expectation {
+    /// Create a new instance, matching requests with a method and a URL path.
+    ///
+    /// The expectation responds with an empty `200 OK` response by default.
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            headers: vec![],
+            body: vec![],
+            times: None,
+            calls: 0,
+            response: MockResponse::new(200),
+        }
+    }
+
+    /// Only match requests with a header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the name or value isn't valid in a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = name.parse().expect("invalid header name");
+        let value = value.parse().expect("invalid header value");
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Only match requests with this body.
+    pub fn body(self, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        self.body_matches(move |actual| actual == &body[..])
+    }
+
+    /// Only match requests whose body passes a predicate.
+    pub fn body_matches(
+        mut self,
+        predicate: impl Fn(&[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.body.push(Box::new(predicate));
+        self
+    }
+
+    /// Expect exactly `times` matching requests, and stop matching after that.
+    ///
+    /// By default an expectation matches any number of requests, and expects at least one.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    /// Set the response to matching requests.
+    pub fn respond_with(mut self, response: MockResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    fn matches(&self, req: &Request, body: &[u8]) -> bool {
+        if let Some(times) = self.times {
+            if self.calls >= times {
+                return false;
+            }
+        }
+        *req.method() == self.method
+            && req.uri().path() == self.path
+            && self
+                .headers
+                .iter()
+                .all(|(name, value)| req.headers().get_all(name).iter().any(|v| v == value))
+            && self.body.iter().all(|predicate| predicate(body))
+    }
+}
+
+impl fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("times", &self.times)
+            .field("calls", &self.calls)
+            .field("response", &self.response)
+            .finish()
+    }
+}
+
+/// A canned response of a `MockClient`.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Create a new instance with a status and an empty body.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the status isn't valid.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status: StatusCode::from_u16(status).expect("invalid status"),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    /// Add a header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the name or value isn't valid in a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = name.parse().expect("invalid header name");
+        let value = value.parse().expect("invalid header value");
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Set the body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn to_response(&self) -> Response {
+        let mut res = http::Response::new(Body::from(self.body.clone()));
+        *res.status_mut() = self.status;
+        for (name, value) in &self.headers {
+            res.headers_mut().append(name, value.clone());
+        }
+        res
+    }
+}
@@ -0,0 +1,19 @@
+//! Helpers for testing applications built on surf.
+//!
+//! The [assertions] submodule contains macros to check responses in integration tests, and the
+//! [mock] submodule an HTTP backend answering requests with canned responses. The backends
+//! themselves aren't public, which is why the mock backend lives here.
+//!
+//! This module is only available with the `testing` feature, usually enabled for
+//! `dev-dependencies` only:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! surf = { version = "1.0.2", features = ["testing"] }
+//! ```
+//!
+//! [assertions]: assertions/index.html
+//! [mock]: mock/index.html
+
+pub mod assertions;
+pub mod mock;
@@ -0,0 +1,112 @@
+//! Enforcing request timeouts.
+
+use futures::future::{self, Either};
+use futures::prelude::*;
+use futures_timer::Delay;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::http_client::{Body, Response};
+use crate::Exception;
+
+/// An error returned when a request doesn't complete within its timeout.
+///
+/// Sending the request fails with this error if the response headers aren't received in time.
+/// If the timeout expires while the response body is read, reading fails with an
+/// `io::ErrorKind::TimedOut` error wrapping this error.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// use std::time::Duration;
+/// use surf::TimeoutError;
+///
+/// let res = surf::get("https://httpbin.org/delay/10")
+///     .timeout(Duration::from_secs(1))
+///     .await;
+/// match res {
+///     Err(err) if err.is::<TimeoutError>() => println!("{}", err),
+///     res => println!("{:?}", res?.status()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError {
+    timeout: Duration,
+}
+
+impl TimeoutError {
+    /// Get the timeout that expired.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request timed out after {:?}", self.timeout)
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Run `fut` to receive a response, and read its body, within `timeout`.
+///
+/// Dropping the backend's future, or the body, cancels the transfer.
+pub(crate) async fn within<F>(timeout: Duration, fut: F) -> Result<Response, Exception>
+where
+    F: Future<Output = Result<Response, Exception>> + Send,
+{
+    let mut delay = Delay::new(timeout);
+    let res = match future::select(Box::pin(fut), &mut delay).await {
+        Either::Left((res, _)) => res?,
+        Either::Right(_) => return Err(TimeoutError { timeout }.into()),
+    };
+    let (parts, body) = res.into_parts();
+    let body = Body::from_reader(Deadline {
+        body,
+        delay,
+        timeout,
+        done: false,
+    });
+    Ok(http::Response::from_parts(parts, body))
+}
+
+/// A body that fails to read once its deadline has passed.
+struct Deadline {
+    body: Body,
+    delay: Delay,
+    timeout: Duration,
+    /// Whether the whole body has been read, after which the deadline no longer matters.
+    done: bool,
+}
+
+impl AsyncRead for Deadline {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.done {
+            return Poll::Ready(Ok(0));
+        }
+        if let Poll::Ready(()) = Pin::new(&mut self.delay).poll(cx) {
+            let err = TimeoutError {
+                timeout: self.timeout,
+            };
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, err)));
+        }
+        let read = Pin::new(&mut self.body).poll_read(cx, buf);
+        if let Poll::Ready(Ok(0)) = read {
+            self.done = !buf.is_empty();
+        }
+        read
+    }
+}
@@ -0,0 +1,190 @@
+//! Client certificates.
+//!
+//! A [`Keystore`] holds the paths of a client certificate and its private key, which the client
+//! presents to servers asking for one (mutual TLS). Set it on a client with
+//! `ClientBuilder::keystore`.
+//!
+//! The files are read whenever a connection is made, so a renewed certificate is used for new
+//! connections as soon as it has been written. Services using short-lived certificates, such as
+//! the ones issued by a service mesh, also need connections that were authenticated with the old
+//! certificate to be closed: `Keystore::reload` discards the idle connections and TLS sessions of
+//! the client, and `Keystore::watch` does so when the files change. Responses that are being
+//! received aren't interrupted.
+//!
+//! Only the curl backend presents client certificates. In the browser, certificates are managed
+//! by the browser instead.
+//!
+//! [`Keystore`]: struct.Keystore.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use std::time::Duration;
+//! use surf::tls::Keystore;
+//!
+//! let keystore = Keystore::pem("/etc/my-app/client.pem", "/etc/my-app/client.key")
+//!     .watch(Duration::from_secs(30));
+//! let client = surf::Client::builder().keystore(keystore).build();
+//! let res = client.get("https://payments.internal.example.com/health").await?;
+//! # Ok(()) }
+//! ```
+//!
+//! # SPIFFE
+//!
+//! Workloads in a SPIFFE service mesh get their X.509 SVID, the certificate carrying their
+//! SPIFFE ID, from the Workload API of the local SPIRE agent. The API is served over gRPC on a
+//! Unix socket, which surf's backends can't connect to, so run `spiffe-helper` next to the
+//! workload to write the SVID and its key to files, and watch them with a keystore. The helper
+//! renews the SVID before it expires, and the keystore picks up the new one. The trust bundle the
+//! helper writes as well can't be used to verify servers: the curl backend only trusts the
+//! system's root certificates.
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use std::time::Duration;
+//! use surf::tls::Keystore;
+//!
+//! // With `cert_dir = "/run/spiffe"` in the helper's configuration.
+//! let svid = Keystore::pem("/run/spiffe/svid.pem", "/run/spiffe/svid_key.pem")
+//!     .watch(Duration::from_secs(10));
+//! let client = surf::Client::builder().keystore(svid).build();
+//! # Ok(()) }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A client certificate and its private key, stored in files.
+///
+/// The keystore is a shared handle: clones refer to the same files, and reloading one reloads
+/// all of them.
+#[derive(Clone)]
+pub struct Keystore {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    certificate: PathBuf,
+    private_key: PathBuf,
+    password: Option<String>,
+    watch: Option<Duration>,
+    /// Incremented on every reload.
+    generation: AtomicU64,
+    /// When the files were last checked for changes, and their modification times then.
+    checked: Mutex<(Instant, Option<[SystemTime; 2]>)>,
+}
+
+impl Keystore {
+    /// Create a new instance, with a certificate and an unencrypted private key in PEM files.
+    ///
+    /// The certificate file may contain intermediate certificates after the client certificate.
+    pub fn pem(certificate: impl AsRef<Path>, private_key: impl AsRef<Path>) -> Self {
+        let certificate = certificate.as_ref().to_path_buf();
+        let private_key = private_key.as_ref().to_path_buf();
+        let modified = modified(&certificate, &private_key);
+        Self {
+            inner: Arc::new(Inner {
+                certificate,
+                private_key,
+                password: None,
+                watch: None,
+                generation: AtomicU64::new(0),
+                checked: Mutex::new((Instant::now(), modified)),
+            }),
+        }
+    }
+
+    /// Set the password the private key is encrypted with.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the keystore has been cloned already.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.inner_mut().password = Some(password.into());
+        self
+    }
+
+    /// Reload the keystore when its files change, checking their modification times at most
+    /// once per `interval`.
+    ///
+    /// The files are checked when requests are sent, rather than in the background.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the keystore has been cloned already.
+    pub fn watch(mut self, interval: Duration) -> Self {
+        self.inner_mut().watch = Some(interval);
+        self
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("keystore is shared already")
+    }
+
+    /// Get the path of the certificate.
+    pub fn certificate(&self) -> &Path {
+        &self.inner.certificate
+    }
+
+    /// Get the path of the private key.
+    pub fn private_key(&self) -> &Path {
+        &self.inner.private_key
+    }
+
+    pub(crate) fn key_password(&self) -> Option<&str> {
+        self.inner.password.as_deref()
+    }
+
+    /// Discard the idle connections and TLS sessions of the clients using the keystore, so the
+    /// certificate is read from its file again.
+    ///
+    /// Call this after replacing the certificate, unless the keystore is watched.
+    pub fn reload(&self) {
+        self.inner.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Get the number of times the keystore was reloaded, reloading it first if it's watched and
+    /// its files changed.
+    ///
+    /// Backends discard their connections when this changes.
+    pub(crate) fn generation(&self) -> u64 {
+        if let Some(interval) = self.inner.watch {
+            let mut checked = self.inner.checked.lock().unwrap();
+            if checked.0.elapsed() >= interval {
+                let modified = modified(&self.inner.certificate, &self.inner.private_key);
+                // Files that are missing while they are being replaced are checked again later.
+                if modified.is_some() && modified != checked.1 {
+                    log::debug!("reloading keystore {:?}", self.inner.certificate);
+                    self.reload();
+                    checked.1 = modified;
+                }
+                checked.0 = Instant::now();
+            }
+        }
+        self.inner.generation.load(Ordering::SeqCst)
+    }
+}
+
+/// Get the modification times of the certificate and private key files.
+fn modified(certificate: &Path, private_key: &Path) -> Option<[SystemTime; 2]> {
+    let modified = |path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    Some([modified(certificate).ok()?, modified(private_key).ok()?])
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field("certificate", &self.inner.certificate)
+            .field("private_key", &self.inner.private_key)
+            .field("watch", &self.inner.watch)
+            .field("generation", &self.inner.generation)
+            .finish()
+    }
+}
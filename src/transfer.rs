@@ -0,0 +1,162 @@
+//! Byte accounting for requests and responses.
+
+use futures::io::AsyncRead;
+
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::http_client::{self, Body};
+
+/// The number of bytes transferred for a single request.
+///
+/// Header sizes are computed from the HTTP/1.1 serialization of the status line and headers as
+/// seen by surf. Backends may add headers of their own (curl adds `Accept` and `Host`, for
+/// example), which aren't included. Body sizes are counted as the bodies are streamed to and from
+/// the backend, so the response body counts grow as the body is read. Bodies are counted after
+/// the backend decoded any `Content-Encoding`.
+///
+/// Instances are shared handles: clones observe the same counters.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[runtime::main]
+/// # async fn main() -> Result<(), surf::Exception> {
+/// let mut res = surf::get("https://httpbin.org/get").await?;
+/// let body = res.body_bytes().await?;
+/// let size = res.transfer_size();
+/// assert_eq!(size.response_body_bytes(), body.len() as u64);
+/// println!("sent {} bytes, received {} bytes", size.sent(), size.received());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TransferSize {
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    request_header: AtomicU64,
+    request_body: AtomicU64,
+    response_header: AtomicU64,
+    response_body: AtomicU64,
+}
+
+impl TransferSize {
+    /// Create a new instance with all counters set to zero.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the size of the request line and headers.
+    pub fn request_header_bytes(&self) -> u64 {
+        self.inner.request_header.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of request body bytes handed to the backend.
+    pub fn request_body_bytes(&self) -> u64 {
+        self.inner.request_body.load(Ordering::SeqCst)
+    }
+
+    /// Get the size of the status line and response headers.
+    pub fn response_header_bytes(&self) -> u64 {
+        self.inner.response_header.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of response body bytes read so far.
+    pub fn response_body_bytes(&self) -> u64 {
+        self.inner.response_body.load(Ordering::SeqCst)
+    }
+
+    /// Get the total number of bytes sent, headers included.
+    pub fn sent(&self) -> u64 {
+        self.request_header_bytes() + self.request_body_bytes()
+    }
+
+    /// Get the total number of bytes received so far, headers included.
+    pub fn received(&self) -> u64 {
+        self.response_header_bytes() + self.response_body_bytes()
+    }
+
+    /// Record the header size of a request, and count its body as it's sent.
+    pub(crate) fn track_request(&self, req: http_client::Request) -> http_client::Request {
+        let (parts, body) = req.into_parts();
+        let path = parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        // "METHOD path HTTP/1.1\r\n"
+        let line = parts.method.as_str().len() + path.len() + 11;
+        let size = line + headers_len(&parts.headers) + 2;
+        self.inner
+            .request_header
+            .store(size as u64, Ordering::SeqCst);
+
+        let counter = Arc::clone(&self.inner);
+        let body = Body::from_reader(Counted::new(body, counter, |c| &c.request_body));
+        http::Request::from_parts(parts, body)
+    }
+
+    /// Record the header size of a response, and count its body as it's read.
+    pub(crate) fn track_response(&self, res: http_client::Response) -> http_client::Response {
+        let (mut parts, body) = res.into_parts();
+        let reason = parts
+            .extensions
+            .get::<http_client::ReasonPhrase>()
+            .map(|reason| reason.0.as_str())
+            .or_else(|| parts.status.canonical_reason())
+            .unwrap_or("");
+        // "HTTP/1.1 200 reason\r\n"
+        let line = 15 + reason.len();
+        let size = line + headers_len(&parts.headers) + 2;
+        self.inner
+            .response_header
+            .store(size as u64, Ordering::SeqCst);
+
+        parts.extensions.insert(self.clone());
+        let counter = Arc::clone(&self.inner);
+        let body = Body::from_reader(Counted::new(body, counter, |c| &c.response_body));
+        http::Response::from_parts(parts, body)
+    }
+}
+
+/// The serialized length of a header block, excluding the final empty line.
+fn headers_len(headers: &http::HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum()
+}
+
+/// A reader that adds the number of bytes read to a counter.
+struct Counted {
+    body: Body,
+    counters: Arc<Counters>,
+    counter: fn(&Counters) -> &AtomicU64,
+}
+
+impl Counted {
+    fn new(body: Body, counters: Arc<Counters>, counter: fn(&Counters) -> &AtomicU64) -> Self {
+        Self {
+            body,
+            counters,
+            counter,
+        }
+    }
+}
+
+impl AsyncRead for Counted {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = futures::ready!(Pin::new(&mut self.body).poll_read(cx, buf))?;
+        (self.counter)(&self.counters).fetch_add(read as u64, Ordering::SeqCst);
+        Poll::Ready(Ok(read))
+    }
+}
@@ -0,0 +1,214 @@
+//! Resumable uploads using the [tus protocol].
+//!
+//! Supports the core protocol (offset probing with `HEAD`, uploading with `PATCH`), the
+//! `creation` extension, and the `checksum` extension using SHA-1.
+//!
+//! [tus protocol]: https://tus.io/protocols/resumable-upload.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::tus::TusClient;
+//!
+//! let data = std::fs::read("video.mp4")?;
+//! let tus = TusClient::new(surf::Client::new()).checksum(true);
+//! let upload = tus
+//!     .create("https://tus.example.com/files/", data.len() as u64, &[("filename", "video.mp4")])
+//!     .await?;
+//!
+//! // If this fails halfway, calling `upload` again with the same URL resumes where the server
+//! // left off.
+//! tus.upload(&upload, std::io::Cursor::new(data)).await?;
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::upload::read_chunk;
+use crate::{Client, Exception, Response};
+
+use futures::prelude::*;
+use sha1::{Digest, Sha1};
+use url::Url;
+
+use std::io;
+
+/// The protocol version implemented by this module.
+const TUS_VERSION: &str = "1.0.0";
+
+/// The default size of the chunks uploaded with a single `PATCH` request.
+const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// A tus client.
+#[derive(Debug)]
+pub struct TusClient<C: HttpClient> {
+    client: Client<C>,
+    chunk_size: usize,
+    checksum: bool,
+}
+
+impl<C: HttpClient> TusClient<C> {
+    /// Create a new instance, sending requests through `client`.
+    pub fn new(client: Client<C>) -> Self {
+        Self {
+            client,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            checksum: false,
+        }
+    }
+
+    /// Set the maximum number of bytes uploaded per `PATCH` request.
+    ///
+    /// Defaults to 4 MiB.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `size` is zero.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "chunk size must be greater than zero");
+        self.chunk_size = size;
+        self
+    }
+
+    /// Send an `Upload-Checksum` with every chunk, so the server can detect corruption.
+    ///
+    /// The server must support the `checksum` extension with the `sha1` algorithm.
+    pub fn checksum(mut self, enable: bool) -> Self {
+        self.checksum = enable;
+        self
+    }
+
+    /// Create a new upload of `length` bytes, and return its URL.
+    ///
+    /// `metadata` is sent in the `Upload-Metadata` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server doesn't respond with `201 Created` and a `Location`.
+    pub async fn create(
+        &self,
+        endpoint: impl AsRef<str>,
+        length: u64,
+        metadata: &[(&str, &str)],
+    ) -> Result<Url, Exception> {
+        let endpoint = Url::parse(endpoint.as_ref())?;
+        let mut req = self
+            .client
+            .post(endpoint.as_str())
+            .set_header("Tus-Resumable", TUS_VERSION)
+            .set_header("Upload-Length", length.to_string());
+        if !metadata.is_empty() {
+            let metadata: Vec<_> = metadata
+                .iter()
+                .map(|(key, value)| format!("{} {}", key, base64::encode(value)))
+                .collect();
+            req = req.set_header("Upload-Metadata", metadata.join(","));
+        }
+
+        let res = req.await?;
+        expect_status(&res, 201)?;
+        let location = res
+            .header("Location")
+            .ok_or_else(|| protocol_error("missing Location header"))?;
+        Ok(endpoint.join(location)?)
+    }
+
+    /// Get the number of bytes the server has received for an upload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload doesn't exist or the server doesn't report an offset.
+    pub async fn offset(&self, upload: &Url) -> Result<u64, Exception> {
+        let res = self
+            .client
+            .head(upload.as_str())
+            .set_header("Tus-Resumable", TUS_VERSION)
+            .set_header("Cache-Control", "no-store")
+            .await?;
+        if !res.status().is_success() {
+            return Err(protocol_error(&format!("unexpected status {}", res.status())).into());
+        }
+        upload_offset(&res)
+    }
+
+    /// Upload the contents of `reader`, resuming at the offset reported by the server.
+    ///
+    /// `reader` must start at the beginning of the upload. Bytes the server already has are read
+    /// and skipped. Returns the final offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the server rejects a chunk. Uploading again with
+    /// a fresh reader resumes after the last chunk the server accepted.
+    pub async fn upload(
+        &self,
+        upload: &Url,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> Result<u64, Exception> {
+        let mut offset = self.offset(upload).await?;
+
+        let mut skipped = 0;
+        let mut buf = vec![0; self.chunk_size];
+        while skipped < offset {
+            let len = buf.len().min((offset - skipped) as usize);
+            let read = reader.read(&mut buf[..len]).await?;
+            if read == 0 {
+                return Err(protocol_error("server offset exceeds the input length").into());
+            }
+            skipped += read as u64;
+        }
+
+        loop {
+            let len = read_chunk(&mut reader, &mut buf).await?;
+            if len == 0 {
+                return Ok(offset);
+            }
+            let chunk = &buf[..len];
+
+            let mut req = self
+                .client
+                .patch(upload.as_str())
+                .set_header("Tus-Resumable", TUS_VERSION)
+                .set_header("Upload-Offset", offset.to_string())
+                .body_bytes(chunk)
+                .set_header("Content-Type", "application/offset+octet-stream");
+            if self.checksum {
+                let digest = base64::encode(&Sha1::digest(chunk));
+                req = req.set_header("Upload-Checksum", format!("sha1 {}", digest));
+            }
+
+            let res = req.await?;
+            expect_status(&res, 204)?;
+            let new_offset = upload_offset(&res)?;
+            if new_offset != offset + len as u64 {
+                return Err(protocol_error("server accepted a partial chunk").into());
+            }
+            offset = new_offset;
+        }
+    }
+}
+
+fn upload_offset(res: &Response) -> Result<u64, Exception> {
+    let offset = res
+        .header("Upload-Offset")
+        .ok_or_else(|| protocol_error("missing Upload-Offset header"))?;
+    Ok(offset
+        .parse()
+        .map_err(|_| protocol_error("invalid Upload-Offset header"))?)
+}
+
+fn expect_status(res: &Response, status: u16) -> io::Result<()> {
+    if res.status() == status {
+        Ok(())
+    } else {
+        Err(protocol_error(&format!(
+            "unexpected status {}",
+            res.status()
+        )))
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("tus: {}", msg))
+}
@@ -0,0 +1,249 @@
+//! Twirp clients.
+//!
+//! [Twirp] is an RPC protocol over plain HTTP: every call is a `POST` to
+//! `/twirp/<package>.<Service>/<Method>` with a protobuf or JSON body, and failures are reported
+//! as a JSON error object. [`TwirpClient`] makes those calls, and turns failures into a
+//! [`TwirpError`].
+//!
+//! [Twirp]: https://twitchtv.github.io/twirp/docs/spec_v7.html
+//! [`TwirpClient`]: struct.TwirpClient.html
+//! [`TwirpError`]: struct.TwirpError.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::twirp::{TwirpClient, TwirpError};
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct Size {
+//!     #[prost(int32, tag = "1")]
+//!     inches: i32,
+//! }
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct Hat {
+//!     #[prost(string, tag = "1")]
+//!     color: String,
+//! }
+//!
+//! let client = TwirpClient::new(surf::Client::new(), "https://haberdasher.example.com".parse()?);
+//! match client.call::<_, Hat>("twitch.twirp.example.Haberdasher", "MakeHat", &Size { inches: 12 }).await {
+//!     Ok(hat) => println!("got a {} hat", hat.color),
+//!     Err(err) => match err.downcast_ref::<TwirpError>() {
+//!         Some(err) if err.code() == "invalid_argument" => println!("bad size: {}", err.msg()),
+//!         _ => return Err(err),
+//!     },
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::{Client, Exception, Response};
+
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A client calling Twirp services on a single server.
+#[derive(Debug, Clone)]
+pub struct TwirpClient<C: HttpClient> {
+    client: Client<C>,
+    base: Url,
+    prefix: String,
+}
+
+impl<C: HttpClient> TwirpClient<C> {
+    /// Create a new instance calling services on the server at `base`.
+    pub fn new(client: Client<C>, base: Url) -> Self {
+        Self {
+            client,
+            base,
+            prefix: "/twirp".to_string(),
+        }
+    }
+
+    /// Set the path prefix of the routes. Defaults to `/twirp`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Call a method, with protobuf serialization.
+    ///
+    /// `service` is the fully qualified name of the service, such as
+    /// `twitch.twirp.example.Haberdasher`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TwirpError` if the server reports an error, or any other error if the request
+    /// fails or the response can't be decoded.
+    pub async fn call<Req, Res>(
+        &self,
+        service: &str,
+        method: &str,
+        request: &Req,
+    ) -> Result<Res, Exception>
+    where
+        Req: prost::Message,
+        Res: prost::Message + Default,
+    {
+        let mut res = self
+            .client
+            .post(self.route(service, method))
+            .body_protobuf(request)
+            // Twirp servers reject the `application/x-protobuf` set by `body_protobuf`.
+            .set_header("Content-Type", "application/protobuf")
+            .await?;
+        check(&mut res).await?;
+        Ok(res.body_protobuf().await?)
+    }
+
+    /// Call a method, with JSON serialization.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TwirpError` if the server reports an error, or any other error if the request
+    /// fails or the response can't be decoded.
+    pub async fn call_json<Req, Res>(
+        &self,
+        service: &str,
+        method: &str,
+        request: &Req,
+    ) -> Result<Res, Exception>
+    where
+        Req: Serialize,
+        Res: DeserializeOwned,
+    {
+        let mut res = self
+            .client
+            .post(self.route(service, method))
+            .body_json(request)?
+            .await?;
+        check(&mut res).await?;
+        Ok(res.body_json().await?)
+    }
+
+    fn route(&self, service: &str, method: &str) -> String {
+        format!(
+            "{}{}/{}/{}",
+            self.base.as_str().trim_end_matches('/'),
+            self.prefix.trim_end_matches('/'),
+            service,
+            method
+        )
+    }
+}
+
+/// Turn a non-`200` response into a `TwirpError`.
+async fn check(res: &mut Response) -> Result<(), Exception> {
+    let status = res.status();
+    if status == StatusCode::OK {
+        return Ok(());
+    }
+    let body = res.body_bytes().await.unwrap_or_default();
+    Err(TwirpError::from_response(status, &body).into())
+}
+
+/// An error reported by a Twirp service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TwirpError {
+    status: StatusCode,
+    code: String,
+    msg: String,
+    meta: HashMap<String, String>,
+}
+
+impl TwirpError {
+    fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        let json: Option<Value> = serde_json::from_slice(body).ok();
+        let field = |name: &str| {
+            json.as_ref()
+                .and_then(|json| json.get(name))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+
+        match field("code") {
+            Some(code) => {
+                let meta = json
+                    .as_ref()
+                    .and_then(|json| json.get("meta"))
+                    .and_then(Value::as_object)
+                    .map(|meta| {
+                        meta.iter()
+                            .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Self {
+                    status,
+                    code,
+                    msg: field("msg").unwrap_or_default(),
+                    meta,
+                }
+            }
+            // Not a Twirp error, most likely from a proxy in between.
+            None => {
+                let code = match status.as_u16() {
+                    300..=400 => "internal",
+                    401 => "unauthenticated",
+                    403 => "permission_denied",
+                    404 => "bad_route",
+                    429 | 502 | 503 | 504 => "unavailable",
+                    _ => "unknown",
+                };
+                let mut meta = HashMap::new();
+                meta.insert(
+                    "http_error_from_intermediary".to_string(),
+                    "true".to_string(),
+                );
+                meta.insert(
+                    "body".to_string(),
+                    String::from_utf8_lossy(body).into_owned(),
+                );
+                Self {
+                    status,
+                    code: code.to_string(),
+                    msg: format!("non-twirp error response with status {}", status),
+                    meta,
+                }
+            }
+        }
+    }
+
+    /// Get the HTTP status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the error code, such as `not_found` or `invalid_argument`.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Get the human-readable error message.
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// Get the error metadata.
+    pub fn meta(&self) -> &HashMap<String, String> {
+        &self.meta
+    }
+}
+
+impl fmt::Display for TwirpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "twirp error {}: {}", self.code, self.msg)
+    }
+}
+
+impl Error for TwirpError {}
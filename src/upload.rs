@@ -0,0 +1,215 @@
+//! Chunked uploads.
+//!
+//! Many storage APIs (S3 multipart uploads, GCS and Azure block blobs) accept large files as a
+//! series of independently uploaded chunks, followed by a request that assembles them.
+//! `ChunkedUpload` implements the generic part of that dance: it splits a reader into chunks,
+//! uploads them with a user-supplied function (optionally several at a time), retries failed
+//! chunks, and passes the results to a user-supplied finalize step.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use surf::upload::ChunkedUpload;
+//!
+//! let client = surf::Client::new();
+//! let file = std::io::Cursor::new(std::fs::read("backup.tar")?);
+//!
+//! let etags = ChunkedUpload::new(8 * 1024 * 1024)
+//!     .concurrency(4)
+//!     .retries(2)
+//!     .run(
+//!         file,
+//!         |chunk| {
+//!             let req = client
+//!                 .put(format!("https://storage.example.com/backup.tar?part={}", chunk.index() + 1))
+//!                 .body_bytes(chunk.data());
+//!             async move {
+//!                 let res = req.await?;
+//!                 Ok(res.header("ETag").unwrap_or_default().to_string())
+//!             }
+//!         },
+//!         |etags| async move { Ok(etags) },
+//!     )
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+use crate::Exception;
+
+use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+
+use std::io;
+use std::sync::Arc;
+
+/// A chunk of the input of a `ChunkedUpload`.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    index: usize,
+    offset: u64,
+    data: Arc<[u8]>,
+}
+
+impl Chunk {
+    /// Get the position of the chunk, starting at zero.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the byte offset of the chunk in the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the contents of the chunk.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Splits a reader into chunks and uploads them.
+#[derive(Debug, Clone)]
+pub struct ChunkedUpload {
+    chunk_size: usize,
+    concurrency: usize,
+    retries: u32,
+}
+
+impl ChunkedUpload {
+    /// Create a new instance, splitting the input into chunks of `chunk_size` bytes.
+    ///
+    /// All chunks but the last are exactly `chunk_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `chunk_size` is zero.
+    pub fn new(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+        Self {
+            chunk_size,
+            concurrency: 1,
+            retries: 0,
+        }
+    }
+
+    /// Set the maximum number of chunks uploaded at the same time.
+    ///
+    /// Defaults to `1`. Up to this many chunks are held in memory at once.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `concurrency` is zero.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "concurrency must be greater than zero");
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Set the number of times a failed chunk is retried before the upload fails.
+    ///
+    /// Defaults to `0`. Retries are attempted immediately.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Upload `reader`.
+    ///
+    /// `upload` is called for every chunk, and once more for every retry. Once all chunks are
+    /// uploaded, `finalize` is called with the results of `upload`, in chunk order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, if a chunk still fails after all retries, or if
+    /// `finalize` fails. Chunks that are still in flight when an error occurs are dropped.
+    pub async fn run<R, U, UFut, T, F, FFut, O>(
+        &self,
+        mut reader: R,
+        upload: U,
+        finalize: F,
+    ) -> Result<O, Exception>
+    where
+        R: AsyncRead + Unpin,
+        U: Fn(Chunk) -> UFut,
+        UFut: Future<Output = Result<T, Exception>>,
+        F: FnOnce(Vec<T>) -> FFut,
+        FFut: Future<Output = Result<O, Exception>>,
+    {
+        let mut results = vec![];
+        let mut pending = FuturesUnordered::new();
+        let mut offset = 0;
+        let mut eof = false;
+
+        loop {
+            while !eof && pending.len() < self.concurrency {
+                let mut buf = vec![0; self.chunk_size];
+                let len = read_chunk(&mut reader, &mut buf).await?;
+                eof = len < self.chunk_size;
+                if len == 0 {
+                    break;
+                }
+                buf.truncate(len);
+                let chunk = Chunk {
+                    index: results.len(),
+                    offset,
+                    data: buf.into(),
+                };
+                pending.push(self.upload_chunk(&upload, chunk));
+                results.push(None);
+                offset += len as u64;
+            }
+
+            match pending.next().await {
+                Some(res) => {
+                    let (index, value) = res?;
+                    results[index] = Some(value);
+                }
+                None => break,
+            }
+        }
+
+        let results = results.into_iter().map(Option::unwrap).collect();
+        finalize(results).await
+    }
+
+    /// Upload a single chunk, retrying on failure.
+    async fn upload_chunk<U, UFut, T>(
+        &self,
+        upload: &U,
+        chunk: Chunk,
+    ) -> Result<(usize, T), Exception>
+    where
+        U: Fn(Chunk) -> UFut,
+        UFut: Future<Output = Result<T, Exception>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match upload(chunk.clone()).await {
+                Ok(value) => return Ok((chunk.index, value)),
+                Err(err) if attempt < self.retries => {
+                    attempt += 1;
+                    log::debug!("retrying chunk {} after error: {}", chunk.index, err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Fill `buf` from `reader`, returning fewer bytes only at the end of the input.
+pub(crate) async fn read_chunk(
+    reader: &mut (impl AsyncRead + Unpin),
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        let read = reader.read(&mut buf[len..]).await?;
+        if read == 0 {
+            break;
+        }
+        len += read;
+    }
+    Ok(len)
+}
@@ -0,0 +1,456 @@
+//! Long-lived watch streams.
+//!
+//! Many APIs push changes over a single long-running response: Kubernetes watches and change
+//! feeds send newline-delimited JSON, and others use server-sent events. [`Watch`] turns such an
+//! endpoint into a `Stream` of events that survives disconnects. When the connection drops, it
+//! reconnects and asks the server to resume after the last event it saw, either with a query
+//! parameter or a header. Events that are replayed after a reconnect are skipped.
+//!
+//! [`Watch`]: struct.Watch.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[runtime::main]
+//! # async fn main() -> Result<(), surf::Exception> {
+//! use futures::prelude::*;
+//! use surf::watch::Watch;
+//!
+//! let client = surf::Client::new();
+//! let url = "https://kubernetes.default.svc/api/v1/namespaces/default/pods?watch=1".parse()?;
+//! let mut events = Watch::ndjson(client, url)
+//!     .resume_query("resourceVersion")
+//!     .id(|event| {
+//!         let value: serde_json::Value = event.json().ok()?;
+//!         Some(value["object"]["metadata"]["resourceVersion"].as_str()?.to_string())
+//!     })
+//!     .stream();
+//!
+//! while let Some(event) = events.next().await {
+//!     println!("{}", event?.data());
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::http_client::HttpClient;
+use crate::response;
+use crate::{Client, Exception, Response};
+
+use futures::io::BufReader;
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use futures_timer::Delay;
+use url::Url;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A single event received from a watch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    name: Option<String>,
+    data: String,
+}
+
+impl Event {
+    /// Get the id of the event, used to resume and to skip duplicates.
+    ///
+    /// Server-sent events without an `id` field have the id of the last event that had one.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Get the event type of a server-sent event.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Get the event payload.
+    ///
+    /// For newline-delimited JSON this is a single line. For server-sent events the `data`
+    /// fields are joined with newlines.
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// Parse the payload as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.data)
+    }
+}
+
+/// The wire format of a watch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ndjson,
+    EventStream,
+}
+
+/// How the last seen event id is sent when reconnecting.
+#[derive(Debug, Clone)]
+enum Resume {
+    None,
+    Query(String),
+    Header(&'static str),
+}
+
+type IdFn = Arc<dyn Fn(&Event) -> Option<String> + Send + Sync>;
+
+/// A reconnecting stream of events.
+pub struct Watch<C: HttpClient> {
+    client: Client<C>,
+    url: Url,
+    format: Format,
+    resume: Resume,
+    id: Option<IdFn>,
+    reconnect_delay: Duration,
+    max_reconnects: Option<u32>,
+    dedup_window: usize,
+    max_len: usize,
+}
+
+impl<C: HttpClient> Watch<C> {
+    /// Watch an endpoint sending newline-delimited JSON.
+    ///
+    /// Events don't have an id unless one is extracted with `id`.
+    pub fn ndjson(client: Client<C>, url: Url) -> Self {
+        Self::new(client, url, Format::Ndjson, Resume::None)
+    }
+
+    /// Watch an endpoint sending server-sent events.
+    ///
+    /// Events are identified by their `id` field, and resumed with the `Last-Event-ID` header.
+    /// A `retry` field sent by the server replaces the reconnect delay.
+    pub fn event_stream(client: Client<C>, url: Url) -> Self {
+        Self::new(
+            client,
+            url,
+            Format::EventStream,
+            Resume::Header("Last-Event-ID"),
+        )
+    }
+
+    fn new(client: Client<C>, url: Url, format: Format, resume: Resume) -> Self {
+        Self {
+            client,
+            url,
+            format,
+            resume,
+            id: None,
+            reconnect_delay: Duration::from_secs(1),
+            max_reconnects: Some(10),
+            dedup_window: 1024,
+            max_len: 1024 * 1024,
+        }
+    }
+
+    /// Resume by setting the query parameter `name` to the last seen event id.
+    pub fn resume_query(mut self, name: impl Into<String>) -> Self {
+        self.resume = Resume::Query(name.into());
+        self
+    }
+
+    /// Resume by sending the last seen event id in the header `name`.
+    pub fn resume_header(mut self, name: &'static str) -> Self {
+        self.resume = Resume::Header(name);
+        self
+    }
+
+    /// Extract the id of an event, such as a resource version or sequence number.
+    ///
+    /// Events for which this returns `None` are never skipped, and don't move the resume point.
+    pub fn id<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Event) -> Option<String> + Send + Sync + 'static,
+    {
+        self.id = Some(Arc::new(f));
+        self
+    }
+
+    /// Set how long to wait before reconnecting. Defaults to one second.
+    ///
+    /// For server-sent events, a `retry` field sent by the server takes precedence.
+    pub fn reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// Set how many consecutive reconnects without receiving an event are attempted before the
+    /// stream fails. Defaults to `10`; `None` reconnects forever.
+    pub fn max_reconnects(mut self, max: Option<u32>) -> Self {
+        self.max_reconnects = max;
+        self
+    }
+
+    /// Set how many recent event ids are remembered to skip duplicates. Defaults to `1024`.
+    pub fn dedup_window(mut self, size: usize) -> Self {
+        self.dedup_window = size;
+        self
+    }
+
+    /// Set the maximum length of a line in bytes, and of the data of a server-sent event.
+    /// Defaults to 1 MiB.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Start watching.
+    ///
+    /// The stream ends after yielding an error. Responses with a `4xx` status fail immediately,
+    /// since retrying them won't help; a resume point that's too old is usually reported this
+    /// way, such as Kubernetes' `410 Gone`. A line or event that is longer than the maximum
+    /// length, or isn't valid UTF-8, fails with an `io::ErrorKind::InvalidData` error rather than
+    /// reconnecting, since the server would send it again.
+    pub fn stream(self) -> BoxStream<'static, Result<Event, Exception>> {
+        let state = State {
+            watch: self,
+            reader: None,
+            sse: Sse::default(),
+            last_id: None,
+            seen: VecDeque::new(),
+            failures: 0,
+            done: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+            let item = state.next_event().await;
+            state.done = item.is_err();
+            Some((item, state))
+        })
+        .boxed()
+    }
+}
+
+impl<C: HttpClient> fmt::Debug for Watch<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("url", &self.url.as_str())
+            .field("format", &self.format)
+            .field("resume", &self.resume)
+            .field("reconnect_delay", &self.reconnect_delay)
+            .field("max_reconnects", &self.max_reconnects)
+            .field("dedup_window", &self.dedup_window)
+            .field("max_len", &self.max_len)
+            .finish()
+    }
+}
+
+struct State<C: HttpClient> {
+    watch: Watch<C>,
+    reader: Option<BufReader<Response>>,
+    sse: Sse,
+    last_id: Option<String>,
+    seen: VecDeque<String>,
+    failures: u32,
+    done: bool,
+}
+
+impl<C: HttpClient> State<C> {
+    async fn next_event(&mut self) -> Result<Event, Exception> {
+        loop {
+            let reader = match &mut self.reader {
+                Some(reader) => reader,
+                None => {
+                    if let Err(err) = self.connect().await {
+                        if self.done {
+                            return Err(err);
+                        }
+                        self.fail(err)?;
+                    }
+                    continue;
+                }
+            };
+
+            let max_len = self.watch.max_len;
+            let event = match self.watch.format {
+                Format::Ndjson => read_line(reader, max_len)
+                    .await
+                    .map(|event| event.map(|event| (event, true))),
+                Format::EventStream => read_event(reader, max_len, &mut self.sse).await,
+            };
+            let (mut event, own_id) = match event {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    self.reader = None;
+                    self.fail(io::Error::from(io::ErrorKind::UnexpectedEof).into())?;
+                    continue;
+                }
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => return Err(err.into()),
+                Err(err) => {
+                    self.reader = None;
+                    self.fail(err.into())?;
+                    continue;
+                }
+            };
+            self.failures = 0;
+
+            let own_id = own_id || self.watch.id.is_some();
+            if let Some(id_fn) = &self.watch.id {
+                event.id = id_fn(&event);
+            }
+            // An id carried over from an earlier event was already recorded with that event.
+            if let (Some(id), true) = (&event.id, own_id) {
+                if self.seen.contains(id) {
+                    continue;
+                }
+                if self.watch.dedup_window > 0 {
+                    if self.seen.len() == self.watch.dedup_window {
+                        self.seen.pop_front();
+                    }
+                    self.seen.push_back(id.clone());
+                }
+                self.last_id = Some(id.clone());
+            }
+            return Ok(event);
+        }
+    }
+
+    /// Record a failed connection, returning the error once the reconnect budget is used up.
+    fn fail(&mut self, err: Exception) -> Result<(), Exception> {
+        self.failures += 1;
+        match self.watch.max_reconnects {
+            Some(max) if self.failures > max => Err(err),
+            _ => {
+                log::debug!("watch disconnected, reconnecting: {}", err);
+                Ok(())
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<(), Exception> {
+        if self.failures > 0 {
+            let delay = self.sse.retry.unwrap_or(self.watch.reconnect_delay);
+            Delay::new(delay).await;
+        }
+
+        let mut url = self.watch.url.clone();
+        if let (Resume::Query(name), Some(id)) = (&self.watch.resume, &self.last_id) {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(key, _)| key != name)
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(pairs)
+                .append_pair(name, id);
+        }
+
+        let mut req = self.watch.client.get(url);
+        if let (Resume::Header(name), Some(id)) = (&self.watch.resume, &self.last_id) {
+            req = req.set_header(name, id);
+        }
+        if self.watch.format == Format::EventStream {
+            req = req.set_header("Accept", "text/event-stream");
+        }
+
+        let res = req.await?;
+        if !res.status().is_success() {
+            self.done = res.status().is_client_error();
+            return Err(format!("watch request failed with status {}", res.status()).into());
+        }
+        self.reader = Some(BufReader::new(res));
+        Ok(())
+    }
+}
+
+/// What a server-sent event stream sets for the events that follow.
+#[derive(Debug, Default)]
+struct Sse {
+    /// The id of the last event with an `id` field.
+    last_event_id: Option<String>,
+    /// The reconnect delay set with a `retry` field.
+    retry: Option<Duration>,
+}
+
+/// Read the next non-empty line.
+async fn read_line<R>(reader: &mut R, max_len: usize) -> io::Result<Option<Event>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        match response::read_line(reader, max_len).await? {
+            Some(data) if data.is_empty() => continue,
+            Some(data) => {
+                return Ok(Some(Event {
+                    id: None,
+                    name: None,
+                    data,
+                }))
+            }
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Read the next server-sent event, ignoring comments and unknown fields.
+///
+/// Returns the event, and whether it had an `id` field of its own.
+async fn read_event<R>(
+    reader: &mut R,
+    max_len: usize,
+    sse: &mut Sse,
+) -> io::Result<Option<(Event, bool)>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut name = None;
+    let mut data = String::new();
+    let mut has_data = false;
+    let mut has_id = false;
+    loop {
+        let line = match response::read_line(reader, max_len).await? {
+            Some(line) => line,
+            // An incomplete event at the end of the stream is discarded.
+            None => return Ok(None),
+        };
+        if line.is_empty() {
+            if has_data {
+                let id = sse.last_event_id.clone();
+                return Ok(Some((Event { id, name, data }, has_id)));
+            }
+            name = None;
+            continue;
+        }
+
+        let (field, value) = match line.find(':') {
+            Some(0) => continue,
+            Some(i) => {
+                let value = &line[i + 1..];
+                (&line[..i], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line.as_str(), ""),
+        };
+        match field {
+            "data" => {
+                if has_data {
+                    data.push('\n');
+                }
+                data.push_str(value);
+                has_data = true;
+                if data.len() > max_len {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "event too long"));
+                }
+            }
+            // An empty id clears the id of the following events, an id with a NUL is ignored.
+            "id" if !value.contains('\0') => {
+                sse.last_event_id = Some(value.to_string()).filter(|id| !id.is_empty());
+                has_id = true;
+            }
+            "event" => name = Some(value.to_string()),
+            "retry" if value.bytes().all(|byte| byte.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    sse.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+    }
+}
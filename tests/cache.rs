@@ -0,0 +1,385 @@
+use http::Method;
+use surf::middleware::cache::{Cache, CacheStatus};
+use surf::middleware::tenant::{Bearer, Tenant, TenantCredentials};
+use surf::middleware::token::{BearerAuth, Token};
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+
+fn private(token: &str) -> Expectation {
+    Expectation::new(Method::GET, "/me")
+        .header("authorization", &format!("Bearer {}", token))
+        .times(1)
+        .respond_with(
+            MockResponse::new(200)
+                .header("cache-control", "max-age=60")
+                .body(token),
+        )
+}
+
+#[runtime::test]
+async fn credentials_get_entries_of_their_own() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(private("chashu"));
+    mock.expect(private("nori"));
+    let client = surf::Client::with_client(mock.clone())
+        .scoped()
+        .cache(Cache::new())
+        .build();
+    let chashu = client
+        .scoped()
+        .middleware(BearerAuth::new(|| async { Ok(Token::new("chashu")) }))
+        .build();
+    let nori = client
+        .scoped()
+        .middleware(BearerAuth::new(|| async { Ok(Token::new("nori")) }))
+        .build();
+
+    for _ in 0..2 {
+        assert_eq!(
+            chashu
+                .get("https://api.example.com/me")
+                .recv_string()
+                .await?,
+            "chashu"
+        );
+        assert_eq!(
+            nori.get("https://api.example.com/me").recv_string().await?,
+            "nori"
+        );
+    }
+    let res = nori.get("https://api.example.com/me").await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Hit));
+    assert_eq!(client.cache().unwrap().keys().len(), 2);
+    assert!(client
+        .cache()
+        .unwrap()
+        .get("https://api.example.com/me")
+        .is_none());
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn tenants_get_entries_of_their_own() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(private("chashu"));
+    mock.expect(private("nori"));
+    let credentials = TenantCredentials::new()
+        .tenant("chashu", Bearer::new("chashu"))
+        .tenant("nori", Bearer::new("nori"));
+    let client = surf::Client::with_client(mock.clone())
+        .scoped()
+        .middleware(credentials)
+        .cache(Cache::new())
+        .build();
+
+    for _ in 0..2 {
+        for tenant in &["chashu", "nori"] {
+            let body = client
+                .get("https://api.example.com/me")
+                .set_ext(Tenant::new(*tenant))
+                .recv_string()
+                .await?;
+            assert_eq!(body, *tenant);
+        }
+    }
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn purge_removes_entries_for_all_credentials() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(private("chashu").times(2));
+    let cache = Cache::new();
+    let client = surf::Client::with_client(mock.clone())
+        .scoped()
+        .middleware(BearerAuth::new(|| async { Ok(Token::new("chashu")) }))
+        .cache(cache.clone())
+        .build();
+
+    client.get("https://api.example.com/me").await?;
+    cache.purge("https://api.example.com/me");
+    assert!(cache.keys().is_empty());
+    client.get("https://api.example.com/me").await?;
+    mock.verify();
+    Ok(())
+}
+
+fn client(mock: &MockClient, cache: &Cache) -> surf::Client<MockClient> {
+    surf::Client::with_client(mock.clone())
+        .scoped()
+        .cache(cache.clone())
+        .build()
+}
+
+#[runtime::test]
+async fn fresh_responses_are_served_from_the_cache() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/fresh")
+            .times(1)
+            .respond_with(
+                MockResponse::new(200)
+                    .header("cache-control", "max-age=60")
+                    .body("hello"),
+            ),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    let res = client.get("https://example.com/fresh").await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Miss));
+    let mut res = client.get("https://example.com/fresh").await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Hit));
+    assert_eq!(res.body_string().await?, "hello");
+    assert!(res.header("age").is_some());
+    assert_eq!(cache.stats().hits(), 1);
+    assert_eq!(cache.stats().misses(), 1);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn no_store_responses_are_not_stored() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/secret")
+            .times(2)
+            .respond_with(MockResponse::new(200).header("cache-control", "no-store, max-age=60")),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/secret").await?;
+    client.get("https://example.com/secret").await?;
+    assert!(cache.keys().is_empty());
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn request_no_cache_skips_fresh_responses() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/fresh")
+            .times(2)
+            .respond_with(MockResponse::new(200).header("cache-control", "max-age=60")),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/fresh").await?;
+    let res = client
+        .get("https://example.com/fresh")
+        .set_header("cache-control", "no-cache")
+        .await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Miss));
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn stale_responses_are_revalidated() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/doc")
+            .header("if-none-match", "\"v1\"")
+            .times(1)
+            .respond_with(
+                MockResponse::new(304)
+                    .header("etag", "\"v1\"")
+                    .header("x-revalidated", "yes"),
+            ),
+    );
+    mock.expect(
+        Expectation::new(Method::GET, "/doc").times(1).respond_with(
+            MockResponse::new(200)
+                .header("cache-control", "max-age=0")
+                .header("etag", "\"v1\"")
+                .body("document"),
+        ),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/doc").await?;
+    let mut res = client.get("https://example.com/doc").await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Revalidated));
+    assert_eq!(res.header("x-revalidated"), Some("yes"));
+    assert_eq!(res.body_string().await?, "document");
+    let entry = cache.get("https://example.com/doc").unwrap();
+    assert_eq!(entry.headers()["x-revalidated"], "yes");
+    assert_eq!(cache.stats().revalidations(), 1);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn variants_are_stored_per_vary_header() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    for (language, greeting) in &[("en", "hello"), ("de", "hallo")] {
+        mock.expect(
+            Expectation::new(Method::GET, "/greeting")
+                .header("accept-language", language)
+                .times(1)
+                .respond_with(
+                    MockResponse::new(200)
+                        .header("cache-control", "max-age=60")
+                        .header("vary", "Accept-Language")
+                        .body(*greeting),
+                ),
+        );
+    }
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    for _ in 0..2 {
+        for (language, greeting) in &[("en", "hello"), ("de", "hallo")] {
+            let body = client
+                .get("https://example.com/greeting")
+                .set_header("accept-language", language)
+                .recv_string()
+                .await?;
+            assert_eq!(body, *greeting);
+        }
+    }
+    assert_eq!(cache.keys().len(), 2);
+    assert_eq!(cache.stats().hits(), 2);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn vary_star_responses_are_not_stored() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/random")
+            .times(2)
+            .respond_with(
+                MockResponse::new(200)
+                    .header("cache-control", "max-age=60")
+                    .header("vary", "*"),
+            ),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/random").await?;
+    client.get("https://example.com/random").await?;
+    assert!(cache.keys().is_empty());
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn huge_ages_are_capped() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/old").times(2).respond_with(
+            MockResponse::new(200)
+                .header("cache-control", "max-age=60")
+                .header("age", "99999999999999999999999999"),
+        ),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/old").await?;
+    // The capped age is still far beyond the lifetime, so the response is stale.
+    let res = client
+        .get("https://example.com/old")
+        .set_header("cache-control", "max-stale=60")
+        .await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Miss));
+    let res = client
+        .get("https://example.com/old")
+        .set_header("cache-control", "max-stale")
+        .await?;
+    assert_eq!(res.ext::<CacheStatus>(), Some(&CacheStatus::Hit));
+    assert_eq!(res.header("age"), Some("2147483648"));
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn unsafe_requests_invalidate_stored_responses() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/items/1")
+            .times(2)
+            .respond_with(MockResponse::new(200).header("cache-control", "max-age=60")),
+    );
+    mock.expect(
+        Expectation::new(Method::GET, "/items")
+            .times(2)
+            .respond_with(MockResponse::new(200).header("cache-control", "max-age=60")),
+    );
+    mock.expect(
+        Expectation::new(Method::PUT, "/items/1")
+            .respond_with(MockResponse::new(204).header("content-location", "/items")),
+    );
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    client.get("https://example.com/items/1").await?;
+    client.get("https://example.com/items").await?;
+    client
+        .put("https://example.com/items/1")
+        .body_string("updated".to_string())
+        .await?;
+    assert!(cache.keys().is_empty());
+    client.get("https://example.com/items/1").await?;
+    client.get("https://example.com/items").await?;
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn purging_removes_stored_responses() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    for path in &["/a/1", "/a/2", "/b/1"] {
+        mock.expect(
+            Expectation::new(Method::GET, *path)
+                .respond_with(MockResponse::new(200).header("cache-control", "max-age=60")),
+        );
+    }
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+    let fetch_all = || async {
+        for path in &["/a/1", "/a/2", "/b/1"] {
+            client.get(format!("https://example.com{}", path)).await?;
+        }
+        Ok::<_, surf::Exception>(())
+    };
+
+    fetch_all().await?;
+    assert_eq!(cache.keys().len(), 3);
+    cache.purge("https://example.com/a/1");
+    assert_eq!(cache.keys().len(), 2);
+    cache.purge_prefix("https://example.com/a/");
+    assert_eq!(cache.keys(), vec!["https://example.com/b/1".to_string()]);
+    cache.clear();
+    assert!(cache.keys().is_empty());
+
+    fetch_all().await?;
+    cache.purge_where(|key, _| key.ends_with("/1"));
+    assert_eq!(cache.keys(), vec!["https://example.com/a/2".to_string()]);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn only_if_cached_answers_misses_with_504() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    let cache = Cache::new();
+    let client = client(&mock, &cache);
+
+    let res = client
+        .get("https://example.com/missing")
+        .set_header("cache-control", "only-if-cached")
+        .await?;
+    assert_eq!(res.status(), 504);
+    mock.verify();
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use surf::pac::{Pac, PacError};
+use surf::url::Url;
+
+fn url() -> Url {
+    Url::parse("http://www.example.com/chashu").unwrap()
+}
+
+/// Run a script with the given body for `FindProxyForURL`.
+fn run(body: &str) -> Result<String, PacError> {
+    let script = format!("function FindProxyForURL(url, host) {{\n{}\n}}", body);
+    Pac::parse(&script)?.find_proxy_for_url(&url())
+}
+
+/// Evaluate an expression, panicking if that fails.
+fn eval(expr: &str) -> String {
+    match run(&format!("return {};", expr)) {
+        Ok(value) => value,
+        Err(err) => panic!("failed to evaluate {}: {}", expr, err),
+    }
+}
+
+fn assert_evals(cases: &[(&str, &str)]) {
+    for (expr, expected) in cases {
+        assert_eq!(eval(expr), *expected, "{}", expr);
+    }
+}
+
+#[test]
+fn operators() {
+    assert_evals(&[
+        ("1 + 2 * 3", "7"),
+        ("(1 + 2) * 3", "9"),
+        ("10 - 4 - 3", "3"),
+        ("12 / 4 / 3", "1"),
+        ("7 % 4 * 2", "6"),
+        ("-2 * 3", "-6"),
+        ("7 / 2", "3.5"),
+        ("\"a\" + 1 + 2", "a12"),
+        ("1 + 2 + \"a\"", "3a"),
+        ("\"3\" * \"4\"", "12"),
+        ("1 < 2 == true", "true"),
+        ("1 + 1 == 2", "true"),
+        ("\"1\" == 1", "true"),
+        ("\"1\" === 1", "false"),
+        ("\"1\" !== 1", "true"),
+        ("null == undefined", "true"),
+        ("null === undefined", "false"),
+        ("null == 0", "false"),
+        ("\"b\" > \"a\"", "true"),
+        ("\"10\" < \"9\"", "true"),
+        ("10 < 9", "false"),
+        ("2 >= 2 && 2 <= 2", "true"),
+        ("!0", "true"),
+        ("!\"\"", "true"),
+        ("!!\"a\"", "true"),
+        ("!1 == false", "true"),
+        ("\"\" || \"direct\"", "direct"),
+        ("\"a\" && \"b\"", "b"),
+        ("0 && undefinedFunction()", "0"),
+        ("1 || undefinedFunction()", "1"),
+        ("true || false && false", "true"),
+        ("(true || false) && false", "false"),
+        ("false ? 1 : true ? 2 : 3", "2"),
+        ("1 ? \"yes\" : \"no\"", "yes"),
+        ("1 + 1 ? 0 || 3 : 4", "3"),
+    ]);
+}
+
+#[test]
+fn variables() {
+    let body = "var a = \"PROXY \", b; a += \"proxy:\"; c = 80; return a + b + c;";
+    assert_eq!(run(body).unwrap(), "PROXY proxy:undefined80");
+
+    let err = run("return missing;").unwrap_err();
+    assert!(err.to_string().contains("`missing` isn't defined"));
+}
+
+#[test]
+fn string_methods() {
+    assert_evals(&[
+        ("\"chashu\".length", "6"),
+        ("\"chashu\".charAt(1)", "h"),
+        ("\"chashu\".charAt(-1)", ""),
+        ("\"chashu\".charAt(6)", ""),
+        ("\"chashu\".endsWith(\"shu\")", "true"),
+        ("\"chashu\".endsWith(\"cha\")", "false"),
+        ("\"chashu\".includes(\"ash\")", "true"),
+        ("\"chashu\".includes(\"nori\")", "false"),
+        ("\"chashu\".startsWith(\"cha\")", "true"),
+        ("\"chashu\".startsWith(\"shu\")", "false"),
+        ("\"chashu\".indexOf(\"h\")", "1"),
+        ("\"chashu\".indexOf(\"h\", 2)", "4"),
+        ("\"chashu\".indexOf(\"h\", -5)", "1"),
+        ("\"chashu\".indexOf(\"h\", 10)", "-1"),
+        ("\"chashu\".indexOf(\"x\")", "-1"),
+        ("\"chashu\".indexOf(\"\")", "0"),
+        ("\"chashu\".lastIndexOf(\"h\")", "4"),
+        ("\"chashu\".lastIndexOf(\"h\", 3)", "1"),
+        ("\"chashu\".lastIndexOf(\"h\", -1)", "-1"),
+        ("\"chashu\".lastIndexOf(\"\")", "6"),
+        ("\"chashu\".substr(1, 3)", "has"),
+        ("\"chashu\".substr(2)", "ashu"),
+        ("\"chashu\".substr(-3)", "shu"),
+        ("\"chashu\".substr(-3, 2)", "sh"),
+        ("\"chashu\".substr(-10, 2)", "ch"),
+        ("\"chashu\".substr(4, 100)", "hu"),
+        ("\"chashu\".substr(10)", ""),
+        ("\"chashu\".substr(1, -1)", ""),
+        ("\"chashu\".substring(1, 3)", "ha"),
+        ("\"chashu\".substring(3, 1)", "ha"),
+        ("\"chashu\".substring(2)", "ashu"),
+        ("\"chashu\".substring(-2, 2)", "ch"),
+        ("\"chashu\".substring(2, -2)", "ch"),
+        ("\"chashu\".substring(4, 100)", "hu"),
+        ("\"chashu\".substring(10)", ""),
+        ("\"ChaShu\".toLowerCase()", "chashu"),
+        ("\"ChaShu\".toUpperCase()", "CHASHU"),
+        ("\"  chashu \\t\".trim()", "chashu"),
+        ("\"\".length", "0"),
+    ]);
+
+    let err = run("return host.length.charAt(0);").unwrap_err();
+    assert!(err.to_string().contains("can only be called on strings"));
+    let err = run("return host.split(\".\");").unwrap_err();
+    assert!(err.to_string().contains("unsupported method `split`"));
+}
+
+#[test]
+fn sh_exp_match() {
+    let cases = &[
+        ("a.b.example.com", "*.example.com", true),
+        ("example.com", "*.example.com", false),
+        ("abcabd", "*abd", true),
+        ("aaab", "*a?b", true),
+        ("abc", "a*c*", true),
+        ("abc", "a*d", false),
+        ("mississippi", "*sip*", true),
+        ("mississippi", "m*iss*ppi", true),
+        ("mississippi", "m*iss*ppa", false),
+        ("ab", "a?", true),
+        ("a", "a?", false),
+        ("", "*", true),
+        ("", "?", false),
+        ("", "", true),
+        ("a", "", false),
+    ];
+    for (string, pattern, expected) in cases {
+        let expr = format!("shExpMatch({:?}, {:?})", string, pattern);
+        assert_eq!(eval(&expr), expected.to_string(), "{}", expr);
+    }
+
+    // Backtracking to the last `*` only keeps this linear.
+    let string = format!("{}b", "a".repeat(10_000));
+    let expr = format!("shExpMatch({:?}, \"*a*a*a*a*a*c\")", string);
+    assert_eq!(eval(&expr), "false");
+}
+
+#[test]
+fn local_host_or_domain_is() {
+    let cases = &[
+        ("www", "www.example.com", true),
+        ("WWW", "www.example.com", true),
+        ("www.example.com", "www.example.com", true),
+        ("www.other.com", "www.example.com", false),
+        ("home", "www.example.com", false),
+        ("ww", "www.example.com", false),
+    ];
+    for (host, domain, expected) in cases {
+        let expr = format!("localHostOrDomainIs({:?}, {:?})", host, domain);
+        assert_eq!(eval(&expr), expected.to_string(), "{}", expr);
+    }
+}
+
+#[test]
+fn parse_errors_have_line_numbers() {
+    // The bodies of `FindProxyForURL`, which start on line 2.
+    let cases = &[
+        ("  return \"DIRECT;\n}", 2),
+        ("\n  return @;\n}", 3),
+        ("  /*\n\n*/ return # }", 4),
+        ("  // comment\n  /* unterminated", 3),
+        ("  while (true) {}\n}", 2),
+        ("  return 1.2.3;\n}", 2),
+        ("  return \"DIRECT\";\n", 3),
+    ];
+    for (body, line) in cases {
+        let script = format!("function FindProxyForURL(url, host) {{\n{}", body);
+        let err = Pac::parse(&script).unwrap_err();
+        assert_eq!(err.line(), Some(*line), "{}: {}", script, err);
+    }
+
+    let err = Pac::parse("function findProxyForURL(url, host) {}").unwrap_err();
+    assert_eq!(err.line(), None);
+    assert!(err.to_string().contains("missing FindProxyForURL"));
+}
+
+#[test]
+fn evaluation_errors() {
+    for body in &[
+        "return dateRange(\"JAN\", \"MAR\");",
+        "return undefinedFunction();",
+        "return null.length;",
+    ] {
+        let err = run(body).unwrap_err();
+        assert_eq!(err.line(), None, "{}: {}", body, err);
+    }
+}
+
+#[test]
+fn steps_are_limited() {
+    let script = "
+        function double(n) {
+            if (n > 0) {
+                double(n - 1);
+                double(n - 1);
+            }
+            return n;
+        }
+        function FindProxyForURL(url, host) {
+            return double(30);
+        }
+    ";
+    let err = Pac::parse(script).unwrap().find_proxy_for_url(&url());
+    assert!(err.unwrap_err().to_string().contains("takes too long"));
+
+    let script = script.replace("double(30)", "double(5)");
+    let result = Pac::parse(&script).unwrap().find_proxy_for_url(&url());
+    assert_eq!(result.unwrap(), "5");
+}
+
+#[test]
+fn recursion_is_limited() {
+    let script = "
+        function deeper(n) { return deeper(n + 1); }
+        function FindProxyForURL(url, host) { return deeper(0); }
+    ";
+    let err = Pac::parse(script).unwrap().find_proxy_for_url(&url());
+    assert!(err.unwrap_err().to_string().contains("too much recursion"));
+}
+
+#[test]
+fn nesting_is_limited() {
+    let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(eval(&nested(16)), "1");
+    let err = run(&format!("return {};", nested(64))).unwrap_err();
+    assert!(err.to_string().contains("nested too deeply"));
+    assert_eq!(err.line(), Some(2));
+
+    let blocks = |depth| format!("{}return 1;{}", "{".repeat(depth), "}".repeat(depth));
+    assert_eq!(run(&blocks(16)).unwrap(), "1");
+    let err = run(&blocks(64)).unwrap_err();
+    assert!(err.to_string().contains("nested too deeply"));
+}
@@ -0,0 +1,218 @@
+use http::Method;
+use std::io::Cursor;
+use surf::middleware::redirect::{
+    BodyReplay, Redirect, RedirectError, RedirectLoop, RedirectPolicy,
+};
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+use surf::AttemptReason;
+
+fn client(mock: &MockClient, policy: RedirectPolicy) -> surf::Client<MockClient> {
+    surf::Client::with_client(mock.clone())
+        .scoped()
+        .middleware(Redirect::with_policy(policy))
+        .build()
+}
+
+fn redirect(method: Method, path: &str, status: u16, location: &str) -> Expectation {
+    Expectation::new(method, path)
+        .times(1)
+        .respond_with(MockResponse::new(status).header("location", location))
+}
+
+#[runtime::test]
+async fn see_other_is_followed_with_get_without_body() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::POST, "/orders", 303, "/orders/1"));
+    mock.expect(
+        Expectation::new(Method::GET, "/orders/1")
+            .body("")
+            .times(1)
+            .respond_with(MockResponse::new(200).body("chashu")),
+    );
+    let client = client(&mock, RedirectPolicy::new());
+
+    let mut res = client
+        .post("https://api.example.com/orders")
+        .body_string("chashu".into())
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body_string().await?, "chashu");
+    let attempts = res.attempts();
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(
+        attempts.first().unwrap().reason(),
+        Some(&AttemptReason::Redirect(http::StatusCode::SEE_OTHER))
+    );
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn found_keeps_post_when_preserving_the_method() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::POST, "/old", 302, "/new"));
+    mock.expect(
+        Expectation::new(Method::POST, "/new")
+            .body("chashu")
+            .times(1),
+    );
+    let client = client(&mock, RedirectPolicy::new().preserve_method(true));
+
+    let res = client
+        .post("https://api.example.com/old")
+        .body_string("chashu".into())
+        .await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn temporary_redirect_sends_the_body_again() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::PUT, "/upload", 307, "/storage/upload"));
+    mock.expect(
+        Expectation::new(Method::PUT, "/storage/upload")
+            .body("chashu")
+            .times(1),
+    );
+    let client = client(&mock, RedirectPolicy::new());
+
+    let res = client
+        .put("https://api.example.com/upload")
+        .body(Cursor::new(b"chashu".to_vec()))
+        .await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn factory_bodies_are_recreated() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::PUT, "/upload", 308, "/storage/upload"));
+    mock.expect(
+        Expectation::new(Method::PUT, "/storage/upload")
+            .body("chashu")
+            .times(1),
+    );
+    let client = client(&mock, RedirectPolicy::new().replay(BodyReplay::Factory));
+
+    let res = client
+        .put("https://api.example.com/upload")
+        .replayable_body(|| Cursor::new(b"chashu".to_vec()))
+        .await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn bodies_that_cant_be_replayed_fail() {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::PUT, "/upload", 307, "/storage/upload"));
+    let client = client(&mock, RedirectPolicy::new().replay(BodyReplay::Never));
+
+    let err = client
+        .put("https://api.example.com/upload")
+        .body_string("chashu".into())
+        .await
+        .unwrap_err();
+    match err.downcast_ref::<RedirectError>() {
+        Some(RedirectError::BodyNotReplayable { status, location }) => {
+            assert_eq!(*status, 307);
+            assert_eq!(location.as_str(), "https://api.example.com/storage/upload");
+        }
+        None => panic!("unexpected error: {}", err),
+    }
+    mock.verify();
+}
+
+#[runtime::test]
+async fn cycles_fail_right_away() {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::GET, "/a", 302, "/b"));
+    mock.expect(redirect(Method::GET, "/b", 302, "/a"));
+    let client = client(&mock, RedirectPolicy::new());
+
+    let err = client.get("https://api.example.com/a").await.unwrap_err();
+    let err = err.downcast_ref::<RedirectLoop>().unwrap();
+    assert!(err.is_cycle());
+    let chain: Vec<_> = err.chain().iter().map(|url| url.path()).collect();
+    assert_eq!(chain, ["/a", "/b", "/a"]);
+    mock.verify();
+}
+
+#[runtime::test]
+async fn too_many_redirects_fail() {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::GET, "/1", 302, "/2"));
+    mock.expect(redirect(Method::GET, "/2", 302, "/3"));
+    let client = client(&mock, RedirectPolicy::limited(1));
+
+    let err = client.get("https://api.example.com/1").await.unwrap_err();
+    let err = err.downcast_ref::<RedirectLoop>().unwrap();
+    assert!(!err.is_cycle());
+    let chain: Vec<_> = err.chain().iter().map(|url| url.path()).collect();
+    assert_eq!(chain, ["/1", "/2", "/3"]);
+    mock.verify();
+}
+
+#[runtime::test]
+async fn redirects_are_returned_without_following() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::GET, "/a", 302, "/b"));
+    let client = client(&mock, RedirectPolicy::none());
+
+    let res = client.get("https://api.example.com/a").await?;
+    assert_eq!(res.status(), 302);
+    assert_eq!(res.header("location"), Some("/b"));
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn credentials_are_stripped_for_other_origins() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(
+        Method::GET,
+        "/download",
+        302,
+        "https://cdn.example.com/file",
+    ));
+    // Matched first if the credentials are still sent.
+    mock.expect(
+        Expectation::new(Method::GET, "/file")
+            .header("authorization", "Bearer chashu")
+            .respond_with(MockResponse::new(401)),
+    );
+    mock.expect(Expectation::new(Method::GET, "/file").respond_with(MockResponse::new(200)));
+    let client = client(&mock, RedirectPolicy::new());
+
+    let res = client
+        .get("https://api.example.com/download")
+        .set_header("authorization", "Bearer chashu")
+        .await?;
+    assert_eq!(res.status(), 200);
+    Ok(())
+}
+
+#[runtime::test]
+async fn credentials_are_kept_for_the_same_origin() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(redirect(Method::GET, "/download", 302, "/file"));
+    mock.expect(
+        Expectation::new(Method::GET, "/file")
+            .header("authorization", "Bearer chashu")
+            .times(1),
+    );
+    let client = client(&mock, RedirectPolicy::new());
+
+    let res = client
+        .get("https://api.example.com/download")
+        .set_header("authorization", "Bearer chashu")
+        .await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
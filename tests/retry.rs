@@ -0,0 +1,175 @@
+use http::{Method, StatusCode};
+use std::time::Duration;
+use surf::middleware::redirect::BodyReplay;
+use surf::middleware::retry::Retry;
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+use surf::AttemptReason;
+
+fn client(mock: &MockClient, retry: Retry) -> surf::Client<MockClient> {
+    let retry = retry.initial_delay(Duration::from_millis(1));
+    surf::Client::with_client(mock.clone())
+        .scoped()
+        .middleware(retry)
+        .build()
+}
+
+fn unavailable(method: Method, path: &str, times: usize) -> Expectation {
+    Expectation::new(method, path)
+        .times(times)
+        .respond_with(MockResponse::new(503))
+}
+
+#[runtime::test]
+async fn retried_statuses_are_sent_again() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(unavailable(Method::GET, "/status", 2));
+    mock.expect(
+        Expectation::new(Method::GET, "/status")
+            .times(1)
+            .respond_with(MockResponse::new(200).body("ok")),
+    );
+    let client = client(&mock, Retry::new());
+
+    let mut res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body_string().await?, "ok");
+    let attempts = res.attempts();
+    assert_eq!(attempts.len(), 3);
+    let reasons: Vec<_> = attempts.iter().map(|attempt| attempt.reason()).collect();
+    let unavailable = AttemptReason::Status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(reasons, [Some(&unavailable), Some(&unavailable), None]);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn the_last_response_is_returned_after_max_attempts() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(unavailable(Method::GET, "/status", 2));
+    let client = client(&mock, Retry::new().max_attempts(2));
+
+    let res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.attempts().len(), 2);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn other_statuses_are_returned_right_away() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/status")
+            .times(1)
+            .respond_with(MockResponse::new(500)),
+    );
+    let client = client(&mock, Retry::new());
+
+    let res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 500);
+    assert_eq!(res.attempts().len(), 1);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn configured_statuses_are_retried() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/status")
+            .times(1)
+            .respond_with(MockResponse::new(504)),
+    );
+    mock.expect(Expectation::new(Method::GET, "/status").times(1));
+    let client = client(&mock, Retry::new().statuses(&[StatusCode::GATEWAY_TIMEOUT]));
+
+    let res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn non_idempotent_requests_are_not_retried() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(unavailable(Method::POST, "/orders", 1));
+    let client = client(&mock, Retry::new());
+
+    let res = client
+        .post("https://api.example.com/orders")
+        .body_string("chashu".into())
+        .await?;
+    assert_eq!(res.status(), 503);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn the_body_is_sent_again() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(unavailable(Method::PUT, "/cats/chashu", 1).body("chashu"));
+    mock.expect(
+        Expectation::new(Method::PUT, "/cats/chashu")
+            .body("chashu")
+            .times(1),
+    );
+    let client = client(&mock, Retry::new());
+
+    let res = client
+        .put("https://api.example.com/cats/chashu")
+        .body_string("chashu".into())
+        .await?;
+    assert_eq!(res.status(), 200);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn bodies_that_cant_be_replayed_are_not_retried() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(unavailable(Method::PUT, "/cats/chashu", 1));
+    let client = client(&mock, Retry::new().replay(BodyReplay::Never));
+
+    let res = client
+        .put("https://api.example.com/cats/chashu")
+        .body_string("chashu".into())
+        .await?;
+    assert_eq!(res.status(), 503);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn retry_after_is_waited_for() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/status")
+            .times(1)
+            .respond_with(MockResponse::new(429).header("retry-after", "1")),
+    );
+    mock.expect(Expectation::new(Method::GET, "/status").times(1));
+    let client = client(&mock, Retry::new());
+
+    let res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 200);
+    assert!(res.attempts().elapsed() >= Duration::from_secs(1));
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn retry_after_longer_than_the_max_delay_ends_retries() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/status")
+            .times(1)
+            .respond_with(MockResponse::new(429).header("retry-after", "120")),
+    );
+    let client = client(&mock, Retry::new().max_delay(Duration::from_secs(10)));
+
+    let res = client.get("https://api.example.com/status").await?;
+    assert_eq!(res.status(), 429);
+    assert_eq!(res.header("retry-after"), Some("120"));
+    mock.verify();
+    Ok(())
+}
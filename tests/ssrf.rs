@@ -0,0 +1,99 @@
+use surf::middleware::ssrf::SsrfGuard;
+
+use std::net::IpAddr;
+
+fn allowed(addr: &str) -> bool {
+    SsrfGuard::new().is_allowed(addr.parse::<IpAddr>().unwrap())
+}
+
+#[test]
+fn rejects_non_public_ipv4_addresses() {
+    let rejected = [
+        "0.0.0.0",
+        "0.1.2.3",
+        "10.0.0.1",
+        "100.64.0.1",
+        "100.127.255.255",
+        "127.0.0.1",
+        "169.254.169.254",
+        "172.16.0.1",
+        "172.31.255.255",
+        "192.0.0.1",
+        "192.0.0.255",
+        "192.168.1.1",
+        "198.18.0.1",
+        "198.19.255.255",
+        "224.0.0.1",
+        "239.255.255.250",
+        "240.0.0.1",
+        "255.255.255.255",
+    ];
+    for addr in &rejected {
+        assert!(!allowed(addr), "{} should be rejected", addr);
+    }
+}
+
+#[test]
+fn allows_public_ipv4_addresses() {
+    let allowed_addrs = [
+        "1.1.1.1",
+        "8.8.8.8",
+        "93.184.216.34",
+        "100.63.255.255",
+        "100.128.0.1",
+        "172.32.0.1",
+        "192.0.1.1",
+        "198.17.255.255",
+        "198.20.0.1",
+        "223.255.255.255",
+    ];
+    for addr in &allowed_addrs {
+        assert!(allowed(addr), "{} should be allowed", addr);
+    }
+}
+
+#[test]
+fn rejects_non_public_ipv6_addresses() {
+    let rejected = [
+        "::",
+        "::1",
+        "::ffff:10.0.0.1",
+        "::ffff:127.0.0.1",
+        "64:ff9b::a00:1",
+        "64:ff9b::7f00:1",
+        "64:ff9b::a9fe:a9fe",
+        "fc00::1",
+        "fd12:3456::1",
+        "fe80::1",
+        "febf::1",
+        "fec0::1",
+        "feff::1",
+        "ff02::1",
+        "ff0e::1",
+    ];
+    for addr in &rejected {
+        assert!(!allowed(addr), "{} should be rejected", addr);
+    }
+}
+
+#[test]
+fn allows_public_ipv6_addresses() {
+    let allowed_addrs = [
+        "2001:4860:4860::8888",
+        "2606:4700:4700::1111",
+        "::ffff:8.8.8.8",
+        "64:ff9b::808:808",
+    ];
+    for addr in &allowed_addrs {
+        assert!(allowed(addr), "{} should be allowed", addr);
+    }
+}
+
+#[test]
+fn allows_listed_addresses() {
+    let addr: IpAddr = "10.0.0.1".parse().unwrap();
+    assert!(SsrfGuard::new().allow(addr).is_allowed(addr));
+    assert!(!SsrfGuard::new()
+        .allow(addr)
+        .is_allowed("10.0.0.2".parse().unwrap()));
+}
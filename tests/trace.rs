@@ -0,0 +1,68 @@
+use http::Method;
+use surf::middleware::trace::{Trace, TraceContext};
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+
+const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+fn traced(mock: &MockClient, trace: Trace) -> surf::Client<MockClient> {
+    surf::Client::with_client(mock.clone()).middleware(trace)
+}
+
+#[runtime::test]
+async fn the_trace_is_continued_with_a_new_span() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(Expectation::new(Method::GET, "/").respond_with(MockResponse::new(200)));
+    let client = traced(&mock, Trace::new());
+
+    let res = client
+        .get("https://api.example.com/")
+        .set_ext(TraceContext::parse(PARENT, Some("congo=t61rcWkgMzE")).unwrap())
+        .await?;
+    let context = res.ext::<TraceContext>().unwrap();
+    assert_eq!(
+        context.trace_id(),
+        0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736
+    );
+    assert_ne!(context.span_id(), 0x00f0_67aa_0ba9_02b7);
+    assert_eq!(context.trace_state(), Some("congo=t61rcWkgMzE"));
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn the_span_id_is_read_from_the_span() -> Result<(), surf::Exception> {
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000abc-01";
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/")
+            .header("traceparent", traceparent)
+            .times(1),
+    );
+    let client = traced(&mock, Trace::new().span_id(|_| Some(0xabc)));
+
+    let res = client
+        .get("https://api.example.com/")
+        .set_ext(TraceContext::parse(PARENT, None).unwrap())
+        .await?;
+    assert_eq!(res.ext::<TraceContext>().unwrap().span_id(), 0xabc);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn a_random_span_id_is_the_fallback() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(Expectation::new(Method::GET, "/").times(2));
+
+    for span_id in &[None, Some(0)] {
+        let span_id = *span_id;
+        let client = traced(&mock, Trace::new().span_id(move |_| span_id));
+        let res = client.get("https://api.example.com/").await?;
+        let context = res.ext::<TraceContext>().unwrap();
+        assert_ne!(context.span_id(), 0);
+        let traceparent = context.to_string();
+        assert!(traceparent.starts_with("00-") && traceparent.ends_with("-01"));
+    }
+    mock.verify();
+    Ok(())
+}
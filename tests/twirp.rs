@@ -0,0 +1,108 @@
+use http::Method;
+use prost::Message;
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+use surf::twirp::{TwirpClient, TwirpError};
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+struct Size {
+    #[prost(int32, tag = "1")]
+    inches: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+struct Hat {
+    #[prost(string, tag = "1")]
+    color: String,
+}
+
+const ROUTE: &str = "/twirp/twitch.twirp.example.Haberdasher/MakeHat";
+
+fn client(mock: &MockClient) -> TwirpClient<MockClient> {
+    let client = surf::Client::with_client(mock.clone());
+    TwirpClient::new(client, "https://haberdasher.example.com".parse().unwrap())
+}
+
+fn encode(message: &impl Message) -> Vec<u8> {
+    let mut buf = Vec::new();
+    message.encode(&mut buf).unwrap();
+    buf
+}
+
+#[runtime::test]
+async fn protobuf_calls_use_the_twirp_content_type() -> Result<(), surf::Exception> {
+    let hat = Hat {
+        color: "red".to_string(),
+    };
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::POST, ROUTE)
+            .header("content-type", "application/protobuf")
+            .body(encode(&Size { inches: 12 }))
+            .respond_with(
+                MockResponse::new(200)
+                    .header("content-type", "application/protobuf")
+                    .body(encode(&hat)),
+            ),
+    );
+
+    let res: Hat = client(&mock)
+        .call(
+            "twitch.twirp.example.Haberdasher",
+            "MakeHat",
+            &Size { inches: 12 },
+        )
+        .await?;
+    assert_eq!(res, hat);
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn json_calls_use_the_json_content_type() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::POST, ROUTE)
+            .header("content-type", "application/json")
+            .body(r#"{"inches":12}"#)
+            .respond_with(MockResponse::new(200).body(r#"{"color":"red"}"#)),
+    );
+
+    let res: Hat = client(&mock)
+        .call_json(
+            "twitch.twirp.example.Haberdasher",
+            "MakeHat",
+            &Size { inches: 12 },
+        )
+        .await?;
+    assert_eq!(res.color, "red");
+    mock.verify();
+    Ok(())
+}
+
+#[runtime::test]
+async fn errors_are_decoded() -> Result<(), surf::Exception> {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::POST, ROUTE).respond_with(
+            MockResponse::new(400)
+                .header("content-type", "application/json")
+                .body(
+                    r#"{"code":"invalid_argument","msg":"too small","meta":{"argument":"inches"}}"#,
+                ),
+        ),
+    );
+
+    let err = client(&mock)
+        .call::<_, Hat>(
+            "twitch.twirp.example.Haberdasher",
+            "MakeHat",
+            &Size { inches: -1 },
+        )
+        .await
+        .unwrap_err();
+    let err = err.downcast_ref::<TwirpError>().unwrap();
+    assert_eq!(err.code(), "invalid_argument");
+    assert_eq!(err.msg(), "too small");
+    mock.verify();
+    Ok(())
+}
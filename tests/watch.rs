@@ -0,0 +1,182 @@
+use futures::prelude::*;
+use http::Method;
+use std::io;
+use std::time::{Duration, Instant};
+use surf::testing::mock::{Expectation, MockClient, MockResponse};
+use surf::watch::{Event, Watch};
+
+fn events(body: &str) -> MockClient {
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/events")
+            .header("accept", "text/event-stream")
+            .times(1)
+            .respond_with(MockResponse::new(200).body(body)),
+    );
+    mock
+}
+
+fn watch(mock: &MockClient) -> Watch<MockClient> {
+    let client = surf::Client::with_client(mock.clone());
+    let url = "https://api.example.com/events".parse().unwrap();
+    Watch::event_stream(client, url).max_reconnects(Some(0))
+}
+
+/// Collect events until the stream fails, returning them with the error.
+async fn collect(watch: Watch<MockClient>) -> (Vec<Event>, surf::Exception) {
+    let mut stream = watch.stream();
+    let mut events = vec![];
+    loop {
+        match stream.next().await {
+            Some(Ok(event)) => events.push(event),
+            Some(Err(err)) => return (events, err),
+            None => panic!("the stream ended without an error"),
+        }
+    }
+}
+
+fn summary(events: &[Event]) -> Vec<(Option<&str>, Option<&str>, &str)> {
+    events
+        .iter()
+        .map(|event| (event.id(), event.name(), event.data()))
+        .collect()
+}
+
+#[runtime::test]
+async fn fields_are_parsed() {
+    let mock = events(concat!(
+        ": a comment\n",
+        "event: update\n",
+        "data: chashu\n",
+        "data:nori\n",
+        "unknown: field\n",
+        "\n",
+        "data\r\n",
+        "\r\n",
+        "event: ignored without data\n",
+        "\n",
+        "data: last\n",
+        "\n",
+        "data: incomplete\n",
+    ));
+
+    let (events, _) = collect(watch(&mock)).await;
+    assert_eq!(
+        summary(&events),
+        [
+            (None, Some("update"), "chashu\nnori"),
+            (None, None, ""),
+            (None, None, "last"),
+        ]
+    );
+    mock.verify();
+}
+
+#[runtime::test]
+async fn ids_carry_over_to_later_events() {
+    let mock = events(concat!(
+        "data: 1\n\n",
+        "id: a\ndata: 2\n\n",
+        "data: 3\n\n",
+        "id: b\n\n",
+        "data: 4\n\n",
+        "id\ndata: 5\n\n",
+        "id: c\0\ndata: 6\n\n",
+    ));
+
+    let (events, _) = collect(watch(&mock)).await;
+    assert_eq!(
+        summary(&events),
+        [
+            (None, None, "1"),
+            (Some("a"), None, "2"),
+            (Some("a"), None, "3"),
+            (Some("b"), None, "4"),
+            (None, None, "5"),
+            (None, None, "6"),
+        ]
+    );
+    mock.verify();
+}
+
+#[runtime::test]
+async fn reconnects_resume_after_the_last_id() {
+    let first = "retry: 10\nid: 1\ndata: a\n\ndata: b\n\n";
+    let second = "id: 1\ndata: a\n\nid: 2\ndata: c\n\n";
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/events")
+            .times(1)
+            .respond_with(MockResponse::new(200).body(first)),
+    );
+    mock.expect(
+        Expectation::new(Method::GET, "/events")
+            .header("last-event-id", "1")
+            .times(1)
+            .respond_with(MockResponse::new(200).body(second)),
+    );
+    mock.expect(
+        Expectation::new(Method::GET, "/events")
+            .header("last-event-id", "2")
+            .times(1)
+            .respond_with(MockResponse::new(410)),
+    );
+    let watch = watch(&mock)
+        .max_reconnects(Some(1))
+        .reconnect_delay(Duration::from_secs(60));
+
+    let started = Instant::now();
+    let (events, _) = collect(watch).await;
+    assert!(started.elapsed() < Duration::from_secs(30));
+    assert_eq!(
+        summary(&events),
+        [
+            (Some("1"), None, "a"),
+            (Some("1"), None, "b"),
+            (Some("2"), None, "c"),
+        ]
+    );
+    mock.verify();
+}
+
+#[runtime::test]
+async fn long_lines_fail_the_stream() {
+    let mock = events(&format!("data: ok\n\ndata: {}\n\n", "x".repeat(64)));
+
+    let (events, err) = collect(watch(&mock).max_len(32)).await;
+    assert_eq!(summary(&events), [(None, None, "ok")]);
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    mock.verify();
+}
+
+#[runtime::test]
+async fn long_events_fail_the_stream() {
+    let mock = events(&"data: xxxxxxxx\n".repeat(8));
+
+    let (events, err) = collect(watch(&mock).max_len(32)).await;
+    assert!(events.is_empty());
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    mock.verify();
+}
+
+#[runtime::test]
+async fn long_json_lines_fail_the_stream() {
+    let body = format!("{{}}\n\n[{}]\n", "1,".repeat(32));
+    let mock = MockClient::new();
+    mock.expect(
+        Expectation::new(Method::GET, "/watch")
+            .times(1)
+            .respond_with(MockResponse::new(200).body(body)),
+    );
+    let client = surf::Client::with_client(mock.clone());
+    let url = "https://api.example.com/watch".parse().unwrap();
+    let watch = Watch::ndjson(client, url).max_len(32);
+
+    let (events, err) = collect(watch).await;
+    assert_eq!(summary(&events), [(None, None, "{}")]);
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    mock.verify();
+}